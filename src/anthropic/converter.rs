@@ -2,43 +2,97 @@
 //!
 //! 负责将 Anthropic API 请求格式转换为 Kiro API 请求格式
 
+use std::sync::{LazyLock, OnceLock};
+
+use serde_json::{Map, Value};
 use uuid::Uuid;
 
+use crate::kiro::error::KiroError;
 use crate::kiro::model::requests::conversation::{
     AssistantMessage, ConversationState, CurrentMessage, HistoryAssistantMessage,
     HistoryUserMessage, KiroImage, Message, UserInputMessage, UserInputMessageContext, UserMessage,
 };
+use crate::kiro::model::requests::kiro::{InferenceConfig, KiroRequest};
 use crate::kiro::model::requests::tool::{
     InputSchema, Tool, ToolResult, ToolSpecification, ToolUseEntry,
 };
+use crate::model::model_map::ModelMap;
 
+use super::image::{decode_image, max_image_bytes};
 use super::types::{ContentBlock, MessagesRequest, Thinking};
 
-/// 模型映射：将 Anthropic 模型名映射到 Kiro 模型 ID
+/// 模型映射：将客户端模型名映射到 Kiro 模型 ID
 ///
-/// 按照用户要求：
+/// 使用 `ModelMap` 的内置映射：
 /// - 所有 sonnet → claude-sonnet-4.5
 /// - 所有 opus → claude-opus-4.5
 /// - 所有 haiku → claude-haiku-4.5
+/// - 常见 OpenAI 模型名（如 gpt-4o）→ 对应的 Claude 模型
 pub fn map_model(model: &str) -> Option<String> {
-    let model_lower = model.to_lowercase();
-
-    if model_lower.contains("sonnet") {
-        Some("claude-sonnet-4.5".to_string())
-    } else if model_lower.contains("opus") {
-        Some("claude-opus-4.5".to_string())
-    } else if model_lower.contains("haiku") {
-        Some("claude-haiku-4.5".to_string())
-    } else {
-        None
-    }
+    static MODEL_MAP: LazyLock<ModelMap> = LazyLock::new(ModelMap::default);
+
+    MODEL_MAP.resolve_model(model).ok().map(str::to_string)
 }
 
+/// 上游允许的最大输出 tokens
+const MAX_OUTPUT_TOKENS: i32 = 32000;
+
+/// 最后一条消息为 assistant（prefill）时发送的当前消息
+///
+/// Kiro 要求当前消息必须来自用户，prefill 本身作为历史中的最后一条 assistant 消息
+const PREFILL_CONTINUATION_PROMPT: &str =
+    "Continue your previous response exactly where it stopped. Do not repeat any of it.";
+
 /// 转换结果
 #[derive(Debug)]
 pub struct ConversionResult {
     /// 转换后的 Kiro 请求
     pub conversation_state: ConversationState,
+    /// 推理参数
+    pub inference_config: InferenceConfig,
+    /// 最后一条 assistant 消息的文本（prefill），模型续写时重复输出的部分需要去除
+    pub prefill: Option<String>,
+    /// 按白名单透传的客户端字段
+    pub metadata: Map<String, Value>,
+}
+
+/// 全局字段透传白名单
+static PASSTHROUGH_FIELDS: OnceLock<Vec<String>> = OnceLock::new();
+
+/// 初始化字段透传白名单
+///
+/// 应在应用启动时调用一次；未初始化时不透传任何字段
+pub fn init_passthrough_fields(fields: Vec<String>) {
+    let _ = PASSTHROUGH_FIELDS.set(fields);
+}
+
+/// 是否合并连续的同角色消息
+static MERGE_CONSECUTIVE_MESSAGES: OnceLock<bool> = OnceLock::new();
+
+/// 初始化是否合并连续的同角色消息
+///
+/// 应在应用启动时调用一次；未初始化时默认合并
+pub fn init_merge_consecutive_messages(enabled: bool) {
+    let _ = MERGE_CONSECUTIVE_MESSAGES.set(enabled);
+}
+
+/// 从未识别的顶层字段中挑出白名单内的字段，其余直接丢弃
+pub(crate) fn passthrough_fields(
+    extra: &Map<String, Value>,
+    allowlist: &[String],
+) -> Map<String, Value> {
+    let fields: Map<String, Value> = extra
+        .iter()
+        .filter(|(key, _)| allowlist.contains(key))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    if fields.len() < extra.len() {
+        tracing::debug!(
+            "丢弃 {} 个不在白名单内的请求字段",
+            extra.len() - fields.len()
+        );
+    }
+    fields
 }
 
 /// 转换错误
@@ -46,6 +100,60 @@ pub struct ConversionResult {
 pub enum ConversionError {
     UnsupportedModel(String),
     EmptyMessages,
+    /// 请求内容无法转换（如未知角色、非法的工具参数）
+    InvalidRequest(String),
+    /// 图片无效（不支持的类型、base64 解码失败或内容与类型不符）
+    InvalidImage(String),
+    /// 图片超过大小限制
+    ImageTooLarge {
+        size: usize,
+        limit: usize,
+    },
+    /// Kiro 没有对应能力的 tool_choice 模式（如强制调用工具）
+    UnsupportedToolChoice(String),
+    /// 推理参数超出允许范围且无法修正
+    InvalidParameter {
+        field: &'static str,
+        value: String,
+        allowed: &'static str,
+    },
+    /// 出错的输入位置（JSON Pointer 形式，如 `/messages/3/content/1/source`）与具体原因
+    At {
+        path: String,
+        source: Box<ConversionError>,
+    },
+}
+
+impl ConversionError {
+    /// 在错误路径前追加一段前缀（由内向外逐层调用）
+    pub(crate) fn at(self, prefix: impl std::fmt::Display) -> Self {
+        match self {
+            ConversionError::At { path, source } => ConversionError::At {
+                path: format!("{}{}", prefix, path),
+                source,
+            },
+            other => ConversionError::At {
+                path: prefix.to_string(),
+                source: Box::new(other),
+            },
+        }
+    }
+
+    /// 出错的输入位置，未知时返回 `None`
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            ConversionError::At { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// 去掉位置信息后的具体原因
+    pub fn reason(&self) -> &ConversionError {
+        match self {
+            ConversionError::At { source, .. } => source.reason(),
+            other => other,
+        }
+    }
 }
 
 impl std::fmt::Display for ConversionError {
@@ -53,6 +161,24 @@ impl std::fmt::Display for ConversionError {
         match self {
             ConversionError::UnsupportedModel(model) => write!(f, "模型不支持: {}", model),
             ConversionError::EmptyMessages => write!(f, "消息列表为空"),
+            ConversionError::InvalidRequest(msg) => write!(f, "请求无效: {}", msg),
+            ConversionError::InvalidImage(msg) => write!(f, "图片无效: {}", msg),
+            ConversionError::ImageTooLarge { size, limit } => {
+                write!(f, "图片过大: {} 字节，超过限制 {} 字节", size, limit)
+            }
+            ConversionError::UnsupportedToolChoice(mode) => {
+                write!(
+                    f,
+                    "不支持的 tool_choice: {}（Kiro 无法强制模型调用工具）",
+                    mode
+                )
+            }
+            ConversionError::InvalidParameter {
+                field,
+                value,
+                allowed,
+            } => write!(f, "参数 {} 无效: {}（允许范围: {}）", field, value, allowed),
+            ConversionError::At { path, source } => write!(f, "{}: {}", path, source),
         }
     }
 }
@@ -118,7 +244,19 @@ fn create_placeholder_tool(name: &str) -> Tool {
 }
 
 /// 将 Anthropic 请求转换为 Kiro 请求
+///
+/// Kiro 要求 user / assistant 交替出现，默认将连续的同角色消息按顺序合并为一轮
+/// （见 `init_merge_consecutive_messages`）
 pub fn convert_request(req: &MessagesRequest) -> Result<ConversionResult, ConversionError> {
+    let merge = MERGE_CONSECUTIVE_MESSAGES.get().copied().unwrap_or(true);
+    convert_request_with(req, merge)
+}
+
+/// `convert_request` 的实现，`merge` 指定是否合并连续的同角色消息
+fn convert_request_with(
+    req: &MessagesRequest,
+    merge: bool,
+) -> Result<ConversionResult, ConversionError> {
     // 1. 映射模型
     let model_id = map_model(&req.model)
         .ok_or_else(|| ConversionError::UnsupportedModel(req.model.clone()))?;
@@ -128,6 +266,20 @@ pub fn convert_request(req: &MessagesRequest) -> Result<ConversionResult, Conver
         return Err(ConversionError::EmptyMessages);
     }
 
+    // 校验并修正推理参数
+    let inference_config = inference_config(req)?;
+
+    validate_tool_results(&req.messages, merge)?;
+
+    // Kiro 没有 prompt caching 的对应字段，cache_control 标记直接忽略
+    let cache_markers = count_cache_markers(req);
+    if cache_markers > 0 {
+        tracing::debug!(
+            "上游不支持 prompt caching，忽略 {} 个 cache_control 标记",
+            cache_markers
+        );
+    }
+
     // 3. 生成会话 ID 和代理 ID
     // 优先从 metadata.user_id 中提取 session UUID 作为 conversationId
     let conversation_id = req
@@ -141,15 +293,31 @@ pub fn convert_request(req: &MessagesRequest) -> Result<ConversionResult, Conver
     // 4. 确定触发类型
     let chat_trigger_type = determine_chat_trigger_type(req);
 
-    // 5. 处理最后一条消息作为 current_message
-    let last_message = req.messages.last().unwrap();
-    let (text_content, images, tool_results) = process_message_content(&last_message.content)?;
+    // 5. 处理最后一轮消息作为 current_message（合并时为结尾的连续同角色消息）
+    let current_start = current_turn_start(&req.messages, merge);
+    let last_message = &req.messages[req.messages.len() - 1];
+    let mut text_parts = Vec::new();
+    let mut images = Vec::new();
+    let mut tool_results = Vec::new();
+    for (index, msg) in req.messages.iter().enumerate().skip(current_start) {
+        let (text, msg_images, msg_tool_results) = process_message_content(&msg.content)
+            .map_err(|e| e.at(format_args!("/messages/{}", index)))?;
+        if !text.is_empty() {
+            text_parts.push(text);
+        }
+        images.extend(msg_images);
+        tool_results.extend(msg_tool_results);
+    }
+    let text_content = text_parts.join("\n");
 
-    // 6. 转换工具定义
-    let mut tools = convert_tools(&req.tools);
+    // 6. 转换工具定义；tool_choice 为 none 时不向模型提供工具
+    let mut tools = match tool_choice_mode(&req.tool_choice)? {
+        ToolChoiceMode::Auto => convert_tools(&req.tools),
+        ToolChoiceMode::None => Vec::new(),
+    };
 
     // 7. 构建历史消息（需要先构建，以便收集历史中使用的工具）
-    let history = build_history(req, &model_id)?;
+    let history = build_history(req, &model_id, current_start, merge)?;
 
     // 8. 收集历史中使用的工具名称，为缺失的工具生成占位符定义
     // Kiro API 要求：历史消息中引用的工具必须在 tools 列表中有定义
@@ -166,6 +334,9 @@ pub fn convert_request(req: &MessagesRequest) -> Result<ConversionResult, Conver
         }
     }
 
+    // 工具定义与顺序无关，按名称排序使相同工具集得到相同的请求（便于比较与缓存）
+    tools.sort_by(|a, b| a.tool_specification.name.cmp(&b.tool_specification.name));
+
     // 9. 构建 UserInputMessageContext
     let mut context = UserInputMessageContext::new();
     if !tools.is_empty() {
@@ -176,8 +347,15 @@ pub fn convert_request(req: &MessagesRequest) -> Result<ConversionResult, Conver
     }
 
     // 10. 构建当前消息
-    // 保留文本内容，即使有工具结果也不丢弃用户文本
-    let content = text_content;
+    // 保留文本内容，即使有工具结果也不丢弃用户文本；
+    // 最后一条为 assistant 时该消息已作为 prefill 进入历史，当前消息改为续写指令
+    let prefill_mode = last_message.role == "assistant";
+    let (content, images, prefill) = if prefill_mode {
+        let prefill = Some(text_content).filter(|t| !t.is_empty());
+        (PREFILL_CONTINUATION_PROMPT.to_string(), Vec::new(), prefill)
+    } else {
+        (text_content, images, None)
+    };
 
     let mut user_input = UserInputMessage::new(content, &model_id)
         .with_context(context)
@@ -197,7 +375,129 @@ pub fn convert_request(req: &MessagesRequest) -> Result<ConversionResult, Conver
         .with_current_message(current_message)
         .with_history(history);
 
-    Ok(ConversionResult { conversation_state })
+    let allowlist = PASSTHROUGH_FIELDS
+        .get()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+
+    Ok(ConversionResult {
+        conversation_state,
+        inference_config,
+        prefill,
+        metadata: passthrough_fields(&req.extra, allowlist),
+    })
+}
+
+/// 统计系统消息、工具定义与消息内容块上的 cache_control 标记
+fn count_cache_markers(req: &MessagesRequest) -> usize {
+    let system = req
+        .system
+        .iter()
+        .flatten()
+        .filter(|s| s.cache_control.is_some())
+        .count();
+    let tools = req
+        .tools
+        .iter()
+        .flatten()
+        .filter(|t| t.cache_control.is_some())
+        .count();
+    let blocks = req
+        .messages
+        .iter()
+        .filter_map(|m| m.content.as_array())
+        .flatten()
+        .filter(|block| block.get("cache_control").is_some())
+        .count();
+    system + tools + blocks
+}
+
+/// 提取推理参数，并限制在上游允许的范围内
+///
+/// - max_tokens: 负数直接拒绝，其余限制在 1 ~ MAX_OUTPUT_TOKENS
+/// - temperature / top_p: 限制在 0.0 ~ 1.0，非有限值直接拒绝
+/// - 忽略空的停止序列
+fn inference_config(req: &MessagesRequest) -> Result<InferenceConfig, ConversionError> {
+    if req.max_tokens < 0 {
+        return Err(ConversionError::InvalidParameter {
+            field: "max_tokens",
+            value: req.max_tokens.to_string(),
+            allowed: "大于 0 的整数",
+        });
+    }
+    let unit_interval = |field: &'static str, value: Option<f32>| match value {
+        Some(v) if !v.is_finite() => Err(ConversionError::InvalidParameter {
+            field,
+            value: v.to_string(),
+            allowed: "0.0 ~ 1.0",
+        }),
+        _ => Ok(value.map(|v| v.clamp(0.0, 1.0))),
+    };
+
+    Ok(InferenceConfig {
+        max_tokens: Some(req.max_tokens.clamp(1, MAX_OUTPUT_TOKENS)),
+        temperature: unit_interval("temperature", req.temperature)?,
+        top_p: unit_interval("top_p", req.top_p)?,
+        stop_sequences: req
+            .stop_sequences
+            .iter()
+            .flatten()
+            .filter(|s| !s.is_empty())
+            .cloned()
+            .collect(),
+    })
+}
+
+/// 将 Anthropic 请求转换为完整的 Kiro 请求（不含 profileArn）
+#[allow(dead_code)]
+pub fn from_anthropic(req: MessagesRequest) -> Result<KiroRequest, KiroError> {
+    let result = convert_request(&req)?;
+    Ok(KiroRequest {
+        conversation_state: result.conversation_state,
+        profile_arn: None,
+        inference_config: Some(result.inference_config),
+        metadata: result.metadata,
+    })
+}
+
+/// Kiro 能够表达的 tool_choice 模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ToolChoiceMode {
+    /// 由模型决定是否调用工具（默认）
+    Auto,
+    /// 禁止调用工具
+    None,
+}
+
+/// 解析 tool_choice
+///
+/// Kiro 没有控制工具选择的字段：`auto` 为默认行为，`none` 通过不提供工具定义实现，
+/// `any` 与 `tool`（强制调用某个工具）无法实现，返回 `UnsupportedToolChoice`
+fn tool_choice_mode(
+    tool_choice: &Option<serde_json::Value>,
+) -> Result<ToolChoiceMode, ConversionError> {
+    let Some(choice) = tool_choice else {
+        return Ok(ToolChoiceMode::Auto);
+    };
+
+    match choice.get("type").and_then(|t| t.as_str()) {
+        Some("auto") => Ok(ToolChoiceMode::Auto),
+        Some("none") => Ok(ToolChoiceMode::None),
+        Some("any") => Err(ConversionError::UnsupportedToolChoice("any".to_string())),
+        Some("tool") => {
+            let name = choice.get("name").and_then(|n| n.as_str()).ok_or_else(|| {
+                ConversionError::InvalidRequest("tool_choice 缺少 name".to_string())
+            })?;
+            Err(ConversionError::UnsupportedToolChoice(format!(
+                "tool({})",
+                name
+            )))
+        }
+        _ => Err(ConversionError::InvalidRequest(format!(
+            "无法识别的 tool_choice: {}",
+            choice
+        ))),
+    }
 }
 
 /// 确定聊天触发类型
@@ -206,7 +506,35 @@ fn determine_chat_trigger_type(_req: &MessagesRequest) -> String {
     "MANUAL".to_string()
 }
 
+/// 会被转换的内容块类型，其余类型格式无效时直接忽略
+const CONVERTED_BLOCK_TYPES: &[&str] = &["text", "image", "tool_result", "tool_use", "thinking"];
+
+/// 解析内容块
+///
+/// 会被转换的块格式无效时返回带块位置的错误；其余无法解析的块返回 `None`
+fn parse_content_block(
+    index: usize,
+    item: &serde_json::Value,
+) -> Result<Option<ContentBlock>, ConversionError> {
+    match serde_json::from_value(item.clone()) {
+        Ok(block) => Ok(Some(block)),
+        Err(e) => {
+            let block_type = item.get("type").and_then(|t| t.as_str());
+            if block_type.is_some_and(|t| CONVERTED_BLOCK_TYPES.contains(&t)) {
+                Err(
+                    ConversionError::InvalidRequest(format!("内容块格式无效: {}", e))
+                        .at(format_args!("/content/{}", index)),
+                )
+            } else {
+                Ok(None)
+            }
+        }
+    }
+}
+
 /// 处理消息内容，提取文本、图片和工具结果
+///
+/// 错误路径相对于消息本身（如 `/content/1/source`）
 fn process_message_content(
     content: &serde_json::Value,
 ) -> Result<(String, Vec<KiroImage>, Vec<ToolResult>), ConversionError> {
@@ -219,42 +547,47 @@ fn process_message_content(
             text_parts.push(s.clone());
         }
         serde_json::Value::Array(arr) => {
-            for item in arr {
-                if let Ok(block) = serde_json::from_value::<ContentBlock>(item.clone()) {
-                    match block.block_type.as_str() {
-                        "text" => {
-                            if let Some(text) = block.text {
-                                text_parts.push(text);
-                            }
+            for (index, item) in arr.iter().enumerate() {
+                let Some(block) = parse_content_block(index, item)? else {
+                    continue;
+                };
+                match block.block_type.as_str() {
+                    "text" => {
+                        if let Some(text) = block.text {
+                            text_parts.push(text);
                         }
-                        "image" => {
-                            if let Some(source) = block.source {
-                                if let Some(format) = get_image_format(&source.media_type) {
-                                    images.push(KiroImage::from_base64(format, source.data));
-                                }
-                            }
-                        }
-                        "tool_result" => {
-                            if let Some(tool_use_id) = block.tool_use_id {
-                                let result_content = extract_tool_result_content(&block.content);
-                                let is_error = block.is_error.unwrap_or(false);
-
-                                let mut result = if is_error {
-                                    ToolResult::error(&tool_use_id, result_content)
-                                } else {
-                                    ToolResult::success(&tool_use_id, result_content)
-                                };
-                                result.status =
-                                    Some(if is_error { "error" } else { "success" }.to_string());
-
-                                tool_results.push(result);
-                            }
+                    }
+                    "image" => {
+                        if let Some(source) = block.source {
+                            let image =
+                                decode_image(&source.media_type, &source.data, max_image_bytes())
+                                    .map_err(|e| e.at(format_args!("/content/{}/source", index)))?;
+                            images.push(image);
                         }
-                        "tool_use" => {
-                            // tool_use 在 assistant 消息中处理，这里忽略
+                    }
+                    "tool_result" => {
+                        if let Some(tool_use_id) = block.tool_use_id {
+                            let (result_content, result_images) =
+                                extract_tool_result_content(&block.content)
+                                    .map_err(|e| e.at(format_args!("/content/{}", index)))?;
+                            images.extend(result_images);
+                            let is_error = block.is_error.unwrap_or(false);
+
+                            let mut result = if is_error {
+                                ToolResult::error(&tool_use_id, result_content)
+                            } else {
+                                ToolResult::success(&tool_use_id, result_content)
+                            };
+                            result.status =
+                                Some(if is_error { "error" } else { "success" }.to_string());
+
+                            tool_results.push(result);
                         }
-                        _ => {}
                     }
+                    "tool_use" => {
+                        // tool_use 在 assistant 消息中处理，这里忽略
+                    }
+                    _ => {}
                 }
             }
         }
@@ -264,35 +597,102 @@ fn process_message_content(
     Ok((text_parts.join("\n"), images, tool_results))
 }
 
-/// 从 media_type 获取图片格式
-fn get_image_format(media_type: &str) -> Option<String> {
-    match media_type {
-        "image/jpeg" => Some("jpeg".to_string()),
-        "image/png" => Some("png".to_string()),
-        "image/gif" => Some("gif".to_string()),
-        "image/webp" => Some("webp".to_string()),
-        _ => None,
-    }
-}
-
-/// 提取工具结果内容
-fn extract_tool_result_content(content: &Option<serde_json::Value>) -> String {
+/// 提取工具结果内容，返回文本与结果中的图片
+///
+/// Kiro 的工具结果只支持文本，图片随所在的 user 消息一起发送；
+/// 错误路径相对于 tool_result 块（如 `/content/1/source`）
+fn extract_tool_result_content(
+    content: &Option<serde_json::Value>,
+) -> Result<(String, Vec<KiroImage>), ConversionError> {
     match content {
-        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(serde_json::Value::String(s)) => Ok((s.clone(), Vec::new())),
         Some(serde_json::Value::Array(arr)) => {
             let mut parts = Vec::new();
-            for item in arr {
-                if let Some(text) = item.get("text").and_then(|v| v.as_str()) {
+            let mut images = Vec::new();
+            for (index, item) in arr.iter().enumerate() {
+                if item.get("type").and_then(|t| t.as_str()) == Some("image") {
+                    let image = parse_content_block(index, item)?
+                        .and_then(|block| block.source)
+                        .ok_or_else(|| {
+                            ConversionError::InvalidRequest("图片块缺少 source".to_string())
+                                .at(format_args!("/content/{}", index))
+                        })?;
+                    images.push(
+                        decode_image(&image.media_type, &image.data, max_image_bytes())
+                            .map_err(|e| e.at(format_args!("/content/{}/source", index)))?,
+                    );
+                } else if let Some(text) = item.get("text").and_then(|v| v.as_str()) {
                     parts.push(text.to_string());
                 }
             }
-            parts.join("\n")
+            Ok((parts.join("\n"), images))
         }
-        Some(v) => v.to_string(),
-        None => String::new(),
+        Some(v) => Ok((v.to_string(), Vec::new())),
+        None => Ok((String::new(), Vec::new())),
     }
 }
 
+/// 校验 tool_result 与 tool_use 的配对
+///
+/// 每个 tool_result 必须带有 `tool_use_id`，且对应最近一条 assistant 消息中尚未给出结果的 tool_use
+///
+/// 合并连续同角色消息时，连续的 assistant 消息视为同一轮，其 tool_use 一并计入
+fn validate_tool_results(
+    messages: &[super::types::Message],
+    merge: bool,
+) -> Result<(), ConversionError> {
+    let mut open_tool_uses: Vec<String> = Vec::new();
+
+    for (msg_index, msg) in messages.iter().enumerate() {
+        if msg.role == "assistant" {
+            let continues_turn =
+                merge && msg_index > 0 && messages[msg_index - 1].role == "assistant";
+            if !continues_turn {
+                open_tool_uses.clear();
+            }
+        }
+        let serde_json::Value::Array(blocks) = &msg.content else {
+            continue;
+        };
+
+        if msg.role == "assistant" {
+            open_tool_uses.extend(
+                blocks
+                    .iter()
+                    .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("tool_use"))
+                    .filter_map(|b| b.get("id").and_then(|id| id.as_str()))
+                    .map(str::to_string),
+            );
+            continue;
+        }
+
+        for (index, block) in blocks.iter().enumerate() {
+            if block.get("type").and_then(|t| t.as_str()) != Some("tool_result") {
+                continue;
+            }
+            let path = format!("/messages/{}/content/{}/tool_use_id", msg_index, index);
+            let tool_use_id = block
+                .get("tool_use_id")
+                .and_then(|id| id.as_str())
+                .filter(|id| !id.is_empty())
+                .ok_or_else(|| {
+                    ConversionError::InvalidRequest("tool_result 缺少 tool_use_id".to_string())
+                        .at(&path)
+                })?;
+            let Some(pos) = open_tool_uses.iter().position(|id| id == tool_use_id) else {
+                return Err(ConversionError::InvalidRequest(format!(
+                    "tool_result 的 tool_use_id {} 没有对应的 tool_use（或已有结果）",
+                    tool_use_id
+                ))
+                .at(&path));
+            };
+            open_tool_uses.remove(pos);
+        }
+    }
+
+    Ok(())
+}
+
 /// 转换工具定义
 fn convert_tools(tools: &Option<Vec<super::types::Tool>>) -> Vec<Tool> {
     let Some(tools) = tools else {
@@ -344,8 +744,30 @@ fn has_thinking_tags(content: &str) -> bool {
     content.contains("<thinking_mode>") || content.contains("<max_thinking_length>")
 }
 
-/// 构建历史消息
-fn build_history(req: &MessagesRequest, model_id: &str) -> Result<Vec<Message>, ConversionError> {
+/// 当前消息所在轮次的起始下标
+///
+/// 合并时为结尾连续同角色消息中的第一条，否则为最后一条消息
+fn current_turn_start(messages: &[super::types::Message], merge: bool) -> usize {
+    let last = messages.len().saturating_sub(1);
+    if !merge {
+        return last;
+    }
+    let mut start = last;
+    while start > 0 && messages[start - 1].role == messages[last].role {
+        start -= 1;
+    }
+    start
+}
+
+/// 构建历史消息，`current_start` 为当前消息所在轮次的起始下标
+///
+/// 最后一轮为 assistant（prefill）时也计入历史
+fn build_history(
+    req: &MessagesRequest,
+    model_id: &str,
+    current_start: usize,
+    merge: bool,
+) -> Result<Vec<Message>, ConversionError> {
     let mut history = Vec::new();
 
     // 生成thinking前缀（如果需要）
@@ -388,9 +810,7 @@ fn build_history(req: &MessagesRequest, model_id: &str) -> Result<Vec<Message>,
     }
 
     // 2. 处理常规消息历史
-    // 最后一条消息作为 currentMessage，不加入历史
-    let history_end_index = req.messages.len().saturating_sub(1);
-
+    // 最后一轮消息作为 currentMessage，不加入历史
     // 如果最后一条是 assistant，则包含在历史中
     let last_is_assistant = req
         .messages
@@ -401,18 +821,27 @@ fn build_history(req: &MessagesRequest, model_id: &str) -> Result<Vec<Message>,
     let history_end_index = if last_is_assistant {
         req.messages.len()
     } else {
-        history_end_index
+        current_start
     };
 
     // 收集并配对消息
-    let mut user_buffer: Vec<&super::types::Message> = Vec::new();
+    let mut user_buffer: Vec<(usize, &super::types::Message)> = Vec::new();
 
-    for i in 0..history_end_index {
+    let mut i = 0;
+    while i < history_end_index {
         let msg = &req.messages[i];
+        let mut end = i + 1;
 
         if msg.role == "user" {
-            user_buffer.push(msg);
+            user_buffer.push((i, msg));
         } else if msg.role == "assistant" {
+            // 合并时连续的 assistant 消息作为同一轮
+            if merge {
+                while end < history_end_index && req.messages[end].role == "assistant" {
+                    end += 1;
+                }
+            }
+
             // 遇到 assistant，处理累积的 user 消息
             if !user_buffer.is_empty() {
                 let merged_user = merge_user_messages(&user_buffer, model_id)?;
@@ -420,10 +849,11 @@ fn build_history(req: &MessagesRequest, model_id: &str) -> Result<Vec<Message>,
                 user_buffer.clear();
 
                 // 添加 assistant 消息
-                let assistant = convert_assistant_message(msg)?;
+                let assistant = merge_assistant_messages(&req.messages, i..end)?;
                 history.push(Message::Assistant(assistant));
             }
         }
+        i = end;
     }
 
     // 处理结尾的孤立 user 消息
@@ -439,17 +869,44 @@ fn build_history(req: &MessagesRequest, model_id: &str) -> Result<Vec<Message>,
     Ok(history)
 }
 
-/// 合并多个 user 消息
+/// 按顺序合并同一轮的多个 assistant 消息：文本以换行连接，工具调用依次追加
+fn merge_assistant_messages(
+    messages: &[super::types::Message],
+    range: std::ops::Range<usize>,
+) -> Result<HistoryAssistantMessage, ConversionError> {
+    let mut content_parts = Vec::new();
+    let mut tool_uses = Vec::new();
+    for index in range {
+        let assistant = convert_assistant_message(&messages[index])
+            .map_err(|e| e.at(format_args!("/messages/{}", index)))?;
+        let response = assistant.assistant_response_message;
+        if !response.content.is_empty() {
+            content_parts.push(response.content);
+        }
+        tool_uses.extend(response.tool_uses.into_iter().flatten());
+    }
+
+    let mut assistant = HistoryAssistantMessage::new(content_parts.join("\n"));
+    if !tool_uses.is_empty() {
+        assistant.assistant_response_message = assistant
+            .assistant_response_message
+            .with_tool_uses(tool_uses);
+    }
+    Ok(assistant)
+}
+
+/// 合并多个 user 消息（附带各自在请求中的下标，用于错误路径）
 fn merge_user_messages(
-    messages: &[&super::types::Message],
+    messages: &[(usize, &super::types::Message)],
     model_id: &str,
 ) -> Result<HistoryUserMessage, ConversionError> {
     let mut content_parts = Vec::new();
     let mut all_images = Vec::new();
     let mut all_tool_results = Vec::new();
 
-    for msg in messages {
-        let (text, images, tool_results) = process_message_content(&msg.content)?;
+    for (index, msg) in messages {
+        let (text, images, tool_results) = process_message_content(&msg.content)
+            .map_err(|e| e.at(format_args!("/messages/{}", index)))?;
         if !text.is_empty() {
             content_parts.push(text);
         }
@@ -489,34 +946,35 @@ fn convert_assistant_message(
             text_content = s.clone();
         }
         serde_json::Value::Array(arr) => {
-            for item in arr {
-                if let Ok(block) = serde_json::from_value::<ContentBlock>(item.clone()) {
-                    match block.block_type.as_str() {
-                        "thinking" => {
-                            if let Some(thinking) = block.thinking {
-                                thinking_content.push_str(&thinking);
-                            }
+            for (index, item) in arr.iter().enumerate() {
+                let Some(block) = parse_content_block(index, item)? else {
+                    continue;
+                };
+                match block.block_type.as_str() {
+                    "thinking" => {
+                        if let Some(thinking) = block.thinking {
+                            thinking_content.push_str(&thinking);
                         }
-                        "text" => {
-                            if let Some(text) = block.text {
-                                text_content.push_str(&text);
-                            }
+                    }
+                    "text" => {
+                        if let Some(text) = block.text {
+                            text_content.push_str(&text);
                         }
-                        "tool_use" => {
-                            // 过滤不支持的工具
-                            if let Some(ref name) = block.name {
-                                if is_unsupported_tool(name) {
-                                    continue;
-                                }
+                    }
+                    "tool_use" => {
+                        // 过滤不支持的工具
+                        if let Some(ref name) = block.name {
+                            if is_unsupported_tool(name) {
+                                continue;
                             }
+                        }
 
-                            if let (Some(id), Some(name)) = (block.id, block.name) {
-                                let input = block.input.unwrap_or(serde_json::json!({}));
-                                tool_uses.push(ToolUseEntry::new(id, name).with_input(input));
-                            }
+                        if let (Some(id), Some(name)) = (block.id, block.name) {
+                            let input = block.input.unwrap_or(serde_json::json!({}));
+                            tool_uses.push(ToolUseEntry::new(id, name).with_input(input));
                         }
-                        _ => {}
                     }
+                    _ => {}
                 }
             }
         }
@@ -601,7 +1059,11 @@ mod tests {
             tools: None,
             tool_choice: None,
             thinking: None,
+            temperature: None,
+            top_p: None,
+            stop_sequences: None,
             metadata: None,
+            extra: Default::default(),
         };
         assert_eq!(determine_chat_trigger_type(&req), "MANUAL");
     }
@@ -687,7 +1149,11 @@ mod tests {
             tools: None, // 没有提供工具定义
             tool_choice: None,
             thinking: None,
+            temperature: None,
+            top_p: None,
+            stop_sequences: None,
             metadata: None,
+            extra: Default::default(),
         };
 
         let result = convert_request(&req).unwrap();
@@ -751,11 +1217,15 @@ mod tests {
             tools: None,
             tool_choice: None,
             thinking: None,
+            temperature: None,
+            top_p: None,
+            stop_sequences: None,
             metadata: Some(Metadata {
                 user_id: Some(
                     "user_0dede55c6dcc4a11a30bbb5e7f22e6fdf86cdeba3820019cc27612af4e1243cd_account__session_a0662283-7fd3-4399-a7eb-52b9a717ae88".to_string(),
                 ),
             }),
+            extra: Default::default(),
         };
 
         let result = convert_request(&req).unwrap();
@@ -782,7 +1252,11 @@ mod tests {
             tools: None,
             tool_choice: None,
             thinking: None,
+            temperature: None,
+            top_p: None,
+            stop_sequences: None,
             metadata: None,
+            extra: Default::default(),
         };
 
         let result = convert_request(&req).unwrap();
@@ -798,4 +1272,645 @@ mod tests {
             4
         );
     }
+
+    fn parse_request(value: serde_json::Value) -> MessagesRequest {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_from_anthropic_string_content() {
+        let req = parse_request(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 512,
+            "system": "Be brief.",
+            "messages": [{"role": "user", "content": "Hello there"}]
+        }));
+
+        let kiro = from_anthropic(req).unwrap();
+        let state = &kiro.conversation_state;
+        assert_eq!(
+            state.current_message.user_input_message.content,
+            "Hello there"
+        );
+        match &state.history[0] {
+            Message::User(user) => assert_eq!(user.user_input_message.content, "Be brief."),
+            other => panic!("expected user message, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_from_anthropic_inference_config() {
+        let req = parse_request(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 100000,
+            "temperature": 0.3,
+            "top_p": 1.5,
+            "stop_sequences": ["###", ""],
+            "messages": [{"role": "user", "content": "Hello"}]
+        }));
+
+        let kiro = from_anthropic(req).unwrap();
+        let config = kiro.inference_config.as_ref().unwrap();
+        assert_eq!(config.max_tokens, Some(MAX_OUTPUT_TOKENS));
+        assert_eq!(config.temperature, Some(0.3));
+        assert_eq!(config.top_p, Some(1.0));
+        assert_eq!(config.stop_sequences, vec!["###".to_string()]);
+
+        let json = serde_json::to_value(&kiro).unwrap();
+        assert_eq!(json["inferenceConfig"]["maxTokens"], MAX_OUTPUT_TOKENS);
+        assert_eq!(
+            json["inferenceConfig"]["stopSequences"],
+            serde_json::json!(["###"])
+        );
+    }
+
+    #[test]
+    fn test_from_anthropic_unsupported_model() {
+        let req = parse_request(serde_json::json!({
+            "model": "llama-3",
+            "max_tokens": 512,
+            "messages": [{"role": "user", "content": "Hello"}]
+        }));
+
+        let err = from_anthropic(req).unwrap_err();
+        assert!(matches!(
+            err,
+            KiroError::Conversion(ConversionError::UnsupportedModel(_))
+        ));
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[test]
+    fn test_from_anthropic_block_content_with_tool_result() {
+        let req = parse_request(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 512,
+            "system": [{"type": "text", "text": "Rule one."}, {"type": "text", "text": "Rule two."}],
+            "tools": [{
+                "name": "lookup",
+                "description": "Look something up",
+                "input_schema": {"type": "object", "properties": {"q": {"type": "string"}}}
+            }],
+            "tool_choice": {"type": "auto"},
+            "messages": [
+                {"role": "user", "content": [
+                    {"type": "text", "text": "First part."},
+                    {"type": "text", "text": "Second part."}
+                ]},
+                {"role": "assistant", "content": [
+                    {"type": "text", "text": "Let me check."},
+                    {"type": "tool_use", "id": "toolu_1", "name": "lookup", "input": {"q": "rust"}}
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "toolu_1", "content": [
+                        {"type": "text", "text": "Rust is a language."}
+                    ]},
+                    {"type": "text", "text": "Summarize it."}
+                ]}
+            ]
+        }));
+
+        let kiro = from_anthropic(req).unwrap();
+        let state = &kiro.conversation_state;
+
+        match &state.history[0] {
+            Message::User(user) => {
+                assert_eq!(user.user_input_message.content, "Rule one.\nRule two.")
+            }
+            other => panic!("expected user message, got {:?}", other),
+        }
+        match &state.history[2] {
+            Message::User(user) => {
+                assert_eq!(user.user_input_message.content, "First part.\nSecond part.")
+            }
+            other => panic!("expected user message, got {:?}", other),
+        }
+        match &state.history[3] {
+            Message::Assistant(assistant) => {
+                let message = &assistant.assistant_response_message;
+                assert_eq!(message.content, "Let me check.");
+                assert_eq!(
+                    message.tool_uses.as_ref().unwrap()[0].tool_use_id,
+                    "toolu_1"
+                );
+            }
+            other => panic!("expected assistant message, got {:?}", other),
+        }
+
+        let current = &state.current_message.user_input_message;
+        assert_eq!(current.content, "Summarize it.");
+        let results = &current.user_input_message_context.tool_results;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].tool_use_id, "toolu_1");
+        assert_eq!(results[0].content[0]["text"], "Rust is a language.");
+    }
+
+    fn tool_choice_request(tool_choice: serde_json::Value) -> MessagesRequest {
+        parse_request(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 512,
+            "tools": [{
+                "name": "lookup",
+                "description": "Look something up",
+                "input_schema": {"type": "object"}
+            }],
+            "tool_choice": tool_choice,
+            "messages": [{"role": "user", "content": "Hi"}]
+        }))
+    }
+
+    fn current_tools(result: &ConversionResult) -> usize {
+        result
+            .conversation_state
+            .current_message
+            .user_input_message
+            .user_input_message_context
+            .tools
+            .len()
+    }
+
+    #[test]
+    fn test_tool_choice_auto_and_none() {
+        let result = convert_request(&tool_choice_request(serde_json::json!({"type": "auto"})));
+        assert_eq!(current_tools(&result.unwrap()), 1);
+
+        let mut req = tool_choice_request(serde_json::Value::Null);
+        req.tool_choice = None;
+        assert_eq!(current_tools(&convert_request(&req).unwrap()), 1);
+
+        // none：不向模型提供工具
+        let result = convert_request(&tool_choice_request(serde_json::json!({"type": "none"})));
+        assert_eq!(current_tools(&result.unwrap()), 0);
+    }
+
+    #[test]
+    fn test_tool_choice_forced_is_unsupported() {
+        let err =
+            convert_request(&tool_choice_request(serde_json::json!({"type": "any"}))).unwrap_err();
+        assert!(matches!(err, ConversionError::UnsupportedToolChoice(ref m) if m == "any"));
+
+        let err = convert_request(&tool_choice_request(
+            serde_json::json!({"type": "tool", "name": "lookup"}),
+        ))
+        .unwrap_err();
+        assert!(matches!(err, ConversionError::UnsupportedToolChoice(_)));
+        assert!(err.to_string().contains("lookup"));
+    }
+
+    #[test]
+    fn test_tool_choice_invalid() {
+        for choice in [
+            serde_json::json!({"type": "tool"}),
+            serde_json::json!({"type": "sometimes"}),
+            serde_json::json!("auto"),
+        ] {
+            let err = convert_request(&tool_choice_request(choice)).unwrap_err();
+            assert!(matches!(err, ConversionError::InvalidRequest(_)));
+        }
+    }
+
+    #[test]
+    fn test_trailing_assistant_is_kept_as_prefill() {
+        let req = parse_request(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 512,
+            "messages": [
+                {"role": "user", "content": "Give JSON"},
+                {"role": "assistant", "content": "{\"a\""}
+            ]
+        }));
+
+        let result = convert_request(&req).unwrap();
+        assert_eq!(result.prefill.as_deref(), Some("{\"a\""));
+
+        let state = &result.conversation_state;
+        assert_eq!(
+            state.current_message.user_input_message.content,
+            PREFILL_CONTINUATION_PROMPT
+        );
+        match state.history.last() {
+            Some(Message::Assistant(assistant)) => {
+                assert_eq!(assistant.assistant_response_message.content, "{\"a\"")
+            }
+            other => panic!("expected assistant prefill, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_cache_control_markers_are_dropped() {
+        let req = parse_request(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 512,
+            "system": [{
+                "type": "text",
+                "text": "Long reusable context.",
+                "cache_control": {"type": "ephemeral"}
+            }],
+            "tools": [{
+                "name": "lookup",
+                "description": "Look something up",
+                "input_schema": {"type": "object"},
+                "cache_control": {"type": "ephemeral"}
+            }],
+            "messages": [{
+                "role": "user",
+                "content": [{
+                    "type": "text",
+                    "text": "Hello",
+                    "cache_control": {"type": "ephemeral", "ttl": "1h"}
+                }]
+            }]
+        }));
+        assert_eq!(count_cache_markers(&req), 3);
+
+        let kiro = from_anthropic(req).unwrap();
+        let state = &kiro.conversation_state;
+        match &state.history[0] {
+            Message::User(user) => {
+                assert_eq!(user.user_input_message.content, "Long reusable context.")
+            }
+            other => panic!("expected user message, got {:?}", other),
+        }
+        assert_eq!(state.current_message.user_input_message.content, "Hello");
+        assert!(
+            !serde_json::to_string(&kiro)
+                .unwrap()
+                .contains("cache_control")
+        );
+    }
+
+    fn parameter_request(params: serde_json::Value) -> MessagesRequest {
+        let mut value = serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 512,
+            "messages": [{"role": "user", "content": "Hello"}]
+        });
+        value
+            .as_object_mut()
+            .unwrap()
+            .extend(params.as_object().unwrap().clone());
+        parse_request(value)
+    }
+
+    #[test]
+    fn test_out_of_range_sampling_parameters_are_clamped() {
+        let config = convert_request(&parameter_request(
+            serde_json::json!({"temperature": 3.0, "top_p": -0.5}),
+        ))
+        .unwrap()
+        .inference_config;
+        assert_eq!(config.temperature, Some(1.0));
+        assert_eq!(config.top_p, Some(0.0));
+
+        let config = convert_request(&parameter_request(serde_json::json!({"temperature": -1.0})))
+            .unwrap()
+            .inference_config;
+        assert_eq!(config.temperature, Some(0.0));
+    }
+
+    #[test]
+    fn test_negative_max_tokens_is_rejected() {
+        let err =
+            convert_request(&parameter_request(serde_json::json!({"max_tokens": -5}))).unwrap_err();
+        assert!(matches!(
+            err,
+            ConversionError::InvalidParameter {
+                field: "max_tokens",
+                ..
+            }
+        ));
+        let message = err.to_string();
+        assert!(message.contains("max_tokens") && message.contains("-5"));
+    }
+
+    #[test]
+    fn test_non_finite_temperature_is_rejected() {
+        let mut req = parameter_request(serde_json::json!({}));
+        req.temperature = Some(f32::INFINITY);
+        let err = convert_request(&req).unwrap_err();
+        assert!(matches!(
+            err,
+            ConversionError::InvalidParameter {
+                field: "temperature",
+                allowed: "0.0 ~ 1.0",
+                ..
+            }
+        ));
+
+        let mut req = parameter_request(serde_json::json!({}));
+        req.top_p = Some(f32::NAN);
+        let err = convert_request(&req).unwrap_err();
+        assert!(matches!(
+            err,
+            ConversionError::InvalidParameter { field: "top_p", .. }
+        ));
+    }
+
+    #[test]
+    fn test_passthrough_fields_allowlist() {
+        let req = parse_request(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 256,
+            "messages": [{"role": "user", "content": "Hello"}],
+            "user": "user-42",
+            "vendor_flag": true
+        }));
+        assert_eq!(req.extra["user"], "user-42");
+
+        let forwarded = passthrough_fields(&req.extra, &["user".to_string()]);
+        assert_eq!(
+            serde_json::Value::Object(forwarded),
+            serde_json::json!({"user": "user-42"})
+        );
+        assert!(passthrough_fields(&req.extra, &[]).is_empty());
+
+        // 未配置白名单时全部丢弃，也不会出现在序列化后的请求中
+        let kiro = from_anthropic(req).unwrap();
+        assert!(kiro.metadata.is_empty());
+        assert!(
+            serde_json::to_value(&kiro)
+                .unwrap()
+                .get("metadata")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_tools_are_sorted_by_name() {
+        let tool = |name: &str| {
+            serde_json::json!({
+                "name": name,
+                "description": format!("{} tool", name),
+                "input_schema": {"type": "object", "properties": {}}
+            })
+        };
+        let request = |tools: serde_json::Value| {
+            let mut req = from_anthropic(parse_request(serde_json::json!({
+                "model": "claude-sonnet-4-5",
+                "max_tokens": 256,
+                "messages": [{"role": "user", "content": "Hello"}],
+                "tools": tools
+            })))
+            .unwrap();
+            req.conversation_state.conversation_id.clear();
+            req.conversation_state.agent_continuation_id = None;
+            req
+        };
+
+        let forward = request(serde_json::json!([
+            tool("search"),
+            tool("edit"),
+            tool("read")
+        ]));
+        let reversed = request(serde_json::json!([
+            tool("read"),
+            tool("edit"),
+            tool("search")
+        ]));
+
+        let names: Vec<&str> = forward
+            .conversation_state
+            .current_message
+            .user_input_message
+            .user_input_message_context
+            .tools
+            .iter()
+            .map(|t| t.tool_specification.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["edit", "read", "search"]);
+        assert_eq!(
+            serde_json::to_value(&forward).unwrap(),
+            serde_json::to_value(&reversed).unwrap()
+        );
+        assert_eq!(forward.stable_hash(), reversed.stable_hash());
+    }
+
+    #[test]
+    fn test_malformed_image_error_points_to_block() {
+        let req = parse_request(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 256,
+            "messages": [
+                {"role": "user", "content": "Hello"},
+                {"role": "assistant", "content": "Hi"},
+                {"role": "user", "content": "Look"},
+                {"role": "user", "content": [
+                    {"type": "text", "text": "this one"},
+                    {"type": "image", "source": {
+                        "type": "base64",
+                        "media_type": "image/png",
+                        "data": "not base64!"
+                    }}
+                ]},
+                {"role": "assistant", "content": "Nice"},
+                {"role": "user", "content": "Thanks"}
+            ]
+        }));
+        let err = convert_request(&req).unwrap_err();
+        assert_eq!(err.path(), Some("/messages/3/content/1/source"));
+        assert!(matches!(err.reason(), ConversionError::InvalidImage(_)));
+        assert!(
+            err.to_string()
+                .starts_with("/messages/3/content/1/source: ")
+        );
+    }
+
+    #[test]
+    fn test_malformed_block_error_points_to_block() {
+        // 当前消息中缺少 media_type 的图片块
+        let req = parse_request(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 256,
+            "messages": [{"role": "user", "content": [
+                {"type": "text", "text": "Hello"},
+                {"type": "text", "text": "again"},
+                {"type": "image", "source": {"type": "base64", "data": "AAAA"}}
+            ]}]
+        }));
+        let err = convert_request(&req).unwrap_err();
+        assert_eq!(err.path(), Some("/messages/0/content/2"));
+        assert!(matches!(err.reason(), ConversionError::InvalidRequest(_)));
+
+        // assistant 历史消息中 text 字段类型错误
+        let req = parse_request(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 256,
+            "messages": [
+                {"role": "user", "content": "Hello"},
+                {"role": "assistant", "content": [{"type": "text", "text": 42}]},
+                {"role": "user", "content": "Thanks"}
+            ]
+        }));
+        let err = convert_request(&req).unwrap_err();
+        assert_eq!(err.path(), Some("/messages/1/content/0"));
+
+        // 无法识别的块类型仍被忽略
+        let req = parse_request(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 256,
+            "messages": [{"role": "user", "content": [
+                {"type": "document", "source": {"type": "url", "url": "https://example.com/a.pdf"}},
+                {"type": "text", "text": "Hello"}
+            ]}]
+        }));
+        assert!(convert_request(&req).is_ok());
+    }
+
+    fn tool_turn_request(results: serde_json::Value) -> MessagesRequest {
+        parse_request(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 256,
+            "messages": [
+                {"role": "user", "content": "Check both files"},
+                {"role": "assistant", "content": [
+                    {"type": "tool_use", "id": "toolu_a", "name": "read", "input": {"path": "a"}},
+                    {"type": "tool_use", "id": "toolu_b", "name": "screenshot", "input": {}}
+                ]},
+                {"role": "user", "content": results}
+            ]
+        }))
+    }
+
+    #[test]
+    fn test_multiple_tool_results() {
+        let req = tool_turn_request(serde_json::json!([
+            {"type": "tool_result", "tool_use_id": "toolu_b", "content": "shot taken"},
+            {"type": "tool_result", "tool_use_id": "toolu_a", "is_error": true, "content": [
+                {"type": "text", "text": "permission denied"}
+            ]}
+        ]));
+        let result = convert_request(&req).unwrap();
+        let results = &result
+            .conversation_state
+            .current_message
+            .user_input_message
+            .user_input_message_context
+            .tool_results;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].tool_use_id, "toolu_b");
+        assert_eq!(results[0].content[0]["text"], "shot taken");
+        assert_eq!(results[1].tool_use_id, "toolu_a");
+        assert!(results[1].is_error);
+        assert_eq!(results[1].content[0]["text"], "permission denied");
+    }
+
+    #[test]
+    fn test_tool_result_with_image() {
+        const TINY_PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+        let req = tool_turn_request(serde_json::json!([
+            {"type": "tool_result", "tool_use_id": "toolu_a", "content": "contents of a"},
+            {"type": "tool_result", "tool_use_id": "toolu_b", "content": [
+                {"type": "text", "text": "Here is the screen"},
+                {"type": "image", "source": {
+                    "type": "base64", "media_type": "image/png", "data": TINY_PNG
+                }}
+            ]}
+        ]));
+        let result = convert_request(&req).unwrap();
+        let current = &result.conversation_state.current_message.user_input_message;
+        let results = &current.user_input_message_context.tool_results;
+        assert_eq!(results[1].content[0]["text"], "Here is the screen");
+        assert_eq!(current.images.len(), 1);
+        assert_eq!(current.images[0].format, "png");
+        assert_eq!(current.images[0].source.bytes, TINY_PNG);
+
+        // 结果中的无效图片报告其位置
+        let req = tool_turn_request(serde_json::json!([
+            {"type": "tool_result", "tool_use_id": "toolu_b", "content": [
+                {"type": "image", "source": {
+                    "type": "base64", "media_type": "image/png", "data": "???"
+                }}
+            ]}
+        ]));
+        let err = convert_request(&req).unwrap_err();
+        assert_eq!(err.path(), Some("/messages/2/content/0/content/0/source"));
+    }
+
+    #[test]
+    fn test_mismatched_tool_use_ids_are_rejected() {
+        let req = tool_turn_request(serde_json::json!([
+            {"type": "tool_result", "tool_use_id": "toolu_a", "content": "ok"},
+            {"type": "tool_result", "tool_use_id": "toolu_zzz", "content": "ok"}
+        ]));
+        let err = convert_request(&req).unwrap_err();
+        assert_eq!(err.path(), Some("/messages/2/content/1/tool_use_id"));
+        assert!(err.to_string().contains("toolu_zzz"));
+
+        // 同一个 tool_use 的重复结果
+        let req = tool_turn_request(serde_json::json!([
+            {"type": "tool_result", "tool_use_id": "toolu_a", "content": "ok"},
+            {"type": "tool_result", "tool_use_id": "toolu_a", "content": "again"}
+        ]));
+        let err = convert_request(&req).unwrap_err();
+        assert_eq!(err.path(), Some("/messages/2/content/1/tool_use_id"));
+
+        let req = tool_turn_request(serde_json::json!([
+            {"type": "tool_result", "content": "no id"}
+        ]));
+        let err = convert_request(&req).unwrap_err();
+        assert_eq!(err.path(), Some("/messages/2/content/0/tool_use_id"));
+        assert!(matches!(err.reason(), ConversionError::InvalidRequest(_)));
+    }
+
+    #[test]
+    fn test_consecutive_user_messages_are_merged() {
+        let req = parse_request(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 256,
+            "messages": [
+                {"role": "user", "content": "A"},
+                {"role": "user", "content": [{"type": "text", "text": "B"}]}
+            ]
+        }));
+
+        let result = convert_request_with(&req, true).unwrap();
+        let state = &result.conversation_state;
+        assert_eq!(state.current_message.user_input_message.content, "A\nB");
+        assert!(state.history.is_empty());
+
+        // 关闭合并时保持原有行为：只有最后一条作为当前消息
+        let result = convert_request_with(&req, false).unwrap();
+        let state = &result.conversation_state;
+        assert_eq!(state.current_message.user_input_message.content, "B");
+    }
+
+    #[test]
+    fn test_consecutive_history_messages_are_merged_in_order() {
+        let req = parse_request(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 256,
+            "messages": [
+                {"role": "user", "content": "first"},
+                {"role": "user", "content": "second"},
+                {"role": "assistant", "content": "Let me look."},
+                {"role": "assistant", "content": [
+                    {"type": "tool_use", "id": "toolu_a", "name": "read", "input": {"path": "a"}}
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "toolu_a", "content": "contents"}
+                ]}
+            ]
+        }));
+
+        let result = convert_request_with(&req, true).unwrap();
+        let history = &result.conversation_state.history;
+        assert_eq!(history.len(), 2);
+        match &history[0] {
+            Message::User(user) => assert_eq!(user.user_input_message.content, "first\nsecond"),
+            other => panic!("expected user message, got {:?}", other),
+        }
+        match &history[1] {
+            Message::Assistant(assistant) => {
+                let response = &assistant.assistant_response_message;
+                assert_eq!(response.content, "Let me look.");
+                let tool_uses = response.tool_uses.as_ref().unwrap();
+                assert_eq!(tool_uses.len(), 1);
+                assert_eq!(tool_uses[0].tool_use_id, "toolu_a");
+            }
+            other => panic!("expected assistant message, got {:?}", other),
+        }
+
+        // 不合并时第二条 assistant 之后才出现 tool_use，结果仍可配对
+        assert!(convert_request_with(&req, false).is_ok());
+    }
 }
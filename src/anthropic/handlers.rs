@@ -2,10 +2,12 @@
 
 use std::convert::Infallible;
 
-use crate::kiro::model::events::Event;
+use crate::kiro::finish_reason::{FinishReason, FinishTracker, StopSequenceTracker};
+use crate::kiro::model::events::{Event, ToolUseAccumulator};
 use crate::kiro::model::requests::kiro::KiroRequest;
-use crate::kiro::parser::decoder::EventStreamDecoder;
-use crate::token;
+use crate::kiro::parser::stream::StreamParser;
+use crate::kiro::response::KiroResponse;
+use crate::token::{self, UsageTracker};
 use axum::{
     Json as JsonExtractor,
     body::Body,
@@ -22,7 +24,9 @@ use uuid::Uuid;
 
 use super::converter::{ConversionError, convert_request};
 use super::middleware::AppState;
-use super::stream::{SseEvent, StreamContext};
+use super::stream::{
+    PrefillFilter, SseEvent, StreamContext, anthropic_error_type, text_coalescing,
+};
 use super::types::{
     CountTokensRequest, CountTokensResponse, ErrorResponse, MessagesRequest, Model, ModelsResponse,
 };
@@ -103,13 +107,19 @@ pub async fn post_messages(
     let conversion_result = match convert_request(&payload) {
         Ok(result) => result,
         Err(e) => {
-            let (error_type, message) = match &e {
+            let (error_type, message) = match e.reason() {
                 ConversionError::UnsupportedModel(model) => {
                     ("invalid_request_error", format!("模型不支持: {}", model))
                 }
                 ConversionError::EmptyMessages => {
                     ("invalid_request_error", "消息列表为空".to_string())
                 }
+                ConversionError::InvalidRequest(_)
+                | ConversionError::InvalidImage(_)
+                | ConversionError::ImageTooLarge { .. }
+                | ConversionError::UnsupportedToolChoice(_)
+                | ConversionError::InvalidParameter { .. }
+                | ConversionError::At { .. } => ("invalid_request_error", e.to_string()),
             };
             tracing::warn!("请求转换失败: {}", e);
             return (
@@ -120,10 +130,15 @@ pub async fn post_messages(
         }
     };
 
+    let prefill = conversion_result.prefill;
+    let stop_sequences = conversion_result.inference_config.stop_sequences.clone();
+
     // 构建 Kiro 请求
     let kiro_request = KiroRequest {
         conversation_state: conversion_result.conversation_state,
         profile_arn: state.profile_arn.clone(),
+        inference_config: Some(conversion_result.inference_config),
+        metadata: conversion_result.metadata,
     };
 
     let request_body = match serde_json::to_string(&kiro_request) {
@@ -166,11 +181,21 @@ pub async fn post_messages(
             &payload.model,
             input_tokens,
             thinking_enabled,
+            prefill,
+            stop_sequences,
         )
         .await
     } else {
         // 非流式响应
-        handle_non_stream_request(provider, &request_body, &payload.model, input_tokens).await
+        handle_non_stream_request(
+            provider,
+            &request_body,
+            &payload.model,
+            input_tokens,
+            prefill,
+            stop_sequences,
+        )
+        .await
     }
 }
 
@@ -181,6 +206,8 @@ async fn handle_stream_request(
     model: &str,
     input_tokens: i32,
     thinking_enabled: bool,
+    prefill: Option<String>,
+    stop_sequences: Vec<String>,
 ) -> Response {
     // 调用 Kiro API（支持多凭据故障转移）
     let response = match provider.call_api_stream(request_body).await {
@@ -199,7 +226,12 @@ async fn handle_stream_request(
     };
 
     // 创建流处理上下文
-    let mut ctx = StreamContext::new_with_thinking(model, input_tokens, thinking_enabled);
+    let mut ctx = StreamContext::new_with_thinking(model, input_tokens, thinking_enabled)
+        .with_prefill(prefill)
+        .with_stop_sequences(stop_sequences);
+    if let Some(coalescing) = text_coalescing() {
+        ctx = ctx.with_text_coalescing(coalescing);
+    }
 
     // 生成初始事件
     let initial_events = ctx.generate_initial_events();
@@ -227,87 +259,91 @@ fn create_ping_sse() -> Bytes {
 
 /// 创建 SSE 事件流
 fn create_sse_stream(
-    response: reqwest::Response,
+    response: KiroResponse,
     ctx: StreamContext,
     initial_events: Vec<SseEvent>,
 ) -> impl Stream<Item = Result<Bytes, Infallible>> {
-    // 先发送初始事件
-    let initial_stream = stream::iter(
-        initial_events
-            .into_iter()
-            .map(|e| Ok(Bytes::from(e.to_sse_string()))),
-    );
+    relay_sse_stream(response.bytes_stream(), ctx, initial_events)
+}
 
-    // 然后处理 Kiro 响应流，同时每25秒发送 ping 保活
-    let body_stream = response.bytes_stream();
+/// 将 SSE 事件转换为字节块
+fn sse_bytes(events: impl IntoIterator<Item = SseEvent>) -> Vec<Result<Bytes, Infallible>> {
+    events
+        .into_iter()
+        .map(|e| Ok(Bytes::from(e.to_sse_string())))
+        .collect()
+}
+
+/// 转发上游字节流为 SSE 事件流
+///
+/// 同时每 25 秒发送 ping 保活；启用文本合并时，缓冲的文本到期后即使上游暂无新事件也会输出
+fn relay_sse_stream<S, E>(
+    body_stream: S,
+    ctx: StreamContext,
+    initial_events: Vec<SseEvent>,
+) -> impl Stream<Item = Result<Bytes, Infallible>>
+where
+    S: Stream<Item = Result<Bytes, E>> + Send + 'static,
+    E: std::fmt::Display,
+{
+    // 先发送初始事件
+    let initial_stream = stream::iter(sse_bytes(initial_events));
 
     let processing_stream = stream::unfold(
-        (body_stream, ctx, EventStreamDecoder::new(), false, interval(Duration::from_secs(PING_INTERVAL_SECS))),
-        |(mut body_stream, mut ctx, mut decoder, finished, mut ping_interval)| async move {
+        (
+            Box::pin(body_stream),
+            ctx,
+            StreamParser::new(),
+            false,
+            interval(Duration::from_secs(PING_INTERVAL_SECS)),
+        ),
+        |(mut body_stream, mut ctx, mut parser, finished, mut ping_interval)| async move {
             if finished {
                 return None;
             }
+            let flush_at = ctx.buffered_text_deadline();
 
-            // 使用 select! 同时等待数据和 ping 定时器
+            // 使用 select! 同时等待数据、ping 定时器与合并文本的输出时间
             tokio::select! {
                 // 处理数据流
                 chunk_result = body_stream.next() => {
                     match chunk_result {
                         Some(Ok(chunk)) => {
                             // 解码事件
-                            if let Err(e) = decoder.feed(&chunk) {
-                                tracing::warn!("缓冲区溢出: {}", e);
-                            }
-
                             let mut events = Vec::new();
-                            for result in decoder.decode_iter() {
-                                match result {
-                                    Ok(frame) => {
-                                        if let Ok(event) = Event::from_frame(frame) {
-                                            let sse_events = ctx.process_kiro_event(&event);
-                                            events.extend(sse_events);
-                                        }
-                                    }
-                                    Err(e) => {
-                                        tracing::warn!("解码事件失败: {}", e);
-                                    }
-                                }
+                            for event in parser.push(&chunk) {
+                                let sse_events = ctx.process_kiro_event(&event);
+                                events.extend(sse_events);
                             }
 
-                            // 转换为 SSE 字节流
-                            let bytes: Vec<Result<Bytes, Infallible>> = events
-                                .into_iter()
-                                .map(|e| Ok(Bytes::from(e.to_sse_string())))
-                                .collect();
-
-                            Some((stream::iter(bytes), (body_stream, ctx, decoder, false, ping_interval)))
+                            Some((stream::iter(sse_bytes(events)), (body_stream, ctx, parser, false, ping_interval)))
                         }
                         Some(Err(e)) => {
                             tracing::error!("读取响应流失败: {}", e);
                             // 发送最终事件并结束
                             let final_events = ctx.generate_final_events();
-                            let bytes: Vec<Result<Bytes, Infallible>> = final_events
-                                .into_iter()
-                                .map(|e| Ok(Bytes::from(e.to_sse_string())))
-                                .collect();
-                            Some((stream::iter(bytes), (body_stream, ctx, decoder, true, ping_interval)))
+                            Some((stream::iter(sse_bytes(final_events)), (body_stream, ctx, parser, true, ping_interval)))
                         }
                         None => {
                             // 流结束，发送最终事件
+                            if let Err(e) = parser.finish() {
+                                tracing::warn!("响应流结束时存在未解析的数据: {}", e);
+                            }
                             let final_events = ctx.generate_final_events();
-                            let bytes: Vec<Result<Bytes, Infallible>> = final_events
-                                .into_iter()
-                                .map(|e| Ok(Bytes::from(e.to_sse_string())))
-                                .collect();
-                            Some((stream::iter(bytes), (body_stream, ctx, decoder, true, ping_interval)))
+                            Some((stream::iter(sse_bytes(final_events)), (body_stream, ctx, parser, true, ping_interval)))
                         }
                     }
                 }
+                // 输出到期的合并文本
+                _ = tokio::time::sleep_until(flush_at.unwrap_or_else(tokio::time::Instant::now)), if flush_at.is_some() => {
+                    let bytes = sse_bytes(ctx.flush_buffered_text());
+                    Some((stream::iter(bytes), (body_stream, ctx, parser, false, ping_interval)))
+                }
                 // 发送 ping 保活
                 _ = ping_interval.tick() => {
                     tracing::trace!("发送 ping 保活事件");
                     let bytes: Vec<Result<Bytes, Infallible>> = vec![Ok(create_ping_sse())];
-                    Some((stream::iter(bytes), (body_stream, ctx, decoder, false, ping_interval)))
+                    Some((stream::iter(bytes), (body_stream, ctx, parser, false, ping_interval)))
                 }
             }
         },
@@ -317,15 +353,14 @@ fn create_sse_stream(
     initial_stream.chain(processing_stream)
 }
 
-/// 上下文窗口大小（200k tokens）
-const CONTEXT_WINDOW_SIZE: i32 = 200_000;
-
 /// 处理非流式请求
 async fn handle_non_stream_request(
     provider: std::sync::Arc<crate::kiro::provider::KiroProvider>,
     request_body: &str,
     model: &str,
     input_tokens: i32,
+    prefill: Option<String>,
+    stop_sequences: Vec<String>,
 ) -> Response {
     // 调用 Kiro API（支持多凭据故障转移）
     let response = match provider.call_api(request_body).await {
@@ -360,95 +395,93 @@ async fn handle_non_stream_request(
     };
 
     // 解析事件流
-    let mut decoder = EventStreamDecoder::new();
-    if let Err(e) = decoder.feed(&body_bytes) {
-        tracing::warn!("缓冲区溢出: {}", e);
+    let mut parser = StreamParser::new();
+    let events = parser.push(&body_bytes);
+    if let Err(e) = parser.finish() {
+        tracing::warn!("响应体中存在未解析的数据: {}", e);
     }
 
     let mut text_content = String::new();
+    // 上游单独下发的思考内容，放入独立的 thinking 块
+    let mut thinking_content = String::new();
+    let mut thinking_signature: Option<String> = None;
     let mut tool_uses: Vec<serde_json::Value> = Vec::new();
-    let mut has_tool_use = false;
-    let mut stop_reason = "end_turn".to_string();
-    // 从 contextUsageEvent 计算的实际输入 tokens
-    let mut context_input_tokens: Option<i32> = None;
+    let mut finish = FinishTracker::new();
+    // 统计 token 用量（上游下发的用量优先于估算值）
+    let mut usage_tracker = UsageTracker::with_estimated_input(input_tokens);
 
     // 收集工具调用的增量 JSON
-    let mut tool_json_buffers: std::collections::HashMap<String, String> =
-        std::collections::HashMap::new();
-
-    for result in decoder.decode_iter() {
-        match result {
-            Ok(frame) => {
-                if let Ok(event) = Event::from_frame(frame) {
-                    match event {
-                        Event::AssistantResponse(resp) => {
-                            text_content.push_str(&resp.content);
-                        }
-                        Event::ToolUse(tool_use) => {
-                            has_tool_use = true;
-
-                            // 累积工具的 JSON 输入
-                            let buffer = tool_json_buffers
-                                .entry(tool_use.tool_use_id.clone())
-                                .or_insert_with(String::new);
-                            buffer.push_str(&tool_use.input);
-
-                            // 如果是完整的工具调用，添加到列表
-                            if tool_use.stop {
-                                let input: serde_json::Value = serde_json::from_str(buffer)
-                                    .unwrap_or_else(|e| {
-                                        tracing::warn!(
-                                            "工具输入 JSON 解析失败: {}, tool_use_id: {}, 原始内容: {}",
-                                            e, tool_use.tool_use_id, buffer
-                                        );
-                                        serde_json::json!({})
-                                    });
-
-                                tool_uses.push(json!({
-                                    "type": "tool_use",
-                                    "id": tool_use.tool_use_id,
-                                    "name": tool_use.name,
-                                    "input": input
-                                }));
-                            }
-                        }
-                        Event::ContextUsage(context_usage) => {
-                            // 从上下文使用百分比计算实际的 input_tokens
-                            // 公式: percentage * 200000 / 100 = percentage * 2000
-                            let actual_input_tokens = (context_usage.context_usage_percentage
-                                * (CONTEXT_WINDOW_SIZE as f64)
-                                / 100.0)
-                                as i32;
-                            context_input_tokens = Some(actual_input_tokens);
-                            tracing::debug!(
-                                "收到 contextUsageEvent: {}%, 计算 input_tokens: {}",
-                                context_usage.context_usage_percentage,
-                                actual_input_tokens
-                            );
-                        }
-                        Event::Exception { exception_type, .. } => {
-                            if exception_type == "ContentLengthExceededException" {
-                                stop_reason = "max_tokens".to_string();
-                            }
-                        }
-                        _ => {}
-                    }
+    let mut tool_accumulator = ToolUseAccumulator::new();
+    // 去除续写时重复输出的 prefill
+    let mut prefill = prefill.map(PrefillFilter::new);
+
+    for event in events {
+        usage_tracker.observe(&event);
+        finish.observe(&event);
+
+        match event {
+            Event::AssistantResponse(resp) => match &mut prefill {
+                Some(filter) => text_content.push_str(&filter.push(&resp.content)),
+                None => text_content.push_str(&resp.content),
+            },
+            Event::ThinkingDelta(thinking) => {
+                thinking_content.push_str(&thinking.text);
+                thinking_signature = thinking.signature.or(thinking_signature);
+            }
+            Event::ToolUse(tool_use) => {
+                // 如果是完整的工具调用，添加到列表
+                if let Some(tool_use) = tool_accumulator.push(&tool_use) {
+                    tool_uses.push(tool_use.to_content_block());
                 }
             }
-            Err(e) => {
-                tracing::warn!("解码事件失败: {}", e);
+            Event::Error { code, message } => {
+                tracing::error!("收到错误事件: {} - {}", code, message);
+                let error_type = anthropic_error_type(&code);
+                let status = match error_type {
+                    "rate_limit_error" => StatusCode::TOO_MANY_REQUESTS,
+                    "invalid_request_error" => StatusCode::BAD_REQUEST,
+                    "permission_error" => StatusCode::FORBIDDEN,
+                    _ => StatusCode::BAD_GATEWAY,
+                };
+                return (status, Json(ErrorResponse::new(error_type, message))).into_response();
             }
+            _ => {}
         }
     }
 
-    // 确定 stop_reason
-    if has_tool_use && stop_reason == "end_turn" {
-        stop_reason = "tool_use".to_string();
+    if let Some(filter) = &mut prefill {
+        text_content.push_str(&filter.finish());
     }
 
+    // 上游错误已在上面直接返回
+    // 正常结束且文本以停止序列结尾时报告该序列
+    let stop_sequence = match finish.reason() {
+        FinishReason::Stop => {
+            let mut tracker = StopSequenceTracker::new(stop_sequences);
+            tracker.push(&text_content);
+            tracker.matched().map(str::to_string)
+        }
+        _ => None,
+    };
+    let stop_reason = match stop_sequence {
+        Some(_) => "stop_sequence",
+        None => finish.reason().anthropic().unwrap_or("end_turn"),
+    };
+
     // 构建响应内容
     let mut content: Vec<serde_json::Value> = Vec::new();
 
+    if !thinking_content.is_empty() {
+        let mut block = json!({
+            "type": "thinking",
+            "thinking": thinking_content
+        });
+        if let Some(signature) = thinking_signature {
+            block["signature"] = json!(signature);
+        }
+        content.push(block);
+    }
+
     if !text_content.is_empty() {
         content.push(json!({
             "type": "text",
@@ -458,11 +491,10 @@ async fn handle_non_stream_request(
 
     content.extend(tool_uses);
 
-    // 估算输出 tokens
-    let output_tokens = token::estimate_output_tokens(&content);
-
-    // 使用从 contextUsageEvent 计算的 input_tokens，如果没有则使用估算值
-    let final_input_tokens = context_input_tokens.unwrap_or(input_tokens);
+    let usage = usage_tracker.usage();
+    if usage.estimated {
+        tracing::debug!("上游未下发完整用量，返回估算值: {:?}", usage);
+    }
 
     // 构建 Anthropic 响应
     let response_body = json!({
@@ -472,10 +504,10 @@ async fn handle_non_stream_request(
         "content": content,
         "model": model,
         "stop_reason": stop_reason,
-        "stop_sequence": null,
+        "stop_sequence": stop_sequence,
         "usage": {
-            "input_tokens": final_input_tokens,
-            "output_tokens": output_tokens
+            "input_tokens": usage.input_tokens,
+            "output_tokens": usage.output_tokens
         }
     });
 
@@ -505,3 +537,46 @@ pub async fn count_tokens(
         input_tokens: total_tokens.max(1) as i32,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anthropic::stream::TextCoalescing;
+    use crate::test_support::encode_event;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_coalesced_text_flushes_while_upstream_is_idle() {
+        let (tx, rx) = futures::channel::mpsc::unbounded::<Result<Bytes, std::io::Error>>();
+        let mut ctx =
+            StreamContext::new_with_thinking("m", 1, false).with_text_coalescing(TextCoalescing {
+                max_bytes: 1024,
+                max_delay: Duration::from_millis(50),
+            });
+        let initial_events = ctx.generate_initial_events();
+        let mut sse = Box::pin(relay_sse_stream(rx, ctx, initial_events));
+
+        tx.unbounded_send(Ok(Bytes::from(encode_event(
+            "assistantResponseEvent",
+            r#"{"content":"Hello"}"#,
+        ))))
+        .unwrap();
+        let started = tokio::time::Instant::now();
+
+        // 上游保持空闲（发送端未关闭），缓冲的文本到期后仍应输出
+        let next_text = async {
+            loop {
+                let bytes = sse.next().await.unwrap().unwrap();
+                let frame = String::from_utf8(bytes.to_vec()).unwrap();
+                if frame.contains("text_delta") {
+                    return frame;
+                }
+            }
+        };
+        let frame = tokio::time::timeout(Duration::from_secs(1), next_text)
+            .await
+            .expect("缓冲的文本未按时输出");
+        assert!(frame.contains("Hello"));
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        drop(tx);
+    }
+}
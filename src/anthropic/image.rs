@@ -0,0 +1,178 @@
+//! 图片内容处理
+//!
+//! 解码并校验 base64 图片，转换为 Kiro 请求中的图片字段
+
+use std::sync::OnceLock;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+use crate::kiro::model::requests::conversation::KiroImage;
+
+use super::converter::ConversionError;
+
+/// 单张图片默认的最大字节数（解码后，5MB）
+pub const DEFAULT_MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+
+/// 全局图片大小限制
+static MAX_IMAGE_BYTES: OnceLock<usize> = OnceLock::new();
+
+/// 初始化图片大小限制
+///
+/// 应在应用启动时调用一次
+pub fn init_max_image_bytes(limit: usize) {
+    let _ = MAX_IMAGE_BYTES.set(limit);
+}
+
+/// 获取图片大小限制
+pub(crate) fn max_image_bytes() -> usize {
+    MAX_IMAGE_BYTES
+        .get()
+        .copied()
+        .unwrap_or(DEFAULT_MAX_IMAGE_BYTES)
+}
+
+/// 从 media_type 获取图片格式
+fn image_format(media_type: &str) -> Option<&'static str> {
+    match media_type {
+        "image/jpeg" => Some("jpeg"),
+        "image/png" => Some("png"),
+        "image/gif" => Some("gif"),
+        "image/webp" => Some("webp"),
+        _ => None,
+    }
+}
+
+/// 根据文件头判断图片数据是否与声明的格式一致
+fn matches_format(format: &str, bytes: &[u8]) -> bool {
+    match format {
+        "png" => bytes.starts_with(b"\x89PNG\r\n\x1a\n"),
+        "jpeg" => bytes.starts_with(&[0xFF, 0xD8, 0xFF]),
+        "gif" => bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a"),
+        "webp" => bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP",
+        _ => false,
+    }
+}
+
+/// 根据 base64 编码长度计算解码后的字节数（忽略空白字符）
+fn decoded_len(data: &str) -> usize {
+    let encoded = data.bytes().filter(|b| !b.is_ascii_whitespace()).count();
+    let padding = data
+        .bytes()
+        .rev()
+        .filter(|b| !b.is_ascii_whitespace())
+        .take(2)
+        .take_while(|&b| b == b'=')
+        .count();
+    (encoded / 4 * 3 + (encoded % 4) * 3 / 4).saturating_sub(padding)
+}
+
+/// 解码并校验 base64 图片
+///
+/// 校验 media_type 是否受支持、数据是否为合法 base64、大小是否超过 `limit`，
+/// 以及文件头是否与声明的格式一致。大小先按编码长度预估，超限时不做解码
+pub(crate) fn decode_image(
+    media_type: &str,
+    data: &str,
+    limit: usize,
+) -> Result<KiroImage, ConversionError> {
+    let format = image_format(media_type).ok_or_else(|| {
+        ConversionError::InvalidImage(format!(
+            "不支持的图片类型: {}（仅支持 png/jpeg/webp/gif）",
+            media_type
+        ))
+    })?;
+
+    let size = decoded_len(data);
+    if size > limit {
+        return Err(ConversionError::ImageTooLarge { size, limit });
+    }
+
+    // 兼容带换行的 base64 数据
+    let data: String = data.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    let bytes = STANDARD
+        .decode(&data)
+        .map_err(|e| ConversionError::InvalidImage(format!("图片 base64 解码失败: {}", e)))?;
+
+    if bytes.len() > limit {
+        return Err(ConversionError::ImageTooLarge {
+            size: bytes.len(),
+            limit,
+        });
+    }
+
+    if !matches_format(format, &bytes) {
+        return Err(ConversionError::InvalidImage(format!(
+            "图片数据与声明的类型 {} 不符",
+            media_type
+        )));
+    }
+
+    Ok(KiroImage::from_base64(format, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 1x1 透明 PNG
+    const TINY_PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+
+    #[test]
+    fn test_decode_valid_png() {
+        let image = decode_image("image/png", TINY_PNG, DEFAULT_MAX_IMAGE_BYTES).unwrap();
+        assert_eq!(image.format, "png");
+        assert_eq!(image.source.bytes, TINY_PNG);
+    }
+
+    #[test]
+    fn test_unsupported_media_type() {
+        let err = decode_image("image/bmp", TINY_PNG, DEFAULT_MAX_IMAGE_BYTES).unwrap_err();
+        assert!(matches!(err, ConversionError::InvalidImage(_)));
+        assert!(err.to_string().contains("image/bmp"));
+    }
+
+    #[test]
+    fn test_invalid_base64_and_format_mismatch() {
+        let err = decode_image("image/png", "not base64!", DEFAULT_MAX_IMAGE_BYTES).unwrap_err();
+        assert!(matches!(err, ConversionError::InvalidImage(_)));
+
+        let err = decode_image("image/jpeg", TINY_PNG, DEFAULT_MAX_IMAGE_BYTES).unwrap_err();
+        assert!(matches!(err, ConversionError::InvalidImage(_)));
+    }
+
+    #[test]
+    fn test_image_too_large() {
+        let err = decode_image("image/png", TINY_PNG, 16).unwrap_err();
+        assert!(matches!(
+            err,
+            ConversionError::ImageTooLarge { limit: 16, .. }
+        ));
+    }
+
+    #[test]
+    fn test_decoded_len() {
+        assert_eq!(
+            decoded_len(TINY_PNG),
+            STANDARD.decode(TINY_PNG).unwrap().len()
+        );
+        assert_eq!(decoded_len("QUJD"), 3);
+        assert_eq!(decoded_len("QUI=\n"), 2);
+        assert_eq!(decoded_len("QQ=="), 1);
+        assert_eq!(decoded_len(""), 0);
+    }
+
+    #[test]
+    fn test_oversized_image_rejected_before_decode() {
+        // 非法 base64：若先解码会报告 InvalidImage，提前按长度拒绝则报告 ImageTooLarge
+        let data = "!".repeat(4 * 1024);
+        let err = decode_image("image/png", &data, 1024).unwrap_err();
+        assert!(matches!(
+            err,
+            ConversionError::ImageTooLarge {
+                size: 3072,
+                limit: 1024
+            }
+        ));
+    }
+}
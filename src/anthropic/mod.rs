@@ -6,6 +6,7 @@
 //! - `GET /v1/models` - 获取可用模型列表
 //! - `POST /v1/messages` - 创建消息（对话）
 //! - `POST /v1/messages/count_tokens` - 计算 token 数量
+//! - `POST /v1/chat/completions` - OpenAI 兼容的对话补全
 //!
 //! # 使用示例
 //! ```rust,ignore
@@ -16,11 +17,13 @@
 //! axum::serve(listener, app).await?;
 //! ```
 
-mod converter;
+pub(crate) mod converter;
 mod handlers;
+pub(crate) mod image;
 mod middleware;
 mod router;
-mod stream;
+pub(crate) mod stream;
 pub mod types;
 
+pub(crate) use middleware::AppState;
 pub use router::create_router_with_provider;
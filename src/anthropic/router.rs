@@ -6,6 +6,7 @@ use axum::{
 };
 
 use crate::kiro::provider::KiroProvider;
+use crate::openai::handlers::post_chat_completions;
 
 use super::{
     handlers::{count_tokens, get_models, post_messages},
@@ -18,6 +19,7 @@ use super::{
 /// - `GET /v1/models` - 获取可用模型列表
 /// - `POST /v1/messages` - 创建消息（对话）
 /// - `POST /v1/messages/count_tokens` - 计算 token 数量
+/// - `POST /v1/chat/completions` - OpenAI 兼容的对话补全
 ///
 /// # 认证
 /// 所有 `/v1` 路径需要 API Key 认证，支持：
@@ -47,6 +49,7 @@ pub fn create_router_with_provider(
         .route("/models", get(get_models))
         .route("/messages", post(post_messages))
         .route("/messages/count_tokens", post(count_tokens))
+        .route("/chat/completions", post(post_chat_completions))
         .layer(middleware::from_fn_with_state(
             state.clone(),
             auth_middleware,
@@ -2,12 +2,18 @@
 //!
 //! 实现 Kiro → Anthropic 流式响应转换和 SSE 状态管理
 
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::Duration;
 
 use serde_json::json;
+use tokio::time::Instant;
 use uuid::Uuid;
 
-use crate::kiro::model::events::Event;
+use crate::kiro::finish_reason::{FinishReason, FinishTracker, StopSequenceTracker};
+use crate::kiro::model::events::{Event, ReasoningContentEvent};
+use crate::kiro::parser::stream::ParsedEvent;
 
 /// 找到小于等于目标位置的最近有效UTF-8字符边界
 ///
@@ -233,10 +239,12 @@ pub struct SseStateManager {
     message_ended: bool,
     /// 下一个块索引
     next_block_index: i32,
-    /// 当前 stop_reason
-    stop_reason: Option<String>,
+    /// 结束原因跟踪
+    finish: FinishTracker,
     /// 是否有工具调用
     has_tool_use: bool,
+    /// 文本结尾命中的停止序列
+    stop_sequence: Option<String>,
 }
 
 impl Default for SseStateManager {
@@ -253,8 +261,9 @@ impl SseStateManager {
             active_blocks: HashMap::new(),
             message_ended: false,
             next_block_index: 0,
-            stop_reason: None,
+            finish: FinishTracker::new(),
             has_tool_use: false,
+            stop_sequence: None,
         }
     }
 
@@ -277,20 +286,34 @@ impl SseStateManager {
         self.has_tool_use = has;
     }
 
-    /// 设置 stop_reason
-    pub fn set_stop_reason(&mut self, reason: impl Into<String>) {
-        self.stop_reason = Some(reason.into());
+    /// 记录上游事件，用于确定结束原因
+    pub fn observe(&mut self, event: &Event) {
+        self.finish.observe(event);
+    }
+
+    /// 记录文本结尾命中的停止序列
+    pub fn set_stop_sequence(&mut self, stop_sequence: Option<String>) {
+        self.stop_sequence = stop_sequence;
+    }
+
+    /// 因命中停止序列而正常结束时返回该序列
+    fn matched_stop_sequence(&self) -> Option<&str> {
+        match self.finish.reason() {
+            FinishReason::Stop if !self.has_tool_use => self.stop_sequence.as_deref(),
+            _ => None,
+        }
     }
 
     /// 获取最终的 stop_reason
     pub fn get_stop_reason(&self) -> String {
-        if let Some(ref reason) = self.stop_reason {
-            reason.clone()
-        } else if self.has_tool_use {
-            "tool_use".to_string()
-        } else {
-            "end_turn".to_string()
+        if self.matched_stop_sequence().is_some() {
+            return "stop_sequence".to_string();
         }
+        let reason = match self.finish.reason() {
+            FinishReason::Stop if self.has_tool_use => FinishReason::ToolUse,
+            reason => reason,
+        };
+        reason.anthropic().unwrap_or("end_turn").to_string()
     }
 
     /// 处理 message_start 事件
@@ -423,7 +446,7 @@ impl SseStateManager {
                     "type": "message_delta",
                     "delta": {
                         "stop_reason": self.get_stop_reason(),
-                        "stop_sequence": null
+                        "stop_sequence": self.matched_stop_sequence()
                     },
                     "usage": {
                         "input_tokens": input_tokens,
@@ -446,9 +469,107 @@ impl SseStateManager {
     }
 }
 
+/// 将上游错误代码映射为 Anthropic 错误类型
+pub(crate) fn anthropic_error_type(code: &str) -> &'static str {
+    match code {
+        "ThrottlingException" | "ServiceQuotaExceededException" => "rate_limit_error",
+        "ValidationException" => "invalid_request_error",
+        "AccessDeniedException" => "permission_error",
+        _ => "api_error",
+    }
+}
+
 /// 上下文窗口大小（200k tokens）
 const CONTEXT_WINDOW_SIZE: i32 = 200_000;
 
+/// 去除模型续写 prefill 时重复输出的 prefill 文本
+///
+/// 输出开头与 prefill 一致时先缓冲，确认完整重复后丢弃这一段，
+/// 一旦出现不一致的内容便原样放行，之后不再处理
+#[derive(Debug, Clone)]
+pub struct PrefillFilter {
+    prefill: String,
+    buffer: String,
+    done: bool,
+}
+
+impl PrefillFilter {
+    pub fn new(prefill: impl Into<String>) -> Self {
+        let prefill = prefill.into();
+        Self {
+            done: prefill.is_empty(),
+            prefill,
+            buffer: String::new(),
+        }
+    }
+
+    /// 处理一段文本增量，返回可以输出的文本（仍在比对时为空）
+    pub fn push<'a>(&mut self, text: &'a str) -> Cow<'a, str> {
+        if self.done {
+            return Cow::Borrowed(text);
+        }
+
+        self.buffer.push_str(text);
+        if self.buffer.len() < self.prefill.len() && self.prefill.starts_with(&self.buffer) {
+            return Cow::Borrowed("");
+        }
+
+        self.done = true;
+        let buffer = std::mem::take(&mut self.buffer);
+        match buffer.strip_prefix(&self.prefill) {
+            Some(rest) => Cow::Owned(rest.to_string()),
+            None => Cow::Owned(buffer),
+        }
+    }
+
+    /// 结束比对，取出仍在缓冲中的文本（输出只是 prefill 的一部分时原样保留）
+    pub fn finish(&mut self) -> String {
+        self.done = true;
+        std::mem::take(&mut self.buffer)
+    }
+}
+
+/// 文本增量合并阈值
+///
+/// 缓冲的文本达到 `max_bytes` 字节，或距第一段缓冲文本超过 `max_delay` 时输出；
+/// 时间阈值由转发循环按 `StreamContext::buffered_text_deadline` 定时检查
+#[derive(Debug, Clone, Copy)]
+pub struct TextCoalescing {
+    pub max_bytes: usize,
+    pub max_delay: Duration,
+}
+
+impl Default for TextCoalescing {
+    fn default() -> Self {
+        Self {
+            max_bytes: 256,
+            max_delay: Duration::from_millis(50),
+        }
+    }
+}
+
+/// 全局文本增量合并配置（未设置时不合并）
+static TEXT_COALESCING: OnceLock<TextCoalescing> = OnceLock::new();
+
+/// 初始化流式响应的文本增量合并
+///
+/// 应在应用启动时调用一次；未初始化时每个增量单独输出
+pub fn init_text_coalescing(coalescing: TextCoalescing) {
+    let _ = TEXT_COALESCING.set(coalescing);
+}
+
+/// 当前的文本增量合并配置
+pub(crate) fn text_coalescing() -> Option<TextCoalescing> {
+    TEXT_COALESCING.get().copied()
+}
+
+/// 尚未输出的合并文本
+struct PendingText {
+    index: i32,
+    text: String,
+    since: Instant,
+}
+
 /// 流处理上下文
 pub struct StreamContext {
     /// SSE 状态管理器
@@ -477,6 +598,18 @@ pub struct StreamContext {
     pub thinking_block_index: Option<i32>,
     /// 文本块索引（thinking 启用时动态分配）
     pub text_block_index: Option<i32>,
+    /// 上游单独下发的思考内容（reasoningContentEvent）所在的 thinking 块索引
+    pub reasoning_block_index: Option<i32>,
+    /// 是否已向客户端发送 error 事件（此后不再发送结束事件）
+    pub error_sent: bool,
+    /// 请求以 assistant 消息结尾时，去除输出中重复的 prefill
+    pub prefill: Option<PrefillFilter>,
+    /// 文本增量合并阈值（未设置时每个增量单独输出）
+    pub coalescing: Option<TextCoalescing>,
+    /// 已合并、尚未输出的文本增量
+    pending_text: Option<PendingText>,
+    /// 停止序列跟踪，用于填充 `stop_sequence`
+    stop_sequences: StopSequenceTracker,
 }
 
 impl StreamContext {
@@ -500,9 +633,36 @@ impl StreamContext {
             thinking_extracted: false,
             thinking_block_index: None,
             text_block_index: None,
+            reasoning_block_index: None,
+            error_sent: false,
+            prefill: None,
+            coalescing: None,
+            pending_text: None,
+            stop_sequences: StopSequenceTracker::default(),
         }
     }
 
+    /// 设置请求的 prefill 文本
+    pub fn with_prefill(mut self, prefill: Option<String>) -> Self {
+        self.prefill = prefill.map(PrefillFilter::new);
+        self
+    }
+
+    /// 设置请求中的停止序列，文本以其中之一结尾时在 message_delta 中报告
+    pub fn with_stop_sequences(mut self, stop_sequences: Vec<String>) -> Self {
+        self.stop_sequences = StopSequenceTracker::new(stop_sequences);
+        self
+    }
+
+    /// 合并相邻的文本增量后再输出，减少 SSE 帧数量
+    ///
+    /// 只影响分帧，输出的文本内容不变；工具调用、thinking 等其他事件
+    /// 以及流结束前会先输出已缓冲的文本
+    pub fn with_text_coalescing(mut self, coalescing: TextCoalescing) -> Self {
+        self.coalescing = Some(coalescing);
+        self
+    }
+
     /// 生成 message_start 事件
     pub fn create_message_start_event(&self) -> serde_json::Value {
         json!({
@@ -564,9 +724,88 @@ impl StreamContext {
 
     /// 处理 Kiro 事件并转换为 Anthropic SSE 事件
     pub fn process_kiro_event(&mut self, event: &Event) -> Vec<SseEvent> {
+        let events = self.convert_kiro_event(event);
+        self.coalesce(events)
+    }
+
+    /// 输出已合并但尚未发送的文本增量
+    pub fn flush_buffered_text(&mut self) -> Option<SseEvent> {
+        let pending = self.pending_text.take()?;
+        Some(SseEvent::new(
+            "content_block_delta",
+            json!({
+                "type": "content_block_delta",
+                "index": pending.index,
+                "delta": {
+                    "type": "text_delta",
+                    "text": pending.text
+                }
+            }),
+        ))
+    }
+
+    /// 缓冲的文本应当输出的时间，没有缓冲文本时返回 `None`
+    pub fn buffered_text_deadline(&self) -> Option<Instant> {
+        let coalescing = self.coalescing?;
+        let pending = self.pending_text.as_ref()?;
+        Some(pending.since + coalescing.max_delay)
+    }
+
+    /// 按合并阈值缓冲文本增量，遇到其他事件时先输出缓冲的文本
+    fn coalesce(&mut self, events: Vec<SseEvent>) -> Vec<SseEvent> {
+        let Some(coalescing) = self.coalescing else {
+            return events;
+        };
+
+        let mut output = Vec::with_capacity(events.len());
+        for event in events {
+            match text_delta(&event) {
+                Some((index, text)) => {
+                    if self.pending_text.as_ref().is_some_and(|p| p.index != index) {
+                        output.extend(self.flush_buffered_text());
+                    }
+                    self.pending_text
+                        .get_or_insert_with(|| PendingText {
+                            index,
+                            text: String::new(),
+                            since: Instant::now(),
+                        })
+                        .text
+                        .push_str(text);
+                }
+                None => {
+                    output.extend(self.flush_buffered_text());
+                    output.push(event);
+                }
+            }
+        }
+
+        if self.pending_text.as_ref().is_some_and(|p| {
+            p.text.len() >= coalescing.max_bytes || p.since.elapsed() >= coalescing.max_delay
+        }) {
+            output.extend(self.flush_buffered_text());
+        }
+        output
+    }
+
+    fn convert_kiro_event(&mut self, event: &Event) -> Vec<SseEvent> {
+        self.state_manager.observe(event);
+
         match event {
-            Event::AssistantResponse(resp) => self.process_assistant_response(&resp.content),
-            Event::ToolUse(tool_use) => self.process_tool_use(tool_use),
+            Event::AssistantResponse(resp) => {
+                let content = match &mut self.prefill {
+                    Some(filter) => filter.push(&resp.content),
+                    None => Cow::Borrowed(resp.content.as_str()),
+                };
+                self.process_assistant_response(&content)
+            }
+            Event::ThinkingDelta(thinking) => self.process_thinking_delta(thinking),
+            Event::ToolUse(tool_use) => {
+                self.stop_sequences.reset();
+                let mut events = self.flush_prefill();
+                events.extend(self.process_tool_use(tool_use));
+                events
+            }
             Event::ContextUsage(context_usage) => {
                 // 从上下文使用百分比计算实际的 input_tokens
                 // 公式: percentage * 200000 / 100 = percentage * 2000
@@ -581,21 +820,34 @@ impl StreamContext {
                 );
                 Vec::new()
             }
-            Event::Error {
-                error_code,
-                error_message,
-            } => {
-                tracing::error!("收到错误事件: {} - {}", error_code, error_message);
+            Event::Usage { input, output } => {
+                // 上游下发的权威用量（累计值）覆盖估算值
+                if let Some(input_tokens) = *input {
+                    self.context_input_tokens = Some(input_tokens);
+                }
+                if let Some(output_tokens) = *output {
+                    self.output_tokens = output_tokens;
+                }
                 Vec::new()
             }
+            Event::Error { code, message } => {
+                tracing::error!("收到错误事件: {} - {}", code, message);
+                self.error_sent = true;
+                vec![SseEvent::new(
+                    "error",
+                    json!({
+                        "type": "error",
+                        "error": {
+                            "type": anthropic_error_type(code),
+                            "message": message
+                        }
+                    }),
+                )]
+            }
             Event::Exception {
                 exception_type,
                 message,
             } => {
-                // 处理 ContentLengthExceededException
-                if exception_type == "ContentLengthExceededException" {
-                    self.state_manager.set_stop_reason("max_tokens");
-                }
                 tracing::warn!("收到异常事件: {} - {}", exception_type, message);
                 Vec::new()
             }
@@ -603,11 +855,20 @@ impl StreamContext {
         }
     }
 
+    /// 输出仍在与 prefill 比对时，放行已缓冲的文本
+    fn flush_prefill(&mut self) -> Vec<SseEvent> {
+        match self.prefill.as_mut().map(PrefillFilter::finish) {
+            Some(text) => self.process_assistant_response(&text),
+            None => Vec::new(),
+        }
+    }
+
     /// 处理助手响应事件
     fn process_assistant_response(&mut self, content: &str) -> Vec<SseEvent> {
         if content.is_empty() {
             return Vec::new();
         }
+        self.stop_sequences.push(content);
 
         // 估算 tokens
         self.output_tokens += estimate_tokens(content);
@@ -622,6 +883,63 @@ impl StreamContext {
         self.create_text_delta_events(content)
     }
 
+    /// 处理上游单独下发的思考内容，输出到独立的 thinking 块，不与回答文本混合
+    fn process_thinking_delta(&mut self, thinking: &ReasoningContentEvent) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        let open = self
+            .reasoning_block_index
+            .filter(|&idx| self.state_manager.is_block_open_of_type(idx, "thinking"));
+        let index = match open {
+            Some(idx) => idx,
+            None => {
+                // 先关闭正在输出的文本块，thinking 块按到达顺序排在其后
+                if let Some(idx) = self.text_block_index.take() {
+                    events.extend(self.state_manager.handle_content_block_stop(idx));
+                }
+                let idx = self.state_manager.next_block_index();
+                self.reasoning_block_index = Some(idx);
+                events.extend(self.state_manager.handle_content_block_start(
+                    idx,
+                    "thinking",
+                    json!({
+                        "type": "content_block_start",
+                        "index": idx,
+                        "content_block": {
+                            "type": "thinking",
+                            "thinking": ""
+                        }
+                    }),
+                ));
+                idx
+            }
+        };
+
+        if !thinking.text.is_empty() {
+            self.output_tokens += estimate_tokens(&thinking.text);
+            events.push(self.create_thinking_delta_event(index, &thinking.text));
+        }
+        if let Some(signature) = &thinking.signature {
+            events.push(SseEvent::new(
+                "content_block_delta",
+                json!({
+                    "type": "content_block_delta",
+                    "index": index,
+                    "delta": {
+                        "type": "signature_delta",
+                        "signature": signature
+                    }
+                }),
+            ));
+        }
+        events
+    }
+
+    /// 关闭 `process_thinking_delta` 打开的 thinking 块
+    fn close_reasoning_block(&mut self) -> Option<SseEvent> {
+        let index = self.reasoning_block_index.take()?;
+        self.state_manager.handle_content_block_stop(index)
+    }
+
     /// 处理包含thinking块的内容
     fn process_content_with_thinking(&mut self, content: &str) -> Vec<SseEvent> {
         let mut events = Vec::new();
@@ -750,7 +1068,7 @@ impl StreamContext {
     ///
     /// 返回值包含可能的 content_block_start 事件和 content_block_delta 事件。
     fn create_text_delta_events(&mut self, text: &str) -> Vec<SseEvent> {
-        let mut events = Vec::new();
+        let mut events: Vec<SseEvent> = self.close_reasoning_block().into_iter().collect();
 
         // 如果当前 text_block_index 指向的块已经被关闭（例如 tool_use 开始时自动 stop），
         // 则丢弃该索引并创建新的文本块继续输出，避免 delta 被状态机拒绝导致“吞字”。
@@ -823,7 +1141,7 @@ impl StreamContext {
         &mut self,
         tool_use: &crate::kiro::model::events::ToolUseEvent,
     ) -> Vec<SseEvent> {
-        let mut events = Vec::new();
+        let mut events: Vec<SseEvent> = self.close_reasoning_block().into_iter().collect();
 
         self.state_manager.set_has_tool_use(true);
 
@@ -879,7 +1197,7 @@ impl StreamContext {
             events.extend(self.create_text_delta_events(&buffered));
         }
 
-        // 获取或分配块索引
+        // 按 tool_use_id 获取或分配块索引，交错到达的多个工具调用各自写入自己的块
         let block_index = if let Some(&idx) = self.tool_block_indices.get(&tool_use.tool_use_id) {
             idx
         } else {
@@ -937,7 +1255,14 @@ impl StreamContext {
 
     /// 生成最终事件序列
     pub fn generate_final_events(&mut self) -> Vec<SseEvent> {
-        let mut events = Vec::new();
+        let mut events: Vec<SseEvent> = self.flush_buffered_text().into_iter().collect();
+
+        // error 事件已终止本次响应
+        if self.error_sent {
+            return events;
+        }
+
+        events.extend(self.flush_prefill());
 
         // Flush thinking_buffer 中的剩余内容
         if self.thinking_enabled && !self.thinking_buffer.is_empty() {
@@ -1005,6 +1330,8 @@ impl StreamContext {
         let final_input_tokens = self.context_input_tokens.unwrap_or(self.input_tokens);
 
         // 生成最终事件
+        let stop_sequence = self.stop_sequences.matched().map(str::to_string);
+        self.state_manager.set_stop_sequence(stop_sequence);
         events.extend(
             self.state_manager
                 .generate_final_events(final_input_tokens, self.output_tokens),
@@ -1013,6 +1340,73 @@ impl StreamContext {
     }
 }
 
+/// Anthropic 流式序列化状态
+///
+/// 在 `StreamContext` 之上按需补发 `message_start`，输出带 `event:` 行的 SSE 帧
+#[allow(dead_code)]
+pub struct AnthropicSerState {
+    ctx: StreamContext,
+    started: bool,
+}
+
+impl AnthropicSerState {
+    #[allow(dead_code)]
+    pub fn new(model: impl Into<String>, input_tokens: i32) -> Self {
+        Self {
+            ctx: StreamContext::new_with_thinking(model, input_tokens, false),
+            started: false,
+        }
+    }
+
+    /// 设置请求中的停止序列，见 `StreamContext::with_stop_sequences`
+    #[allow(dead_code)]
+    pub fn with_stop_sequences(mut self, stop_sequences: Vec<String>) -> Self {
+        self.ctx = self.ctx.with_stop_sequences(stop_sequences);
+        self
+    }
+
+    /// 本次响应的消息 ID（`msg_` 前缀）
+    #[allow(dead_code)]
+    pub fn message_id(&self) -> &str {
+        &self.ctx.message_id
+    }
+
+    /// 首次调用时生成 `message_start` 与初始文本块
+    fn start(&mut self) -> Vec<SseEvent> {
+        if std::mem::replace(&mut self.started, true) {
+            return Vec::new();
+        }
+        self.ctx.generate_initial_events()
+    }
+}
+
+/// 将单个事件序列化为 Anthropic 命名 SSE 帧（首次调用时包含 `message_start`）
+#[allow(dead_code)]
+pub fn to_anthropic_sse(event: &ParsedEvent, state: &mut AnthropicSerState) -> Vec<String> {
+    let mut events = state.start();
+    events.extend(state.ctx.process_kiro_event(event));
+    events.iter().map(SseEvent::to_sse_string).collect()
+}
+
+/// 生成结束帧：关闭未结束的内容块，输出 `message_delta` 与 `message_stop`
+///
+/// 上游返回错误时已输出 error 帧，不再输出结束帧
+#[allow(dead_code)]
+pub fn finish_anthropic_sse(state: &mut AnthropicSerState) -> Vec<String> {
+    let mut events = state.start();
+    events.extend(state.ctx.generate_final_events());
+    events.iter().map(SseEvent::to_sse_string).collect()
+}
+
+/// 文本增量事件的块索引与文本
+fn text_delta(event: &SseEvent) -> Option<(i32, &str)> {
+    if event.event != "content_block_delta" || event.data["delta"]["type"] != "text_delta" {
+        return None;
+    }
+    let index = event.data["index"].as_i64()? as i32;
+    Some((index, event.data["delta"]["text"].as_str()?))
+}
+
 /// 简单的 token 估算
 fn estimate_tokens(text: &str) -> i32 {
     let chars: Vec<char> = text.chars().collect();
@@ -1420,4 +1814,377 @@ mod tests {
             "`</thinking>` should be filtered during final flush"
         );
     }
+
+    /// 依次处理事件，返回最终 message_delta 中的 stop_reason（没有 message_delta 时为 None）
+    fn final_stop_reason(events: Vec<Event>) -> Option<serde_json::Value> {
+        let mut ctx = StreamContext::new_with_thinking("test-model", 1, false);
+        let mut output = ctx.generate_initial_events();
+        for event in &events {
+            output.extend(ctx.process_kiro_event(event));
+        }
+        output.extend(ctx.generate_final_events());
+        output
+            .into_iter()
+            .find(|e| e.event == "message_delta")
+            .map(|e| e.data["delta"]["stop_reason"].clone())
+    }
+
+    fn text_event(content: &str) -> Event {
+        Event::AssistantResponse(serde_json::from_value(json!({ "content": content })).unwrap())
+    }
+
+    #[test]
+    fn test_stop_reason_for_each_termination_cause() {
+        assert_eq!(
+            final_stop_reason(vec![text_event("Hello")]),
+            Some(json!("end_turn"))
+        );
+
+        let tool_use = Event::ToolUse(crate::kiro::model::events::ToolUseEvent {
+            name: "test_tool".to_string(),
+            tool_use_id: "tool_1".to_string(),
+            input: "{}".to_string(),
+            stop: true,
+        });
+        assert_eq!(
+            final_stop_reason(vec![text_event("Checking."), tool_use]),
+            Some(json!("tool_use"))
+        );
+
+        let truncated = Event::Exception {
+            exception_type: "ContentLengthExceededException".to_string(),
+            message: String::new(),
+        };
+        assert_eq!(
+            final_stop_reason(vec![text_event("Hello"), truncated]),
+            Some(json!("max_tokens"))
+        );
+
+        // 上游错误以 error 事件终止，不再发送 message_delta
+        let error = Event::Error {
+            code: "InternalServerException".to_string(),
+            message: "boom".to_string(),
+        };
+        assert_eq!(final_stop_reason(vec![text_event("Hello"), error]), None);
+    }
+
+    #[test]
+    fn test_stop_sequence_reported_in_message_delta() {
+        let message_delta = |chunks: &[&str]| {
+            let mut ctx = StreamContext::new_with_thinking("test-model", 1, false)
+                .with_stop_sequences(vec!["###".to_string()]);
+            let mut output = ctx.generate_initial_events();
+            for chunk in chunks {
+                output.extend(ctx.process_kiro_event(&text_event(chunk)));
+            }
+            output.extend(ctx.generate_final_events());
+            output
+                .into_iter()
+                .find(|e| e.event == "message_delta")
+                .unwrap()
+                .data["delta"]
+                .clone()
+        };
+
+        let delta = message_delta(&["Answer: 4 #", "##"]);
+        assert_eq!(delta["stop_reason"], "stop_sequence");
+        assert_eq!(delta["stop_sequence"], "###");
+
+        let delta = message_delta(&["Answer: ### 4"]);
+        assert_eq!(delta["stop_reason"], "end_turn");
+        assert!(delta["stop_sequence"].is_null());
+    }
+
+    /// 依次处理文本事件，返回拼接后的 text_delta 内容
+    fn streamed_text(prefill: &str, chunks: &[&str]) -> String {
+        let mut ctx = StreamContext::new_with_thinking("test-model", 1, false)
+            .with_prefill(Some(prefill.to_string()));
+        let mut output = ctx.generate_initial_events();
+        for chunk in chunks {
+            output.extend(ctx.process_kiro_event(&text_event(chunk)));
+        }
+        output.extend(ctx.generate_final_events());
+        output
+            .iter()
+            .filter(|e| e.event == "content_block_delta" && e.data["delta"]["type"] == "text_delta")
+            .map(|e| e.data["delta"]["text"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_prefill_not_repeated_in_output() {
+        // 模型先重复 prefill（跨多个分片）再续写
+        assert_eq!(streamed_text("{\"a\"", &["{\"a", "\": 1}"]), ": 1}");
+        // 直接续写时原样输出
+        assert_eq!(streamed_text("{\"a\"", &[": 1}"]), ": 1}");
+        // 只匹配到一半的前缀在结束时补发
+        assert_eq!(streamed_text("{\"a\"", &["{\"b"]), "{\"b");
+    }
+
+    /// 依次处理事件，返回所有 SSE 事件
+    fn serialize(ctx: &mut StreamContext, events: &[Event]) -> Vec<SseEvent> {
+        let mut output = ctx.generate_initial_events();
+        for event in events {
+            output.extend(ctx.process_kiro_event(event));
+        }
+        output.extend(ctx.generate_final_events());
+        output
+    }
+
+    #[test]
+    fn test_text_coalescing_preserves_content() {
+        let mut events: Vec<Event> = "The quick brown fox jumps over the lazy dog"
+            .split_inclusive(' ')
+            .map(text_event)
+            .collect();
+        events.insert(
+            4,
+            Event::ToolUse(crate::kiro::model::events::ToolUseEvent {
+                name: "lookup".to_string(),
+                tool_use_id: "tool_1".to_string(),
+                input: "{}".to_string(),
+                stop: true,
+            }),
+        );
+
+        let plain = serialize(
+            &mut StreamContext::new_with_thinking("m", 1, false),
+            &events,
+        );
+        let coalesced = serialize(
+            &mut StreamContext::new_with_thinking("m", 1, false).with_text_coalescing(
+                TextCoalescing {
+                    max_bytes: 16,
+                    max_delay: Duration::from_secs(3600),
+                },
+            ),
+            &events,
+        );
+
+        let text = |events: &[SseEvent]| -> Vec<(i32, String)> {
+            let mut blocks: Vec<(i32, String)> = Vec::new();
+            for (index, delta) in events.iter().filter_map(text_delta) {
+                match blocks.last_mut() {
+                    Some((last, text)) if *last == index => text.push_str(delta),
+                    _ => blocks.push((index, delta.to_string())),
+                }
+            }
+            blocks
+        };
+        assert_eq!(text(&coalesced), text(&plain));
+        assert_eq!(text(&plain).len(), 2);
+
+        let delta_count = |events: &[SseEvent]| events.iter().filter_map(text_delta).count();
+        assert!(delta_count(&coalesced) < delta_count(&plain));
+
+        // 工具调用前的文本在 tool_use 块开始前输出
+        let tool_start = coalesced
+            .iter()
+            .position(|e| e.data["content_block"]["type"] == "tool_use")
+            .unwrap();
+        let first_text = coalesced
+            .iter()
+            .position(|e| text_delta(e).is_some())
+            .unwrap();
+        assert!(first_text < tool_start);
+    }
+
+    /// 解析 `to_anthropic_sse` 输出的帧为 (事件名, data)
+    fn parse_frames(frames: &[String]) -> Vec<(String, serde_json::Value)> {
+        frames
+            .iter()
+            .map(|frame| {
+                let (event, data) = frame
+                    .strip_prefix("event: ")
+                    .and_then(|f| f.strip_suffix("\n\n"))
+                    .and_then(|f| f.split_once("\ndata: "))
+                    .unwrap();
+                (event.to_string(), serde_json::from_str(data).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_thinking_deltas_use_separate_blocks() {
+        let thinking = |text: &str| Event::ThinkingDelta(ReasoningContentEvent::new(text));
+        let signature = Event::ThinkingDelta(ReasoningContentEvent {
+            text: String::new(),
+            signature: Some("sig-1".to_string()),
+        });
+        let events = [
+            thinking("Let me "),
+            thinking("think."),
+            signature,
+            text_event("The answer"),
+            text_event(" is 4."),
+            thinking("Double-check."),
+            text_event(" Sure."),
+        ];
+
+        let mut ctx = StreamContext::new_with_thinking("claude-sonnet-4.5", 1, false);
+        let output = serialize(&mut ctx, &events);
+
+        // (块类型, 累计内容, 签名)，同一时间只有一个打开的块
+        let mut blocks: Vec<(String, String, Option<String>)> = Vec::new();
+        let mut open: Option<i64> = None;
+        for event in &output {
+            let data = &event.data;
+            match event.event.as_str() {
+                "content_block_start" => {
+                    assert_eq!(open, None, "块未关闭就开始了新块: {:?}", data);
+                    open = data["index"].as_i64();
+                    assert_eq!(open, Some(blocks.len() as i64));
+                    let block_type = data["content_block"]["type"].as_str().unwrap();
+                    blocks.push((block_type.to_string(), String::new(), None));
+                }
+                "content_block_delta" => {
+                    assert_eq!(data["index"].as_i64(), open);
+                    let block = blocks.last_mut().unwrap();
+                    let delta = &data["delta"];
+                    let (block_type, field) = match delta["type"].as_str().unwrap() {
+                        "text_delta" => ("text", "text"),
+                        "thinking_delta" => ("thinking", "thinking"),
+                        "signature_delta" => ("thinking", "signature"),
+                        other => panic!("unexpected delta {}", other),
+                    };
+                    assert_eq!(block.0, block_type);
+                    let value = delta[field].as_str().unwrap();
+                    match field {
+                        "signature" => block.2 = Some(value.to_string()),
+                        _ => block.1.push_str(value),
+                    }
+                }
+                "content_block_stop" => {
+                    assert_eq!(data["index"].as_i64(), open.take());
+                }
+                _ => {}
+            }
+        }
+
+        let signatures: Vec<&str> = blocks
+            .iter()
+            .filter_map(|block| block.2.as_deref())
+            .collect();
+        assert_eq!(signatures, vec!["sig-1"]);
+
+        let blocks: Vec<(&str, &str)> = blocks
+            .iter()
+            .filter(|(_, content, _)| !content.is_empty())
+            .map(|(block_type, content, _)| (block_type.as_str(), content.as_str()))
+            .collect();
+        assert_eq!(
+            blocks,
+            vec![
+                ("thinking", "Let me think."),
+                ("text", "The answer is 4."),
+                ("thinking", "Double-check."),
+                ("text", " Sure."),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_anthropic_sse_event_sequence() {
+        let tool = |input: &str, stop: bool| {
+            Event::ToolUse(crate::kiro::model::events::ToolUseEvent {
+                name: "get_weather".to_string(),
+                tool_use_id: "tooluse_1".to_string(),
+                input: input.to_string(),
+                stop,
+            })
+        };
+
+        let mut state = AnthropicSerState::new("claude-sonnet-4.5", 12);
+        let mut frames = Vec::new();
+        for event in [
+            text_event("Checking."),
+            tool("{\"city\":", false),
+            tool("\"Paris\"}", true),
+        ] {
+            frames.extend(to_anthropic_sse(&event, &mut state));
+        }
+        frames.extend(finish_anthropic_sse(&mut state));
+
+        let parsed = parse_frames(&frames);
+        let names: Vec<&str> = parsed.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "message_start",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_stop",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_delta",
+                "content_block_stop",
+                "message_delta",
+                "message_stop",
+            ]
+        );
+        for (name, data) in &parsed {
+            assert_eq!(data["type"], json!(name));
+        }
+
+        let message = &parsed[0].1["message"];
+        assert_eq!(message["id"], json!(state.message_id()));
+        assert!(state.message_id().starts_with("msg_"));
+        assert_eq!(message["usage"]["input_tokens"], 12);
+
+        assert_eq!(parsed[1].1["content_block"]["type"], "text");
+        assert_eq!(parsed[2].1["delta"]["text"], "Checking.");
+        assert_eq!(parsed[4].1["index"], 1);
+        assert_eq!(parsed[4].1["content_block"]["name"], "get_weather");
+        assert_eq!(parsed[6].1["delta"]["partial_json"], "\"Paris\"}");
+        assert_eq!(parsed[8].1["delta"]["stop_reason"], "tool_use");
+    }
+
+    #[test]
+    fn test_interleaved_tool_calls_use_distinct_blocks() {
+        let tool = |id: &str, name: &str, input: &str, stop: bool| {
+            Event::ToolUse(crate::kiro::model::events::ToolUseEvent {
+                name: name.to_string(),
+                tool_use_id: id.to_string(),
+                input: input.to_string(),
+                stop,
+            })
+        };
+
+        let mut state = AnthropicSerState::new("claude-sonnet-4.5", 1);
+        let mut frames = Vec::new();
+        for event in [
+            tool("tool_a", "get_weather", "{\"city\":", false),
+            tool("tool_b", "search", "{\"q\":", false),
+            tool("tool_a", "get_weather", "\"Paris\"}", false),
+            tool("tool_b", "search", "\"news\"}", true),
+            tool("tool_a", "get_weather", "", true),
+        ] {
+            frames.extend(to_anthropic_sse(&event, &mut state));
+        }
+        frames.extend(finish_anthropic_sse(&mut state));
+
+        // 按块索引还原每个工具调用
+        let mut starts = Vec::new();
+        let mut inputs: HashMap<i64, String> = HashMap::new();
+        for (name, data) in parse_frames(&frames) {
+            let index = data["index"].as_i64();
+            match name.as_str() {
+                "content_block_start" if data["content_block"]["type"] == "tool_use" => {
+                    starts.push((index.unwrap(), data["content_block"]["id"].clone()));
+                }
+                "content_block_delta" if data["delta"]["type"] == "input_json_delta" => {
+                    inputs
+                        .entry(index.unwrap())
+                        .or_default()
+                        .push_str(data["delta"]["partial_json"].as_str().unwrap());
+                }
+                _ => {}
+            }
+        }
+
+        // 索引按出现顺序单调递增，排在初始文本块之后
+        assert_eq!(starts, vec![(1, json!("tool_a")), (2, json!("tool_b"))]);
+        assert_eq!(inputs[&1], "{\"city\":\"Paris\"}");
+        assert_eq!(inputs[&2], "{\"q\":\"news\"}");
+    }
 }
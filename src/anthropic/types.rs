@@ -101,12 +101,22 @@ pub struct MessagesRequest {
     pub messages: Vec<Message>,
     #[serde(default)]
     pub stream: bool,
+    #[serde(default, deserialize_with = "deserialize_system")]
     pub system: Option<Vec<SystemMessage>>,
     pub tools: Option<Vec<Tool>>,
     pub tool_choice: Option<serde_json::Value>,
     pub thinking: Option<Thinking>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub top_p: Option<f32>,
+    #[serde(default)]
+    pub stop_sequences: Option<Vec<String>>,
     /// Claude Code 请求中的 metadata，包含 session 信息
     pub metadata: Option<Metadata>,
+    /// 未识别的顶层字段（如 `user`），仅白名单内的字段会透传到 Kiro 请求
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 /// 消息
@@ -121,6 +131,32 @@ pub struct Message {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct SystemMessage {
     pub text: String,
+    /// prompt caching 标记，Kiro 不支持，转换时忽略
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<serde_json::Value>,
+}
+
+/// 系统消息可以是字符串或文本块数组
+fn deserialize_system<'de, D>(deserializer: D) -> Result<Option<Vec<SystemMessage>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum SystemField {
+        Text(String),
+        Blocks(Vec<SystemMessage>),
+    }
+
+    Ok(
+        Option::<SystemField>::deserialize(deserializer)?.map(|field| match field {
+            SystemField::Text(text) => vec![SystemMessage {
+                text,
+                cache_control: None,
+            }],
+            SystemField::Blocks(blocks) => blocks,
+        }),
+    )
 }
 
 /// 工具定义
@@ -129,6 +165,9 @@ pub struct Tool {
     pub name: String,
     pub description: String,
     pub input_schema: HashMap<String, serde_json::Value>,
+    /// prompt caching 标记，Kiro 不支持，转换时忽略
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<serde_json::Value>,
 }
 
 /// 内容块
@@ -172,7 +211,11 @@ pub struct ImageSource {
 pub struct CountTokensRequest {
     pub model: String,
     pub messages: Vec<Message>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_system"
+    )]
     pub system: Option<Vec<SystemMessage>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<Tool>>,
@@ -151,13 +151,10 @@ pub fn print_event_verbose(event: &Event) {
             println!("  payload ({} bytes):", payload.len());
             print_hex(payload);
         }
-        Event::Error {
-            error_code,
-            error_message,
-        } => {
+        Event::Error { code, message } => {
             println!("\n[事件] Error");
-            println!("  error_code: {:?}", error_code);
-            println!("  error_message: {:?}", error_message);
+            println!("  code: {:?}", code);
+            println!("  message: {:?}", message);
         }
         Event::Exception {
             exception_type,
@@ -194,11 +191,8 @@ pub fn print_event(event: &Event) {
         Event::Unknown { event_type, .. } => {
             println!("\n[未知事件] {}", event_type);
         }
-        Event::Error {
-            error_code,
-            error_message,
-        } => {
-            println!("\n[错误] {}: {}", error_code, error_message);
+        Event::Error { code, message } => {
+            println!("\n[错误] {}: {}", code, message);
         }
         Event::Exception {
             exception_type,
@@ -0,0 +1,81 @@
+//! 原子文件写入
+//!
+//! 先写入同目录下的临时文件再移动到目标位置，其他进程不会读到写了一半的文件；
+//! 写入的文件可能包含 refreshToken，unix 下权限固定为仅所有者可读写（0600）
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// 在目标文件所在目录写入临时文件，返回临时文件路径
+fn write_temp(path: &Path, contents: &[u8]) -> io::Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "路径缺少文件名"))?;
+    let temp = path.with_file_name(format!(
+        ".{}.{:016x}.tmp",
+        file_name.to_string_lossy(),
+        fastrand::u64(..)
+    ));
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&temp)?;
+    if let Err(e) = file.write_all(contents) {
+        fs::remove_file(&temp).ok();
+        return Err(e);
+    }
+    Ok(temp)
+}
+
+/// 原子地写入（替换）文件
+pub(crate) fn write(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let temp = write_temp(path, contents.as_ref())?;
+    fs::rename(&temp, path).inspect_err(|_| {
+        fs::remove_file(&temp).ok();
+    })
+}
+
+/// 仅在文件不存在时原子地创建文件，文件已存在时返回 `Ok(false)` 且不修改
+pub(crate) fn create_new(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<bool> {
+    let temp = write_temp(path, contents.as_ref())?;
+    // 硬链接在目标已存在时失败，且目标一出现就是完整内容
+    let result = match fs::hard_link(&temp, path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(e),
+    };
+    fs::remove_file(&temp).ok();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_and_create_new() {
+        let dir = std::env::temp_dir().join(format!("kiro-rs-atomic-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file");
+
+        assert!(create_new(&path, "first").unwrap());
+        assert!(!create_new(&path, "second").unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first");
+
+        write(&path, "third").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "third");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // 不残留临时文件
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).ok();
+    }
+}
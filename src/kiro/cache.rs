@@ -0,0 +1,263 @@
+//! 响应缓存
+//!
+//! 对确定性请求（`temperature` 为 0 或显式开启）缓存拼装好的非流式响应，
+//! 相同请求在有效期内直接返回缓存结果，不再消耗上游额度
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use parking_lot::Mutex;
+
+use crate::kiro::clock::{Clock, SystemClock};
+use crate::kiro::completion::{CompletionBuilder, CompletionResponse};
+use crate::kiro::model::events::Event;
+use crate::kiro::model::requests::kiro::KiroRequest;
+
+/// 默认最大缓存条目数
+#[allow(dead_code)]
+pub const DEFAULT_MAX_ENTRIES: usize = 1024;
+
+/// 缓存条目
+struct CacheEntry {
+    expires_at: SystemTime,
+    response: CompletionResponse,
+}
+
+/// 按请求内容缓存完整响应
+///
+/// 缓存键为 `KiroRequest::stable_hash`：会话 ID 等每次随机生成的字段不参与计算。
+/// 条目数达到 `max_entries` 时淘汰最早过期的条目
+pub struct ResponseCache {
+    ttl: Duration,
+    max_entries: usize,
+    clock: Arc<dyn Clock>,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl ResponseCache {
+    /// 创建缓存，`ttl` 为每个条目的有效期
+    #[allow(dead_code)]
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            max_entries: DEFAULT_MAX_ENTRIES,
+            clock: Arc::new(SystemClock),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 设置最大缓存条目数（默认 `DEFAULT_MAX_ENTRIES`）
+    #[allow(dead_code)]
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// 使用自定义时钟判断过期（测试中可替换为 `TestClock`）
+    #[allow(dead_code)]
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// 计算请求的缓存键
+    pub fn key(req: &KiroRequest) -> String {
+        hex::encode(req.stable_hash())
+    }
+
+    /// 请求是否可以缓存：`temperature` 为 0，或调用方显式开启
+    pub fn is_cacheable(req: &KiroRequest, opt_in: bool) -> bool {
+        opt_in
+            || req
+                .inference_config
+                .as_ref()
+                .and_then(|config| config.temperature)
+                == Some(0.0)
+    }
+
+    /// 读取未过期的缓存响应，过期条目会被移除
+    pub fn get(&self, key: &str) -> Option<CompletionResponse> {
+        let now = self.clock.now();
+        let mut entries = self.entries.lock();
+        match entries.get(key) {
+            Some(entry) if entry.expires_at > now => Some(entry.response.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// 写入响应，同时清理已过期的条目；条目数已满时淘汰最早过期的条目
+    pub fn insert(&self, key: String, response: CompletionResponse) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let now = self.clock.now();
+        let mut entries = self.entries.lock();
+        entries.retain(|_, entry| entry.expires_at > now);
+        while entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.expires_at)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            entries.remove(&oldest);
+        }
+        entries.insert(
+            key,
+            CacheEntry {
+                expires_at: now + self.ttl,
+                response,
+            },
+        );
+    }
+
+    /// 当前缓存条目数（含尚未清理的过期条目）
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().is_empty()
+    }
+
+    /// 清空缓存
+    #[allow(dead_code)]
+    pub fn clear(&self) {
+        self.entries.lock().clear();
+    }
+}
+
+/// 在流式响应经过时拼装完整响应，流正常结束后写入缓存
+pub(crate) struct CacheRecorder {
+    cache: Arc<ResponseCache>,
+    key: String,
+    builder: CompletionBuilder,
+}
+
+impl CacheRecorder {
+    pub(crate) fn new(cache: Arc<ResponseCache>, key: String, builder: CompletionBuilder) -> Self {
+        Self {
+            cache,
+            key,
+            builder,
+        }
+    }
+
+    /// 记录一个事件，上游错误事件返回 `false`，此时不应写入缓存
+    pub(crate) fn push(&mut self, event: &Event) -> bool {
+        self.builder.push(event).is_ok()
+    }
+
+    /// 写入缓存
+    pub(crate) fn finish(self) {
+        self.cache.insert(self.key, self.builder.finish());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::clock::TestClock;
+    use crate::kiro::completion::CompletionContent;
+    use crate::kiro::finish_reason::FinishReason;
+    use crate::kiro::model::requests::conversation::{
+        ConversationState, CurrentMessage, UserInputMessage,
+    };
+    use crate::kiro::model::requests::kiro::InferenceConfig;
+    use crate::token::Usage;
+
+    fn request(conversation_id: &str, content: &str) -> KiroRequest {
+        KiroRequest {
+            conversation_state: ConversationState::new(conversation_id)
+                .with_agent_continuation_id(conversation_id)
+                .with_current_message(CurrentMessage::new(UserInputMessage::new(
+                    content,
+                    "claude-sonnet-4.5",
+                ))),
+            profile_arn: None,
+            inference_config: Some(InferenceConfig {
+                temperature: Some(0.0),
+                ..Default::default()
+            }),
+            metadata: Default::default(),
+        }
+    }
+
+    fn response(text: &str) -> CompletionResponse {
+        CompletionResponse {
+            content: vec![CompletionContent::Text(text.to_string())],
+            stop_reason: FinishReason::Stop,
+            usage: Usage {
+                input_tokens: 3,
+                output_tokens: 1,
+                estimated: false,
+            },
+        }
+    }
+
+    #[test]
+    fn test_key_ignores_conversation_ids() {
+        assert_eq!(
+            ResponseCache::key(&request("conv-1", "Hello")),
+            ResponseCache::key(&request("conv-2", "Hello"))
+        );
+        assert_ne!(
+            ResponseCache::key(&request("conv-1", "Hello")),
+            ResponseCache::key(&request("conv-1", "Bye"))
+        );
+    }
+
+    #[test]
+    fn test_is_cacheable() {
+        let mut req = request("conv-1", "Hello");
+        assert!(ResponseCache::is_cacheable(&req, false));
+
+        req.inference_config = None;
+        assert!(!ResponseCache::is_cacheable(&req, false));
+        assert!(ResponseCache::is_cacheable(&req, true));
+    }
+
+    #[test]
+    fn test_entries_expire_after_ttl() {
+        let clock = Arc::new(TestClock::starting_now());
+        let cache = ResponseCache::new(Duration::from_secs(60)).with_clock(clock.clone());
+        let key = ResponseCache::key(&request("conv-1", "Hello"));
+
+        cache.insert(key.clone(), response("Hi"));
+        clock.advance(Duration::from_secs(59));
+        assert_eq!(cache.get(&key).unwrap().text(), "Hi");
+
+        clock.advance(Duration::from_secs(1));
+        assert!(cache.get(&key).is_none());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_max_entries_evicts_oldest() {
+        let clock = Arc::new(TestClock::starting_now());
+        let cache = ResponseCache::new(Duration::from_secs(60))
+            .with_max_entries(2)
+            .with_clock(clock.clone());
+
+        for key in ["a", "b", "c"] {
+            cache.insert(key.to_string(), response(key));
+            clock.advance(Duration::from_secs(1));
+        }
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("a").is_none());
+        assert_eq!(cache.get("c").unwrap().text(), "c");
+
+        // 覆盖已有条目不淘汰其他条目
+        cache.insert("b".to_string(), response("b2"));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("c").unwrap().text(), "c");
+    }
+}
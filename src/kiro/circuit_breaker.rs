@@ -0,0 +1,236 @@
+//! 熔断器
+//!
+//! 上游持续不可用时继续发送请求只会堆积超时并浪费额度。
+//! 连续失败达到阈值后熔断器打开，冷却期内的请求直接以 `KiroError::CircuitOpen` 返回；
+//! 冷却结束后半开，只放行一个探测请求，成功则关闭，失败则重新打开
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use parking_lot::Mutex;
+
+use crate::kiro::clock::{Clock, SystemClock};
+use crate::kiro::error::KiroError;
+
+/// 熔断器状态
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// 正常放行请求
+    Closed,
+    /// 冷却中，请求被直接拒绝
+    Open,
+    /// 冷却结束，允许一个探测请求
+    HalfOpen,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    /// 连续失败次数
+    consecutive_failures: u32,
+    /// 最近一次打开的时间
+    opened_at: Option<SystemTime>,
+    /// 半开状态下是否已有探测请求在途
+    probing: bool,
+}
+
+/// 按连续失败次数打开的熔断器
+///
+/// 只有网络错误、超时与 5xx 计为失败；4xx 说明上游可达，视为成功
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    clock: Arc<dyn Clock>,
+    inner: Mutex<Inner>,
+}
+
+impl CircuitBreaker {
+    /// 连续失败 `failure_threshold` 次后打开，`cooldown` 后进入半开
+    #[allow(dead_code)]
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            clock: Arc::new(SystemClock),
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// 使用自定义时钟判断冷却（测试中可替换为 `TestClock`）
+    #[allow(dead_code)]
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// 当前状态
+    #[allow(dead_code)]
+    pub fn state(&self) -> CircuitState {
+        let inner = self.inner.lock();
+        match self.remaining_cooldown(&inner) {
+            None => CircuitState::Closed,
+            Some(remaining) if !remaining.is_zero() => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// 请求发送前调用：放行时返回许可，否则返回 `KiroError::CircuitOpen`
+    ///
+    /// 半开状态下只放行一个探测请求，探测结束前其余请求同样被拒绝；
+    /// 探测许可未记录结果就被丢弃（请求被取消）时，允许下一个请求重新探测
+    pub(crate) fn acquire(&self) -> Result<CircuitPermit<'_>, KiroError> {
+        let mut inner = self.inner.lock();
+        let probe = match self.remaining_cooldown(&inner) {
+            None => false,
+            Some(remaining) if !remaining.is_zero() => {
+                return Err(KiroError::CircuitOpen {
+                    retry_in: remaining,
+                });
+            }
+            Some(_) if inner.probing => {
+                return Err(KiroError::CircuitOpen {
+                    retry_in: Duration::ZERO,
+                });
+            }
+            Some(_) => {
+                inner.probing = true;
+                true
+            }
+        };
+        Ok(CircuitPermit {
+            breaker: self,
+            probe,
+        })
+    }
+
+    /// 记录一个已放行请求的结果
+    fn record<T>(&self, result: &Result<T, KiroError>) {
+        let failed = result.as_ref().err().is_some_and(is_circuit_failure);
+        let mut inner = self.inner.lock();
+        if !failed {
+            *inner = Inner::default();
+            return;
+        }
+
+        inner.probing = false;
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+        // 半开探测失败或达到阈值时（重新）打开
+        if inner.opened_at.is_some() || inner.consecutive_failures >= self.failure_threshold {
+            if inner.opened_at.is_none() {
+                tracing::warn!(
+                    "上游连续失败 {} 次，熔断 {:?}",
+                    inner.consecutive_failures,
+                    self.cooldown
+                );
+            }
+            inner.opened_at = Some(self.clock.now());
+        }
+    }
+
+    /// 打开状态下剩余的冷却时间，关闭时返回 `None`
+    fn remaining_cooldown(&self, inner: &Inner) -> Option<Duration> {
+        let opened_at = inner.opened_at?;
+        let elapsed = self
+            .clock
+            .now()
+            .duration_since(opened_at)
+            .unwrap_or_default();
+        Some(self.cooldown.saturating_sub(elapsed))
+    }
+}
+
+/// 熔断器放行的一个请求，请求结束后通过 `record` 报告结果
+pub(crate) struct CircuitPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    /// 是否为半开状态下的探测请求
+    probe: bool,
+}
+
+impl CircuitPermit<'_> {
+    /// 报告请求结果
+    pub(crate) fn record<T>(mut self, result: &Result<T, KiroError>) {
+        self.probe = false;
+        self.breaker.record(result);
+    }
+}
+
+impl Drop for CircuitPermit<'_> {
+    fn drop(&mut self) {
+        if self.probe {
+            self.breaker.inner.lock().probing = false;
+        }
+    }
+}
+
+/// 是否计入熔断失败：网络错误、超时与 5xx
+fn is_circuit_failure(err: &KiroError) -> bool {
+    match err {
+        KiroError::Http(e) => !e.is_builder(),
+        KiroError::Timeout(_) => true,
+        KiroError::Upstream { status, .. } => *status >= 500,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::clock::TestClock;
+
+    fn server_error() -> Result<(), KiroError> {
+        Err(KiroError::Upstream {
+            status: 503,
+            body: String::new(),
+        })
+    }
+
+    #[test]
+    fn test_transitions() {
+        let clock = Arc::new(TestClock::starting_now());
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(30)).with_clock(clock.clone());
+
+        breaker.acquire().unwrap().record(&server_error());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        breaker.acquire().unwrap().record(&server_error());
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        assert!(matches!(
+            breaker.acquire(),
+            Err(KiroError::CircuitOpen { retry_in }) if retry_in == Duration::from_secs(30)
+        ));
+
+        // 半开：只放行一个探测请求，探测失败后重新打开
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        let probe = breaker.acquire().unwrap();
+        assert!(breaker.acquire().is_err());
+        probe.record(&server_error());
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        // 被取消的探测不会让熔断器卡在半开
+        clock.advance(Duration::from_secs(30));
+        drop(breaker.acquire().unwrap());
+        breaker.acquire().unwrap().record(&Ok(()));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        breaker.acquire().unwrap();
+    }
+
+    #[test]
+    fn test_client_errors_do_not_count() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(30));
+        for status in [400, 404, 429] {
+            breaker
+                .acquire()
+                .unwrap()
+                .record::<()>(&Err(KiroError::Upstream {
+                    status,
+                    body: String::new(),
+                }));
+        }
+        breaker
+            .acquire()
+            .unwrap()
+            .record::<()>(&Err(KiroError::auth("denied")));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
@@ -0,0 +1,81 @@
+//! 时钟抽象
+//!
+//! Token 过期判断通过 `Clock` 获取当前时间，测试中可替换为手动推进的 `TestClock`
+
+use std::time::{Duration, SystemTime};
+
+use parking_lot::Mutex;
+
+/// 当前时间来源
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+/// 系统时钟
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// 手动推进的时钟，用于确定性测试
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct TestClock {
+    now: Mutex<SystemTime>,
+}
+
+impl TestClock {
+    /// 从指定时间开始
+    #[allow(dead_code)]
+    pub fn new(start: SystemTime) -> Self {
+        Self {
+            now: Mutex::new(start),
+        }
+    }
+
+    /// 从当前系统时间开始
+    #[allow(dead_code)]
+    pub fn starting_now() -> Self {
+        Self::new(SystemTime::now())
+    }
+
+    /// 将时钟向前推进
+    #[allow(dead_code)]
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock() += duration;
+    }
+
+    /// 将时钟设置为指定时间
+    #[allow(dead_code)]
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock() = now;
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_test_clock_advance() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let clock = TestClock::new(start);
+        assert_eq!(clock.now(), start);
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(clock.now(), start + Duration::from_secs(90));
+
+        clock.set(start);
+        assert_eq!(clock.now(), start);
+    }
+}
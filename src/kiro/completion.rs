@@ -0,0 +1,308 @@
+//! 非流式补全结果
+//!
+//! 将流式事件拼装为完整的响应：合并文本与思考增量、拼接工具调用，并统计用量与结束原因
+
+use crate::kiro::error::KiroError;
+use crate::kiro::finish_reason::{FinishReason, FinishTracker};
+use crate::kiro::model::events::{
+    AssistantResponseEvent, Event, ReasoningContentEvent, ToolUse, ToolUseAccumulator, ToolUseEvent,
+};
+use crate::token::{Usage, UsageTracker};
+
+/// 响应内容块
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompletionContent {
+    /// 文本（相邻的文本增量已合并）
+    Text(String),
+    /// 思考内容（相邻的思考增量已合并）
+    Thinking {
+        thinking: String,
+        signature: Option<String>,
+    },
+    /// 完整的工具调用
+    ToolUse(ToolUse),
+}
+
+/// 完整的非流式响应
+#[derive(Debug, Clone)]
+pub struct CompletionResponse {
+    /// 按到达顺序排列的内容块
+    pub content: Vec<CompletionContent>,
+    /// 结束原因
+    pub stop_reason: FinishReason,
+    /// token 用量
+    pub usage: Usage,
+}
+
+impl CompletionResponse {
+    /// 拼接所有文本块（不含思考内容）
+    #[allow(dead_code)]
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|block| match block {
+                CompletionContent::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 拼接所有思考块
+    pub fn thinking(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|block| match block {
+                CompletionContent::Thinking { thinking, .. } => Some(thinking.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 还原为等价的事件序列，用于以流式形式返回已拼装的响应（如缓存命中）
+    ///
+    /// 每个内容块对应一个完整事件，最后附带结束原因与用量，
+    /// 重新拼装后得到相同的内容、结束原因与用量
+    pub fn to_events(&self) -> Vec<Event> {
+        let mut events: Vec<Event> = self
+            .content
+            .iter()
+            .map(|block| match block {
+                CompletionContent::Text(text) => {
+                    Event::AssistantResponse(AssistantResponseEvent::new(text.clone()))
+                }
+                CompletionContent::Thinking {
+                    thinking,
+                    signature,
+                } => Event::ThinkingDelta(ReasoningContentEvent {
+                    text: thinking.clone(),
+                    signature: signature.clone(),
+                }),
+                CompletionContent::ToolUse(tool_use) => Event::ToolUse(ToolUseEvent {
+                    name: tool_use.name.clone(),
+                    tool_use_id: tool_use.id.clone(),
+                    input: tool_use.input.to_string(),
+                    stop: true,
+                }),
+            })
+            .collect();
+
+        if self.stop_reason == FinishReason::MaxTokens {
+            events.push(Event::Exception {
+                exception_type: "ContentLengthExceededException".to_string(),
+                message: String::new(),
+            });
+        }
+        events.push(Event::Usage {
+            input: Some(self.usage.input_tokens),
+            output: Some(self.usage.output_tokens),
+        });
+        events
+    }
+}
+
+/// 拼装过程中遇到上游错误事件时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AssemblyMode {
+    /// 直接返回错误（默认）
+    #[default]
+    Strict,
+    /// 停止读取并返回已拼装的部分响应，结束原因为 `FinishReason::Error`
+    #[allow(dead_code)]
+    BestEffort,
+}
+
+/// 响应拼装器
+pub(crate) struct CompletionBuilder {
+    content: Vec<CompletionContent>,
+    text: String,
+    thinking: String,
+    signature: Option<String>,
+    tools: ToolUseAccumulator,
+    finish: FinishTracker,
+    usage: UsageTracker,
+}
+
+impl CompletionBuilder {
+    pub(crate) fn new(usage: UsageTracker) -> Self {
+        Self {
+            content: Vec::new(),
+            text: String::new(),
+            thinking: String::new(),
+            signature: None,
+            tools: ToolUseAccumulator::new(),
+            finish: FinishTracker::new(),
+            usage,
+        }
+    }
+
+    /// 工具输入 JSON 被截断时尝试补全，补全的工具调用标记为 `repaired`（默认保留原始文本）
+    pub(crate) fn with_json_repair(mut self, enabled: bool) -> Self {
+        self.tools = ToolUseAccumulator::new().with_repair(enabled);
+        self
+    }
+
+    /// 处理一个事件，上游错误事件转换为 `KiroError`
+    pub(crate) fn push(&mut self, event: &Event) -> Result<(), KiroError> {
+        self.usage.observe(event);
+        self.finish.observe(event);
+
+        match event {
+            Event::AssistantResponse(resp) if !resp.content.is_empty() => {
+                self.flush_thinking();
+                self.text.push_str(&resp.content);
+            }
+            Event::ThinkingDelta(thinking) => {
+                self.flush_text();
+                self.thinking.push_str(&thinking.text);
+                if thinking.signature.is_some() {
+                    self.signature = thinking.signature.clone();
+                }
+            }
+            Event::ToolUse(tool_use) => {
+                if let Some(tool_use) = self.tools.push(tool_use) {
+                    self.flush_thinking();
+                    self.flush_text();
+                    self.content.push(CompletionContent::ToolUse(tool_use));
+                }
+            }
+            Event::Error { code, message } => {
+                let body = format!("{}: {}", code, message);
+                return Err(match code.as_str() {
+                    "ThrottlingException" | "ServiceQuotaExceededException" => {
+                        KiroError::RateLimited {
+                            retry_after: None,
+                            body,
+                        }
+                    }
+                    _ => KiroError::Upstream { status: 502, body },
+                });
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// 完成拼装，未收到 `stop` 就中断的工具调用追加在最后
+    pub(crate) fn finish(mut self) -> CompletionResponse {
+        self.flush_thinking();
+        self.flush_text();
+        let truncated = self.tools.finish();
+        self.content
+            .extend(truncated.into_iter().map(CompletionContent::ToolUse));
+        CompletionResponse {
+            content: self.content,
+            stop_reason: self.finish.reason(),
+            usage: self.usage.usage(),
+        }
+    }
+
+    fn flush_text(&mut self) {
+        if !self.text.is_empty() {
+            let text = std::mem::take(&mut self.text);
+            self.content.push(CompletionContent::Text(text));
+        }
+    }
+
+    fn flush_thinking(&mut self) {
+        if !self.thinking.is_empty() || self.signature.is_some() {
+            self.content.push(CompletionContent::Thinking {
+                thinking: std::mem::take(&mut self.thinking),
+                signature: self.signature.take(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thinking(text: &str) -> Event {
+        Event::ThinkingDelta(ReasoningContentEvent::new(text))
+    }
+
+    fn text(content: &str) -> Event {
+        Event::AssistantResponse(AssistantResponseEvent::new(content))
+    }
+
+    #[test]
+    fn test_thinking_assembled_into_separate_blocks() {
+        let signed = Event::ThinkingDelta(ReasoningContentEvent {
+            text: String::new(),
+            signature: Some("sig".to_string()),
+        });
+        let events = [
+            thinking("Let me "),
+            thinking("think."),
+            signed,
+            text("The answer"),
+            text(" is 4."),
+            thinking("Double-check."),
+            text(" Sure."),
+        ];
+
+        let mut builder = CompletionBuilder::new(UsageTracker::with_estimated_input(1));
+        for event in &events {
+            builder.push(event).unwrap();
+        }
+        let response = builder.finish();
+
+        assert_eq!(
+            response.content,
+            vec![
+                CompletionContent::Thinking {
+                    thinking: "Let me think.".to_string(),
+                    signature: Some("sig".to_string()),
+                },
+                CompletionContent::Text("The answer is 4.".to_string()),
+                CompletionContent::Thinking {
+                    thinking: "Double-check.".to_string(),
+                    signature: None,
+                },
+                CompletionContent::Text(" Sure.".to_string()),
+            ]
+        );
+        assert_eq!(response.text(), "The answer is 4. Sure.");
+        assert_eq!(response.thinking(), "Let me think.Double-check.");
+
+        // 重放后得到相同的内容块
+        let mut replay = CompletionBuilder::new(UsageTracker::with_estimated_input(1));
+        for event in &response.to_events() {
+            replay.push(event).unwrap();
+        }
+        assert_eq!(replay.finish().content, response.content);
+    }
+
+    #[test]
+    fn test_truncated_tool_input() {
+        let events = [
+            text("Calling."),
+            Event::ToolUse(ToolUseEvent {
+                name: "f".to_string(),
+                tool_use_id: "t1".to_string(),
+                input: r#"{"a": 1, "b":"#.to_string(),
+                stop: false,
+            }),
+        ];
+        let assemble = |repair: bool| {
+            let mut builder = CompletionBuilder::new(UsageTracker::with_estimated_input(1))
+                .with_json_repair(repair);
+            for event in &events {
+                builder.push(event).unwrap();
+            }
+            match builder.finish().content.pop() {
+                Some(CompletionContent::ToolUse(tool_use)) => tool_use,
+                other => panic!("expected tool use, got {:?}", other),
+            }
+        };
+
+        let repaired = assemble(true);
+        assert_eq!(repaired.input, serde_json::json!({"a": 1, "b": null}));
+        assert!(repaired.repaired);
+
+        let raw = assemble(false);
+        assert_eq!(raw.input, serde_json::json!({}));
+        assert!(!raw.repaired);
+        assert_eq!(raw.raw_input.as_deref(), Some(r#"{"a": 1, "b":"#));
+    }
+}
@@ -0,0 +1,302 @@
+//! Kiro Provider 错误定义
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::anthropic::converter::ConversionError;
+use crate::kiro::parser::error::ParseError;
+use crate::kiro::token_manager::WarmUpReport;
+
+/// Kiro 统一错误类型
+///
+/// 按失败原因分类，便于调用方匹配处理；底层错误通过 `source()` 保留
+#[derive(Debug, thiserror::Error)]
+pub enum KiroError {
+    /// 认证失败（无可用凭据、Token 刷新失败、上游返回 401/403）
+    #[error("认证失败: {message}")]
+    Auth {
+        message: String,
+        #[source]
+        source: Option<anyhow::Error>,
+    },
+    /// refreshToken 已被上游撤销（`invalid_grant`），需要重新登录
+    ///
+    /// 对应的凭据已被禁用，重试不会成功
+    #[error("{}refreshToken 已被撤销: {message}", credential_prefix(*credential_id))]
+    RefreshTokenRevoked {
+        credential_id: Option<u64>,
+        message: String,
+    },
+    /// 网络传输错误（连接失败、连接中断等）
+    #[error("HTTP 请求失败: {0}")]
+    Http(#[from] reqwest::Error),
+    /// 请求超时
+    #[error("{0}")]
+    Timeout(#[from] TimeoutError),
+    /// 响应解析失败
+    #[error("响应解析失败: {0}")]
+    Parse(#[from] ParseError),
+    /// 请求转换失败
+    #[error("请求转换失败: {0}")]
+    Conversion(#[from] ConversionError),
+    /// 被上游限流（429）
+    #[error("请求被限流: 429 {body}")]
+    RateLimited {
+        /// 上游通过 `Retry-After` 给出的等待时长
+        retry_after: Option<Duration>,
+        body: String,
+    },
+    /// 上游返回的其他错误响应
+    #[error("上游 API 请求失败: {status} {body}")]
+    Upstream { status: u16, body: String },
+    /// 响应超过配置的上限，流已中止（中止前已发出截断的结束原因）
+    #[error("响应超过上限: {0}")]
+    ResponseTooLarge(ResponseLimit),
+    /// 启动预热时所有凭据均刷新失败，附带各账号的结果
+    #[error("所有凭据预热失败（{0}）")]
+    WarmUpFailed(WarmUpReport),
+    /// 熔断器处于打开状态，请求未发送
+    #[error("上游熔断中，{} 毫秒后重试", retry_in.as_millis())]
+    CircuitOpen {
+        /// 距离熔断器半开（允许探测请求）的剩余时间
+        retry_in: Duration,
+    },
+}
+
+/// 被超过的响应上限
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseLimit {
+    /// 响应体总字节数（`ProviderConfig::max_response_bytes`）
+    Bytes(u64),
+    /// 输出 tokens（`ProviderConfig::max_output_tokens`）
+    OutputTokens(u64),
+}
+
+impl fmt::Display for ResponseLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bytes(limit) => write!(f, "超过 {} 字节", limit),
+            Self::OutputTokens(limit) => write!(f, "超过 {} 个输出 tokens", limit),
+        }
+    }
+}
+
+/// 错误信息中的凭据前缀（未知凭据时为空）
+fn credential_prefix(credential_id: Option<u64>) -> String {
+    credential_id
+        .map(|id| format!("凭据 #{} 的 ", id))
+        .unwrap_or_default()
+}
+
+impl KiroError {
+    /// 构造认证错误
+    pub fn auth(message: impl Into<String>) -> Self {
+        Self::Auth {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    /// 将底层错误包装为认证错误
+    pub fn auth_from(source: anyhow::Error) -> Self {
+        Self::Auth {
+            message: source.to_string(),
+            source: Some(source),
+        }
+    }
+
+    /// 是否值得重试
+    ///
+    /// 网络瞬态错误、超时、429、5xx 与熔断（冷却后）可重试；认证失败、其他 4xx、解析与转换错误不可重试
+    #[allow(dead_code)]
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Http(e) => !e.is_builder(),
+            Self::Timeout(_) | Self::RateLimited { .. } | Self::CircuitOpen { .. } => true,
+            Self::Upstream { status, .. } => *status >= 500,
+            Self::Auth { .. }
+            | Self::RefreshTokenRevoked { .. }
+            | Self::Parse(_)
+            | Self::Conversion(_)
+            | Self::ResponseTooLarge(_)
+            | Self::WarmUpFailed(_) => false,
+        }
+    }
+
+    /// 上游给出的重试等待时长（`Retry-After`）
+    #[allow(dead_code)]
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited { retry_after, .. } => *retry_after,
+            Self::CircuitOpen { retry_in } => Some(*retry_in),
+            _ => None,
+        }
+    }
+}
+
+/// 超时类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutKind {
+    /// 建立连接超时
+    Connect,
+    /// 两次读取之间的间隔超时（流式响应中即分块间隔）
+    Read,
+    /// 整个请求超时
+    Request,
+}
+
+impl fmt::Display for TimeoutKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connect => write!(f, "连接超时"),
+            Self::Read => write!(f, "读取超时"),
+            Self::Request => write!(f, "请求超时"),
+        }
+    }
+}
+
+/// 请求超时错误
+#[derive(Debug, thiserror::Error)]
+#[error("{kind}{}", timeout.map(|t| format!(" ({:?})", t)).unwrap_or_default())]
+pub struct TimeoutError {
+    /// 超时类型
+    pub kind: TimeoutKind,
+    /// 触发超时的时长配置
+    pub timeout: Option<Duration>,
+    #[source]
+    source: Option<reqwest::Error>,
+}
+
+impl TimeoutError {
+    pub fn new(kind: TimeoutKind, timeout: Option<Duration>) -> Self {
+        Self {
+            kind,
+            timeout,
+            source: None,
+        }
+    }
+
+    /// 从 reqwest 错误识别超时类型，非超时错误返回 `Err` 原样交还
+    ///
+    /// `elapsed` 为请求已耗费的时间，用于区分读取超时与整体请求超时
+    pub fn classify(
+        err: reqwest::Error,
+        elapsed: Duration,
+        read_timeout: Option<Duration>,
+        request_timeout: Option<Duration>,
+    ) -> Result<Self, reqwest::Error> {
+        if !err.is_timeout() {
+            return Err(err);
+        }
+
+        let (kind, timeout) = if err.is_connect() {
+            (TimeoutKind::Connect, None)
+        } else if request_timeout.is_some_and(|t| elapsed >= t) {
+            (TimeoutKind::Request, request_timeout)
+        } else {
+            (TimeoutKind::Read, read_timeout)
+        };
+
+        Ok(Self {
+            kind,
+            timeout,
+            source: Some(err),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_retryable_errors() {
+        // 连接一个没有监听的端口
+        let err: KiroError = reqwest::get("http://127.0.0.1:1/")
+            .await
+            .unwrap_err()
+            .into();
+        assert!(err.is_retryable());
+
+        let err: KiroError = TimeoutError::new(TimeoutKind::Read, None).into();
+        assert!(err.is_retryable());
+
+        let err = KiroError::Upstream {
+            status: 503,
+            body: String::new(),
+        };
+        assert!(err.is_retryable());
+        assert_eq!(err.retry_after(), None);
+
+        let err = KiroError::RateLimited {
+            retry_after: Some(Duration::from_secs(3)),
+            body: String::new(),
+        };
+        assert!(err.is_retryable());
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn test_display_and_source() {
+        use std::error::Error;
+
+        let err = KiroError::RefreshTokenRevoked {
+            credential_id: Some(2),
+            message: "invalid_grant".to_string(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "凭据 #2 的 refreshToken 已被撤销: invalid_grant"
+        );
+        let err = KiroError::RefreshTokenRevoked {
+            credential_id: None,
+            message: "invalid_grant".to_string(),
+        };
+        assert_eq!(err.to_string(), "refreshToken 已被撤销: invalid_grant");
+
+        let err: KiroError =
+            TimeoutError::new(TimeoutKind::Read, Some(Duration::from_secs(3))).into();
+        assert_eq!(err.to_string(), "读取超时 (3s)");
+        assert!(err.source().is_some());
+
+        let err = KiroError::auth_from(anyhow::anyhow!("刷新失败"));
+        assert_eq!(err.to_string(), "认证失败: 刷新失败");
+        assert_eq!(err.source().unwrap().to_string(), "刷新失败");
+        assert!(KiroError::auth("无效的凭据").source().is_none());
+
+        let err = KiroError::ResponseTooLarge(ResponseLimit::OutputTokens(10));
+        assert_eq!(err.to_string(), "响应超过上限: 超过 10 个输出 tokens");
+    }
+
+    #[test]
+    fn test_non_retryable_errors() {
+        assert!(!KiroError::auth("无效的凭据").is_retryable());
+        assert!(
+            !KiroError::Upstream {
+                status: 400,
+                body: String::new(),
+            }
+            .is_retryable()
+        );
+        assert!(
+            !KiroError::RefreshTokenRevoked {
+                credential_id: Some(1),
+                message: "invalid_grant".to_string(),
+            }
+            .is_retryable()
+        );
+        assert!(!KiroError::from(ParseError::Incomplete { needed: 4 }).is_retryable());
+        assert!(
+            !KiroError::from(ConversionError::UnsupportedModel("llama-3".to_string()))
+                .is_retryable()
+        );
+
+        // 构造请求时的错误（如非法 URL）重试也不会成功
+        let err: KiroError = reqwest::Client::new()
+            .get("not a url")
+            .build()
+            .unwrap_err()
+            .into();
+        assert!(!err.is_retryable());
+    }
+}
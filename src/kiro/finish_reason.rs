@@ -0,0 +1,235 @@
+//! 生成结束原因
+//!
+//! 根据上游事件判断一次生成为何结束，并映射为 OpenAI / Anthropic 协议中的取值
+
+use crate::kiro::model::events::Event;
+
+/// 生成结束原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FinishReason {
+    /// 正常结束
+    Stop,
+    /// 输出达到长度上限
+    MaxTokens,
+    /// 模型请求调用工具
+    ToolUse,
+    /// 上游返回错误
+    Error,
+}
+
+impl FinishReason {
+    /// OpenAI 的 `finish_reason`，上游错误没有对应取值
+    pub fn openai(self) -> Option<&'static str> {
+        match self {
+            FinishReason::Stop => Some("stop"),
+            FinishReason::MaxTokens => Some("length"),
+            FinishReason::ToolUse => Some("tool_calls"),
+            FinishReason::Error => None,
+        }
+    }
+
+    /// Anthropic 的 `stop_reason`，上游错误没有对应取值
+    pub fn anthropic(self) -> Option<&'static str> {
+        match self {
+            FinishReason::Stop => Some("end_turn"),
+            FinishReason::MaxTokens => Some("max_tokens"),
+            FinishReason::ToolUse => Some("tool_use"),
+            FinishReason::Error => None,
+        }
+    }
+}
+
+/// 结束原因跟踪
+///
+/// 优先级：上游错误 > 长度截断 > 工具调用 > 正常结束
+#[derive(Debug, Clone, Default)]
+pub struct FinishTracker {
+    error: bool,
+    truncated: bool,
+    tool_use: bool,
+}
+
+impl FinishTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 处理一个上游事件
+    pub fn observe(&mut self, event: &Event) {
+        match event {
+            Event::ToolUse(_) => self.tool_use = true,
+            Event::Error { .. } => self.error = true,
+            Event::Exception { exception_type, .. }
+                if exception_type == "ContentLengthExceededException" =>
+            {
+                self.truncated = true
+            }
+            Event::Truncated { .. } => self.truncated = true,
+            _ => {}
+        }
+    }
+
+    /// 最终的结束原因
+    pub fn reason(&self) -> FinishReason {
+        if self.error {
+            FinishReason::Error
+        } else if self.truncated {
+            FinishReason::MaxTokens
+        } else if self.tool_use {
+            FinishReason::ToolUse
+        } else {
+            FinishReason::Stop
+        }
+    }
+}
+
+/// 停止序列跟踪
+///
+/// 只保留输出文本中仍可能构成停止序列的结尾部分，
+/// 用于判断生成是否以请求中的某个停止序列结束
+#[derive(Debug, Clone, Default)]
+pub struct StopSequenceTracker {
+    stop_sequences: Vec<String>,
+    /// 可能是停止序列开头（或完整停止序列）的文本结尾
+    tail: String,
+}
+
+impl StopSequenceTracker {
+    /// 忽略空的停止序列
+    pub fn new(stop_sequences: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            stop_sequences: stop_sequences
+                .into_iter()
+                .filter(|s| !s.is_empty())
+                .collect(),
+            tail: String::new(),
+        }
+    }
+
+    /// 追加一段输出文本，返回此前保留、现已确定不属于停止序列的文本
+    pub fn push(&mut self, text: &str) -> String {
+        self.tail.push_str(text);
+        let keep = self.holdback();
+        self.tail.drain(..self.tail.len() - keep).collect()
+    }
+
+    /// 输出了非文本内容（如工具调用），此前的文本不再可能以停止序列结尾
+    pub fn reset(&mut self) -> String {
+        std::mem::take(&mut self.tail)
+    }
+
+    /// 文本结尾命中的停止序列（多个命中时取最长的）
+    pub fn matched(&self) -> Option<&str> {
+        self.stop_sequences
+            .iter()
+            .filter(|s| self.tail.ends_with(s.as_str()))
+            .max_by_key(|s| s.len())
+            .map(String::as_str)
+    }
+
+    /// 结束跟踪，返回去掉命中的停止序列后剩余的文本与命中的停止序列
+    pub fn finish(&mut self) -> (String, Option<String>) {
+        let matched = self.matched().map(str::to_string);
+        let mut tail = std::mem::take(&mut self.tail);
+        if let Some(stop) = &matched {
+            tail.truncate(tail.len() - stop.len());
+        }
+        (tail, matched)
+    }
+
+    /// 结尾中需要继续保留的字节数：最长的、同时是某个停止序列前缀的后缀
+    fn holdback(&self) -> usize {
+        self.tail
+            .char_indices()
+            .map(|(start, _)| &self.tail[start..])
+            .find(|suffix| self.stop_sequences.iter().any(|s| s.starts_with(suffix)))
+            .map_or(0, str::len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::model::events::ToolUseEvent;
+
+    fn tool_use() -> Event {
+        Event::ToolUse(ToolUseEvent {
+            name: "f".to_string(),
+            tool_use_id: "t1".to_string(),
+            input: String::new(),
+            stop: true,
+        })
+    }
+
+    fn exception(exception_type: &str) -> Event {
+        Event::Exception {
+            exception_type: exception_type.to_string(),
+            message: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_finish_reason_priority() {
+        let mut tracker = FinishTracker::new();
+        assert_eq!(tracker.reason(), FinishReason::Stop);
+
+        tracker.observe(&tool_use());
+        assert_eq!(tracker.reason(), FinishReason::ToolUse);
+
+        // 其他异常不影响结束原因
+        tracker.observe(&exception("SomeOtherException"));
+        assert_eq!(tracker.reason(), FinishReason::ToolUse);
+
+        tracker.observe(&exception("ContentLengthExceededException"));
+        assert_eq!(tracker.reason(), FinishReason::MaxTokens);
+
+        // 本地截断同样以长度截断结束
+        let mut local = FinishTracker::new();
+        local.observe(&Event::Truncated {
+            limit: crate::kiro::error::ResponseLimit::OutputTokens(10),
+        });
+        assert_eq!(local.reason(), FinishReason::MaxTokens);
+
+        tracker.observe(&Event::Error {
+            code: "InternalServerException".to_string(),
+            message: String::new(),
+        });
+        assert_eq!(tracker.reason(), FinishReason::Error);
+    }
+
+    #[test]
+    fn test_stop_sequence_tracker() {
+        let mut tracker = StopSequenceTracker::new(["###".to_string(), String::new()]);
+        assert_eq!(tracker.push("Hello #"), "Hello ");
+        assert_eq!(tracker.push("#"), "");
+        assert_eq!(tracker.matched(), None);
+        assert_eq!(tracker.push("#"), "");
+        assert_eq!(tracker.matched(), Some("###"));
+        assert_eq!(tracker.finish(), (String::new(), Some("###".to_string())));
+
+        // 部分匹配后继续输出其他文本
+        let mut tracker = StopSequenceTracker::new(["END".to_string()]);
+        assert_eq!(tracker.push("The EN"), "The ");
+        assert_eq!(tracker.push("D of 世界"), "END of 世界");
+        assert_eq!(tracker.finish(), (String::new(), None));
+
+        let mut tracker = StopSequenceTracker::new(["END".to_string()]);
+        assert_eq!(tracker.push("x EN"), "x ");
+        assert_eq!(tracker.reset(), "EN");
+        assert_eq!(tracker.finish(), (String::new(), None));
+    }
+
+    #[test]
+    fn test_protocol_mapping() {
+        let cases = [
+            (FinishReason::Stop, Some("stop"), Some("end_turn")),
+            (FinishReason::MaxTokens, Some("length"), Some("max_tokens")),
+            (FinishReason::ToolUse, Some("tool_calls"), Some("tool_use")),
+            (FinishReason::Error, None, None),
+        ];
+        for (reason, openai, anthropic) in cases {
+            assert_eq!(reason.openai(), openai);
+            assert_eq!(reason.anthropic(), anthropic);
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! 健康检查结果
+//!
+//! 由 `KiroProvider::health_check` 生成，用于就绪探针等场景
+
+use std::time::Duration;
+
+/// 一次健康检查的结果
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthStatus {
+    /// 是否拿到了可用的 Token，且上游未拒绝该 Token（401/403）
+    pub token_valid: bool,
+    /// 上游是否返回了 HTTP 响应（无论状态码）
+    pub upstream_reachable: bool,
+    /// 上游返回的状态码
+    pub status: Option<u16>,
+    /// 检查耗时
+    pub latency: Duration,
+    /// 不健康时的原因
+    pub error: Option<String>,
+}
+
+impl HealthStatus {
+    /// Token 有效、上游可达且返回成功状态码
+    #[allow(dead_code)]
+    pub fn is_healthy(&self) -> bool {
+        self.token_valid
+            && self.upstream_reachable
+            && self
+                .status
+                .is_some_and(|status| (200..300).contains(&status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_healthy() {
+        let healthy = HealthStatus {
+            token_valid: true,
+            upstream_reachable: true,
+            status: Some(200),
+            latency: Duration::from_millis(10),
+            error: None,
+        };
+        assert!(healthy.is_healthy());
+
+        let rejected = HealthStatus {
+            token_valid: false,
+            status: Some(401),
+            ..healthy.clone()
+        };
+        assert!(!rejected.is_healthy());
+
+        let throttled = HealthStatus {
+            status: Some(429),
+            ..healthy
+        };
+        assert!(!throttled.is_healthy());
+    }
+}
@@ -1,18 +1,25 @@
 //! 设备指纹生成器
 //!
 
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
 use sha2::{Digest, Sha256};
 
+use crate::kiro::atomic_file;
 use crate::kiro::model::credentials::KiroCredentials;
+use crate::kiro::random_utils::HexCase;
 use crate::model::config::Config;
 
 /// 根据凭证信息生成唯一的 Machine ID
 ///
 /// 优先使用自定义配置，然后使用 refreshToken 生成
 pub fn generate_from_credentials(credentials: &KiroCredentials, config: &Config) -> Option<String> {
-    // 如果配置了自定义 machineId 且长度为 64，优先使用
+    // 如果配置了自定义 machineId 且格式合法，优先使用
     if let Some(ref machine_id) = config.machine_id {
-        if machine_id.len() == 64 {
+        if is_valid_machine_id(machine_id) {
             return Some(machine_id.clone());
         }
     }
@@ -28,6 +35,190 @@ pub fn generate_from_credentials(credentials: &KiroCredentials, config: &Config)
     None
 }
 
+/// 从缓存文件读取 Machine ID，不存在或已损坏时重新生成并写回
+///
+/// 同一主机上的多个进程共享同一个缓存文件即可获得稳定的设备标识：
+/// 文件以原子方式写入，多个进程同时创建时只有一个生效，所有进程返回磁盘上最终的值
+pub fn get_or_create_machine_id(path: &Path) -> io::Result<String> {
+    let corrupt = match read_cached(path)? {
+        Cached::Valid(cached) => return Ok(cached),
+        Cached::Corrupt => {
+            tracing::warn!("Machine ID 缓存文件已损坏，重新生成: {}", path.display());
+            true
+        }
+        Cached::Missing => false,
+    };
+
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        fs::create_dir_all(parent)?;
+    }
+    let machine_id = generate_random();
+    if corrupt {
+        atomic_file::write(path, &machine_id)?;
+    } else {
+        atomic_file::create_new(path, &machine_id)?;
+    }
+
+    match read_cached(path)? {
+        Cached::Valid(cached) => Ok(cached),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Machine ID 缓存文件写入后无效: {}", path.display()),
+        )),
+    }
+}
+
+/// 缓存文件的状态
+enum Cached {
+    Missing,
+    Corrupt,
+    Valid(String),
+}
+
+/// 读取缓存文件
+fn read_cached(path: &Path) -> io::Result<Cached> {
+    match fs::read_to_string(path) {
+        Ok(content) => {
+            let cached = content.trim();
+            Ok(if is_valid_machine_id(cached) {
+                Cached::Valid(cached.to_string())
+            } else {
+                Cached::Corrupt
+            })
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Cached::Missing),
+        Err(e) => Err(e),
+    }
+}
+
+/// 生成随机 Machine ID（64 字符小写十六进制）
+pub fn generate_random() -> String {
+    generate_random_in(HexCase::Lower)
+}
+
+/// 生成指定大小写的随机 Machine ID
+pub fn generate_random_in(case: HexCase) -> String {
+    let bytes: Vec<u8> = std::iter::repeat_with(|| fastrand::u8(..))
+        .take(32)
+        .collect();
+    case.encode(bytes)
+}
+
+/// 从稳定的种子确定性地派生 Machine ID（SHA256 十六进制，64 字符）
+#[allow(dead_code)]
+pub fn machine_id_from_seed(seed: &[u8]) -> String {
+    hex::encode(Sha256::digest(seed))
+}
+
+/// 从主机信息（主机名 + 首个物理网卡 MAC）派生 Machine ID
+///
+/// 同一台机器多次调用结果一致，无需可写的缓存文件
+#[allow(dead_code)]
+pub fn machine_id_from_host() -> io::Result<String> {
+    let hostname = read_hostname();
+    let mac = read_primary_mac();
+
+    if hostname.is_none() && mac.is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "无法获取主机名或 MAC 地址",
+        ));
+    }
+
+    let seed = format!(
+        "{}|{}",
+        hostname.unwrap_or_default(),
+        mac.unwrap_or_default()
+    );
+    Ok(machine_id_from_seed(seed.as_bytes()))
+}
+
+/// 读取主机名
+fn read_hostname() -> Option<String> {
+    ["HOSTNAME", "COMPUTERNAME"]
+        .iter()
+        .filter_map(|key| std::env::var(key).ok())
+        .chain(fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .find(|name| !name.is_empty())
+}
+
+/// 读取首个物理网卡的 MAC 地址（按网卡名排序，仅 Linux 可用）
+fn read_primary_mac() -> Option<String> {
+    read_primary_mac_in(Path::new("/sys/class/net"))
+}
+
+/// 从 `root`（`/sys/class/net` 结构）中读取首个物理网卡的 MAC 地址
+///
+/// 只保留带 `device` 链接的网卡，跳过 `lo`、`docker0`、`br-*`、`veth*` 等虚拟网卡
+/// （其 MAC 在容器重启后会变化）以及全零 MAC
+fn read_primary_mac_in(root: &Path) -> Option<String> {
+    let mut interfaces: Vec<_> = fs::read_dir(root)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.join("device").exists())
+        .collect();
+    interfaces.sort();
+
+    interfaces.iter().find_map(|path| {
+        let mac = fs::read_to_string(path.join("address")).ok()?;
+        let mac = mac.trim();
+        (!mac.is_empty() && mac != "00:00:00:00:00:00").then(|| mac.to_string())
+    })
+}
+
+/// Machine ID 的长度（32 字节的十六进制表示）
+pub const MACHINE_ID_LEN: usize = 64;
+
+/// Machine ID 格式错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MachineIdError {
+    /// 长度不是 64
+    InvalidLength(usize),
+    /// 含有非十六进制字符
+    InvalidChar { index: usize, ch: char },
+}
+
+impl fmt::Display for MachineIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MachineIdError::InvalidLength(len) => write!(
+                f,
+                "Machine ID 长度应为 {} 个字符，实际为 {}",
+                MACHINE_ID_LEN, len
+            ),
+            MachineIdError::InvalidChar { index, ch } => {
+                write!(
+                    f,
+                    "Machine ID 第 {} 个字符 {:?} 不是十六进制字符",
+                    index, ch
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for MachineIdError {}
+
+/// 校验 Machine ID 格式：64 字符十六进制
+pub fn validate_machine_id(value: &str) -> Result<(), MachineIdError> {
+    if let Some((index, ch)) = value.char_indices().find(|(_, c)| !c.is_ascii_hexdigit()) {
+        return Err(MachineIdError::InvalidChar { index, ch });
+    }
+    if value.len() != MACHINE_ID_LEN {
+        return Err(MachineIdError::InvalidLength(value.len()));
+    }
+    Ok(())
+}
+
+/// 判断 Machine ID 格式是否合法，见 `validate_machine_id`
+pub fn is_valid_machine_id(value: &str) -> bool {
+    validate_machine_id(value).is_ok()
+}
+
 /// SHA256 哈希实现（返回十六进制字符串）
 fn sha256_hex(input: &str) -> String {
     let mut hasher = Sha256::new();
@@ -79,4 +270,141 @@ mod tests {
         let result = generate_from_credentials(&credentials, &config);
         assert!(result.is_none());
     }
+
+    #[test]
+    fn test_machine_id_from_seed() {
+        let a = machine_id_from_seed(b"host-a|00:11:22:33:44:55");
+        let b = machine_id_from_seed(b"host-a|00:11:22:33:44:55");
+        let c = machine_id_from_seed(b"host-b|00:11:22:33:44:55");
+
+        assert!(is_valid_machine_id(&a));
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(machine_id_from_seed(b"test"), sha256_hex("test"));
+    }
+
+    #[test]
+    fn test_machine_id_from_host_is_stable() {
+        if let Ok(first) = machine_id_from_host() {
+            assert!(is_valid_machine_id(&first));
+            assert_eq!(first, machine_id_from_host().unwrap());
+        }
+    }
+
+    fn temp_cache_path() -> std::path::PathBuf {
+        std::env::temp_dir()
+            .join(format!("kiro-rs-test-{}", uuid::Uuid::new_v4()))
+            .join("machine_id")
+    }
+
+    #[test]
+    fn test_read_primary_mac_skips_virtual_interfaces() {
+        let root = std::env::temp_dir().join(format!("kiro-rs-net-{}", uuid::Uuid::new_v4()));
+        let add = |name: &str, mac: &str, physical: bool| {
+            let dir = root.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("address"), format!("{}\n", mac)).unwrap();
+            if physical {
+                fs::create_dir_all(dir.join("device")).unwrap();
+            }
+        };
+        add("br-1a2b", "02:42:aa:bb:cc:01", false);
+        add("docker0", "02:42:aa:bb:cc:02", false);
+        add("eno0", "00:00:00:00:00:00", true);
+        add("eth0", "52:54:00:12:34:56", true);
+        add("lo", "00:00:00:00:00:00", false);
+        add("veth0abc", "02:42:aa:bb:cc:03", false);
+
+        assert_eq!(
+            read_primary_mac_in(&root).as_deref(),
+            Some("52:54:00:12:34:56")
+        );
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_get_or_create_machine_id_is_stable() {
+        let path = temp_cache_path();
+
+        let first = get_or_create_machine_id(&path).unwrap();
+        assert!(is_valid_machine_id(&first));
+        assert_eq!(fs::read_to_string(&path).unwrap(), first);
+
+        let second = get_or_create_machine_id(&path).unwrap();
+        assert_eq!(first, second);
+
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_get_or_create_machine_id_concurrent_callers_agree() {
+        let path = temp_cache_path();
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let path = path.clone();
+                std::thread::spawn(move || get_or_create_machine_id(&path).unwrap())
+            })
+            .collect();
+        let ids: Vec<String> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        assert!(ids.iter().all(|id| *id == ids[0]), "{:?}", ids);
+        assert_eq!(fs::read_to_string(&path).unwrap(), ids[0]);
+        // 不残留临时文件
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_get_or_create_machine_id_regenerates_corrupt_cache() {
+        let path = temp_cache_path();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "abc123").unwrap();
+
+        let machine_id = get_or_create_machine_id(&path).unwrap();
+        assert!(is_valid_machine_id(&machine_id));
+        assert_eq!(fs::read_to_string(&path).unwrap(), machine_id);
+
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_validate_machine_id() {
+        let valid = generate_random();
+        assert!(is_valid_machine_id(&valid));
+        assert_eq!(validate_machine_id(&valid.to_uppercase()), Ok(()));
+
+        let upper = generate_random_in(HexCase::Upper);
+        assert!(is_valid_machine_id(&upper));
+        assert!(!upper.bytes().any(|b| b.is_ascii_lowercase()));
+
+        assert_eq!(
+            validate_machine_id("abc123"),
+            Err(MachineIdError::InvalidLength(6))
+        );
+        assert!(!is_valid_machine_id(""));
+
+        let non_hex = format!("{}g", &valid[..63]);
+        assert_eq!(
+            validate_machine_id(&non_hex),
+            Err(MachineIdError::InvalidChar { index: 63, ch: 'g' })
+        );
+        assert!(!is_valid_machine_id(&non_hex));
+    }
+
+    #[test]
+    fn test_generate_ignores_invalid_custom_machine_id() {
+        let credentials = KiroCredentials {
+            refresh_token: Some("test_refresh_token".to_string()),
+            ..Default::default()
+        };
+        let config = Config {
+            machine_id: Some("z".repeat(64)),
+            ..Default::default()
+        };
+
+        let result = generate_from_credentials(&credentials, &config).unwrap();
+        assert_ne!(result, "z".repeat(64));
+        assert!(is_valid_machine_id(&result));
+    }
 }
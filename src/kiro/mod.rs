@@ -1,8 +1,27 @@
 //! Kiro API 客户端模块
 
+pub(crate) mod atomic_file;
+pub mod cache;
+pub mod circuit_breaker;
+pub mod clock;
+pub mod completion;
+pub mod error;
+pub mod finish_reason;
+pub mod health;
 pub mod machine_id;
 pub mod model;
+pub mod observer;
 pub mod parser;
+pub mod preview;
 pub mod provider;
+pub mod provider_config;
 pub mod random_utils;
+#[cfg(any(test, feature = "test-support"))]
+pub mod replay;
+pub mod response;
+pub mod retry;
+#[cfg(feature = "tower")]
+pub mod service;
 pub mod token_manager;
+pub mod token_store;
+mod trace;
@@ -4,11 +4,14 @@
 //! 支持单凭据和多凭据配置格式
 
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::fs;
 use std::path::Path;
 
 /// Kiro OAuth 凭证
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+///
+/// `Debug` 输出会隐藏令牌和 Client Secret，需要原值时直接读取对应字段
+#[derive(Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct KiroCredentials {
     /// 凭据唯一标识符（自增 ID）
@@ -49,6 +52,72 @@ pub struct KiroCredentials {
     pub priority: u32,
 }
 
+impl fmt::Debug for KiroCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KiroCredentials")
+            .field("id", &self.id)
+            .field("access_token", &Redacted(&self.access_token))
+            .field("refresh_token", &Redacted(&self.refresh_token))
+            .field("profile_arn", &self.profile_arn)
+            .field("expires_at", &self.expires_at)
+            .field("auth_method", &self.auth_method)
+            .field("client_id", &self.client_id)
+            .field("client_secret", &Redacted(&self.client_secret))
+            .field("priority", &self.priority)
+            .finish()
+    }
+}
+
+/// 敏感字段的 `Debug` 包装，只显示是否存在，不输出原值
+pub(crate) struct Redacted<'a, T: ?Sized>(pub &'a T);
+
+impl fmt::Debug for Redacted<'_, str> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("\"***redacted***\"")
+    }
+}
+
+impl fmt::Debug for Redacted<'_, String> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Redacted(self.0.as_str()).fmt(f)
+    }
+}
+
+impl fmt::Debug for Redacted<'_, Option<String>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(value) => f.debug_tuple("Some").field(&Redacted(value)).finish(),
+            None => f.write_str("None"),
+        }
+    }
+}
+
+/// 认证方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMethod {
+    /// Social 登录（Kiro 桌面端刷新接口）
+    Social,
+    /// AWS IAM Identity Center / Builder ID（AWS SSO OIDC 刷新接口）
+    Idc,
+}
+
+impl AuthMethod {
+    /// 从配置中的 authMethod 字符串解析（不区分大小写，未知值视为 Social）
+    pub fn parse(value: &str) -> Self {
+        match value.to_lowercase().as_str() {
+            "idc" | "builder-id" => AuthMethod::Idc,
+            _ => AuthMethod::Social,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthMethod::Social => "social",
+            AuthMethod::Idc => "idc",
+        }
+    }
+}
+
 /// 判断是否为零（用于跳过序列化）
 fn is_zero(value: &u32) -> bool {
     *value == 0
@@ -128,6 +197,14 @@ impl CredentialsConfig {
 }
 
 impl KiroCredentials {
+    /// 解析认证方式（未配置时为 Social）
+    pub fn auth_method_kind(&self) -> AuthMethod {
+        self.auth_method
+            .as_deref()
+            .map(AuthMethod::parse)
+            .unwrap_or(AuthMethod::Social)
+    }
+
     /// 获取默认凭证文件路径
     pub fn default_credentials_path() -> &'static str {
         "credentials.json"
@@ -265,4 +342,51 @@ mod tests {
         assert_eq!(list[1].refresh_token, Some("t3".to_string())); // priority 1
         assert_eq!(list[2].refresh_token, Some("t1".to_string())); // priority 2
     }
+
+    #[test]
+    fn test_auth_method_kind() {
+        let mut creds = KiroCredentials::default();
+        assert_eq!(creds.auth_method_kind(), AuthMethod::Social);
+
+        creds.auth_method = Some("IdC".to_string());
+        assert_eq!(creds.auth_method_kind(), AuthMethod::Idc);
+
+        creds.auth_method = Some("builder-id".to_string());
+        assert_eq!(creds.auth_method_kind(), AuthMethod::Idc);
+
+        creds.auth_method = Some("social".to_string());
+        assert_eq!(creds.auth_method_kind(), AuthMethod::Social);
+    }
+
+    /// 断言 `output` 不包含 `secret` 的任何长度为 4 的片段
+    fn assert_no_leak(output: &str, secret: &str) {
+        for window in secret.as_bytes().windows(4) {
+            let window = std::str::from_utf8(window).unwrap();
+            assert!(!output.contains(window), "泄露了 {}: {}", window, output);
+        }
+    }
+
+    #[test]
+    fn test_debug_redacts_secrets() {
+        let creds = KiroCredentials {
+            id: Some(7),
+            access_token: Some("aoaAT9xQzLmWvB3k".to_string()),
+            refresh_token: Some("aorRT5pJhYnGcE8u".to_string()),
+            client_secret: Some("csKq2VdZfXw7".to_string()),
+            expires_at: Some("2030-01-01T00:00:00Z".to_string()),
+            ..Default::default()
+        };
+
+        let output = format!("{:?}", creds);
+        assert!(output.contains("access_token: Some(\"***redacted***\")"));
+        assert!(output.contains("2030-01-01T00:00:00Z"));
+        for secret in ["aoaAT9xQzLmWvB3k", "aorRT5pJhYnGcE8u", "csKq2VdZfXw7"] {
+            assert_no_leak(&output, secret);
+            assert_no_leak(&format!("{:#?}", creds), secret);
+        }
+
+        // 原值仍可直接读取
+        assert_eq!(creds.access_token.as_deref(), Some("aoaAT9xQzLmWvB3k"));
+        assert!(format!("{:?}", KiroCredentials::default()).contains("access_token: None"));
+    }
 }
@@ -47,6 +47,16 @@ impl EventPayload for AssistantResponseEvent {
     }
 }
 
+impl AssistantResponseEvent {
+    /// 创建只包含文本内容的事件
+    pub fn new(content: impl Into<String>) -> Self {
+        Self {
+            content: content.into(),
+            ..Self::default()
+        }
+    }
+}
+
 impl Default for AssistantResponseEvent {
     fn default() -> Self {
         Self {
@@ -2,6 +2,9 @@
 //!
 //! 定义事件类型枚举、trait 和统一事件结构
 
+use serde::Deserialize;
+
+use crate::kiro::error::ResponseLimit;
 use crate::kiro::parser::error::{ParseError, ParseResult};
 use crate::kiro::parser::frame::Frame;
 
@@ -16,6 +19,10 @@ pub enum EventType {
     Metering,
     /// 上下文使用率事件
     ContextUsage,
+    /// 元数据事件
+    Metadata,
+    /// 推理内容事件
+    ReasoningContent,
     /// 未知事件类型
     Unknown,
 }
@@ -28,6 +35,8 @@ impl EventType {
             "toolUseEvent" => Self::ToolUse,
             "meteringEvent" => Self::Metering,
             "contextUsageEvent" => Self::ContextUsage,
+            "metadataEvent" => Self::Metadata,
+            "reasoningContentEvent" => Self::ReasoningContent,
             _ => Self::Unknown,
         }
     }
@@ -39,6 +48,8 @@ impl EventType {
             Self::ToolUse => "toolUseEvent",
             Self::Metering => "meteringEvent",
             Self::ContextUsage => "contextUsageEvent",
+            Self::Metadata => "metadataEvent",
+            Self::ReasoningContent => "reasoningContentEvent",
             Self::Unknown => "unknown",
         }
     }
@@ -71,22 +82,45 @@ pub enum Event {
     Metering(()),
     /// 上下文使用率
     ContextUsage(super::ContextUsageEvent),
+    /// 思考内容增量（reasoningContentEvent），与回答文本分开输出
+    ThinkingDelta(super::ReasoningContentEvent),
+    /// 上游下发的权威 token 用量（来自携带 `tokenUsage` 的 metadataEvent）
+    ///
+    /// 值为截至当前的累计用量而非增量，可能多次出现并与文本增量交错，
+    /// 使用方应以最后一次为准，不能累加
+    Usage {
+        /// 输入 tokens
+        input: Option<i32>,
+        /// 输出 tokens
+        output: Option<i32>,
+    },
     /// 未知事件 (保留原始帧数据)
     Unknown {},
     /// 服务端错误
+    ///
+    /// 来源于 `:message-type` 为 `error`/`exception` 的帧，或携带错误结构的事件负载
     Error {
-        /// 错误代码
-        error_code: String,
+        /// 错误代码 (如 `ThrottlingException`)
+        code: String,
         /// 错误消息
-        error_message: String,
+        message: String,
     },
     /// 服务端异常
+    ///
+    /// 仅保留 `ContentLengthExceededException`：它表示输出被截断，属于正常的结束信号
     Exception {
         /// 异常类型
         exception_type: String,
         /// 异常消息
         message: String,
     },
+    /// 本地截断：响应超过 `ProviderConfig` 配置的上限，由 provider 生成而非上游下发
+    ///
+    /// 结束原因为 max_tokens，之后流以 `KiroError::ResponseTooLarge` 结束
+    Truncated {
+        /// 被超过的上限
+        limit: ResponseLimit,
+    },
 }
 
 impl Event {
@@ -121,37 +155,95 @@ impl Event {
                 let payload = super::ContextUsageEvent::from_frame(&frame)?;
                 Ok(Self::ContextUsage(payload))
             }
-            EventType::Unknown => Ok(Self::Unknown {}),
+            EventType::ReasoningContent => {
+                let payload = super::ReasoningContentEvent::from_frame(&frame)?;
+                Ok(Self::ThinkingDelta(payload))
+            }
+            EventType::Metadata => {
+                let payload = super::MetadataEvent::from_frame(&frame)?;
+                Ok(match payload.token_usage {
+                    Some(usage) => Self::Usage {
+                        input: usage.input_tokens,
+                        output: usage.output_tokens,
+                    },
+                    None => Self::Unknown {},
+                })
+            }
+            EventType::Unknown => match ErrorPayload::from_frame(&frame) {
+                Some(payload) if payload.code().is_some() => Ok(Self::Error {
+                    code: payload.code().unwrap_or_default().to_string(),
+                    message: payload.message.unwrap_or_else(|| frame.payload_as_str()),
+                }),
+                _ => Ok(Self::Unknown {}),
+            },
         }
     }
 
     /// 解析错误类型消息
     fn parse_error(frame: Frame) -> ParseResult<Self> {
-        let error_code = frame
-            .headers
-            .error_code()
-            .unwrap_or("UnknownError")
-            .to_string();
-        let error_message = frame.payload_as_str();
-
-        Ok(Self::Error {
-            error_code,
-            error_message,
-        })
+        Ok(Self::error_from_frame(
+            &frame,
+            frame.headers.error_code(),
+            "UnknownError",
+        ))
     }
 
     /// 解析异常类型消息
     fn parse_exception(frame: Frame) -> ParseResult<Self> {
-        let exception_type = frame
-            .headers
-            .exception_type()
-            .unwrap_or("UnknownException")
+        let event =
+            Self::error_from_frame(&frame, frame.headers.exception_type(), "UnknownException");
+
+        match event {
+            Self::Error { code, message } if code == "ContentLengthExceededException" => {
+                Ok(Self::Exception {
+                    exception_type: code,
+                    message,
+                })
+            }
+            event => Ok(event),
+        }
+    }
+
+    /// 从错误帧构造 `Event::Error`：优先使用头部中的错误代码，其次使用负载中的
+    fn error_from_frame(frame: &Frame, header_code: Option<&str>, fallback: &str) -> Self {
+        let payload = ErrorPayload::from_frame(frame);
+        let code = header_code
+            .or_else(|| payload.as_ref().and_then(|p| p.code()))
+            .unwrap_or(fallback)
             .to_string();
-        let message = frame.payload_as_str();
+        let message = payload
+            .and_then(|p| p.message)
+            .unwrap_or_else(|| frame.payload_as_str());
+
+        Self::Error { code, message }
+    }
+}
+
+/// 错误负载结构
+///
+/// 兼容 `{"__type": "...#ThrottlingException", "message": "..."}` 与
+/// `{"errorCode": "...", "Message": "..."}` 等写法
+#[derive(Debug, Default, Deserialize)]
+struct ErrorPayload {
+    #[serde(rename = "__type")]
+    type_name: Option<String>,
+    #[serde(alias = "errorCode")]
+    code: Option<String>,
+    #[serde(alias = "Message", alias = "errorMessage")]
+    message: Option<String>,
+}
+
+impl ErrorPayload {
+    fn from_frame(frame: &Frame) -> Option<Self> {
+        frame.payload_as_json().ok()
+    }
 
-        Ok(Self::Exception {
-            exception_type,
-            message,
+    /// 错误代码，`__type` 中的命名空间前缀（`#` 之前的部分）会被去掉
+    fn code(&self) -> Option<&str> {
+        self.code.as_deref().or_else(|| {
+            self.type_name
+                .as_deref()
+                .map(|t| t.rsplit('#').next().unwrap_or(t))
         })
     }
 }
@@ -159,6 +251,7 @@ impl Event {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::{encode_event, encode_frame};
 
     #[test]
     fn test_event_type_from_str() {
@@ -172,6 +265,11 @@ mod tests {
             EventType::from_str("contextUsageEvent"),
             EventType::ContextUsage
         );
+        assert_eq!(EventType::from_str("metadataEvent"), EventType::Metadata);
+        assert_eq!(
+            EventType::from_str("reasoningContentEvent"),
+            EventType::ReasoningContent
+        );
         assert_eq!(EventType::from_str("unknown_type"), EventType::Unknown);
     }
 
@@ -183,4 +281,70 @@ mod tests {
         );
         assert_eq!(EventType::ToolUse.as_str(), "toolUseEvent");
     }
+
+    fn parse(bytes: Vec<u8>) -> Event {
+        let (frame, _) = crate::kiro::parser::frame::parse_frame(&bytes)
+            .unwrap()
+            .unwrap();
+        Event::from_frame(frame).unwrap()
+    }
+
+    #[test]
+    fn test_throttling_exception_frame() {
+        let bytes = encode_frame(
+            &[
+                (":message-type", "exception"),
+                (":exception-type", "ThrottlingException"),
+                (":content-type", "application/json"),
+            ],
+            br#"{"message":"Rate exceeded"}"#,
+        );
+
+        match parse(bytes) {
+            Event::Error { code, message } => {
+                assert_eq!(code, "ThrottlingException");
+                assert_eq!(message, "Rate exceeded");
+            }
+            other => panic!("expected Error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_content_length_exceeded_stays_exception() {
+        let bytes = encode_frame(
+            &[
+                (":message-type", "exception"),
+                (":exception-type", "ContentLengthExceededException"),
+            ],
+            b"too long",
+        );
+
+        assert!(matches!(
+            parse(bytes),
+            Event::Exception { exception_type, message }
+                if exception_type == "ContentLengthExceededException" && message == "too long"
+        ));
+    }
+
+    #[test]
+    fn test_error_shaped_event_payload() {
+        let bytes = encode_event(
+            "internalServerException",
+            r#"{"__type":"com.amazon.aws.codewhisperer#InternalServerException","Message":"boom"}"#,
+        );
+
+        match parse(bytes) {
+            Event::Error { code, message } => {
+                assert_eq!(code, "InternalServerException");
+                assert_eq!(message, "boom");
+            }
+            other => panic!("expected Error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_unknown_event_without_error_shape() {
+        let bytes = encode_event("somethingNewEvent", r#"{"foo":1}"#);
+        assert!(matches!(parse(bytes), Event::Unknown {}));
+    }
 }
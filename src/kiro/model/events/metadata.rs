@@ -0,0 +1,39 @@
+//! 元数据事件
+//!
+//! 处理 metadataEvent 类型的事件
+
+use serde::Deserialize;
+
+use crate::kiro::parser::error::ParseResult;
+use crate::kiro::parser::frame::Frame;
+
+use super::base::EventPayload;
+
+/// 元数据事件
+///
+/// 上游在响应末尾下发，包含权威的 token 用量
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataEvent {
+    /// token 用量
+    #[serde(default)]
+    pub token_usage: Option<TokenUsage>,
+}
+
+/// token 用量
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenUsage {
+    /// 输入 tokens（兼容 `uncachedInputTokens` 写法）
+    #[serde(default, alias = "uncachedInputTokens")]
+    pub input_tokens: Option<i32>,
+    /// 输出 tokens
+    #[serde(default)]
+    pub output_tokens: Option<i32>,
+}
+
+impl EventPayload for MetadataEvent {
+    fn from_frame(frame: &Frame) -> ParseResult<Self> {
+        frame.payload_as_json()
+    }
+}
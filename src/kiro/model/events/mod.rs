@@ -5,9 +5,13 @@
 mod assistant;
 mod base;
 mod context_usage;
+mod metadata;
+mod reasoning;
 mod tool_use;
 
 pub use assistant::AssistantResponseEvent;
 pub use base::Event;
 pub use context_usage::ContextUsageEvent;
-pub use tool_use::ToolUseEvent;
+pub use metadata::MetadataEvent;
+pub use reasoning::ReasoningContentEvent;
+pub use tool_use::{ToolUse, ToolUseAccumulator, ToolUseEvent};
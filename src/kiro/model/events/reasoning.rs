@@ -0,0 +1,57 @@
+//! 推理内容事件
+//!
+//! 处理 reasoningContentEvent 类型的事件（模型的思考过程，与最终回答分开下发）
+
+use serde::{Deserialize, Serialize};
+
+use crate::kiro::parser::error::ParseResult;
+use crate::kiro::parser::frame::Frame;
+
+use super::base::EventPayload;
+
+/// 推理内容事件
+///
+/// `text` 为思考内容的增量；`signature` 通常只在思考结束时的最后一个事件中出现
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReasoningContentEvent {
+    /// 思考内容片段
+    #[serde(default)]
+    pub text: String,
+
+    /// 思考内容的签名
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl EventPayload for ReasoningContentEvent {
+    fn from_frame(frame: &Frame) -> ParseResult<Self> {
+        frame.payload_as_json()
+    }
+}
+
+impl ReasoningContentEvent {
+    /// 创建只包含思考内容的事件
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            signature: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize() {
+        let event: ReasoningContentEvent =
+            serde_json::from_str(r#"{"text":"Let me think","redactedContent":null}"#).unwrap();
+        assert_eq!(event, ReasoningContentEvent::new("Let me think"));
+
+        let event: ReasoningContentEvent = serde_json::from_str(r#"{"signature":"sig"}"#).unwrap();
+        assert_eq!(event.text, "");
+        assert_eq!(event.signature.as_deref(), Some("sig"));
+    }
+}
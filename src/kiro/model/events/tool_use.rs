@@ -2,7 +2,7 @@
 //!
 //! 处理 toolUseEvent 类型的事件
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::kiro::parser::error::ParseResult;
 use crate::kiro::parser::frame::Frame;
@@ -50,3 +50,341 @@ impl std::fmt::Display for ToolUseEvent {
         }
     }
 }
+
+/// 完整的工具调用块
+///
+/// 对应 Anthropic 响应中 `type: "tool_use"` 的 content block
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToolUse {
+    /// 工具调用 ID
+    pub id: String,
+    /// 工具名称
+    pub name: String,
+    /// 拼接并解析后的工具输入
+    pub input: serde_json::Value,
+    /// 输入 JSON 不完整，已补全括号 / 引号后解析
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub repaired: bool,
+    /// 输入无法解析时保留的原始文本（此时 `input` 为空对象）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_input: Option<String>,
+}
+
+impl ToolUse {
+    /// 转换为 Anthropic content block
+    pub fn to_content_block(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "tool_use",
+            "id": self.id,
+            "name": self.name,
+            "input": self.input
+        })
+    }
+}
+
+/// 工具调用累积器
+///
+/// 按 `tool_use_id` 拼接流式到达的 `input` 分片，收到 `stop` 时输出完整的 `ToolUse`
+#[derive(Debug, Default)]
+pub struct ToolUseAccumulator {
+    /// 尚未结束的工具调用：(tool_use_id, 名称, 已拼接的输入)，按首次出现的顺序
+    pending: Vec<(String, String, String)>,
+    /// 是否尝试补全不完整的输入 JSON
+    repair: bool,
+}
+
+impl ToolUseAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 输入 JSON 被截断时尝试补全（闭合字符串与括号）后再解析，默认关闭
+    pub fn with_repair(mut self, enabled: bool) -> Self {
+        self.repair = enabled;
+        self
+    }
+
+    /// 追加一个工具事件分片
+    ///
+    /// 工具调用完成时返回拼接好的 `ToolUse`，否则返回 `None`
+    pub fn push(&mut self, event: &ToolUseEvent) -> Option<ToolUse> {
+        let index = match self
+            .pending
+            .iter()
+            .position(|(id, _, _)| *id == event.tool_use_id)
+        {
+            Some(index) => index,
+            None => {
+                self.pending
+                    .push((event.tool_use_id.clone(), event.name.clone(), String::new()));
+                self.pending.len() - 1
+            }
+        };
+        self.pending[index].2.push_str(&event.input);
+
+        if !event.stop {
+            return None;
+        }
+
+        let (id, _, buffer) = self.pending.remove(index);
+        Some(self.complete(id, event.name.clone(), buffer))
+    }
+
+    /// 结束累积，返回未收到 `stop` 就中断的工具调用
+    pub fn finish(&mut self) -> Vec<ToolUse> {
+        std::mem::take(&mut self.pending)
+            .into_iter()
+            .map(|(id, name, buffer)| {
+                tracing::warn!("工具调用未正常结束: tool_use_id: {}", id);
+                self.complete(id, name, buffer)
+            })
+            .collect()
+    }
+
+    /// 解析拼接好的输入
+    fn complete(&self, id: String, name: String, buffer: String) -> ToolUse {
+        let mut tool_use = ToolUse {
+            id,
+            name,
+            input: serde_json::json!({}),
+            repaired: false,
+            raw_input: None,
+        };
+        if buffer.trim().is_empty() {
+            return tool_use;
+        }
+
+        match serde_json::from_str(&buffer) {
+            Ok(input) => tool_use.input = input,
+            Err(e) => {
+                let repaired = self
+                    .repair
+                    .then(|| repair_json(&buffer))
+                    .flatten()
+                    .and_then(|json| serde_json::from_str(&json).ok());
+                match repaired {
+                    Some(input) => {
+                        tool_use.input = input;
+                        tool_use.repaired = true;
+                    }
+                    None => {
+                        tracing::warn!(
+                            "工具输入 JSON 解析失败: {}, tool_use_id: {}, 原始内容: {}",
+                            e,
+                            tool_use.id,
+                            buffer
+                        );
+                        tool_use.raw_input = Some(buffer);
+                    }
+                }
+            }
+        }
+        tool_use
+    }
+}
+
+/// 尽量少改动地补全被截断的 JSON：闭合未结束的字符串与括号，
+/// 去掉末尾多余的逗号，缺失的值补为 `null`。补全后仍无法解析时返回 `None`
+pub(crate) fn repair_json(input: &str) -> Option<String> {
+    let mut stack = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    // 当前字符串是否为对象的键 / 是否刚结束一个还没有冒号的键 / 下一个字符串是否为键
+    let mut string_is_key = false;
+    let mut key_pending = false;
+    let mut expect_key = false;
+
+    for c in input.chars() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => {
+                    in_string = false;
+                    key_pending = string_is_key;
+                }
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                string_is_key = expect_key;
+                expect_key = false;
+            }
+            '{' | '[' => {
+                stack.push(c);
+                expect_key = c == '{';
+            }
+            '}' | ']' => {
+                stack.pop();
+                expect_key = false;
+            }
+            ',' => expect_key = stack.last() == Some(&'{'),
+            ':' => key_pending = false,
+            _ => {}
+        }
+    }
+
+    let mut output = input.to_string();
+    if in_string {
+        if escaped {
+            output.pop();
+        }
+        output.push('"');
+        key_pending = string_is_key;
+    }
+    let trimmed = output.trim_end().trim_end_matches(',').len();
+    output.truncate(trimmed);
+    if key_pending {
+        output.push_str(": null");
+    } else if output.ends_with(':') {
+        output.push_str(" null");
+    }
+    while let Some(open) = stack.pop() {
+        output.push(if open == '{' { '}' } else { ']' });
+    }
+
+    serde_json::from_str::<serde_json::Value>(&output)
+        .is_ok()
+        .then_some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::model::events::Event;
+    use crate::kiro::parser::stream::StreamParser;
+    use crate::test_support::encode_event;
+
+    fn tool_chunk(input: &str, stop: bool) -> Vec<u8> {
+        let payload = serde_json::json!({
+            "name": "get_weather",
+            "toolUseId": "tooluse_1",
+            "input": input,
+            "stop": stop
+        });
+        encode_event("toolUseEvent", &payload.to_string())
+    }
+
+    #[test]
+    fn test_accumulate_multi_chunk_tool_use() {
+        let mut body = Vec::new();
+        body.extend(tool_chunk("", false));
+        body.extend(tool_chunk("{\"city\": \"Pa", false));
+        body.extend(tool_chunk("ris\", \"days\"", false));
+        body.extend(tool_chunk(": 3}", false));
+        body.extend(tool_chunk("", true));
+
+        // 以不对齐帧边界的小块喂给解析器
+        let mut parser = StreamParser::new();
+        let mut accumulator = ToolUseAccumulator::new();
+        let mut completed = Vec::new();
+        for chunk in body.chunks(13) {
+            for event in parser.push(chunk) {
+                if let Event::ToolUse(tool_use) = event {
+                    completed.extend(accumulator.push(&tool_use));
+                }
+            }
+        }
+        parser.finish().unwrap();
+
+        assert_eq!(
+            completed,
+            vec![ToolUse {
+                id: "tooluse_1".to_string(),
+                name: "get_weather".to_string(),
+                input: serde_json::json!({"city": "Paris", "days": 3}),
+                repaired: false,
+                raw_input: None,
+            }]
+        );
+        assert_eq!(
+            completed[0].to_content_block()["type"],
+            serde_json::json!("tool_use")
+        );
+    }
+
+    #[test]
+    fn test_accumulate_interleaved_tool_uses() {
+        let event = |id: &str, input: &str, stop: bool| ToolUseEvent {
+            name: "tool".to_string(),
+            tool_use_id: id.to_string(),
+            input: input.to_string(),
+            stop,
+        };
+
+        let mut accumulator = ToolUseAccumulator::new();
+        assert!(accumulator.push(&event("a", "{\"x\":", false)).is_none());
+        assert!(accumulator.push(&event("b", "{\"y\":2}", false)).is_none());
+        let a = accumulator.push(&event("a", "1}", true)).unwrap();
+        let b = accumulator.push(&event("b", "", true)).unwrap();
+
+        assert_eq!(a.input, serde_json::json!({"x": 1}));
+        assert_eq!(b.input, serde_json::json!({"y": 2}));
+    }
+
+    #[test]
+    fn test_accumulate_empty_input() {
+        let mut accumulator = ToolUseAccumulator::new();
+        let tool_use = accumulator
+            .push(&ToolUseEvent {
+                name: "ping".to_string(),
+                tool_use_id: "t".to_string(),
+                input: String::new(),
+                stop: true,
+            })
+            .unwrap();
+        assert_eq!(tool_use.input, serde_json::json!({}));
+    }
+
+    #[test]
+    fn test_truncated_input_repair() {
+        let truncated = ToolUseEvent {
+            name: "f".to_string(),
+            tool_use_id: "t".to_string(),
+            input: r#"{"a": 1, "b":"#.to_string(),
+            stop: false,
+        };
+
+        // 默认不补全：保留原始文本
+        let mut accumulator = ToolUseAccumulator::new();
+        assert!(accumulator.push(&truncated).is_none());
+        let [raw] = &accumulator.finish()[..] else {
+            panic!("expected one unfinished tool use");
+        };
+        assert_eq!(raw.input, serde_json::json!({}));
+        assert!(!raw.repaired);
+        assert_eq!(raw.raw_input.as_deref(), Some(r#"{"a": 1, "b":"#));
+
+        let mut accumulator = ToolUseAccumulator::new().with_repair(true);
+        let repaired = accumulator
+            .push(&ToolUseEvent {
+                stop: true,
+                ..truncated
+            })
+            .unwrap();
+        assert_eq!(repaired.input, serde_json::json!({"a": 1, "b": null}));
+        assert!(repaired.repaired);
+        assert!(repaired.raw_input.is_none());
+        assert!(accumulator.finish().is_empty());
+    }
+
+    #[test]
+    fn test_repair_json() {
+        let cases = [
+            (r#"{"a": 1, "b":"#, r#"{"a": 1, "b": null}"#),
+            (r#"{"a": [1, 2,"#, r#"{"a": [1, 2]}"#),
+            (r#"{"path": "C:\\di"#, r#"{"path": "C:\\di"}"#),
+            (r#"{"a": "x\"#, r#"{"a": "x"}"#),
+            (r#"{"a": 1, "b"#, r#"{"a": 1, "b": null}"#),
+            (r#"{"a": {"b": "}"#, r#"{"a": {"b": "}"}}"#),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(repair_json(input).as_deref(), Some(expected), "{}", input);
+        }
+        // 截断在字面量中间时不做猜测
+        assert_eq!(repair_json(r#"{"a": tr"#), None);
+    }
+}
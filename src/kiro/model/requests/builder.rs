@@ -0,0 +1,265 @@
+//! Kiro 请求构建器
+//!
+//! 不依赖 OpenAI / Anthropic 的请求格式，直接以消息列表构建 `KiroRequest`
+
+use uuid::Uuid;
+
+use crate::anthropic::converter::ConversionError;
+
+use super::conversation::{
+    ConversationState, CurrentMessage, HistoryAssistantMessage, HistoryUserMessage, Message,
+    UserInputMessage, UserInputMessageContext,
+};
+use super::kiro::{InferenceConfig, KiroRequest};
+use super::tool::Tool;
+
+/// 消息角色
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    User,
+    Assistant,
+}
+
+/// `KiroRequest` 构建器
+///
+/// 最后一条消息作为当前消息（必须是用户消息），其余消息按顺序进入历史；
+/// 系统提示词与转换器一致，以 user + assistant 配对的形式放在历史最前面
+#[allow(dead_code)]
+#[derive(Debug, Clone, Default)]
+pub struct KiroRequestBuilder {
+    model: Option<String>,
+    system: Option<String>,
+    messages: Vec<(Role, String)>,
+    tools: Vec<Tool>,
+    max_tokens: Option<i32>,
+    conversation_id: Option<String>,
+    profile_arn: Option<String>,
+}
+
+impl KiroRequestBuilder {
+    #[allow(dead_code)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Kiro 模型 ID（如 `claude-sonnet-4.5`），必填
+    #[allow(dead_code)]
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// 系统提示词
+    #[allow(dead_code)]
+    pub fn system(mut self, system: impl Into<String>) -> Self {
+        self.system = Some(system.into());
+        self
+    }
+
+    /// 追加用户消息
+    #[allow(dead_code)]
+    pub fn add_user_message(mut self, content: impl Into<String>) -> Self {
+        self.messages.push((Role::User, content.into()));
+        self
+    }
+
+    /// 追加助手消息
+    #[allow(dead_code)]
+    pub fn add_assistant_message(mut self, content: impl Into<String>) -> Self {
+        self.messages.push((Role::Assistant, content.into()));
+        self
+    }
+
+    /// 添加可用工具
+    #[allow(dead_code)]
+    pub fn tool(mut self, tool: Tool) -> Self {
+        self.tools.push(tool);
+        self
+    }
+
+    /// 最大输出 tokens
+    #[allow(dead_code)]
+    pub fn max_tokens(mut self, max_tokens: i32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// 会话 ID，未设置时随机生成
+    #[allow(dead_code)]
+    pub fn conversation_id(mut self, id: impl Into<String>) -> Self {
+        self.conversation_id = Some(id.into());
+        self
+    }
+
+    /// Profile ARN
+    #[allow(dead_code)]
+    pub fn profile_arn(mut self, arn: impl Into<String>) -> Self {
+        self.profile_arn = Some(arn.into());
+        self
+    }
+
+    /// 校验并构建请求
+    #[allow(dead_code)]
+    pub fn build(self) -> Result<KiroRequest, ConversionError> {
+        let model = self
+            .model
+            .filter(|m| !m.is_empty())
+            .ok_or_else(|| ConversionError::InvalidRequest("缺少 model".to_string()))?;
+
+        if let Some(max_tokens) = self.max_tokens
+            && max_tokens < 1
+        {
+            return Err(ConversionError::InvalidRequest(format!(
+                "max_tokens 必须大于 0: {}",
+                max_tokens
+            )));
+        }
+
+        let mut messages = self.messages;
+        let (role, content) = messages.pop().ok_or(ConversionError::EmptyMessages)?;
+        if role != Role::User {
+            return Err(ConversionError::InvalidRequest(
+                "最后一条消息必须是用户消息".to_string(),
+            ));
+        }
+
+        let mut history = Vec::with_capacity(messages.len() + 2);
+        if let Some(system) = self.system.filter(|s| !s.is_empty()) {
+            history.push(Message::User(HistoryUserMessage::new(system, &model)));
+            history.push(Message::Assistant(HistoryAssistantMessage::new(
+                "I will follow these instructions.",
+            )));
+        }
+        history.extend(messages.into_iter().map(|(role, content)| match role {
+            Role::User => Message::user(content, &model),
+            Role::Assistant => Message::assistant(content),
+        }));
+
+        let context = UserInputMessageContext::new().with_tools(self.tools);
+        let current_message =
+            CurrentMessage::new(UserInputMessage::new(content, &model).with_context(context));
+
+        let conversation_id = self
+            .conversation_id
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        let conversation_state = ConversationState::new(conversation_id)
+            .with_agent_continuation_id(Uuid::new_v4().to_string())
+            .with_agent_task_type("vibe")
+            .with_chat_trigger_type("MANUAL")
+            .with_current_message(current_message)
+            .with_history(history);
+
+        Ok(KiroRequest {
+            conversation_state,
+            profile_arn: self.profile_arn,
+            inference_config: self.max_tokens.map(|max_tokens| InferenceConfig {
+                max_tokens: Some(max_tokens),
+                ..Default::default()
+            }),
+            metadata: Default::default(),
+        })
+    }
+}
+
+impl KiroRequest {
+    /// 创建请求构建器
+    #[allow(dead_code)]
+    pub fn builder() -> KiroRequestBuilder {
+        KiroRequestBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_minimal_request() {
+        let request = KiroRequest::builder()
+            .model("claude-sonnet-4.5")
+            .add_user_message("Hello")
+            .build()
+            .unwrap();
+
+        let state = &request.conversation_state;
+        assert_eq!(state.current_message.user_input_message.content, "Hello");
+        assert_eq!(
+            state.current_message.user_input_message.model_id,
+            "claude-sonnet-4.5"
+        );
+        assert!(state.history.is_empty());
+        assert_eq!(state.conversation_id.len(), 36);
+        assert!(request.inference_config.is_none());
+    }
+
+    #[test]
+    fn test_build_full_request() {
+        let request = KiroRequest::builder()
+            .model("claude-sonnet-4.5")
+            .system("Be brief.")
+            .add_user_message("Hi")
+            .add_assistant_message("Hello!")
+            .add_user_message("Weather?")
+            .tool(Tool::new(
+                "get_weather",
+                "查询天气",
+                serde_json::json!({"type": "object"}),
+            ))
+            .max_tokens(256)
+            .conversation_id("conv-1")
+            .build()
+            .unwrap();
+
+        let state = &request.conversation_state;
+        assert_eq!(state.conversation_id, "conv-1");
+        // 系统提示词配对 + 两条历史消息
+        assert_eq!(state.history.len(), 4);
+        assert!(state.history[0].is_user() && state.history[1].is_assistant());
+        assert!(state.history[2].is_user() && state.history[3].is_assistant());
+
+        let current = &state.current_message.user_input_message;
+        assert_eq!(current.content, "Weather?");
+        assert_eq!(
+            current.user_input_message_context.tools[0]
+                .tool_specification
+                .name,
+            "get_weather"
+        );
+        assert_eq!(request.inference_config.unwrap().max_tokens, Some(256));
+    }
+
+    #[test]
+    fn test_build_missing_model() {
+        let err = KiroRequest::builder()
+            .add_user_message("Hello")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, ConversionError::InvalidRequest(_)));
+        assert!(err.to_string().contains("model"));
+    }
+
+    #[test]
+    fn test_build_invalid_messages() {
+        let err = KiroRequest::builder()
+            .model("claude-sonnet-4.5")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, ConversionError::EmptyMessages));
+
+        let err = KiroRequest::builder()
+            .model("claude-sonnet-4.5")
+            .add_user_message("Hi")
+            .add_assistant_message("Hello!")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, ConversionError::InvalidRequest(_)));
+
+        let err = KiroRequest::builder()
+            .model("claude-sonnet-4.5")
+            .add_user_message("Hi")
+            .max_tokens(0)
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, ConversionError::InvalidRequest(_)));
+    }
+}
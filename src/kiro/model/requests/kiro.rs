@@ -3,6 +3,8 @@
 //! 定义 Kiro API 的主请求结构
 
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
 
 use super::conversation::ConversationState;
 
@@ -35,6 +37,78 @@ pub struct KiroRequest {
     /// Profile ARN（可选）
     #[serde(skip_serializing_if = "Option::is_none")]
     pub profile_arn: Option<String>,
+    /// 推理参数（可选）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inference_config: Option<InferenceConfig>,
+    /// 按白名单透传的客户端字段（如 `user`）
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub metadata: serde_json::Map<String, Value>,
+}
+
+impl KiroRequest {
+    /// 规范化后请求的 SHA-256，可用于去重、缓存键与日志关联
+    ///
+    /// 会话 ID 等每次转换随机生成的字段不参与计算，JSON 按键排序、无多余空白；
+    /// 不同客户端协议转换出的等价请求得到相同的值
+    pub fn stable_hash(&self) -> [u8; 32] {
+        let mut normalized = self.clone();
+        normalized.conversation_state.conversation_id.clear();
+        normalized.conversation_state.agent_continuation_id = None;
+
+        let value = serde_json::to_value(&normalized).unwrap_or_default();
+        let mut canonical = String::new();
+        write_canonical(&value, &mut canonical);
+        Sha256::digest(canonical.as_bytes()).into()
+    }
+}
+
+/// 以键排序、紧凑格式输出 JSON
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(value, out);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+/// 推理参数
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InferenceConfig {
+    /// 最大输出 tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<i32>,
+    /// 采样温度
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// 核采样概率
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    /// 停止序列
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop_sequences: Vec<String>,
 }
 #[cfg(test)]
 mod tests {
@@ -65,4 +139,60 @@ mod tests {
             "Test message"
         );
     }
+
+    #[test]
+    fn test_stable_hash_across_client_schemas() {
+        use crate::anthropic::converter::from_anthropic;
+        use crate::openai::converter::from_openai_chat;
+        use serde_json::json;
+
+        let anthropic = |prompt: &str| {
+            from_anthropic(
+                serde_json::from_value(json!({
+                    "model": "claude-sonnet-4-5",
+                    "max_tokens": 256,
+                    "system": "Be brief.",
+                    "messages": [{"role": "user", "content": prompt}]
+                }))
+                .unwrap(),
+            )
+            .unwrap()
+        };
+        let openai = |prompt: &str| {
+            from_openai_chat(
+                serde_json::from_value(json!({
+                    "model": "claude-sonnet-4-5",
+                    "max_tokens": 256,
+                    "messages": [
+                        {"role": "system", "content": "Be brief."},
+                        {"role": "user", "content": prompt}
+                    ]
+                }))
+                .unwrap(),
+            )
+            .unwrap()
+        };
+
+        // 会话 ID 每次随机生成，不影响哈希
+        assert_eq!(
+            anthropic("Hello").stable_hash(),
+            anthropic("Hello").stable_hash()
+        );
+        assert_eq!(
+            anthropic("Hello").stable_hash(),
+            openai("Hello").stable_hash()
+        );
+        assert_ne!(
+            anthropic("Hello").stable_hash(),
+            openai("Bye").stable_hash()
+        );
+    }
+
+    #[test]
+    fn test_write_canonical_sorts_keys() {
+        let value = serde_json::json!({"b": 1, "a": {"d": [1, {"f": 2, "e": 3}], "c": " x "}});
+        let mut out = String::new();
+        write_canonical(&value, &mut out);
+        assert_eq!(out, r#"{"a":{"c":" x ","d":[1,{"e":3,"f":2}]},"b":1}"#);
+    }
 }
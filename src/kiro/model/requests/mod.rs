@@ -2,6 +2,7 @@
 //!
 //! 包含 Kiro API 请求相关的类型定义
 
+pub mod builder;
 pub mod conversation;
 pub mod kiro;
 pub mod tool;
@@ -14,6 +14,24 @@ pub struct Tool {
     pub tool_specification: ToolSpecification,
 }
 
+impl Tool {
+    /// 创建工具定义
+    #[allow(dead_code)]
+    pub fn new(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        input_schema: serde_json::Value,
+    ) -> Self {
+        Self {
+            tool_specification: ToolSpecification {
+                name: name.into(),
+                description: description.into(),
+                input_schema: InputSchema::from_json(input_schema),
+            },
+        }
+    }
+}
+
 /// 工具规范
 ///
 /// 定义工具的名称、描述和输入模式
@@ -1,14 +1,18 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
+use super::credentials::Redacted;
+
 /// 刷新 Token 的请求体 (Social 认证)
-#[derive(Debug, Serialize)]
+#[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RefreshRequest {
     pub refresh_token: String,
 }
 
 /// 刷新 Token 的响应体 (Social 认证)
-#[derive(Debug, Deserialize)]
+#[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RefreshResponse {
     pub access_token: String,
@@ -21,7 +25,7 @@ pub struct RefreshResponse {
 }
 
 /// IdC Token 刷新请求体 (AWS SSO OIDC)
-#[derive(Debug, Serialize)]
+#[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct IdcRefreshRequest {
     pub client_id: String,
@@ -31,7 +35,7 @@ pub struct IdcRefreshRequest {
 }
 
 /// IdC Token 刷新响应体 (AWS SSO OIDC)
-#[derive(Debug, Deserialize)]
+#[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct IdcRefreshResponse {
     pub access_token: String,
@@ -42,3 +46,43 @@ pub struct IdcRefreshResponse {
     #[serde(default)]
     pub expires_in: Option<i64>,
 }
+
+impl fmt::Debug for RefreshRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RefreshRequest")
+            .field("refresh_token", &Redacted(&self.refresh_token))
+            .finish()
+    }
+}
+
+impl fmt::Debug for RefreshResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RefreshResponse")
+            .field("access_token", &Redacted(&self.access_token))
+            .field("refresh_token", &Redacted(&self.refresh_token))
+            .field("profile_arn", &self.profile_arn)
+            .field("expires_in", &self.expires_in)
+            .finish()
+    }
+}
+
+impl fmt::Debug for IdcRefreshRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdcRefreshRequest")
+            .field("client_id", &self.client_id)
+            .field("client_secret", &Redacted(&self.client_secret))
+            .field("refresh_token", &Redacted(&self.refresh_token))
+            .field("grant_type", &self.grant_type)
+            .finish()
+    }
+}
+
+impl fmt::Debug for IdcRefreshResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdcRefreshResponse")
+            .field("access_token", &Redacted(&self.access_token))
+            .field("refresh_token", &Redacted(&self.refresh_token))
+            .field("expires_in", &self.expires_in)
+            .finish()
+    }
+}
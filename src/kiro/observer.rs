@@ -0,0 +1,79 @@
+//! 上游请求 / 响应观察钩子
+//!
+//! 调试转换问题时可通过 `KiroProvider::with_observer` 拿到实际发往上游的请求体
+//! 以及收到的原始响应字节；未配置时不做任何额外处理。
+//! Token 刷新事件可通过 `MultiTokenManager::with_refresh_observer` 观察，用于指标统计
+
+use reqwest::header::{HeaderMap, HeaderValue};
+
+/// 原始请求 / 响应观察者
+///
+/// 回调在请求路径上同步执行，实现方应尽量轻量（如写入缓冲或发送到 channel）
+pub trait RequestObserver: Send + Sync {
+    /// 每次发送（含重试）前的请求体
+    fn on_request(&self, bytes: &[u8]);
+
+    /// 收到的一段响应体（已解压、未解析）；失败响应的完整 body 同样会回调
+    fn on_response_chunk(&self, bytes: &[u8]);
+
+    /// 每次发送前的请求头，认证等敏感值已脱敏
+    fn on_request_headers(&self, _headers: &HeaderMap) {}
+}
+
+/// 一次 Token 刷新的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RefreshOutcome {
+    Success,
+    /// 刷新失败（含超时），`revoked` 表示 refreshToken 已被上游撤销
+    Failed {
+        reason: String,
+        revoked: bool,
+    },
+}
+
+/// Token 刷新观察者
+///
+/// `account` 为账号标签（`#<凭据 ID>`，与 `MultiTokenManager::describe` 一致），
+/// 回调中不会携带任何 Token 内容
+pub trait RefreshObserver: Send + Sync {
+    /// 开始刷新
+    fn on_refresh_start(&self, account: &str);
+
+    /// 刷新结束
+    fn on_refresh_finish(&self, account: &str, outcome: &RefreshOutcome);
+}
+
+/// 复制请求头并脱敏认证信息与名称中带 token / secret / key 的请求头
+pub(crate) fn redact_headers(headers: &HeaderMap) -> HeaderMap {
+    let mut redacted = headers.clone();
+    for (name, value) in redacted.iter_mut() {
+        let name = name.as_str();
+        let sensitive = matches!(name, "authorization" | "proxy-authorization" | "cookie")
+            || ["token", "secret", "key"]
+                .iter()
+                .any(|word| name.contains(word));
+        if sensitive {
+            *value = HeaderValue::from_static("***redacted***");
+        }
+    }
+    redacted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::AUTHORIZATION;
+
+    #[test]
+    fn test_redact_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+        headers.insert("x-gateway-token", HeaderValue::from_static("corp"));
+        headers.insert("x-request-id", HeaderValue::from_static("req-1"));
+
+        let redacted = redact_headers(&headers);
+        assert_eq!(redacted[AUTHORIZATION], "***redacted***");
+        assert_eq!(redacted["x-gateway-token"], "***redacted***");
+        assert_eq!(redacted["x-request-id"], "req-1");
+    }
+}
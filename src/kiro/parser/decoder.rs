@@ -30,8 +30,8 @@
 //!                  └────────────┘
 //! ```
 
-use super::error::{ParseError, ParseResult};
-use super::frame::{Frame, PRELUDE_SIZE, parse_frame};
+use super::error::{CrcKind, ParseError, ParseResult};
+use super::frame::{Frame, PRELUDE_SIZE, parse_frame_with};
 use bytes::{Buf, BytesMut};
 
 /// 默认最大缓冲区大小 (16 MB)
@@ -77,9 +77,11 @@ pub enum DecoderState {
 /// decoder.feed(chunk)?;
 ///
 /// // 解码所有可用帧
-/// for result in decoder.decode_iter() {
-///     match result {
-///         Ok(frame) => println!("Got frame: {:?}", frame.event_type()),
+/// loop {
+///     match decoder.decode() {
+///         Ok(Some(frame)) => println!("Got frame: {:?}", frame.event_type()),
+///         Ok(None) => break,
+///         Err(e) if decoder.is_stopped() => return Err(e),
 ///         Err(e) => eprintln!("Parse error: {}", e),
 ///     }
 /// }
@@ -99,6 +101,8 @@ pub struct EventStreamDecoder {
     max_buffer_size: usize,
     /// 跳过的字节数（用于调试）
     bytes_skipped: usize,
+    /// 是否校验帧 CRC
+    verify_crc: bool,
 }
 
 impl Default for EventStreamDecoder {
@@ -123,6 +127,7 @@ impl EventStreamDecoder {
             max_errors: DEFAULT_MAX_ERRORS,
             max_buffer_size: DEFAULT_MAX_BUFFER_SIZE,
             bytes_skipped: 0,
+            verify_crc: true,
         }
     }
 
@@ -136,9 +141,18 @@ impl EventStreamDecoder {
             max_errors,
             max_buffer_size,
             bytes_skipped: 0,
+            verify_crc: true,
         }
     }
 
+    /// 设置是否校验帧 CRC（默认校验）
+    ///
+    /// 仅建议对可信、注重性能的数据流关闭
+    pub fn with_crc_check(mut self, enabled: bool) -> Self {
+        self.verify_crc = enabled;
+        self
+    }
+
     /// 向解码器提供数据
     ///
     /// # Returns
@@ -188,7 +202,7 @@ impl EventStreamDecoder {
         // 转移到 Parsing 状态
         self.state = DecoderState::Parsing;
 
-        match parse_frame(&self.buffer) {
+        match parse_frame_with(&self.buffer, self.verify_crc) {
             Ok(Some((frame, consumed))) => {
                 // 成功解析
                 self.buffer.advance(consumed);
@@ -228,11 +242,6 @@ impl EventStreamDecoder {
         }
     }
 
-    /// 创建解码迭代器
-    pub fn decode_iter(&mut self) -> DecodeIter<'_> {
-        DecodeIter { decoder: self }
-    }
-
     /// 尝试容错恢复
     ///
     /// 根据错误类型采用不同的恢复策略（参考 kiro-kt 的设计）：
@@ -245,7 +254,10 @@ impl EventStreamDecoder {
 
         match error {
             // Prelude 阶段错误：可能是帧边界错位，逐字节扫描找下一个有效边界
-            ParseError::PreludeCrcMismatch { .. }
+            ParseError::Corrupt {
+                kind: CrcKind::Prelude,
+                ..
+            }
             | ParseError::MessageTooSmall { .. }
             | ParseError::MessageTooLarge { .. } => {
                 let skipped_byte = self.buffer[0];
@@ -259,7 +271,11 @@ impl EventStreamDecoder {
             }
 
             // Data 阶段错误：帧边界正确但数据损坏，跳过整个帧
-            ParseError::MessageCrcMismatch { .. } | ParseError::HeaderParseFailed(_) => {
+            ParseError::Corrupt {
+                kind: CrcKind::Message,
+                ..
+            }
+            | ParseError::HeaderParseFailed(_) => {
                 // 尝试读取 total_length 来跳过整帧
                 if self.buffer.len() >= PRELUDE_SIZE {
                     let total_length = u32::from_be_bytes([
@@ -356,6 +372,11 @@ impl EventStreamDecoder {
         self.buffer.len()
     }
 
+    /// 获取缓冲区中待处理的数据
+    pub fn buffered(&self) -> &[u8] {
+        &self.buffer
+    }
+
     /// 尝试从 Stopped 状态恢复
     ///
     /// 重置错误计数并转移到 Ready 状态
@@ -369,30 +390,6 @@ impl EventStreamDecoder {
     }
 }
 
-/// 解码迭代器
-pub struct DecodeIter<'a> {
-    decoder: &'a mut EventStreamDecoder,
-}
-
-impl<'a> Iterator for DecodeIter<'a> {
-    type Item = ParseResult<Frame>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        // 如果处于 Stopped 或 Recovering 状态，停止迭代
-        match self.decoder.state {
-            DecoderState::Stopped => return None,
-            DecoderState::Recovering => return None,
-            _ => {}
-        }
-
-        match self.decoder.decode() {
-            Ok(Some(frame)) => Some(Ok(frame)),
-            Ok(None) => None,
-            Err(e) => Some(Err(e)),
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -462,4 +459,28 @@ mod tests {
         assert!(decoder.is_ready());
         assert_eq!(decoder.error_count(), 0);
     }
+
+    #[test]
+    fn test_decoder_crc_check_toggle() {
+        let mut bytes =
+            crate::test_support::encode_event("assistantResponseEvent", r#"{"content":"Hi"}"#);
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+
+        let mut decoder = EventStreamDecoder::new();
+        decoder.feed(&bytes).unwrap();
+        assert!(matches!(
+            decoder.decode(),
+            Err(ParseError::Corrupt {
+                kind: CrcKind::Message,
+                ..
+            })
+        ));
+
+        let mut decoder = EventStreamDecoder::new().with_crc_check(false);
+        decoder.feed(&bytes).unwrap();
+        let frame = decoder.decode().unwrap().unwrap();
+        assert_eq!(frame.event_type(), Some("assistantResponseEvent"));
+        assert_eq!(decoder.buffer_len(), 0);
+    }
 }
@@ -2,15 +2,37 @@
 
 use std::fmt;
 
+/// CRC 校验的范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrcKind {
+    /// Prelude（前 8 字节）
+    Prelude,
+    /// 整个消息（不含 Message CRC 自身）
+    Message,
+}
+
+impl fmt::Display for CrcKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Prelude => write!(f, "Prelude"),
+            Self::Message => write!(f, "Message"),
+        }
+    }
+}
+
 /// 解析错误类型
 #[derive(Debug)]
 pub enum ParseError {
-    /// 数据不足，需要更多字节
-    Incomplete { needed: usize, available: usize },
-    /// Prelude CRC 校验失败
-    PreludeCrcMismatch { expected: u32, actual: u32 },
-    /// Message CRC 校验失败
-    MessageCrcMismatch { expected: u32, actual: u32 },
+    /// 数据不足，`needed` 为还需要的字节数
+    ///
+    /// 调用方可据此决定继续等待数据还是放弃
+    Incomplete { needed: usize },
+    /// CRC 校验失败，数据已损坏
+    Corrupt {
+        kind: CrcKind,
+        expected: u32,
+        actual: u32,
+    },
     /// 无效的头部值类型
     InvalidHeaderType(u8),
     /// 头部解析错误
@@ -29,6 +51,8 @@ pub enum ParseError {
     TooManyErrors { count: usize, last_error: String },
     /// 缓冲区溢出
     BufferOverflow { size: usize, max: usize },
+    /// 流在 SSE 事件中间结束，`buffered` 为未完成事件的字节数
+    Truncated { buffered: usize },
 }
 
 impl std::error::Error for ParseError {}
@@ -36,21 +60,16 @@ impl std::error::Error for ParseError {}
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Incomplete { needed, available } => {
-                write!(f, "数据不足: 需要 {} 字节, 当前 {} 字节", needed, available)
-            }
-            Self::PreludeCrcMismatch { expected, actual } => {
+            Self::Incomplete { needed } => write!(f, "数据不足: 还需要 {} 字节", needed),
+            Self::Corrupt {
+                kind,
+                expected,
+                actual,
+            } => {
                 write!(
                     f,
-                    "Prelude CRC 校验失败: 期望 0x{:08x}, 实际 0x{:08x}",
-                    expected, actual
-                )
-            }
-            Self::MessageCrcMismatch { expected, actual } => {
-                write!(
-                    f,
-                    "Message CRC 校验失败: 期望 0x{:08x}, 实际 0x{:08x}",
-                    expected, actual
+                    "{} CRC 校验失败: 期望 0x{:08x}, 实际 0x{:08x}",
+                    kind, expected, actual
                 )
             }
             Self::InvalidHeaderType(t) => write!(f, "无效的头部值类型: {}", t),
@@ -74,6 +93,9 @@ impl fmt::Display for ParseError {
             Self::BufferOverflow { size, max } => {
                 write!(f, "缓冲区溢出: {} 字节 (最大 {})", size, max)
             }
+            Self::Truncated { buffered } => {
+                write!(f, "流在事件中间结束: {} 字节未完成", buffered)
+            }
         }
     }
 }
@@ -16,9 +16,11 @@
 //! - Payload: 载荷数据（通常是 JSON）
 //! - Message CRC: 整个消息（不含 Message CRC 自身）的 CRC32 校验
 
+use std::collections::HashMap;
+
 use super::crc::crc32;
-use super::error::{ParseError, ParseResult};
-use super::header::{Headers, parse_headers};
+use super::error::{CrcKind, ParseError, ParseResult};
+use super::header::{HeaderValue, Headers, parse_headers};
 
 /// Prelude 固定大小 (12 字节)
 pub const PRELUDE_SIZE: usize = 12;
@@ -60,6 +62,85 @@ impl Frame {
     }
 }
 
+/// Event Stream 消息
+///
+/// 与 `Frame` 内容相同，但直接暴露原始头部（保留字符串、整数、时间戳等类型），
+/// 便于检查上游事件的元数据
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventStreamMessage {
+    /// 消息头部
+    pub headers: HashMap<String, HeaderValue>,
+    /// 消息负载
+    pub payload: Vec<u8>,
+}
+
+impl EventStreamMessage {
+    /// 获取字符串类型的头部值
+    #[allow(dead_code)]
+    pub fn string_header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(HeaderValue::as_str)
+    }
+
+    /// 获取消息类型 (:message-type)
+    #[allow(dead_code)]
+    pub fn message_type(&self) -> Option<&str> {
+        self.string_header(":message-type")
+    }
+
+    /// 获取事件类型 (:event-type)
+    #[allow(dead_code)]
+    pub fn event_type(&self) -> Option<&str> {
+        self.string_header(":event-type")
+    }
+
+    /// 获取内容类型 (:content-type)
+    #[allow(dead_code)]
+    pub fn content_type(&self) -> Option<&str> {
+        self.string_header(":content-type")
+    }
+
+    /// 将 payload 解析为字符串
+    #[allow(dead_code)]
+    pub fn payload_as_str(&self) -> String {
+        String::from_utf8_lossy(&self.payload).to_string()
+    }
+}
+
+impl From<Frame> for EventStreamMessage {
+    fn from(frame: Frame) -> Self {
+        Self {
+            headers: frame.headers.into_map(),
+            payload: frame.payload,
+        }
+    }
+}
+
+/// 从缓冲区解码一个完整的 Event Stream 消息
+///
+/// 与 `parse_frame` 相同，但数据不足时返回 `ParseError::Incomplete`，
+/// 其中 `needed` 为还需要的字节数：prelude 不完整时按补全 prelude 计算，
+/// 否则按 prelude 中声明的总长度计算
+///
+/// # Returns
+/// - `Ok((message, consumed))` - 成功解析，返回消息和消费的字节数
+/// - `Err(ParseError::Incomplete { .. })` - 数据不足
+/// - `Err(ParseError::Corrupt { .. })` - CRC 校验失败
+/// - `Err(e)` - 其他解析错误
+pub fn decode_event_stream_frame(buffer: &[u8]) -> ParseResult<(EventStreamMessage, usize)> {
+    match parse_frame(buffer)? {
+        Some((frame, consumed)) => Ok((frame.into(), consumed)),
+        None => {
+            let total = match read_u32(buffer, 0) {
+                Some(total_length) if buffer.len() >= PRELUDE_SIZE => total_length as usize,
+                _ => PRELUDE_SIZE,
+            };
+            Err(ParseError::Incomplete {
+                needed: total.saturating_sub(buffer.len()),
+            })
+        }
+    }
+}
+
 /// 尝试从缓冲区解析一个完整的帧
 ///
 /// 这是一个无状态的纯函数，每次调用独立解析。
@@ -73,15 +154,23 @@ impl Frame {
 /// - `Ok(None)` - 数据不足，需要更多数据
 /// - `Err(e)` - 解析错误
 pub fn parse_frame(buffer: &[u8]) -> ParseResult<Option<(Frame, usize)>> {
-    // 检查是否有足够的数据读取 prelude
-    if buffer.len() < PRELUDE_SIZE {
-        return Ok(None);
-    }
+    parse_frame_with(buffer, true)
+}
 
-    // 读取 prelude
-    let total_length = u32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
-    let header_length = u32::from_be_bytes([buffer[4], buffer[5], buffer[6], buffer[7]]);
-    let prelude_crc = u32::from_be_bytes([buffer[8], buffer[9], buffer[10], buffer[11]]);
+/// 与 `parse_frame` 相同，`verify_crc` 为 `false` 时跳过 prelude 与 message 的 CRC 校验
+///
+/// 仅适用于可信的数据源：跳过校验后，损坏的数据只能通过长度或头部解析错误发现
+///
+/// 输入可以是任意字节：所有长度字段都先与缓冲区比对再使用，畸形数据只会返回错误，不会 panic
+pub fn parse_frame_with(buffer: &[u8], verify_crc: bool) -> ParseResult<Option<(Frame, usize)>> {
+    // 读取 prelude，数据不足时等待更多数据
+    let (Some(total_length), Some(header_length), Some(prelude_crc)) = (
+        read_u32(buffer, 0),
+        read_u32(buffer, 4),
+        read_u32(buffer, 8),
+    ) else {
+        return Ok(None);
+    };
 
     // 验证消息长度范围
     if total_length < MIN_MESSAGE_SIZE as u32 {
@@ -102,55 +191,70 @@ pub fn parse_frame(buffer: &[u8]) -> ParseResult<Option<(Frame, usize)>> {
     let header_length = header_length as usize;
 
     // 检查是否有完整的消息
-    if buffer.len() < total_length {
+    let Some(frame) = buffer.get(..total_length) else {
         return Ok(None);
+    };
+
+    if verify_crc {
+        verify_frame_crc(frame, prelude_crc)?;
     }
 
+    // 验证头部边界：头部必须位于 prelude 与 message CRC 之间
+    let headers_start = PRELUDE_SIZE;
+    let payload_end = total_length - 4;
+    let headers_end = headers_start
+        .checked_add(header_length)
+        .filter(|end| *end <= payload_end)
+        .ok_or_else(|| ParseError::HeaderParseFailed("头部长度超出消息边界".to_string()))?;
+
+    // 帧已完整，头部内部的截断说明数据损坏，而不是需要更多数据
+    let headers =
+        parse_headers(&frame[headers_start..headers_end], header_length).map_err(|e| match e {
+            ParseError::Incomplete { .. } => {
+                ParseError::HeaderParseFailed("头部数据被截断".to_string())
+            }
+            e => e,
+        })?;
+
+    // 提取 payload (去除最后4字节的 message_crc)
+    let payload = frame[headers_end..payload_end].to_vec();
+
+    Ok(Some((Frame { headers, payload }, total_length)))
+}
+
+/// 读取 `bytes[offset..offset + 4]` 处的大端 u32，越界时返回 `None`
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let end = offset.checked_add(4)?;
+    let array: [u8; 4] = bytes.get(offset..end)?.try_into().ok()?;
+    Some(u32::from_be_bytes(array))
+}
+
+/// 校验一个完整帧的 prelude CRC 与 message CRC
+fn verify_frame_crc(frame: &[u8], prelude_crc: u32) -> ParseResult<()> {
     // 验证 Prelude CRC
-    let actual_prelude_crc = crc32(&buffer[..8]);
+    let actual_prelude_crc = crc32(frame.get(..8).unwrap_or_default());
     if actual_prelude_crc != prelude_crc {
-        return Err(ParseError::PreludeCrcMismatch {
+        return Err(ParseError::Corrupt {
+            kind: CrcKind::Prelude,
             expected: prelude_crc,
             actual: actual_prelude_crc,
         });
     }
 
     // 读取 Message CRC
-    let message_crc = u32::from_be_bytes([
-        buffer[total_length - 4],
-        buffer[total_length - 3],
-        buffer[total_length - 2],
-        buffer[total_length - 1],
-    ]);
+    let body_length = frame.len().saturating_sub(4);
+    let message_crc = read_u32(frame, body_length).unwrap_or_default();
 
     // 验证 Message CRC (对整个消息不含最后4字节)
-    let actual_message_crc = crc32(&buffer[..total_length - 4]);
+    let actual_message_crc = crc32(frame.get(..body_length).unwrap_or_default());
     if actual_message_crc != message_crc {
-        return Err(ParseError::MessageCrcMismatch {
+        return Err(ParseError::Corrupt {
+            kind: CrcKind::Message,
             expected: message_crc,
             actual: actual_message_crc,
         });
     }
-
-    // 解析头部
-    let headers_start = PRELUDE_SIZE;
-    let headers_end = headers_start + header_length;
-
-    // 验证头部边界
-    if headers_end > total_length - 4 {
-        return Err(ParseError::HeaderParseFailed(
-            "头部长度超出消息边界".to_string(),
-        ));
-    }
-
-    let headers = parse_headers(&buffer[headers_start..headers_end], header_length)?;
-
-    // 提取 payload (去除最后4字节的 message_crc)
-    let payload_start = headers_end;
-    let payload_end = total_length - 4;
-    let payload = buffer[payload_start..payload_end].to_vec();
-
-    Ok(Some((Frame { headers, payload }, total_length)))
+    Ok(())
 }
 
 #[cfg(test)]
@@ -175,4 +279,293 @@ mod tests {
         let result = parse_frame(&buffer);
         assert!(matches!(result, Err(ParseError::MessageTooSmall { .. })));
     }
+
+    #[test]
+    fn test_decode_event_stream_frame() {
+        let bytes =
+            crate::test_support::encode_event("assistantResponseEvent", r#"{"content":"Hello"}"#);
+        let mut buffer = bytes.clone();
+        buffer.extend_from_slice(&[0u8; 3]); // 尾部多余数据不应被消费
+
+        let (message, consumed) = decode_event_stream_frame(&buffer).unwrap();
+        assert_eq!(consumed, bytes.len());
+        assert_eq!(message.message_type(), Some("event"));
+        assert_eq!(message.event_type(), Some("assistantResponseEvent"));
+        assert_eq!(message.payload_as_str(), r#"{"content":"Hello"}"#);
+    }
+
+    #[test]
+    fn test_decode_event_stream_frame_typed_headers() {
+        // 手工构造的帧：字符串、整数与时间戳头部
+        let mut header_bytes = Vec::new();
+        for (name, value_type, value) in [
+            (":message-type", 7u8, b"\x00\x05event".to_vec()),
+            (":event-type", 7, b"\x00\x0dmeteringEvent".to_vec()),
+            (":content-type", 7, b"\x00\x10application/json".to_vec()),
+            ("retry-count", 4, 3i32.to_be_bytes().to_vec()),
+            ("sent-at", 8, 1_700_000_000_000i64.to_be_bytes().to_vec()),
+        ] {
+            header_bytes.push(name.len() as u8);
+            header_bytes.extend_from_slice(name.as_bytes());
+            header_bytes.push(value_type);
+            header_bytes.extend_from_slice(&value);
+        }
+        let payload = br#"{"unit":"credit","usage":0.5}"#;
+        let total_length = PRELUDE_SIZE + header_bytes.len() + payload.len() + 4;
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&(total_length as u32).to_be_bytes());
+        buffer.extend_from_slice(&(header_bytes.len() as u32).to_be_bytes());
+        buffer.extend_from_slice(&crc32(&buffer).to_be_bytes());
+        buffer.extend_from_slice(&header_bytes);
+        buffer.extend_from_slice(payload);
+        buffer.extend_from_slice(&crc32(&buffer).to_be_bytes());
+
+        let (message, consumed) = decode_event_stream_frame(&buffer).unwrap();
+        assert_eq!(consumed, total_length);
+        assert_eq!(message.headers.len(), 5);
+        assert_eq!(message.message_type(), Some("event"));
+        assert_eq!(message.event_type(), Some("meteringEvent"));
+        assert_eq!(message.content_type(), Some("application/json"));
+        assert_eq!(
+            message.headers.get("retry-count"),
+            Some(&HeaderValue::Integer(3))
+        );
+        assert_eq!(
+            message.headers.get("sent-at"),
+            Some(&HeaderValue::Timestamp(1_700_000_000_000))
+        );
+        assert_eq!(message.string_header("retry-count"), None);
+        assert_eq!(message.payload, payload);
+    }
+
+    #[test]
+    fn test_decode_event_stream_frame_incomplete() {
+        let bytes =
+            crate::test_support::encode_event("assistantResponseEvent", r#"{"content":"Hi"}"#);
+
+        let result = decode_event_stream_frame(&bytes[..5]);
+        assert!(matches!(
+            result,
+            Err(ParseError::Incomplete { needed }) if needed == PRELUDE_SIZE - 5
+        ));
+
+        let result = decode_event_stream_frame(&bytes[..bytes.len() - 1]);
+        assert!(matches!(result, Err(ParseError::Incomplete { needed: 1 })));
+    }
+
+    #[test]
+    fn test_decode_event_stream_frame_progressive_prefixes() {
+        let bytes =
+            crate::test_support::encode_event("assistantResponseEvent", r#"{"content":"Hi"}"#);
+
+        for len in 0..bytes.len() {
+            let expected = if len < PRELUDE_SIZE {
+                PRELUDE_SIZE - len
+            } else {
+                bytes.len() - len
+            };
+            match decode_event_stream_frame(&bytes[..len]) {
+                Err(ParseError::Incomplete { needed }) => {
+                    assert_eq!(needed, expected, "prefix length {}", len)
+                }
+                other => panic!(
+                    "prefix length {}: expected Incomplete, got {:?}",
+                    len, other
+                ),
+            }
+        }
+
+        let (message, consumed) = decode_event_stream_frame(&bytes).unwrap();
+        assert_eq!(consumed, bytes.len());
+        assert_eq!(message.payload_as_str(), r#"{"content":"Hi"}"#);
+    }
+
+    #[test]
+    fn test_decode_event_stream_frame_corrupted_crc() {
+        let bytes =
+            crate::test_support::encode_event("assistantResponseEvent", r#"{"content":"Hi"}"#);
+
+        // 破坏 prelude CRC
+        let mut corrupted = bytes.clone();
+        corrupted[8] ^= 0xff;
+        assert!(matches!(
+            decode_event_stream_frame(&corrupted),
+            Err(ParseError::Corrupt {
+                kind: CrcKind::Prelude,
+                ..
+            })
+        ));
+
+        // 破坏 payload，message CRC 校验失败
+        let mut corrupted = bytes.clone();
+        let payload_index = bytes.len() - 6;
+        corrupted[payload_index] ^= 0xff;
+        assert!(matches!(
+            decode_event_stream_frame(&corrupted),
+            Err(ParseError::Corrupt {
+                kind: CrcKind::Message,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_decode_event_stream_frame_aws_reference_all_headers() {
+        // 独立编码器产出的帧，见 testdata/README.md
+        let bytes = include_bytes!("../testdata/aws_all_headers.eventstream");
+
+        let (message, consumed) = decode_event_stream_frame(bytes).unwrap();
+        assert_eq!(consumed, bytes.len());
+        assert_eq!(message.headers.len(), 10);
+        for (name, expected) in [
+            ("true", HeaderValue::Bool(true)),
+            ("false", HeaderValue::Bool(false)),
+            ("byte", HeaderValue::Byte(50)),
+            ("short", HeaderValue::Short(20_000)),
+            ("int", HeaderValue::Integer(500_000)),
+            ("long", HeaderValue::Long(50_000_000_000)),
+            ("bytes", HeaderValue::ByteArray(b"some bytes".to_vec())),
+            ("str", HeaderValue::String("some str".to_string())),
+            ("time", HeaderValue::Timestamp(5_000_000_000)),
+            (
+                "uuid",
+                HeaderValue::Uuid(0xb79bc914_de21_4e13_b8b2_bc47e85b7f0b_u128.to_be_bytes()),
+            ),
+        ] {
+            assert_eq!(
+                message.headers.get(name),
+                Some(&expected),
+                "header {}",
+                name
+            );
+        }
+        assert_eq!(message.payload, b"some payload");
+    }
+
+    #[test]
+    fn test_decode_event_stream_frame_aws_reference_empty_payload() {
+        let bytes = include_bytes!("../testdata/aws_empty_payload.eventstream");
+
+        let (message, consumed) = decode_event_stream_frame(bytes).unwrap();
+        assert_eq!(consumed, bytes.len());
+        assert_eq!(message.headers.len(), 1);
+        assert_eq!(
+            message.headers.get("some-header"),
+            Some(&HeaderValue::Short(500))
+        );
+        assert!(message.payload.is_empty());
+    }
+
+    #[test]
+    fn test_decode_event_stream_frame_aws_reference_invalid_crc() {
+        let bytes = include_bytes!("../testdata/aws_invalid_prelude_crc.eventstream");
+        assert!(matches!(
+            decode_event_stream_frame(bytes),
+            Err(ParseError::Corrupt {
+                kind: CrcKind::Prelude,
+                ..
+            })
+        ));
+
+        let bytes = include_bytes!("../testdata/aws_invalid_message_crc.eventstream");
+        assert!(matches!(
+            decode_event_stream_frame(bytes),
+            Err(ParseError::Corrupt {
+                kind: CrcKind::Message,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parse_frame_skip_crc() {
+        let bytes =
+            crate::test_support::encode_event("assistantResponseEvent", r#"{"content":"Hi"}"#);
+
+        // 故意写错 message CRC
+        let mut corrupted = bytes.clone();
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0xff;
+        assert!(matches!(
+            parse_frame(&corrupted),
+            Err(ParseError::Corrupt {
+                kind: CrcKind::Message,
+                ..
+            })
+        ));
+
+        let (frame, consumed) = parse_frame_with(&corrupted, false).unwrap().unwrap();
+        assert_eq!(consumed, bytes.len());
+        assert_eq!(frame.payload_as_str(), r#"{"content":"Hi"}"#);
+    }
+
+    /// 构造 prelude CRC 正确的帧，用于绕过 CRC 校验测试长度与头部处理
+    fn frame_with_prelude(total_length: u32, header_length: u32, rest: &[u8]) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&total_length.to_be_bytes());
+        buffer.extend_from_slice(&header_length.to_be_bytes());
+        let prelude_crc = crc32(&buffer);
+        buffer.extend_from_slice(&prelude_crc.to_be_bytes());
+        buffer.extend_from_slice(rest);
+        buffer
+    }
+
+    #[test]
+    fn test_malformed_frame_regression_corpus() {
+        // 完整到达但内容畸形的帧必须返回错误，而不是 Incomplete 或 panic
+        let corpus: Vec<(&str, Vec<u8>)> = vec![
+            (
+                "header_length > total_length",
+                frame_with_prelude(20, 64, &[0; 8]),
+            ),
+            (
+                "header_length = u32::MAX",
+                frame_with_prelude(20, u32::MAX, &[0; 8]),
+            ),
+            (
+                "header 覆盖 message CRC",
+                frame_with_prelude(20, 6, &[0; 8]),
+            ),
+            (
+                "name_length = 0",
+                frame_with_prelude(20, 4, &[0, 7, 0, 0, 0, 0, 0, 0]),
+            ),
+            (
+                "string 长度超出头部",
+                frame_with_prelude(22, 6, &[1, b'a', 7, 0xff, 0xff, 0, 0, 0, 0, 0]),
+            ),
+            (
+                "未知值类型",
+                frame_with_prelude(20, 3, &[1, b'a', 42, 0, 0, 0, 0, 0]),
+            ),
+            (
+                "定长值截断",
+                frame_with_prelude(21, 4, &[1, b'a', 4, 0, 0, 0, 0, 0, 0]),
+            ),
+        ];
+
+        for (name, bytes) in &corpus {
+            match parse_frame_with(bytes, false) {
+                Err(ParseError::Incomplete { .. }) | Ok(_) => {
+                    panic!("{}: 应返回解析错误", name)
+                }
+                Err(_) => {}
+            }
+            assert!(
+                !matches!(
+                    decode_event_stream_frame(bytes),
+                    Err(ParseError::Incomplete { .. }) | Ok(_)
+                ),
+                "{}",
+                name
+            );
+        }
+
+        // 超出上限的 total_length 不应尝试等待数据
+        let huge = frame_with_prelude(u32::MAX, 0, &[]);
+        assert!(matches!(
+            parse_frame_with(&huge, false),
+            Err(ParseError::MessageTooLarge { .. })
+        ));
+    }
 }
@@ -117,10 +117,17 @@ impl Headers {
     pub fn error_code(&self) -> Option<&str> {
         self.get_string(":error-code")
     }
+
+    /// 取出全部头部（保留原始类型）
+    pub fn into_map(self) -> HashMap<String, HeaderValue> {
+        self.inner
+    }
 }
 
 /// 从字节流解析头部
 ///
+/// 所有长度字段在使用前都会与剩余数据比对，数据不足时返回 `Incomplete` 而不是越界访问
+///
 /// # Arguments
 /// * `data` - 头部数据切片
 /// * `header_length` - 头部总长度
@@ -128,23 +135,17 @@ impl Headers {
 /// # Returns
 /// 解析后的 Headers 结构
 pub fn parse_headers(data: &[u8], header_length: usize) -> ParseResult<Headers> {
-    // 验证数据长度是否足够
-    if data.len() < header_length {
-        return Err(ParseError::Incomplete {
-            needed: header_length,
-            available: data.len(),
-        });
-    }
+    let data = data.get(..header_length).ok_or(ParseError::Incomplete {
+        needed: header_length.saturating_sub(data.len()),
+    })?;
 
     let mut headers = Headers::new();
     let mut offset = 0;
 
-    while offset < header_length {
+    while offset < data.len() {
         // 读取头部名称长度 (1 byte)
-        if offset >= data.len() {
-            break;
-        }
-        let name_len = data[offset] as usize;
+        let [name_len] = read_array::<1>(data, offset)?;
+        let name_len = name_len as usize;
         offset += 1;
 
         // 验证名称长度
@@ -155,117 +156,84 @@ pub fn parse_headers(data: &[u8], header_length: usize) -> ParseResult<Headers>
         }
 
         // 读取头部名称
-        if offset + name_len > data.len() {
-            return Err(ParseError::Incomplete {
-                needed: name_len,
-                available: data.len() - offset,
-            });
-        }
-        let name = String::from_utf8_lossy(&data[offset..offset + name_len]).to_string();
+        let name = String::from_utf8_lossy(read_slice(data, offset, name_len)?).to_string();
         offset += name_len;
 
         // 读取值类型 (1 byte)
-        if offset >= data.len() {
-            return Err(ParseError::Incomplete {
-                needed: 1,
-                available: 0,
-            });
-        }
-        let value_type = HeaderValueType::try_from(data[offset])?;
+        let [value_type] = read_array::<1>(data, offset)?;
+        let value_type = HeaderValueType::try_from(value_type)?;
         offset += 1;
 
         // 根据类型解析值
-        let value = parse_header_value(&data[offset..], value_type, &mut offset)?;
+        let rest = data.get(offset..).unwrap_or_default();
+        let (value, consumed) = parse_header_value(rest, value_type)?;
+        offset += consumed;
         headers.insert(name, value);
     }
 
     Ok(headers)
 }
 
-/// 解析头部值
+/// 解析头部值，返回值与消费的字节数
 fn parse_header_value(
     data: &[u8],
     value_type: HeaderValueType,
-    global_offset: &mut usize,
-) -> ParseResult<HeaderValue> {
-    let mut local_offset = 0;
-
+) -> ParseResult<(HeaderValue, usize)> {
     let result = match value_type {
-        HeaderValueType::BoolTrue => Ok(HeaderValue::Bool(true)),
-        HeaderValueType::BoolFalse => Ok(HeaderValue::Bool(false)),
-        HeaderValueType::Byte => {
-            ensure_bytes(data, 1)?;
-            let v = data[0] as i8;
-            local_offset = 1;
-            Ok(HeaderValue::Byte(v))
-        }
-        HeaderValueType::Short => {
-            ensure_bytes(data, 2)?;
-            let v = i16::from_be_bytes([data[0], data[1]]);
-            local_offset = 2;
-            Ok(HeaderValue::Short(v))
-        }
-        HeaderValueType::Integer => {
-            ensure_bytes(data, 4)?;
-            let v = i32::from_be_bytes([data[0], data[1], data[2], data[3]]);
-            local_offset = 4;
-            Ok(HeaderValue::Integer(v))
-        }
-        HeaderValueType::Long => {
-            ensure_bytes(data, 8)?;
-            let v = i64::from_be_bytes([
-                data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7],
-            ]);
-            local_offset = 8;
-            Ok(HeaderValue::Long(v))
-        }
-        HeaderValueType::Timestamp => {
-            ensure_bytes(data, 8)?;
-            let v = i64::from_be_bytes([
-                data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7],
-            ]);
-            local_offset = 8;
-            Ok(HeaderValue::Timestamp(v))
-        }
+        HeaderValueType::BoolTrue => (HeaderValue::Bool(true), 0),
+        HeaderValueType::BoolFalse => (HeaderValue::Bool(false), 0),
+        HeaderValueType::Byte => (
+            HeaderValue::Byte(i8::from_be_bytes(read_array(data, 0)?)),
+            1,
+        ),
+        HeaderValueType::Short => (
+            HeaderValue::Short(i16::from_be_bytes(read_array(data, 0)?)),
+            2,
+        ),
+        HeaderValueType::Integer => (
+            HeaderValue::Integer(i32::from_be_bytes(read_array(data, 0)?)),
+            4,
+        ),
+        HeaderValueType::Long => (
+            HeaderValue::Long(i64::from_be_bytes(read_array(data, 0)?)),
+            8,
+        ),
+        HeaderValueType::Timestamp => (
+            HeaderValue::Timestamp(i64::from_be_bytes(read_array(data, 0)?)),
+            8,
+        ),
         HeaderValueType::ByteArray => {
-            ensure_bytes(data, 2)?;
-            let len = u16::from_be_bytes([data[0], data[1]]) as usize;
-            ensure_bytes(data, 2 + len)?;
-            let v = data[2..2 + len].to_vec();
-            local_offset = 2 + len;
-            Ok(HeaderValue::ByteArray(v))
+            let len = u16::from_be_bytes(read_array(data, 0)?) as usize;
+            let value = read_slice(data, 2, len)?.to_vec();
+            (HeaderValue::ByteArray(value), 2 + len)
         }
         HeaderValueType::String => {
-            ensure_bytes(data, 2)?;
-            let len = u16::from_be_bytes([data[0], data[1]]) as usize;
-            ensure_bytes(data, 2 + len)?;
-            let v = String::from_utf8_lossy(&data[2..2 + len]).to_string();
-            local_offset = 2 + len;
-            Ok(HeaderValue::String(v))
-        }
-        HeaderValueType::Uuid => {
-            ensure_bytes(data, 16)?;
-            let mut uuid = [0u8; 16];
-            uuid.copy_from_slice(&data[..16]);
-            local_offset = 16;
-            Ok(HeaderValue::Uuid(uuid))
+            let len = u16::from_be_bytes(read_array(data, 0)?) as usize;
+            let value = String::from_utf8_lossy(read_slice(data, 2, len)?).to_string();
+            (HeaderValue::String(value), 2 + len)
         }
+        HeaderValueType::Uuid => (HeaderValue::Uuid(read_array(data, 0)?), 16),
     };
 
-    *global_offset += local_offset;
-    result
+    Ok(result)
 }
 
-/// 确保有足够的字节
-fn ensure_bytes(data: &[u8], needed: usize) -> ParseResult<()> {
-    if data.len() < needed {
-        Err(ParseError::Incomplete {
-            needed,
-            available: data.len(),
-        })
-    } else {
-        Ok(())
-    }
+/// 读取 `data[offset..offset + len]`，越界时返回 `Incomplete`
+fn read_slice(data: &[u8], offset: usize, len: usize) -> ParseResult<&[u8]> {
+    let end = offset.checked_add(len).ok_or_else(|| {
+        ParseError::HeaderParseFailed(format!("头部长度溢出: {} + {}", offset, len))
+    })?;
+    data.get(offset..end).ok_or_else(|| ParseError::Incomplete {
+        needed: end.saturating_sub(data.len()),
+    })
+}
+
+/// 从 `offset` 处读取定长数组，越界时返回 `Incomplete`
+fn read_array<const N: usize>(data: &[u8], offset: usize) -> ParseResult<[u8; N]> {
+    let bytes = read_slice(data, offset, N)?;
+    let mut array = [0u8; N];
+    array.copy_from_slice(bytes);
+    Ok(array)
 }
 
 #[cfg(test)]
@@ -304,6 +272,39 @@ mod tests {
         assert_eq!(headers.message_type(), Some("event"));
     }
 
+    #[test]
+    fn test_parse_headers_truncated_values() {
+        // 各类型的值都被截断：只返回错误，不越界
+        for data in [
+            &[1u8, b'x', 2][..],
+            &[1, b'x', 3, 0],
+            &[1, b'x', 4, 0, 0, 0],
+            &[1, b'x', 5, 0, 0, 0, 0, 0, 0, 0],
+            &[1, b'x', 6, 0],
+            &[1, b'x', 7, 0xff, 0xff, b'a'],
+            &[1, b'x', 9, 0, 1, 2],
+            &[5, b'x'],
+            &[1, b'x'],
+        ] {
+            assert!(
+                matches!(
+                    parse_headers(data, data.len()),
+                    Err(ParseError::Incomplete { .. })
+                ),
+                "{:?}",
+                data
+            );
+        }
+        assert!(matches!(
+            parse_headers(&[1, b'x', 42], 3),
+            Err(ParseError::InvalidHeaderType(42))
+        ));
+        assert!(matches!(
+            parse_headers(&[1, b'x'], 10),
+            Err(ParseError::Incomplete { needed: 8 })
+        ));
+    }
+
     #[test]
     fn test_parse_headers_string() {
         // 构造一个简单的头部: name_len(1) + name + type(7=string) + value_len(2) + value
@@ -8,3 +8,5 @@ pub mod decoder;
 pub mod error;
 pub mod frame;
 pub mod header;
+pub mod sse;
+pub mod stream;
@@ -0,0 +1,339 @@
+//! Server-Sent Events 响应解析
+//!
+//! 部分端点（或兼容层）以 `text/event-stream` 返回 `data:` 行 JSON 负载，
+//! 而不是二进制 AWS Event Stream。此处将其转换为与二进制解析器相同的 `ParsedEvent`
+
+use serde_json::Value;
+
+use super::decoder::DEFAULT_MAX_BUFFER_SIZE;
+use super::error::{ParseError, ParseResult};
+use super::frame::Frame;
+use super::header::{HeaderValue, Headers};
+use super::stream::ParsedEvent;
+use crate::kiro::model::events::Event;
+
+/// 可识别的事件类型
+const EVENT_TYPES: &[&str] = &[
+    "assistantResponseEvent",
+    "toolUseEvent",
+    "meteringEvent",
+    "contextUsageEvent",
+    "metadataEvent",
+    "reasoningContentEvent",
+];
+
+/// 解析单行 `data:` SSE 数据
+///
+/// 负载可以是 `{"assistantResponseEvent": {...}}` 形式的包装对象，
+/// 也可以是事件负载本身（按字段推断事件类型）。
+/// 注释行、空行、`[DONE]` 以及无法解析的 JSON 返回 `None`
+#[allow(dead_code)]
+pub fn parse_sse_line(line: &str) -> Option<ParsedEvent> {
+    let data = line.strip_prefix("data:")?.trim();
+    parse_data(None, data)
+}
+
+/// 按事件名（`event:` 行）或负载内容构造事件
+fn parse_data(event_type: Option<&str>, data: &str) -> Option<ParsedEvent> {
+    if data.is_empty() || data == "[DONE]" {
+        return None;
+    }
+    let value: Value = match serde_json::from_str(data) {
+        Ok(value) => value,
+        Err(e) => {
+            tracing::warn!("SSE 数据不是有效的 JSON: {}", e);
+            return None;
+        }
+    };
+
+    let (event_type, payload) = match event_type {
+        Some(event_type) => (event_type.to_string(), value),
+        None => infer_event_type(value),
+    };
+
+    let mut headers = Headers::new();
+    headers.insert(
+        ":message-type".to_string(),
+        HeaderValue::String("event".to_string()),
+    );
+    headers.insert(":event-type".to_string(), HeaderValue::String(event_type));
+    let frame = Frame {
+        headers,
+        payload: serde_json::to_vec(&payload).unwrap_or_default(),
+    };
+    match Event::from_frame(frame) {
+        Ok(event) => Some(event),
+        Err(e) => {
+            tracing::warn!("解析 SSE 事件失败: {}", e);
+            None
+        }
+    }
+}
+
+/// 从负载推断事件类型，返回 (事件类型, 事件负载)
+fn infer_event_type(value: Value) -> (String, Value) {
+    if let Value::Object(map) = &value
+        && map.len() == 1
+        && let Some((key, inner)) = map.iter().next()
+        && EVENT_TYPES.contains(&key.as_str())
+    {
+        return (key.clone(), inner.clone());
+    }
+
+    let has = |field: &str| value.get(field).is_some();
+    let event_type = if has("toolUseId") {
+        "toolUseEvent"
+    } else if has("content") {
+        "assistantResponseEvent"
+    } else if has("contextUsagePercentage") {
+        "contextUsageEvent"
+    } else if has("tokenUsage") {
+        "metadataEvent"
+    } else {
+        // 未知事件类型：错误负载（`__type` / `code`）仍会被识别为 `Event::Error`
+        "unknown"
+    };
+    (event_type.to_string(), value)
+}
+
+/// 增量 SSE 解析器
+///
+/// 与 `StreamParser` 用法相同：每次 `push` 一段数据，返回其中已完整到达的事件。
+/// 同一事件内的多行 `data:` 以 `\n` 拼接，遇到空行时作为一个事件分发，事件名取自 `event:` 行；
+/// 未完成的数据超过 `max_buffer_size` 时停止解析并通过 `take_error` 报告
+pub struct SseStreamParser {
+    /// 尚未遇到换行符的残留数据
+    buffer: Vec<u8>,
+    /// 当前事件的 `event:` 名称
+    event_type: Option<String>,
+    /// 当前事件已收到的 `data:` 内容
+    data: Option<String>,
+    /// 未完成数据（残留行 + 当前事件内容）的字节上限
+    max_buffer_size: usize,
+    /// 致命错误（`take_error` 取出后仍保持停止状态）
+    error: Option<ParseError>,
+    /// 出现致命错误后不再解析
+    stopped: bool,
+}
+
+impl Default for SseStreamParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SseStreamParser {
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            event_type: None,
+            data: None,
+            max_buffer_size: DEFAULT_MAX_BUFFER_SIZE,
+            error: None,
+            stopped: false,
+        }
+    }
+
+    /// 设置未完成数据的字节上限（默认 `DEFAULT_MAX_BUFFER_SIZE`）
+    #[allow(dead_code)]
+    pub fn with_max_buffer_size(mut self, max: usize) -> Self {
+        self.max_buffer_size = max;
+        self
+    }
+
+    /// 未完成数据的字节数
+    fn pending_len(&self) -> usize {
+        self.buffer.len() + self.data.as_ref().map_or(0, String::len)
+    }
+
+    /// 追加数据并返回所有已完整到达的事件
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<ParsedEvent> {
+        let mut events = Vec::new();
+        if self.stopped {
+            return events;
+        }
+        self.buffer.extend_from_slice(bytes);
+
+        let mut start = 0;
+        while let Some(pos) = self.buffer[start..].iter().position(|&b| b == b'\n') {
+            let end = start + pos;
+            let line = String::from_utf8_lossy(&self.buffer[start..end]).into_owned();
+            start = end + 1;
+            self.process_line(line.trim_end_matches('\r'), &mut events);
+        }
+        self.buffer.drain(..start);
+
+        let size = self.pending_len();
+        if size > self.max_buffer_size {
+            tracing::warn!("SSE 缓冲区溢出: {} 字节", size);
+            self.error = Some(ParseError::BufferOverflow {
+                size,
+                max: self.max_buffer_size,
+            });
+            self.stopped = true;
+            self.buffer.clear();
+            self.data = None;
+        }
+        events
+    }
+
+    /// 处理一行（不含换行符）
+    fn process_line(&mut self, line: &str, events: &mut Vec<ParsedEvent>) {
+        if line.is_empty() {
+            // 空行结束一个事件
+            let event_type = self.event_type.take();
+            if let Some(data) = self.data.take() {
+                events.extend(parse_data(event_type.as_deref(), data.trim()));
+            }
+        } else if let Some(name) = line.strip_prefix("event:") {
+            self.event_type = Some(name.trim().to_string());
+        } else if let Some(value) = line.strip_prefix("data:") {
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match &mut self.data {
+                Some(data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                }
+                None => self.data = Some(value.to_string()),
+            }
+        }
+        // 注释行（`:` 开头）与 id / retry 字段忽略
+    }
+
+    /// 取出已发生的致命错误（缓冲区溢出）
+    pub fn take_error(&mut self) -> Option<ParseError> {
+        self.error.take()
+    }
+
+    /// 结束解析
+    ///
+    /// 流在事件中间结束（残留不完整的行，或 `data:` 之后缺少结束事件的空行）时
+    /// 返回 `ParseError::Truncated`
+    pub fn finish(&mut self) -> ParseResult<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        let trailing = self.buffer.iter().all(u8::is_ascii_whitespace);
+        if trailing && self.data.is_none() {
+            return Ok(());
+        }
+        Err(ParseError::Truncated {
+            buffered: self.pending_len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAPTURED: &str = "event: assistantResponseEvent\n\
+        data: {\"content\":\"Hel\"}\n\
+        \n\
+        : keep-alive\n\
+        data: {\"assistantResponseEvent\":{\"content\":\"lo\"}}\n\
+        \n\
+        data: {\"name\":\"read\",\"toolUseId\":\"t1\",\"input\":\"{}\",\"stop\":true}\n\
+        \n\
+        event: metadataEvent\n\
+        data: {\"tokenUsage\":{\"inputTokens\":10,\"outputTokens\":2}}\n\
+        \n\
+        data: [DONE]\n\
+        \n";
+
+    #[test]
+    fn test_parse_sse_line() {
+        assert!(matches!(
+            parse_sse_line(r#"data: {"content":"Hi"}"#),
+            Some(Event::AssistantResponse(e)) if e.content == "Hi"
+        ));
+        assert!(matches!(
+            parse_sse_line(r#"data: {"contextUsageEvent":{"contextUsagePercentage":1.5}}"#),
+            Some(Event::ContextUsage(_))
+        ));
+        assert!(matches!(
+            parse_sse_line(r#"data: {"__type":"ThrottlingException","message":"slow down"}"#),
+            Some(Event::Error { code, .. }) if code == "ThrottlingException"
+        ));
+        assert!(parse_sse_line("data: [DONE]").is_none());
+        assert!(parse_sse_line(": ping").is_none());
+        assert!(parse_sse_line("data: {not json").is_none());
+    }
+
+    #[test]
+    fn test_sse_stream_parser_matches_across_chunks() {
+        let expected = {
+            let mut parser = SseStreamParser::new();
+            let events = parser.push(CAPTURED.as_bytes());
+            parser.finish().unwrap();
+            events
+        };
+        assert_eq!(expected.len(), 4);
+        assert!(matches!(&expected[0], Event::AssistantResponse(e) if e.content == "Hel"));
+        assert!(matches!(&expected[1], Event::AssistantResponse(e) if e.content == "lo"));
+        assert!(matches!(&expected[2], Event::ToolUse(e) if e.tool_use_id == "t1" && e.stop));
+        assert!(matches!(
+            expected[3],
+            Event::Usage {
+                input: Some(10),
+                output: Some(2)
+            }
+        ));
+
+        // 任意分块方式得到相同的事件
+        for chunk_size in [1, 3, 17] {
+            let mut parser = SseStreamParser::new();
+            let mut events = Vec::new();
+            for chunk in CAPTURED.as_bytes().chunks(chunk_size) {
+                events.extend(parser.push(chunk));
+            }
+            parser.finish().unwrap();
+            assert_eq!(format!("{:?}", events), format!("{:?}", expected));
+        }
+    }
+
+    #[test]
+    fn test_sse_stream_parser_truncated_line() {
+        let mut parser = SseStreamParser::new();
+        assert!(parser.push(b"data: {\"content\":").is_empty());
+        assert!(matches!(
+            parser.finish(),
+            Err(ParseError::Truncated { buffered: 17 })
+        ));
+
+        // 缺少结束事件的空行
+        let mut parser = SseStreamParser::new();
+        assert!(parser.push(b"data: {\"content\":\"Hi\"}\n").is_empty());
+        assert!(matches!(parser.finish(), Err(ParseError::Truncated { .. })));
+    }
+
+    #[test]
+    fn test_sse_stream_parser_joins_multiline_data() {
+        let mut parser = SseStreamParser::new();
+        let events = parser.push(
+            b"event: assistantResponseEvent\n\
+              data: {\"content\":\n\
+              data: \"Hi\"}\n\
+              \n",
+        );
+        parser.finish().unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(&events[0], Event::AssistantResponse(e) if e.content == "Hi"));
+    }
+
+    #[test]
+    fn test_sse_stream_parser_buffer_limit() {
+        let mut parser = SseStreamParser::new().with_max_buffer_size(64);
+        assert!(parser.push(&[b'a'; 32]).is_empty());
+        assert!(parser.take_error().is_none());
+
+        // 一直没有换行符
+        assert!(parser.push(&[b'a'; 64]).is_empty());
+        assert!(matches!(
+            parser.take_error(),
+            Some(ParseError::BufferOverflow { size: 96, max: 64 })
+        ));
+        // 溢出后不再解析
+        assert!(parser.push(b"data: {\"content\":\"Hi\"}\n\n").is_empty());
+    }
+}
@@ -0,0 +1,390 @@
+//! 增量事件解析器
+//!
+//! 在 `EventStreamDecoder` 之上封装帧到事件的转换，
+//! 用于逐块处理 TCP 分片到达的响应体，跨调用缓存不完整的数据
+//!
+//! # 顺序保证
+//!
+//! - 事件严格按帧到达的顺序返回，与 `push` 的分块方式无关
+//! - 用量帧（`Event::Usage`、`Event::ContextUsage`）作为独立事件返回，可能出现在流中任意位置，
+//!   包括两个文本增量之间；它们不会改变、合并或打断前后的文本增量
+//! - `Event::Usage` 携带的是累计值，多次出现时以最后一次为准
+
+use super::decoder::EventStreamDecoder;
+use super::error::{ParseError, ParseResult};
+use super::frame::decode_event_stream_frame;
+use super::sse::SseStreamParser;
+use crate::kiro::model::events::Event;
+
+/// 解析出的事件
+pub type ParsedEvent = Event;
+
+/// 增量事件解析器
+///
+/// 每次 `push` 一段任意长度的数据，返回其中已完整到达的事件，
+/// 不完整的尾部数据保留到下一次调用；流结束时调用 `finish` 检查是否有残留数据
+pub struct StreamParser {
+    decoder: EventStreamDecoder,
+    /// 首个致命错误（缓冲区溢出或解码器停止），在 `finish` 时返回
+    error: Option<ParseError>,
+}
+
+impl Default for StreamParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StreamParser {
+    pub fn new() -> Self {
+        Self::with_decoder(EventStreamDecoder::new())
+    }
+
+    /// 使用自定义配置的解码器创建解析器
+    pub fn with_decoder(decoder: EventStreamDecoder) -> Self {
+        Self {
+            decoder,
+            error: None,
+        }
+    }
+
+    /// 追加数据并返回所有已完整解码的事件
+    ///
+    /// 损坏的帧会被解码器跳过并记录警告，不会中断后续帧的解析
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<ParsedEvent> {
+        let mut events = Vec::new();
+
+        if let Err(e) = self.decoder.feed(bytes) {
+            tracing::warn!("缓冲区溢出: {}", e);
+            self.error.get_or_insert(e);
+            return events;
+        }
+
+        while !self.decoder.is_stopped() {
+            match self.decoder.decode() {
+                Ok(Some(frame)) => match Event::from_frame(frame) {
+                    Ok(event) => events.push(event),
+                    Err(e) => tracing::warn!("解析事件失败: {}", e),
+                },
+                Ok(None) => break,
+                Err(e) => {
+                    tracing::warn!("解码事件失败: {}", e);
+                    if self.decoder.is_stopped() {
+                        self.error.get_or_insert(e);
+                    }
+                }
+            }
+        }
+
+        events
+    }
+
+    /// 取出已发生的致命错误
+    ///
+    /// 出现致命错误后解码器不再产出事件，调用方可据此提前结束
+    pub fn take_error(&mut self) -> Option<ParseError> {
+        self.error.take()
+    }
+
+    /// 结束解析
+    ///
+    /// # Returns
+    /// - `Ok(())` - 所有数据均已解析
+    /// - `Err(ParseError::Incomplete { .. })` - 流在帧中间截断
+    /// - `Err(e)` - 解析过程中出现的致命错误
+    pub fn finish(&mut self) -> ParseResult<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+
+        let remaining = self.decoder.buffered();
+        if remaining.is_empty() {
+            return Ok(());
+        }
+        decode_event_stream_frame(remaining).map(|_| ())
+    }
+}
+
+/// 响应体格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseFormat {
+    /// 二进制 AWS Event Stream（默认）
+    EventStream,
+    /// Server-Sent Events（`text/event-stream`）
+    Sse,
+}
+
+impl ResponseFormat {
+    /// 根据响应的 `Content-Type` 选择格式，缺失或无法识别时按二进制 Event Stream 处理
+    pub fn from_content_type(content_type: Option<&str>) -> Self {
+        match content_type {
+            Some(ct) if ct.trim_start().starts_with("text/event-stream") => Self::Sse,
+            _ => Self::EventStream,
+        }
+    }
+}
+
+/// 按响应格式选择的解析器，两种格式产出相同的 `ParsedEvent`
+pub enum ResponseParser {
+    EventStream(StreamParser),
+    Sse(SseStreamParser),
+}
+
+impl ResponseParser {
+    /// 追加数据并返回所有已完整到达的事件
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<ParsedEvent> {
+        match self {
+            Self::EventStream(parser) => parser.push(bytes),
+            Self::Sse(parser) => parser.push(bytes),
+        }
+    }
+
+    /// 取出已发生的致命错误
+    pub fn take_error(&mut self) -> Option<ParseError> {
+        match self {
+            Self::EventStream(parser) => parser.take_error(),
+            Self::Sse(parser) => parser.take_error(),
+        }
+    }
+
+    /// 结束解析
+    pub fn finish(&mut self) -> ParseResult<()> {
+        match self {
+            Self::EventStream(parser) => parser.finish(),
+            Self::Sse(parser) => parser.finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::encode_event;
+
+    fn sample_stream() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend(encode_event(
+            "assistantResponseEvent",
+            r#"{"content":"Hello"}"#,
+        ));
+        bytes.extend(encode_event(
+            "assistantResponseEvent",
+            r#"{"content":" world"}"#,
+        ));
+        bytes.extend(encode_event(
+            "toolUseEvent",
+            r#"{"name":"read","toolUseId":"t1","input":"{}","stop":true}"#,
+        ));
+        bytes.extend(encode_event(
+            "contextUsageEvent",
+            r#"{"contextUsagePercentage":1.5}"#,
+        ));
+        bytes
+    }
+
+    fn parse_in_chunks(bytes: &[u8], chunk_size: usize) -> Vec<String> {
+        let mut parser = StreamParser::new();
+        let mut events = Vec::new();
+        for chunk in bytes.chunks(chunk_size) {
+            events.extend(parser.push(chunk));
+        }
+        parser.finish().unwrap();
+        events.iter().map(|e| format!("{:?}", e)).collect()
+    }
+
+    #[test]
+    fn test_stream_parser_chunk_boundaries() {
+        let bytes = sample_stream();
+        let expected = parse_in_chunks(&bytes, bytes.len());
+        assert_eq!(expected.len(), 4);
+
+        for chunk_size in [1, 2, 3, 7, 16, 33, 100] {
+            assert_eq!(
+                parse_in_chunks(&bytes, chunk_size),
+                expected,
+                "chunk_size = {}",
+                chunk_size
+            );
+        }
+    }
+
+    #[test]
+    fn test_stream_parser_frame_split_across_three_pushes() {
+        let frame = encode_event("assistantResponseEvent", r#"{"content":"Hi"}"#);
+        let (a, rest) = frame.split_at(5);
+        let (b, c) = rest.split_at(rest.len() / 2);
+
+        let mut parser = StreamParser::new();
+        assert!(parser.push(a).is_empty());
+        assert!(parser.push(b).is_empty());
+        let events = parser.push(c);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(&events[0], Event::AssistantResponse(e) if e.content == "Hi"));
+        assert!(parser.finish().is_ok());
+    }
+
+    #[test]
+    fn test_stream_parser_two_frames_in_one_push() {
+        let mut bytes = encode_event("assistantResponseEvent", r#"{"content":"a"}"#);
+        bytes.extend(encode_event("assistantResponseEvent", r#"{"content":"b"}"#));
+        let partial = encode_event("assistantResponseEvent", r#"{"content":"c"}"#);
+        bytes.extend_from_slice(&partial[..4]);
+
+        let mut parser = StreamParser::new();
+        let events = parser.push(&bytes);
+        assert_eq!(events.len(), 2);
+
+        // 尾部残留数据保留到下一次 push
+        let events = parser.push(&partial[4..]);
+        assert_eq!(events.len(), 1);
+        assert!(parser.finish().is_ok());
+    }
+
+    #[test]
+    fn test_stream_parser_interleaved_usage_frames() {
+        let mut bytes = Vec::new();
+        bytes.extend(encode_event(
+            "assistantResponseEvent",
+            r#"{"content":"Hello"}"#,
+        ));
+        bytes.extend(encode_event(
+            "metadataEvent",
+            r#"{"tokenUsage":{"inputTokens":100,"outputTokens":4}}"#,
+        ));
+        bytes.extend(encode_event(
+            "assistantResponseEvent",
+            r#"{"content":", "}"#,
+        ));
+        bytes.extend(encode_event(
+            "contextUsageEvent",
+            r#"{"contextUsagePercentage":0.05}"#,
+        ));
+        // 不含用量的元数据帧不产生 Usage 事件
+        bytes.extend(encode_event("metadataEvent", r#"{"conversationId":"c1"}"#));
+        bytes.extend(encode_event(
+            "assistantResponseEvent",
+            r#"{"content":"world!"}"#,
+        ));
+        bytes.extend(encode_event(
+            "metadataEvent",
+            r#"{"tokenUsage":{"inputTokens":100,"outputTokens":9}}"#,
+        ));
+
+        for chunk_size in [1, 13, bytes.len()] {
+            let mut parser = StreamParser::new();
+            let events: Vec<_> = bytes
+                .chunks(chunk_size)
+                .flat_map(|chunk| parser.push(chunk))
+                .collect();
+            parser.finish().unwrap();
+
+            let text: String = events
+                .iter()
+                .filter_map(|e| match e {
+                    Event::AssistantResponse(resp) => Some(resp.content.as_str()),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(text, "Hello, world!");
+
+            let usages: Vec<_> = events
+                .iter()
+                .filter_map(|e| match e {
+                    Event::Usage { input, output } => Some((*input, *output)),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(usages, vec![(Some(100), Some(4)), (Some(100), Some(9))]);
+            assert!(matches!(events[1], Event::Usage { .. }));
+            assert!(matches!(events[3], Event::ContextUsage(_)));
+            assert!(matches!(events[4], Event::Unknown {}));
+
+            // 累计值以最后一次为准，不会重复计数
+            let mut tracker = crate::token::UsageTracker::with_estimated_input(1);
+            events.iter().for_each(|e| tracker.observe(e));
+            let usage = tracker.usage();
+            assert_eq!((usage.input_tokens, usage.output_tokens), (100, 9));
+        }
+    }
+
+    #[test]
+    fn test_stream_parser_finish_truncated() {
+        let frame = encode_event("assistantResponseEvent", r#"{"content":"Hi"}"#);
+
+        let mut parser = StreamParser::new();
+        assert!(parser.push(&frame[..frame.len() - 2]).is_empty());
+        assert!(matches!(
+            parser.finish(),
+            Err(ParseError::Incomplete { needed: 2 })
+        ));
+    }
+
+    /// 对有效帧做随机变异：翻转比特、截断、篡改长度字段或插入随机字节
+    fn mutate(rng: &mut fastrand::Rng, frame: &[u8]) -> Vec<u8> {
+        let mut bytes = frame.to_vec();
+        match rng.u8(..4) {
+            0 => {
+                for _ in 0..rng.usize(1..4) {
+                    let index = rng.usize(..bytes.len());
+                    bytes[index] ^= 1 << rng.u8(..8);
+                }
+            }
+            1 => bytes.truncate(rng.usize(..bytes.len())),
+            2 => {
+                let offset = if rng.bool() { 0 } else { 4 };
+                bytes[offset..offset + 4].copy_from_slice(&rng.u32(..).to_be_bytes());
+            }
+            _ => {
+                let index = rng.usize(..=bytes.len());
+                let noise: Vec<u8> = (0..rng.usize(1..32)).map(|_| rng.u8(..)).collect();
+                bytes.splice(index..index, noise);
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_stream_parser_never_panics_on_random_input() {
+        let valid = sample_stream();
+        let frame = encode_event("assistantResponseEvent", r#"{"content":"Hi"}"#);
+
+        for seed in 0..2000 {
+            let mut rng = fastrand::Rng::with_seed(seed);
+            let input: Vec<u8> = if seed % 2 == 0 {
+                (0..rng.usize(..512)).map(|_| rng.u8(..)).collect()
+            } else {
+                let mut bytes = mutate(&mut rng, &frame);
+                bytes.extend(mutate(&mut rng, &valid));
+                bytes
+            };
+
+            for verify_crc in [true, false] {
+                let decoder = EventStreamDecoder::new().with_crc_check(verify_crc);
+                let mut parser = StreamParser::with_decoder(decoder);
+                for chunk in input.chunks(rng.usize(1..64)) {
+                    parser.push(chunk);
+                }
+                let _ = parser.finish();
+
+                let _ = super::super::frame::parse_frame_with(&input, verify_crc);
+            }
+            let _ = decode_event_stream_frame(&input);
+        }
+    }
+
+    #[test]
+    fn test_response_format_from_content_type() {
+        assert_eq!(
+            ResponseFormat::from_content_type(Some("text/event-stream; charset=utf-8")),
+            ResponseFormat::Sse
+        );
+        assert_eq!(
+            ResponseFormat::from_content_type(Some("application/vnd.amazon.eventstream")),
+            ResponseFormat::EventStream
+        );
+        assert_eq!(
+            ResponseFormat::from_content_type(None),
+            ResponseFormat::EventStream
+        );
+    }
+}
@@ -0,0 +1,112 @@
+//! 请求预览（dry-run）
+//!
+//! 只做协议转换，返回将要发往上游的 Kiro 请求 JSON，不发送任何网络请求；
+//! 用于排查客户端集成问题
+
+use serde_json::Value;
+
+use crate::anthropic::converter::{ConversionError, convert_request};
+use crate::anthropic::types::MessagesRequest;
+use crate::kiro::model::requests::kiro::KiroRequest;
+use crate::openai::converter::{MessageOrigins, to_messages_request};
+use crate::openai::types::OpenAiChatRequest;
+
+/// 待预览的客户端请求
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum InputRequest {
+    Anthropic(MessagesRequest),
+    OpenAi(OpenAiChatRequest),
+}
+
+impl From<MessagesRequest> for InputRequest {
+    fn from(req: MessagesRequest) -> Self {
+        InputRequest::Anthropic(req)
+    }
+}
+
+impl From<OpenAiChatRequest> for InputRequest {
+    fn from(req: OpenAiChatRequest) -> Self {
+        InputRequest::OpenAi(req)
+    }
+}
+
+/// 返回请求转换后的 Kiro 请求体（不含 profileArn）
+///
+/// 会话 ID 每次随机生成，其余字段与实际发送的请求一致
+#[allow(dead_code)]
+pub fn preview_kiro_request(req: impl Into<InputRequest>) -> Result<Value, ConversionError> {
+    let (messages_request, origins) = match req.into() {
+        InputRequest::Anthropic(req) => (req, MessageOrigins::default()),
+        InputRequest::OpenAi(req) => to_messages_request(req)?,
+    };
+    let result = convert_request(&messages_request).map_err(|e| origins.rewrite(e))?;
+    let request = KiroRequest {
+        conversation_state: result.conversation_state,
+        profile_arn: None,
+        inference_config: Some(result.inference_config),
+        metadata: result.metadata,
+    };
+    serde_json::to_value(&request)
+        .map_err(|e| ConversionError::InvalidRequest(format!("请求序列化失败: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_preview_openai_request() {
+        let req: OpenAiChatRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4.5",
+            "max_tokens": 128,
+            "temperature": 0,
+            "messages": [
+                {"role": "system", "content": "Be brief."},
+                {"role": "user", "content": "Hello"}
+            ]
+        }))
+        .unwrap();
+        let mut preview = preview_kiro_request(req).unwrap();
+
+        let state = &mut preview["conversationState"];
+        assert!(
+            state["conversationId"]
+                .as_str()
+                .is_some_and(|id| !id.is_empty())
+        );
+        state.as_object_mut().unwrap().remove("conversationId");
+        state.as_object_mut().unwrap().remove("agentContinuationId");
+
+        assert_eq!(preview["inferenceConfig"]["maxTokens"], 128);
+        assert_eq!(preview["inferenceConfig"]["temperature"], 0.0);
+        let current = &preview["conversationState"]["currentMessage"]["userInputMessage"];
+        assert_eq!(current["content"], "Hello");
+        assert_eq!(current["origin"], "AI_EDITOR");
+        assert!(preview.get("profileArn").is_none());
+        // system 提示以 user/assistant 配对的形式进入历史
+        let history = preview["conversationState"]["history"].as_array().unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(
+            history[0]["userInputMessage"]["content"]
+                .as_str()
+                .unwrap()
+                .contains("Be brief.")
+        );
+    }
+
+    #[test]
+    fn test_preview_reports_conversion_errors() {
+        let req: MessagesRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "max_tokens": 128,
+            "messages": [{"role": "user", "content": "Hello"}]
+        }))
+        .unwrap();
+        assert!(matches!(
+            preview_kiro_request(req),
+            Err(ConversionError::UnsupportedModel(_))
+        ));
+    }
+}
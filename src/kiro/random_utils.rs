@@ -3,68 +3,324 @@
 //! 同步自 kiro2api 的实现，用于生成随机化的 User-Agent 组件
 //! 降低被识别为同一客户端的风险
 
+use std::ops::RangeInclusive;
+
+use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT};
+
+/// 固定的 AWS SDK 版本
+pub const SDK_VERSION: &str = "1.0.18";
+
+/// 固定的上游 API 名称
+pub const API_NAME: &str = "codewhispererstreaming";
+
+/// 固定不变的版本信息
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PinnedVersions {
+    pub sdk_version: &'static str,
+    pub api_name: &'static str,
+}
+
+/// 获取 User-Agent 中固定不变的版本信息
+#[allow(dead_code)]
+pub fn pinned_versions() -> PinnedVersions {
+    PinnedVersions {
+        sdk_version: SDK_VERSION,
+        api_name: API_NAME,
+    }
+}
+
 /// 生成指定范围内的随机整数 [min, max]
 #[inline]
-fn random_int(min: u32, max: u32) -> u32 {
+fn random_int(rng: &mut fastrand::Rng, min: u32, max: u32) -> u32 {
     if min >= max {
         return min;
     }
-    fastrand::u32(min..=max)
+    rng.u32(min..=max)
 }
 
-/// 生成随机 Git 提交哈希（40 字符十六进制）
-pub fn generate_random_git_hash() -> String {
-    const HEX_CHARS: &[u8] = b"0123456789abcdef";
-    let mut hash = String::with_capacity(40);
-    for _ in 0..40 {
-        let idx = fastrand::usize(..16);
-        hash.push(HEX_CHARS[idx] as char);
+/// 十六进制标识使用的字符表（小写）
+pub const HEX_ALPHABET: &[u8; 16] = b"0123456789abcdef";
+
+/// 十六进制标识使用的字符表（大写）
+pub const HEX_ALPHABET_UPPER: &[u8; 16] = b"0123456789ABCDEF";
+
+/// 十六进制字母的大小写，默认小写
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HexCase {
+    #[default]
+    Lower,
+    #[allow(dead_code)]
+    Upper,
+}
+
+impl HexCase {
+    /// 对应的字符表
+    pub fn alphabet(self) -> &'static [u8; 16] {
+        match self {
+            HexCase::Lower => HEX_ALPHABET,
+            HexCase::Upper => HEX_ALPHABET_UPPER,
+        }
+    }
+
+    /// 按大小写编码字节
+    pub fn encode(self, bytes: impl AsRef<[u8]>) -> String {
+        match self {
+            HexCase::Lower => hex::encode(bytes),
+            HexCase::Upper => hex::encode_upper(bytes),
+        }
     }
+}
+
+/// Git 提交哈希长度
+pub const GIT_HASH_LEN: usize = 40;
+
+/// 生成随机 Git 提交哈希（40 字符小写十六进制）
+#[allow(dead_code)]
+pub fn generate_random_git_hash() -> String {
+    generate_random_git_hash_in(HexCase::Lower)
+}
+
+/// 生成指定大小写的随机 Git 提交哈希
+#[allow(dead_code)]
+pub fn generate_random_git_hash_in(case: HexCase) -> String {
+    generate_git_hash_with(&mut fastrand::Rng::with_seed(fastrand::u64(..)), case)
+}
+
+fn generate_git_hash_with(rng: &mut fastrand::Rng, case: HexCase) -> String {
+    let hash = generate_hex_with(rng, GIT_HASH_LEN, case);
+    debug_assert!(
+        hash.len() == GIT_HASH_LEN && hash.bytes().all(|b| case.alphabet().contains(&b)),
+        "生成的 Git 哈希不合法: {}",
+        hash
+    );
     hash
 }
 
+/// 生成指定长度与大小写的随机十六进制字符串
+#[allow(dead_code)]
+pub fn generate_random_hex(len: usize, case: HexCase) -> String {
+    generate_hex_with(&mut fastrand::Rng::with_seed(fastrand::u64(..)), len, case)
+}
+
+fn generate_hex_with(rng: &mut fastrand::Rng, len: usize, case: HexCase) -> String {
+    let alphabet = case.alphabet();
+    (0..len)
+        .map(|_| alphabet[rng.usize(..alphabet.len())] as char)
+        .collect()
+}
+
+/// 版本号各段的取值范围（闭区间）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionBounds {
+    pub major: RangeInclusive<u32>,
+    pub minor: RangeInclusive<u32>,
+    pub patch: RangeInclusive<u32>,
+    pub build: RangeInclusive<u32>,
+}
+
+impl VersionBounds {
+    fn generate_with(&self, rng: &mut fastrand::Rng) -> [u32; 4] {
+        [&self.major, &self.minor, &self.patch, &self.build]
+            .map(|range| random_int(rng, *range.start(), *range.end()))
+    }
+}
+
+/// User-Agent 中随机版本的取值范围
+///
+/// 默认值与当前 Kiro 客户端一致；客户端升级后可自行调整，无需修改本 crate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionRanges {
+    /// Electron OS 版本，默认 13.7.x.x ~ 13.9.x.x
+    pub os: VersionBounds,
+    /// Node/Chromium 版本，默认 138.0.7200.x ~ 138.0.7210.x
+    pub node: VersionBounds,
+}
+
+impl Default for VersionRanges {
+    fn default() -> Self {
+        Self {
+            os: VersionBounds {
+                major: 13..=13,
+                minor: 7..=9,
+                patch: 0..=99,
+                build: 0..=299,
+            },
+            node: VersionBounds {
+                major: 138..=138,
+                minor: 0..=0,
+                patch: 7200..=7210,
+                build: 0..=999,
+            },
+        }
+    }
+}
+
 /// 生成随机 OS 版本（模拟不同的 Electron 环境）
 ///
 /// 范围: 13.7.x.x-electron.0 ~ 13.9.x.x-electron.0
+#[allow(dead_code)]
 pub fn generate_random_os_version() -> String {
-    let major = 13;
-    let minor = random_int(7, 9);       // 7-9
-    let patch = random_int(0, 99);      // 0-99
-    let build = random_int(0, 299);     // 0-299
+    generate_os_version_in(&VersionRanges::default())
+}
+
+/// 在指定范围内生成随机 OS 版本
+#[allow(dead_code)]
+pub fn generate_os_version_in(ranges: &VersionRanges) -> String {
+    generate_os_version_with(ranges, &mut fastrand::Rng::with_seed(fastrand::u64(..)))
+}
+
+fn generate_os_version_with(ranges: &VersionRanges, rng: &mut fastrand::Rng) -> String {
+    let [major, minor, patch, build] = ranges.os.generate_with(rng);
     format!("{}.{}.{}.{}-electron.0", major, minor, patch, build)
 }
 
 /// 生成随机 Node/Chromium 版本
 ///
 /// 范围: 138.0.7200.x ~ 138.0.7210.x
+#[allow(dead_code)]
 pub fn generate_random_node_version() -> String {
-    let major = 138;
-    let minor = 0;
-    let patch = random_int(7200, 7210); // 7200-7210
-    let build = random_int(0, 999);     // 0-999
+    generate_node_version_in(&VersionRanges::default())
+}
+
+/// 在指定范围内生成随机 Node/Chromium 版本
+#[allow(dead_code)]
+pub fn generate_node_version_in(ranges: &VersionRanges) -> String {
+    generate_node_version_with(ranges, &mut fastrand::Rng::with_seed(fastrand::u64(..)))
+}
+
+fn generate_node_version_with(ranges: &VersionRanges, rng: &mut fastrand::Rng) -> String {
+    let [major, minor, patch, build] = ranges.node.generate_with(rng);
     format!("{}.{}.{}.{}", major, minor, patch, build)
 }
 
+/// 模拟的客户端平台
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientPlatform {
+    MacOs,
+    Windows,
+    Linux,
+}
+
+impl ClientPlatform {
+    const ALL: [ClientPlatform; 3] = [
+        ClientPlatform::MacOs,
+        ClientPlatform::Windows,
+        ClientPlatform::Linux,
+    ];
+
+    /// 随机选择一个平台
+    fn random_with(rng: &mut fastrand::Rng) -> Self {
+        Self::ALL[rng.usize(..Self::ALL.len())]
+    }
+
+    /// 生成该平台的 `os/` 标识（不含 `os/` 前缀）
+    ///
+    /// - macOS: darwin#23.x.0 ~ darwin#24.x.0
+    /// - Windows: win32#10.0.19045 ~ win32#10.0.26100
+    /// - Linux: 沿用 Electron 版本格式
+    fn os_token_with(self, ranges: &VersionRanges, rng: &mut fastrand::Rng) -> String {
+        match self {
+            ClientPlatform::MacOs => {
+                let major = random_int(rng, 23, 24); // 23-24
+                let minor = random_int(rng, 0, 6); // 0-6
+                format!("darwin#{}.{}.0", major, minor)
+            }
+            ClientPlatform::Windows => {
+                let build = random_int(rng, 19045, 26100); // 19045-26100
+                format!("win32#10.0.{}", build)
+            }
+            ClientPlatform::Linux => generate_os_version_with(ranges, rng),
+        }
+    }
+}
+
 /// User-Agent 头部信息
+#[derive(Debug, Clone)]
 pub struct UserAgentHeaders {
     pub x_amzn_kiro_agent_mode: &'static str,
     pub x_amz_user_agent: String,
     pub user_agent: String,
 }
 
+impl UserAgentHeaders {
+    /// 转换为请求头，值包含非法字符时返回错误
+    pub fn to_header_map(&self) -> anyhow::Result<HeaderMap> {
+        let mut headers = HeaderMap::with_capacity(3);
+        headers.insert(
+            "x-amzn-kiro-agent-mode",
+            HeaderValue::from_static(self.x_amzn_kiro_agent_mode),
+        );
+        headers.insert(
+            "x-amz-user-agent",
+            HeaderValue::from_str(&self.x_amz_user_agent)?,
+        );
+        headers.insert(USER_AGENT, HeaderValue::from_str(&self.user_agent)?);
+        Ok(headers)
+    }
+}
+
+/// User-Agent 生成方式
+#[derive(Debug, Clone, Default)]
+pub enum UserAgentMode {
+    /// 每个 (machine_id, Kiro 版本) 会话随机生成一次（默认）
+    #[default]
+    Randomized,
+    /// 所有请求使用给定的请求头，不做任何随机化
+    #[allow(dead_code)]
+    Fixed(UserAgentHeaders),
+}
+
 /// 构建随机化的 User-Agent 请求头
 ///
 /// 保守随机化策略：
 /// - 固定版本：SDK 版本、Kiro IDE 版本
-/// - 随机版本：OS 版本、Node 版本、Git Hash
+/// - 随机版本：平台、OS 版本、Node 版本、Git Hash
 pub fn build_user_agent_headers(kiro_version: &str) -> UserAgentHeaders {
-    // 固定版本（保持稳定）
-    const SDK_VERSION: &str = "1.0.18";
+    build_user_agent_headers_seeded(kiro_version, fastrand::u64(..))
+}
+
+/// 使用指定种子构建 User-Agent 请求头
+///
+/// 相同的版本和种子总是生成相同的结果，便于编写确定性测试
+pub fn build_user_agent_headers_seeded(kiro_version: &str, seed: u64) -> UserAgentHeaders {
+    let mut rng = fastrand::Rng::with_seed(seed);
+    let platform = ClientPlatform::random_with(&mut rng);
+    build_headers_with(kiro_version, platform, &VersionRanges::default(), &mut rng)
+}
+
+/// 使用自定义版本范围构建 User-Agent 请求头
+#[allow(dead_code)]
+pub fn build_user_agent_headers_in(kiro_version: &str, ranges: &VersionRanges) -> UserAgentHeaders {
+    let mut rng = fastrand::Rng::with_seed(fastrand::u64(..));
+    let platform = ClientPlatform::random_with(&mut rng);
+    build_headers_with(kiro_version, platform, ranges, &mut rng)
+}
+
+/// 按指定平台构建 User-Agent 请求头
+#[allow(dead_code)]
+pub fn build_user_agent_headers_for(
+    kiro_version: &str,
+    platform: ClientPlatform,
+) -> UserAgentHeaders {
+    build_headers_with(
+        kiro_version,
+        platform,
+        &VersionRanges::default(),
+        &mut fastrand::Rng::with_seed(fastrand::u64(..)),
+    )
+}
 
+fn build_headers_with(
+    kiro_version: &str,
+    platform: ClientPlatform,
+    ranges: &VersionRanges,
+    rng: &mut fastrand::Rng,
+) -> UserAgentHeaders {
     // 随机版本（模拟不同用户环境）
-    let os_version = generate_random_os_version();
-    let node_version = generate_random_node_version();
-    let hash = generate_random_git_hash();
+    let os_version = platform.os_token_with(ranges, rng);
+    let node_version = generate_node_version_with(ranges, rng);
+    let hash = generate_git_hash_with(rng, HexCase::Lower);
 
     UserAgentHeaders {
         x_amzn_kiro_agent_mode: "spec",
@@ -73,25 +329,68 @@ pub fn build_user_agent_headers(kiro_version: &str) -> UserAgentHeaders {
             SDK_VERSION, kiro_version, hash
         ),
         user_agent: format!(
-            "aws-sdk-js/{} ua/2.1 os/{} lang/js md/nodejs#{} api/codewhispererstreaming#{} m/E KiroIDE-{}-{}",
-            SDK_VERSION, os_version, node_version, SDK_VERSION, kiro_version, hash
+            "aws-sdk-js/{} ua/2.1 os/{} lang/js md/nodejs#{} api/{}#{} m/E KiroIDE-{}-{}",
+            SDK_VERSION, os_version, node_version, API_NAME, SDK_VERSION, kiro_version, hash
         ),
     }
 }
 
+/// 会话级的 User-Agent 请求头
+///
+/// 真实的 Kiro 客户端在整个会话内保持同一个 User-Agent，
+/// 因此只在创建时随机生成一次，之后每次请求复用相同的值
+#[derive(Debug, Clone)]
+pub struct SessionUserAgent {
+    x_amzn_kiro_agent_mode: HeaderValue,
+    x_amz_user_agent: HeaderValue,
+    user_agent: HeaderValue,
+}
+
+impl SessionUserAgent {
+    /// 生成新的会话 User-Agent，`machine_id` 会嵌入 `KiroIDE-{version}-{machine_id}-` 段
+    pub fn new(kiro_version: &str, machine_id: &str) -> Self {
+        let ua_headers = build_user_agent_headers(kiro_version);
+        let from = format!("KiroIDE-{}-", kiro_version);
+        let to = format!("KiroIDE-{}-{}-", kiro_version, machine_id);
+
+        Self {
+            x_amzn_kiro_agent_mode: HeaderValue::from_static(ua_headers.x_amzn_kiro_agent_mode),
+            x_amz_user_agent: header_value(&ua_headers.x_amz_user_agent.replace(&from, &to)),
+            user_agent: header_value(&ua_headers.user_agent.replace(&from, &to)),
+        }
+    }
+
+    /// 获取本会话的 User-Agent 相关请求头
+    pub fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::with_capacity(3);
+        headers.insert(
+            "x-amzn-kiro-agent-mode",
+            self.x_amzn_kiro_agent_mode.clone(),
+        );
+        headers.insert("x-amz-user-agent", self.x_amz_user_agent.clone());
+        headers.insert(USER_AGENT, self.user_agent.clone());
+        headers
+    }
+}
+
+fn header_value(value: &str) -> HeaderValue {
+    HeaderValue::from_str(value).expect("User-Agent 包含非法字符")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_random_int() {
+        let mut rng = fastrand::Rng::new();
         for _ in 0..100 {
-            let val = random_int(5, 10);
+            let val = random_int(&mut rng, 5, 10);
             assert!(val >= 5 && val <= 10);
         }
         // 边界情况
-        assert_eq!(random_int(5, 5), 5);
-        assert_eq!(random_int(10, 5), 10);
+        assert_eq!(random_int(&mut rng, 5, 5), 5);
+        assert_eq!(random_int(&mut rng, 10, 5), 10);
     }
 
     #[test]
@@ -101,6 +400,40 @@ mod tests {
         assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
     }
 
+    #[test]
+    fn test_git_hash_invariants_many_seeds() {
+        for seed in 0..5_000u64 {
+            let mut rng = fastrand::Rng::with_seed(seed);
+            let hash = generate_git_hash_with(&mut rng, HexCase::Lower);
+            assert_eq!(hash.len(), GIT_HASH_LEN, "seed {}", seed);
+            assert!(
+                hash.bytes().all(|b| HEX_ALPHABET.contains(&b)),
+                "seed {}: {}",
+                seed,
+                hash
+            );
+        }
+    }
+
+    #[test]
+    fn test_hex_case() {
+        let upper = generate_random_git_hash_in(HexCase::Upper);
+        assert_eq!(upper.len(), GIT_HASH_LEN);
+        assert!(upper.bytes().all(|b| HEX_ALPHABET_UPPER.contains(&b)));
+
+        let lower = generate_random_hex(64, HexCase::default());
+        assert_eq!(lower.len(), 64);
+        assert!(lower.bytes().all(|b| HEX_ALPHABET.contains(&b)));
+
+        // 固定种子下大小写只影响字母，不影响取值
+        let lower = generate_hex_with(&mut fastrand::Rng::with_seed(7), 32, HexCase::Lower);
+        let upper = generate_hex_with(&mut fastrand::Rng::with_seed(7), 32, HexCase::Upper);
+        assert_eq!(lower.to_ascii_uppercase(), upper);
+
+        assert_eq!(HexCase::Upper.encode([0xab, 0x01]), "AB01");
+        assert_eq!(HexCase::Lower.encode([0xab, 0x01]), "ab01");
+    }
+
     #[test]
     fn test_generate_random_os_version() {
         let version = generate_random_os_version();
@@ -114,6 +447,60 @@ mod tests {
         assert!(version.starts_with("138.0."));
     }
 
+    fn parse_version(version: &str) -> Vec<u32> {
+        version
+            .trim_end_matches("-electron.0")
+            .split('.')
+            .map(|part| part.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_version_ranges_default() {
+        let ranges = VersionRanges::default();
+        for _ in 0..50 {
+            let os = parse_version(&generate_random_os_version());
+            assert_eq!(os[0], 13);
+            assert!(ranges.os.minor.contains(&os[1]));
+            assert!(ranges.os.build.contains(&os[3]));
+
+            let node = parse_version(&generate_random_node_version());
+            assert_eq!(&node[..2], &[138, 0]);
+            assert!(ranges.node.patch.contains(&node[2]));
+        }
+    }
+
+    #[test]
+    fn test_custom_version_ranges() {
+        let bounds = VersionBounds {
+            major: 14..=15,
+            minor: 2..=2,
+            patch: 10..=12,
+            build: 0..=5,
+        };
+        let ranges = VersionRanges {
+            os: bounds.clone(),
+            node: VersionBounds {
+                major: 140..=140,
+                ..bounds.clone()
+            },
+        };
+
+        for _ in 0..50 {
+            let os = parse_version(&generate_os_version_in(&ranges));
+            let node = parse_version(&generate_node_version_in(&ranges));
+            for (parts, major) in [(os, &bounds.major), (node, &(140..=140))] {
+                assert!(major.contains(&parts[0]));
+                assert!(bounds.minor.contains(&parts[1]));
+                assert!(bounds.patch.contains(&parts[2]));
+                assert!(bounds.build.contains(&parts[3]));
+            }
+        }
+
+        let headers = build_user_agent_headers_in("0.8.0", &ranges);
+        assert!(headers.user_agent.contains("md/nodejs#140.2."));
+    }
+
     #[test]
     fn test_build_user_agent_headers() {
         let headers = build_user_agent_headers("0.8.0");
@@ -122,10 +509,50 @@ mod tests {
         assert!(headers.x_amz_user_agent.contains("aws-sdk-js/1.0.18"));
         assert!(headers.x_amz_user_agent.contains("KiroIDE-0.8.0-"));
         assert!(headers.user_agent.contains("aws-sdk-js/1.0.18"));
-        assert!(headers.user_agent.contains("-electron.0"));
+        assert!(headers.user_agent.contains(" os/"));
         assert!(headers.user_agent.contains("138.0."));
     }
 
+    #[test]
+    fn test_pinned_versions() {
+        let pinned = pinned_versions();
+        assert_eq!(pinned.sdk_version, SDK_VERSION);
+        assert_eq!(pinned.api_name, "codewhispererstreaming");
+
+        let headers = build_user_agent_headers("0.8.0");
+        assert!(
+            headers
+                .x_amz_user_agent
+                .starts_with(&format!("aws-sdk-js/{} ", pinned.sdk_version))
+        );
+        assert!(
+            headers
+                .user_agent
+                .contains(&format!("api/{}#{}", pinned.api_name, pinned.sdk_version))
+        );
+    }
+
+    #[test]
+    fn test_build_user_agent_headers_for_platform() {
+        let cases = [
+            (ClientPlatform::MacOs, "os/darwin#"),
+            (ClientPlatform::Windows, "os/win32#10.0."),
+            (ClientPlatform::Linux, "os/13."),
+        ];
+        for (platform, prefix) in cases {
+            let headers = build_user_agent_headers_for("0.8.0", platform);
+            assert!(
+                headers.user_agent.contains(&format!(" {}", prefix)),
+                "{:?}: {}",
+                platform,
+                headers.user_agent
+            );
+        }
+
+        let linux = build_user_agent_headers_for("0.8.0", ClientPlatform::Linux);
+        assert!(linux.user_agent.contains("-electron.0"));
+    }
+
     #[test]
     fn test_randomness() {
         // 验证每次生成的值不同（概率性测试）
@@ -134,4 +561,39 @@ mod tests {
         // 两个随机哈希相同的概率极低
         assert_ne!(hash1, hash2);
     }
+
+    #[test]
+    fn test_build_user_agent_headers_seeded_is_deterministic() {
+        let a = build_user_agent_headers_seeded("0.8.0", 42);
+        let b = build_user_agent_headers_seeded("0.8.0", 42);
+        assert_eq!(a.x_amz_user_agent, b.x_amz_user_agent);
+        assert_eq!(a.user_agent, b.user_agent);
+
+        let c = build_user_agent_headers_seeded("0.8.0", 43);
+        assert_ne!(a.user_agent, c.user_agent);
+    }
+
+    #[test]
+    fn test_session_user_agent_is_stable() {
+        let session = SessionUserAgent::new("0.8.0", "abc123");
+        let first = session.headers();
+        let second = session.headers();
+        assert_eq!(first, second);
+        assert_eq!(first.get("x-amzn-kiro-agent-mode").unwrap(), "spec");
+        assert!(
+            first
+                .get(USER_AGENT)
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .contains("KiroIDE-0.8.0-abc123-")
+        );
+
+        // 不同会话各自随机生成
+        let other = SessionUserAgent::new("0.8.0", "abc123");
+        assert_ne!(
+            first.get("x-amz-user-agent"),
+            other.headers().get("x-amz-user-agent")
+        );
+    }
 }
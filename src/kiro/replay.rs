@@ -0,0 +1,212 @@
+//! 离线回放 Provider
+//!
+//! 启用 `test-support` feature 后可用：不发送任何网络请求，而是把录制好的上游响应字节
+//! 经真实的解析器（`StreamParser` / `SseStreamParser`）解码，
+//! 便于对整条处理链路做确定性的集成测试
+
+use std::path::Path;
+
+use bytes::Bytes;
+use futures::{Stream, stream};
+
+use crate::anthropic::converter::ConversionError;
+use crate::kiro::completion::{CompletionBuilder, CompletionResponse};
+use crate::kiro::error::KiroError;
+use crate::kiro::model::requests::kiro::KiroRequest;
+use crate::kiro::parser::sse::SseStreamParser;
+use crate::kiro::parser::stream::{ParsedEvent, ResponseFormat, ResponseParser, StreamParser};
+use crate::token::UsageTracker;
+
+/// 默认每次交给解析器的字节数，模拟网络分块到达
+const DEFAULT_CHUNK_SIZE: usize = 64;
+
+/// 回放录制响应的 Provider
+///
+/// 与 `KiroProvider` 的 `stream_completion` / `complete` 用法相同；每次调用都从头回放同一份响应
+#[derive(Debug, Clone)]
+pub struct ReplayProvider {
+    bytes: Bytes,
+    format: ResponseFormat,
+    chunk_size: usize,
+}
+
+impl ReplayProvider {
+    /// 从录制的响应体创建（默认按二进制 AWS Event Stream 解析）
+    pub fn from_bytes(bytes: impl Into<Bytes>) -> Self {
+        Self {
+            bytes: bytes.into(),
+            format: ResponseFormat::EventStream,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    /// 从录制的响应文件创建
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .map_err(|e| anyhow::anyhow!("读取回放文件 {} 失败: {}", path.display(), e))?;
+        Ok(Self::from_bytes(bytes))
+    }
+
+    /// 设置响应格式（SSE 录制文件使用 `ResponseFormat::Sse`）
+    pub fn with_format(mut self, format: ResponseFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// 设置每次交给解析器的字节数（最小为 1）
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// 回放为事件流，行为同 `KiroProvider::stream_completion`
+    ///
+    /// 请求仅被序列化校验，不影响回放内容
+    pub async fn stream_completion(
+        &self,
+        req: KiroRequest,
+    ) -> Result<impl Stream<Item = Result<ParsedEvent, KiroError>> + use<>, KiroError> {
+        serialize_request(&req)?;
+        Ok(stream::iter(self.replay()))
+    }
+
+    /// 回放并拼装完整响应，行为同 `KiroProvider::complete`
+    ///
+    /// 输入 tokens 在上游未下发用量时按请求体估算
+    pub async fn complete(&self, req: KiroRequest) -> Result<CompletionResponse, KiroError> {
+        let request_body = serialize_request(&req)?;
+        let mut builder = CompletionBuilder::new(UsageTracker::new(&request_body));
+        for event in self.replay() {
+            builder.push(&event?)?;
+        }
+        Ok(builder.finish())
+    }
+
+    /// 按分块解析整个响应体，出错后不再产出后续事件
+    fn replay(&self) -> Vec<Result<ParsedEvent, KiroError>> {
+        let mut parser = match self.format {
+            ResponseFormat::EventStream => ResponseParser::EventStream(StreamParser::new()),
+            ResponseFormat::Sse => ResponseParser::Sse(SseStreamParser::new()),
+        };
+
+        let mut items = Vec::new();
+        for chunk in self.bytes.chunks(self.chunk_size) {
+            items.extend(parser.push(chunk).into_iter().map(Ok));
+            if let Some(e) = parser.take_error() {
+                items.push(Err(e.into()));
+                return items;
+            }
+        }
+        if let Err(e) = parser.finish() {
+            items.push(Err(e.into()));
+        }
+        items
+    }
+}
+
+fn serialize_request(req: &KiroRequest) -> Result<String, KiroError> {
+    serde_json::to_string(req)
+        .map_err(|e| ConversionError::InvalidRequest(format!("请求序列化失败: {}", e)).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::completion::CompletionContent;
+    use crate::kiro::finish_reason::FinishReason;
+    use crate::kiro::model::events::Event;
+    use crate::kiro::model::requests::conversation::{
+        ConversationState, CurrentMessage, UserInputMessage,
+    };
+    use crate::kiro::parser::error::ParseError;
+    use futures::StreamExt;
+
+    /// 录制的上游响应：两段文本、分片到达的工具调用、contextUsage 与 metadata 用量
+    const FIXTURE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/kiro/testdata/tool_use.eventstream"
+    );
+
+    fn request() -> KiroRequest {
+        KiroRequest {
+            conversation_state: ConversationState::new("conv-1").with_current_message(
+                CurrentMessage::new(UserInputMessage::new("Weather?", "claude-sonnet-4.5")),
+            ),
+            profile_arn: None,
+            inference_config: None,
+            metadata: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_fixture_complete() {
+        let provider = ReplayProvider::from_file(FIXTURE).unwrap();
+        let response = provider.complete(request()).await.unwrap();
+
+        assert_eq!(response.text(), "Let me check the weather.");
+        match &response.content[..] {
+            [
+                CompletionContent::Text(_),
+                CompletionContent::ToolUse(tool_use),
+            ] => {
+                assert_eq!(tool_use.id, "tooluse_replay");
+                assert_eq!(tool_use.name, "get_weather");
+                assert_eq!(tool_use.input, serde_json::json!({"city": "Paris"}));
+            }
+            other => panic!("unexpected content: {:?}", other),
+        }
+        assert_eq!(response.stop_reason, FinishReason::ToolUse);
+        assert_eq!(response.usage.input_tokens, 42);
+        assert_eq!(response.usage.output_tokens, 18);
+        assert!(!response.usage.estimated);
+    }
+
+    #[tokio::test]
+    async fn test_replay_stream_is_independent_of_chunking() {
+        let bytes = std::fs::read(FIXTURE).unwrap();
+        let collect = |chunk_size: usize| {
+            let provider = ReplayProvider::from_bytes(bytes.clone()).with_chunk_size(chunk_size);
+            async move {
+                let stream = provider.stream_completion(request()).await.unwrap();
+                stream
+                    .map(|item| format!("{:?}", item.unwrap()))
+                    .collect::<Vec<_>>()
+                    .await
+            }
+        };
+
+        let expected = collect(bytes.len()).await;
+        assert_eq!(expected.len(), 7);
+        for chunk_size in [1, 7, 64] {
+            assert_eq!(collect(chunk_size).await, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_truncated_fixture_and_sse() {
+        let mut bytes = std::fs::read(FIXTURE).unwrap();
+        bytes.truncate(bytes.len() - 5);
+        let provider = ReplayProvider::from_bytes(bytes);
+        let items: Vec<_> = provider
+            .stream_completion(request())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert!(matches!(
+            items.last(),
+            Some(Err(KiroError::Parse(ParseError::Incomplete { .. })))
+        ));
+
+        let provider = ReplayProvider::from_bytes("data: {\"content\":\"Hi\"}\n\n")
+            .with_format(ResponseFormat::Sse);
+        let events: Vec<_> = provider
+            .stream_completion(request())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert!(matches!(&events[..], [Ok(Event::AssistantResponse(e))] if e.content == "Hi"));
+    }
+}
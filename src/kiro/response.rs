@@ -0,0 +1,121 @@
+//! Kiro API 响应
+//!
+//! 包装 `reqwest::Response`，在响应体读取完毕前持有并发许可
+
+use std::fmt;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt};
+use reqwest::StatusCode;
+use reqwest::header::HeaderMap;
+use tokio::sync::OwnedSemaphorePermit;
+
+use crate::kiro::observer::RequestObserver;
+
+/// Kiro API 响应
+///
+/// 并发许可（见 `ProviderConfig::max_concurrent`）在响应体读取完毕或响应被丢弃时释放，
+/// 而不是在收到响应头时释放
+pub struct KiroResponse {
+    response: reqwest::Response,
+    permit: Option<OwnedSemaphorePermit>,
+    observer: Option<Arc<dyn RequestObserver>>,
+}
+
+impl fmt::Debug for KiroResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KiroResponse")
+            .field("response", &self.response)
+            .field("permit", &self.permit)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KiroResponse {
+    pub(crate) fn new(response: reqwest::Response, permit: Option<OwnedSemaphorePermit>) -> Self {
+        Self {
+            response,
+            permit,
+            observer: None,
+        }
+    }
+
+    /// 读取响应体时回调观察者
+    pub(crate) fn with_observer(mut self, observer: Option<Arc<dyn RequestObserver>>) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    /// HTTP 状态码
+    #[allow(dead_code)]
+    pub fn status(&self) -> StatusCode {
+        self.response.status()
+    }
+
+    /// 响应头
+    pub fn headers(&self) -> &HeaderMap {
+        self.response.headers()
+    }
+
+    /// 读取完整响应体
+    pub async fn bytes(self) -> reqwest::Result<Bytes> {
+        let bytes = self.response.bytes().await?;
+        if let Some(observer) = &self.observer {
+            observer.on_response_chunk(&bytes);
+        }
+        Ok(bytes)
+    }
+
+    /// 读取完整响应体为字符串
+    #[allow(dead_code)]
+    pub async fn text(self) -> reqwest::Result<String> {
+        match self.observer {
+            None => self.response.text().await,
+            Some(observer) => {
+                let bytes = self.response.bytes().await?;
+                observer.on_response_chunk(&bytes);
+                Ok(String::from_utf8_lossy(&bytes).into_owned())
+            }
+        }
+    }
+
+    /// 以字节流形式读取响应体，流结束时释放并发许可
+    pub fn bytes_stream(self) -> impl Stream<Item = reqwest::Result<Bytes>> + Send + 'static {
+        PermitStream {
+            inner: self.response.bytes_stream().boxed(),
+            permit: self.permit,
+            observer: self.observer,
+        }
+    }
+}
+
+/// 在流结束时释放许可的字节流
+struct PermitStream {
+    inner: BoxStream<'static, reqwest::Result<Bytes>>,
+    permit: Option<OwnedSemaphorePermit>,
+    observer: Option<Arc<dyn RequestObserver>>,
+}
+
+impl Stream for PermitStream {
+    type Item = reqwest::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.inner.poll_next_unpin(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(chunk))) => {
+                if let Some(observer) = &self.observer {
+                    observer.on_response_chunk(chunk);
+                }
+            }
+            Poll::Ready(None) => {
+                self.permit.take();
+            }
+            _ => {}
+        }
+        poll
+    }
+}
@@ -0,0 +1,422 @@
+//! 重试策略
+//!
+//! 定义 Provider 请求的重试条件与退避时间（默认指数退避 + 全抖动，可替换为自定义 `BackoffStrategy`）
+
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use reqwest::header::{DATE, HeaderMap, RETRY_AFTER};
+
+/// 可重试的响应状态分类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusClass {
+    /// 408 Request Timeout
+    RequestTimeout,
+    /// 429 Too Many Requests
+    TooManyRequests,
+    /// 5xx 服务端错误
+    ServerError,
+}
+
+impl StatusClass {
+    /// 判断状态码是否属于该分类
+    pub fn matches(&self, status: StatusCode) -> bool {
+        match self {
+            Self::RequestTimeout => status == StatusCode::REQUEST_TIMEOUT,
+            Self::TooManyRequests => status == StatusCode::TOO_MANY_REQUESTS,
+            Self::ServerError => status.is_server_error(),
+        }
+    }
+}
+
+/// 退避策略：给出第 `attempt` 次重试（从 0 开始）前的等待时间
+///
+/// 每次 API 调用使用策略的独立副本，策略可以在调用内保存状态（如上一次的等待时间）
+pub trait BackoffStrategy: BackoffClone + fmt::Debug + Send + Sync {
+    fn next_delay(&mut self, attempt: u32) -> Duration;
+}
+
+/// 复制装箱的退避策略，实现了 `Clone` 的策略自动获得
+pub trait BackoffClone {
+    fn clone_box(&self) -> Box<dyn BackoffStrategy>;
+}
+
+impl<T> BackoffClone for T
+where
+    T: BackoffStrategy + Clone + 'static,
+{
+    fn clone_box(&self) -> Box<dyn BackoffStrategy> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn BackoffStrategy> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// 指数退避 + 全抖动：等待 `random(0, min(max, base * 2^attempt))`
+#[derive(Debug, Clone)]
+pub struct ExponentialJitter {
+    pub base: Duration,
+    pub max: Duration,
+}
+
+impl ExponentialJitter {
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Self { base, max }
+    }
+}
+
+impl BackoffStrategy for ExponentialJitter {
+    fn next_delay(&mut self, attempt: u32) -> Duration {
+        let exp = self
+            .base
+            .saturating_mul(2u32.saturating_pow(attempt.min(16)));
+        let cap = exp.min(self.max).as_millis() as u64;
+        Duration::from_millis(fastrand::u64(0..=cap))
+    }
+}
+
+/// 去相关抖动：等待 `min(max, random(base, prev * 3))`，`prev` 为上一次的等待时间（初始为 `base`）
+///
+/// 相比全抖动，多个客户端的重试时间分布更分散
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct DecorrelatedJitter {
+    pub base: Duration,
+    pub max: Duration,
+    prev: Duration,
+}
+
+impl DecorrelatedJitter {
+    #[allow(dead_code)]
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max,
+            prev: base,
+        }
+    }
+}
+
+impl BackoffStrategy for DecorrelatedJitter {
+    fn next_delay(&mut self, _attempt: u32) -> Duration {
+        let low = self.base.as_millis() as u64;
+        let high = (self.prev.as_millis() as u64).saturating_mul(3).max(low);
+        let delay = Duration::from_millis(fastrand::u64(low..=high)).min(self.max);
+        self.prev = delay;
+        delay
+    }
+}
+
+/// 重试策略
+///
+/// 默认第 n 次重试前等待 `random(0, min(max_delay, base_delay * 2^n))`，
+/// 可通过 `with_backoff` 替换退避策略；响应带有 `Retry-After` 时以其为准，
+/// 但不超过 `max_retry_after`
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// 最大重试次数（不含首次请求）
+    pub max_retries: usize,
+    /// 退避基准时间
+    pub base_delay: Duration,
+    /// 单次退避上限
+    pub max_delay: Duration,
+    /// `Retry-After` 等待上限，上游要求更久时不再重试，直接返回错误
+    pub max_retry_after: Duration,
+    /// 需要重试的状态分类
+    pub retry_on: Vec<StatusClass>,
+    /// 自定义退避策略；为空时使用基于 `base_delay` / `max_delay` 的 `ExponentialJitter`
+    pub backoff: Option<Box<dyn BackoffStrategy>>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 8,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(2),
+            max_retry_after: Duration::from_secs(30),
+            retry_on: vec![
+                StatusClass::RequestTimeout,
+                StatusClass::TooManyRequests,
+                StatusClass::ServerError,
+            ],
+            backoff: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 状态码是否需要重试
+    pub fn should_retry(&self, status: StatusCode) -> bool {
+        self.retry_on.iter().any(|class| class.matches(status))
+    }
+
+    /// 设置自定义退避策略
+    #[allow(dead_code)]
+    pub fn with_backoff(mut self, backoff: impl BackoffStrategy + 'static) -> Self {
+        self.backoff = Some(Box::new(backoff));
+        self
+    }
+
+    /// 为一次 API 调用创建退避策略的独立副本
+    pub fn backoff(&self) -> Box<dyn BackoffStrategy> {
+        match &self.backoff {
+            Some(backoff) => backoff.clone(),
+            None => Box::new(ExponentialJitter::new(self.base_delay, self.max_delay)),
+        }
+    }
+
+    /// 第 `attempt` 次重试（从 0 开始）前的等待时间，使用新的退避策略副本
+    ///
+    /// `retry_after` 为响应头中解析出的等待时间，存在时直接使用（不超过 `max_retry_after`）
+    #[allow(dead_code)]
+    pub fn delay(&self, attempt: usize, retry_after: Option<Duration>) -> Duration {
+        self.delay_with(self.backoff().as_mut(), attempt, retry_after)
+    }
+
+    /// 使用给定的退避策略计算等待时间，`retry_after` 存在时直接使用（不超过 `max_retry_after`）
+    pub fn delay_with(
+        &self,
+        backoff: &mut dyn BackoffStrategy,
+        attempt: usize,
+        retry_after: Option<Duration>,
+    ) -> Duration {
+        match retry_after {
+            Some(retry_after) => retry_after.min(self.max_retry_after),
+            None => backoff.next_delay(attempt as u32),
+        }
+    }
+
+    /// 上游要求的等待时间是否超过 `max_retry_after`（此时应直接返回错误而不是等待）
+    pub fn retry_after_exceeds_cap(&self, retry_after: Option<Duration>) -> bool {
+        retry_after.is_some_and(|retry_after| retry_after > self.max_retry_after)
+    }
+}
+
+/// 解析 `Retry-After` 响应头，支持秒数与 HTTP-date 两种格式
+///
+/// 无法解析时返回 `None`，调用方回退到退避策略
+pub fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    parse_retry_after_at(headers, SystemTime::now())
+}
+
+/// 以 `received_at` 为响应时间解析 `Retry-After`
+///
+/// HTTP-date 形式相对响应的 `Date` 头计算（避免本地与上游的时钟偏差），
+/// 缺少 `Date` 头时相对 `received_at`；已经过去的时间视为立即重试
+pub fn parse_retry_after_at(headers: &HeaderMap, received_at: SystemTime) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let retry_at = parse_http_date(value)?;
+    let response_time = headers
+        .get(DATE)
+        .and_then(|date| date.to_str().ok())
+        .and_then(parse_http_date)
+        .unwrap_or_else(|| received_at.into());
+    Some(
+        (retry_at - response_time)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// 解析 HTTP-date（如 `Wed, 21 Oct 2015 07:28:00 GMT`）
+fn parse_http_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(value.trim())
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_should_retry() {
+        let policy = RetryPolicy::default();
+        assert!(policy.should_retry(StatusCode::TOO_MANY_REQUESTS));
+        assert!(policy.should_retry(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(policy.should_retry(StatusCode::REQUEST_TIMEOUT));
+        assert!(!policy.should_retry(StatusCode::BAD_REQUEST));
+
+        let policy = RetryPolicy {
+            retry_on: vec![StatusClass::TooManyRequests],
+            ..Default::default()
+        };
+        assert!(!policy.should_retry(StatusCode::SERVICE_UNAVAILABLE));
+    }
+
+    #[test]
+    fn test_delay_is_bounded_full_jitter() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(250),
+            ..Default::default()
+        };
+        for attempt in 0..10 {
+            let cap = Duration::from_millis(100 * 2u64.pow(attempt as u32)).min(policy.max_delay);
+            assert!(policy.delay(attempt, None) <= cap);
+        }
+        assert_eq!(
+            policy.delay(0, Some(Duration::from_secs(3))),
+            Duration::from_secs(3)
+        );
+    }
+
+    #[test]
+    fn test_retry_after_is_capped() {
+        let policy = RetryPolicy {
+            max_retry_after: Duration::from_secs(10),
+            ..Default::default()
+        };
+        let retry_after = Some(Duration::from_secs(86400));
+        assert_eq!(policy.delay(0, retry_after), Duration::from_secs(10));
+        assert!(policy.retry_after_exceeds_cap(retry_after));
+        assert!(!policy.retry_after_exceeds_cap(Some(Duration::from_secs(10))));
+        assert!(!policy.retry_after_exceeds_cap(None));
+    }
+
+    #[test]
+    fn test_exponential_jitter_envelope() {
+        let mut backoff =
+            ExponentialJitter::new(Duration::from_millis(100), Duration::from_secs(1));
+        for attempt in 0..20 {
+            let cap = Duration::from_millis(100u64.saturating_mul(1 << attempt.min(16)))
+                .min(Duration::from_secs(1));
+            for _ in 0..20 {
+                assert!(backoff.next_delay(attempt) <= cap);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decorrelated_jitter_envelope() {
+        let base = Duration::from_millis(100);
+        let max = Duration::from_secs(2);
+        for _ in 0..50 {
+            let mut backoff = DecorrelatedJitter::new(base, max);
+            let mut prev = base;
+            for attempt in 0..20 {
+                let delay = backoff.next_delay(attempt);
+                assert!(delay >= base && delay <= max, "{:?}", delay);
+                assert!(delay <= prev * 3, "{:?} > 3 * {:?}", delay, prev);
+                prev = delay;
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom_backoff_replaces_default() {
+        #[derive(Debug, Clone)]
+        struct Constant(Duration);
+        impl BackoffStrategy for Constant {
+            fn next_delay(&mut self, _attempt: u32) -> Duration {
+                self.0
+            }
+        }
+
+        let policy = RetryPolicy::default().with_backoff(Constant(Duration::from_secs(7)));
+        let mut backoff = policy.clone().backoff();
+        assert_eq!(backoff.next_delay(3), Duration::from_secs(7));
+        assert_eq!(policy.delay(0, None), Duration::from_secs(7));
+        assert_eq!(
+            policy.delay(0, Some(Duration::from_secs(1))),
+            Duration::from_secs(1)
+        );
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(parse_retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("2"));
+        assert_eq!(parse_retry_after(&headers), Some(Duration::from_secs(2)));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("soon"));
+        assert_eq!(parse_retry_after(&headers), None);
+    }
+
+    #[test]
+    fn test_parse_retry_after_http_date() {
+        let received_at =
+            SystemTime::from(DateTime::parse_from_rfc3339("2015-10-21T07:27:30Z").unwrap());
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(
+            parse_retry_after_at(&headers, received_at),
+            Some(Duration::from_secs(30))
+        );
+
+        // 存在 Date 头时以上游时间为准
+        headers.insert(
+            DATE,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:27:50 GMT"),
+        );
+        assert_eq!(
+            parse_retry_after_at(&headers, received_at),
+            Some(Duration::from_secs(10))
+        );
+
+        // 已经过去的时间立即重试
+        headers.insert(
+            DATE,
+            HeaderValue::from_static("Wed, 21 Oct 2015 08:00:00 GMT"),
+        );
+        assert_eq!(
+            parse_retry_after_at(&headers, received_at),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn test_far_future_retry_after_date_is_capped() {
+        let received_at =
+            SystemTime::from(DateTime::parse_from_rfc3339("2015-10-21T07:27:30Z").unwrap());
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Fri, 01 Jan 2100 00:00:00 GMT"),
+        );
+        // Date 头错误地落在很久以前，同样不应导致长时间等待
+        headers.insert(
+            DATE,
+            HeaderValue::from_static("Thu, 01 Jan 1970 00:00:00 GMT"),
+        );
+        let retry_after = parse_retry_after_at(&headers, received_at);
+        assert!(retry_after.unwrap() > Duration::from_secs(365 * 24 * 3600));
+
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(0, retry_after), policy.max_retry_after);
+        assert!(policy.retry_after_exceeds_cap(retry_after));
+    }
+
+    #[test]
+    fn test_malformed_retry_after_falls_back_to_backoff() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(250),
+            ..Default::default()
+        };
+        for value in ["Wed, 32 Oct 2015 07:28:00 GMT", "-5", "1.5", ""] {
+            let mut headers = HeaderMap::new();
+            headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+            let retry_after = parse_retry_after(&headers);
+            assert_eq!(retry_after, None, "{:?}", value);
+            assert!(policy.delay(0, retry_after) <= Duration::from_millis(100));
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! `tower::Service` 封装
+//!
+//! 启用 `tower` feature 后可用，便于把 `KiroProvider` 接入已有的 Tower 中间件
+//! （超时、限流、负载削减、tracing 等）
+
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::future::BoxFuture;
+
+use crate::kiro::completion::CompletionResponse;
+use crate::kiro::error::KiroError;
+use crate::kiro::model::requests::kiro::KiroRequest;
+use crate::kiro::provider::KiroProvider;
+
+/// 以 `tower::Service` 形式暴露的非流式补全
+///
+/// 克隆开销很小，所有克隆共享同一个 `KiroProvider`
+#[derive(Clone)]
+pub struct KiroService {
+    provider: Arc<KiroProvider>,
+}
+
+impl KiroService {
+    pub fn new(provider: Arc<KiroProvider>) -> Self {
+        Self { provider }
+    }
+
+    /// 底层的 Provider
+    pub fn provider(&self) -> &Arc<KiroProvider> {
+        &self.provider
+    }
+}
+
+impl tower::Service<KiroRequest> for KiroService {
+    type Response = CompletionResponse;
+    type Error = KiroError;
+    type Future = BoxFuture<'static, Result<CompletionResponse, KiroError>>;
+
+    /// 并发控制由 `KiroProvider` 自身（`max_concurrent`）或外层中间件负责，这里始终就绪
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), KiroError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: KiroRequest) -> Self::Future {
+        let provider = self.provider.clone();
+        Box::pin(async move { provider.complete(req).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::model::credentials::KiroCredentials;
+    use crate::kiro::model::requests::conversation::{
+        ConversationState, CurrentMessage, UserInputMessage,
+    };
+    use crate::kiro::retry::RetryPolicy;
+    use crate::kiro::token_manager::MultiTokenManager;
+    use crate::model::config::Config;
+    use crate::test_support::{MockResponse, MockServer, encode_event};
+    use tower::{Service, ServiceBuilder, ServiceExt};
+
+    fn request() -> KiroRequest {
+        KiroRequest {
+            conversation_state: ConversationState::new("conv-1").with_current_message(
+                CurrentMessage::new(UserInputMessage::new("Hello", "claude-sonnet-4.5")),
+            ),
+            profile_arn: None,
+            inference_config: None,
+            metadata: Default::default(),
+        }
+    }
+
+    fn service(server: &MockServer) -> KiroService {
+        let credentials = KiroCredentials {
+            access_token: Some("test_token".to_string()),
+            refresh_token: Some("a".repeat(150)),
+            expires_at: Some((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+        let tm = MultiTokenManager::new(Config::default(), vec![credentials], None, None, false)
+            .unwrap();
+        let provider = KiroProvider::new(Arc::new(tm))
+            .with_retry_policy(RetryPolicy {
+                max_retries: 0,
+                ..Default::default()
+            })
+            .with_base_url(server.url("/generateAssistantResponse"));
+        KiroService::new(Arc::new(provider))
+    }
+
+    #[tokio::test]
+    async fn test_service_with_concurrency_limit() {
+        let body = encode_event("assistantResponseEvent", r#"{"content":"Hi"}"#);
+        let server = MockServer::start(vec![
+            MockResponse::new(200).with_body(body.clone()),
+            MockResponse::new(200).with_body(body),
+        ])
+        .await;
+        let inner = service(&server);
+
+        let mut svc = ServiceBuilder::new()
+            .concurrency_limit(1)
+            .service(inner.clone());
+        let response = svc.ready().await.unwrap().call(request()).await.unwrap();
+        assert_eq!(response.text(), "Hi");
+
+        // 克隆共享同一个 Provider
+        let response = inner.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.text(), "Hi");
+        assert!(Arc::ptr_eq(inner.provider(), svc.get_ref().provider()));
+        assert_eq!(server.request_count(), 2);
+    }
+}
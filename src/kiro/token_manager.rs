@@ -5,19 +5,31 @@
 
 use anyhow::bail;
 use chrono::{DateTime, Duration, Utc};
-use parking_lot::Mutex;
+use futures::future::BoxFuture;
+use parking_lot::{Mutex, RwLock};
 use serde::Serialize;
 use tokio::sync::Mutex as TokioMutex;
+use tracing::Instrument;
 
+use std::collections::HashMap;
+use std::fmt;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::time::Instant;
 
 use crate::http_client::{ProxyConfig, build_client};
+use crate::kiro::clock::{Clock, SystemClock};
+use crate::kiro::error::KiroError;
 use crate::kiro::machine_id;
-use crate::kiro::model::credentials::KiroCredentials;
+use crate::kiro::model::credentials::{AuthMethod, KiroCredentials, Redacted};
 use crate::kiro::model::token_refresh::{
     IdcRefreshRequest, IdcRefreshResponse, RefreshRequest, RefreshResponse,
 };
 use crate::kiro::model::usage_limits::UsageLimitsResponse;
+use crate::kiro::observer::{RefreshObserver, RefreshOutcome};
+use crate::kiro::token_store::{StoredToken, TokenStore};
+use crate::kiro::trace;
 use crate::model::config::Config;
 
 /// Token 管理器
@@ -29,6 +41,14 @@ pub struct TokenManager {
     proxy: Option<ProxyConfig>,
 }
 
+impl fmt::Debug for TokenManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenManager")
+            .field("credentials", &self.credentials)
+            .finish_non_exhaustive()
+    }
+}
+
 impl TokenManager {
     /// 创建新的 TokenManager 实例
     pub fn new(config: Config, credentials: KiroCredentials, proxy: Option<ProxyConfig>) -> Self {
@@ -54,8 +74,14 @@ impl TokenManager {
     /// 如果 Token 过期或即将过期，会自动刷新
     pub async fn ensure_valid_token(&mut self) -> anyhow::Result<String> {
         if is_token_expired(&self.credentials) || is_token_expiring_soon(&self.credentials) {
-            self.credentials =
-                refresh_token(&self.credentials, &self.config, self.proxy.as_ref()).await?;
+            self.credentials = refresh_token(
+                &self.credentials,
+                &self.config,
+                &TokenManagerConfig::default(),
+                self.proxy.as_ref(),
+                Utc::now(),
+            )
+            .await?;
 
             // 刷新后再次检查 token 时间有效性
             if is_token_expired(&self.credentials) {
@@ -79,25 +105,52 @@ impl TokenManager {
 }
 
 /// 检查 Token 是否在指定时间内过期
+#[allow(dead_code)]
 pub(crate) fn is_token_expiring_within(
     credentials: &KiroCredentials,
     minutes: i64,
+) -> Option<bool> {
+    is_token_expiring_at(credentials, Duration::minutes(minutes), Utc::now())
+}
+
+/// 以 `now` 为当前时间，检查 Token 是否在指定时长内过期
+pub(crate) fn is_token_expiring_at(
+    credentials: &KiroCredentials,
+    skew: Duration,
+    now: DateTime<Utc>,
 ) -> Option<bool> {
     credentials
         .expires_at
         .as_ref()
         .and_then(|expires_at| DateTime::parse_from_rfc3339(expires_at).ok())
-        .map(|expires| expires <= Utc::now() + Duration::minutes(minutes))
+        .map(|expires| expires <= now + skew)
 }
 
 /// 检查 Token 是否已过期（提前 5 分钟判断）
+#[allow(dead_code)]
 pub(crate) fn is_token_expired(credentials: &KiroCredentials) -> bool {
-    is_token_expiring_within(credentials, 5).unwrap_or(true)
+    is_token_expired_at(credentials, Utc::now())
 }
 
 /// 检查 Token 是否即将过期（10分钟内）
+#[allow(dead_code)]
 pub(crate) fn is_token_expiring_soon(credentials: &KiroCredentials) -> bool {
-    is_token_expiring_within(credentials, 10).unwrap_or(false)
+    is_token_expiring_soon_at(credentials, Utc::now())
+}
+
+/// 以 `now` 为当前时间，检查 Token 是否已过期（提前 5 分钟判断）
+pub(crate) fn is_token_expired_at(credentials: &KiroCredentials, now: DateTime<Utc>) -> bool {
+    is_token_expiring_at(credentials, Duration::minutes(5), now).unwrap_or(true)
+}
+
+/// 以 `now` 为当前时间，检查 Token 是否即将过期（10分钟内）
+pub(crate) fn is_token_expiring_soon_at(credentials: &KiroCredentials, now: DateTime<Utc>) -> bool {
+    is_token_expiring_at(credentials, Duration::minutes(10), now).unwrap_or(false)
+}
+
+/// 以 `now` 为当前时间，检查 Token 是否需要刷新
+fn needs_refresh_at(credentials: &KiroCredentials, now: DateTime<Utc>) -> bool {
+    is_token_expired_at(credentials, now) || is_token_expiring_soon_at(credentials, now)
 }
 
 /// 验证 refreshToken 的基本有效性
@@ -123,36 +176,160 @@ pub(crate) fn validate_refresh_token(credentials: &KiroCredentials) -> anyhow::R
     Ok(())
 }
 
+/// refreshToken 已被上游撤销（响应中包含 `invalid_grant` / `InvalidGrantException`）
+///
+/// 作为 `anyhow::Error` 的底层错误传递，由调用方识别后转换为 `KiroError::RefreshTokenRevoked`
+#[derive(Debug)]
+pub(crate) struct RefreshTokenRevoked(String);
+
+impl std::fmt::Display for RefreshTokenRevoked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for RefreshTokenRevoked {}
+
+/// 刷新响应是否表示 refreshToken 已被撤销
+fn is_invalid_grant(body: &str) -> bool {
+    body.contains("invalid_grant") || body.contains("InvalidGrantException")
+}
+
+/// 将刷新失败转换为 `KiroError`，refreshToken 被撤销时返回 `RefreshTokenRevoked`
+fn refresh_error(credential_id: u64, e: anyhow::Error) -> KiroError {
+    match e.downcast_ref::<RefreshTokenRevoked>() {
+        Some(revoked) => KiroError::RefreshTokenRevoked {
+            credential_id: Some(credential_id),
+            message: revoked.0.clone(),
+        },
+        None => KiroError::auth_from(e),
+    }
+}
+
+/// Token 刷新请求的默认超时
+pub const DEFAULT_REFRESH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// Token 刷新配置
+///
+/// 未设置的项使用内置默认值：刷新端点按认证方式与区域生成，
+/// IdC 的 clientId / clientSecret 取自凭据
+#[derive(Debug, Clone)]
+pub struct TokenManagerConfig {
+    /// 刷新端点（Social 为 refreshToken 接口，IdC 为 OIDC CreateToken 接口）
+    pub refresh_endpoint: Option<String>,
+    /// IdC 刷新使用的 clientId，设置后优先于凭据中的值
+    pub client_id: Option<String>,
+    /// IdC 刷新使用的 clientSecret，设置后优先于凭据中的值
+    pub client_secret: Option<String>,
+    /// 单次刷新（含自定义刷新回调）的超时，超时后释放刷新锁并返回认证错误
+    pub refresh_timeout: std::time::Duration,
+}
+
+impl Default for TokenManagerConfig {
+    fn default() -> Self {
+        Self {
+            refresh_endpoint: None,
+            client_id: None,
+            client_secret: None,
+            refresh_timeout: DEFAULT_REFRESH_TIMEOUT,
+        }
+    }
+}
+
+impl TokenManagerConfig {
+    #[allow(dead_code)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[allow(dead_code)]
+    pub fn with_refresh_endpoint(mut self, url: impl Into<String>) -> Self {
+        self.refresh_endpoint = Some(url.into());
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_client_id(mut self, client_id: impl Into<String>) -> Self {
+        self.client_id = Some(client_id.into());
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_client_secret(mut self, client_secret: impl Into<String>) -> Self {
+        self.client_secret = Some(client_secret.into());
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_refresh_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.refresh_timeout = timeout;
+        self
+    }
+
+    /// Social Token 刷新地址
+    fn social_refresh_url(&self, region: &str) -> String {
+        self.refresh_endpoint.clone().unwrap_or_else(|| {
+            format!("https://prod.{}.auth.desktop.kiro.dev/refreshToken", region)
+        })
+    }
+
+    /// IdC Token 刷新地址
+    fn idc_refresh_url(&self, region: &str) -> String {
+        self.refresh_endpoint
+            .clone()
+            .unwrap_or_else(|| format!("https://oidc.{}.amazonaws.com/token", region))
+    }
+}
+
+/// 请求 URL 对应的 Host 请求头
+fn host_header(url: &str) -> anyhow::Result<String> {
+    let url = reqwest::Url::parse(url)?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow::anyhow!("刷新地址缺少主机名: {}", url))?;
+    Ok(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
+
 /// 刷新 Token
+///
+/// 新 Token 的过期时间以 `now` 为起点计算，调用方应传入与过期判断相同的时钟
 pub(crate) async fn refresh_token(
     credentials: &KiroCredentials,
     config: &Config,
+    token_config: &TokenManagerConfig,
     proxy: Option<&ProxyConfig>,
+    now: DateTime<Utc>,
 ) -> anyhow::Result<KiroCredentials> {
     validate_refresh_token(credentials)?;
 
     // 根据 auth_method 选择刷新方式
-    let auth_method = credentials.auth_method.as_deref().unwrap_or("social");
-
-    match auth_method.to_lowercase().as_str() {
-        "idc" | "builder-id" => refresh_idc_token(credentials, config, proxy).await,
-        _ => refresh_social_token(credentials, config, proxy).await,
+    match credentials.auth_method_kind() {
+        AuthMethod::Idc => {
+            let refresh_url = token_config.idc_refresh_url(&config.region);
+            refresh_idc_token_at(&refresh_url, credentials, token_config, proxy, now).await
+        }
+        AuthMethod::Social => {
+            let refresh_url = token_config.social_refresh_url(&config.region);
+            refresh_social_token_at(&refresh_url, credentials, config, proxy, now).await
+        }
     }
 }
 
-/// 刷新 Social Token
-async fn refresh_social_token(
+/// 向指定的 refreshToken 端点刷新 Social Token
+async fn refresh_social_token_at(
+    refresh_url: &str,
     credentials: &KiroCredentials,
     config: &Config,
     proxy: Option<&ProxyConfig>,
+    now: DateTime<Utc>,
 ) -> anyhow::Result<KiroCredentials> {
     tracing::info!("正在刷新 Social Token...");
 
     let refresh_token = credentials.refresh_token.as_ref().unwrap();
-    let region = &config.region;
-
-    let refresh_url = format!("https://prod.{}.auth.desktop.kiro.dev/refreshToken", region);
-    let refresh_domain = format!("prod.{}.auth.desktop.kiro.dev", region);
+    let refresh_domain = host_header(refresh_url)?;
     let machine_id = machine_id::generate_from_credentials(credentials, config)
         .ok_or_else(|| anyhow::anyhow!("无法生成 machineId"))?;
     let kiro_version = &config.kiro_version;
@@ -163,7 +340,7 @@ async fn refresh_social_token(
     };
 
     let response = client
-        .post(&refresh_url)
+        .post(refresh_url)
         .header("Accept", "application/json, text/plain, */*")
         .header("Content-Type", "application/json")
         .header(
@@ -187,7 +364,11 @@ async fn refresh_social_token(
             500..=599 => "服务器错误，AWS OAuth 服务暂时不可用",
             _ => "Token 刷新失败",
         };
-        bail!("{}: {} {}", error_msg, status, body_text);
+        let message = format!("{}: {} {}", error_msg, status, body_text);
+        if is_invalid_grant(&body_text) {
+            return Err(RefreshTokenRevoked(message).into());
+        }
+        bail!("{}", message);
     }
 
     let data: RefreshResponse = response.json().await?;
@@ -204,7 +385,7 @@ async fn refresh_social_token(
     }
 
     if let Some(expires_in) = data.expires_in {
-        let expires_at = Utc::now() + Duration::seconds(expires_in);
+        let expires_at = now + Duration::seconds(expires_in);
         new_credentials.expires_at = Some(expires_at.to_rfc3339());
     }
 
@@ -214,27 +395,31 @@ async fn refresh_social_token(
 /// IdC Token 刷新所需的 x-amz-user-agent header
 const IDC_AMZ_USER_AGENT: &str = "aws-sdk-js/3.738.0 ua/2.1 os/other lang/js md/browser#unknown_unknown api/sso-oidc#3.738.0 m/E KiroIDE";
 
-/// 刷新 IdC Token (AWS SSO OIDC)
-async fn refresh_idc_token(
+/// 向指定的 OIDC CreateToken 端点刷新 IdC Token (AWS SSO OIDC)
+async fn refresh_idc_token_at(
+    refresh_url: &str,
     credentials: &KiroCredentials,
-    config: &Config,
+    token_config: &TokenManagerConfig,
     proxy: Option<&ProxyConfig>,
+    now: DateTime<Utc>,
 ) -> anyhow::Result<KiroCredentials> {
     tracing::info!("正在刷新 IdC Token...");
 
-    let refresh_token = credentials.refresh_token.as_ref().unwrap();
-    let client_id = credentials
+    let refresh_token = credentials
+        .refresh_token
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("缺少 refreshToken"))?;
+    let client_id = token_config
         .client_id
         .as_ref()
+        .or(credentials.client_id.as_ref())
         .ok_or_else(|| anyhow::anyhow!("IdC 刷新需要 clientId"))?;
-    let client_secret = credentials
+    let client_secret = token_config
         .client_secret
         .as_ref()
+        .or(credentials.client_secret.as_ref())
         .ok_or_else(|| anyhow::anyhow!("IdC 刷新需要 clientSecret"))?;
 
-    let region = &config.region;
-    let refresh_url = format!("https://oidc.{}.amazonaws.com/token", region);
-
     let client = build_client(proxy, 60)?;
     let body = IdcRefreshRequest {
         client_id: client_id.to_string(),
@@ -244,9 +429,9 @@ async fn refresh_idc_token(
     };
 
     let response = client
-        .post(&refresh_url)
+        .post(refresh_url)
         .header("Content-Type", "application/json")
-        .header("Host", format!("oidc.{}.amazonaws.com", region))
+        .header("Host", host_header(refresh_url)?)
         .header("Connection", "keep-alive")
         .header("x-amz-user-agent", IDC_AMZ_USER_AGENT)
         .header("Accept", "*/*")
@@ -268,7 +453,11 @@ async fn refresh_idc_token(
             500..=599 => "服务器错误，AWS OIDC 服务暂时不可用",
             _ => "IdC Token 刷新失败",
         };
-        bail!("{}: {} {}", error_msg, status, body_text);
+        let message = format!("{}: {} {}", error_msg, status, body_text);
+        if is_invalid_grant(&body_text) {
+            return Err(RefreshTokenRevoked(message).into());
+        }
+        bail!("{}", message);
     }
 
     let data: IdcRefreshResponse = response.json().await?;
@@ -281,7 +470,7 @@ async fn refresh_idc_token(
     }
 
     if let Some(expires_in) = data.expires_in {
-        let expires_at = Utc::now() + Duration::seconds(expires_in);
+        let expires_at = now + Duration::seconds(expires_in);
         new_credentials.expires_at = Some(expires_at.to_rfc3339());
     }
 
@@ -384,6 +573,8 @@ enum DisabledReason {
     Manual,
     /// 连续失败达到阈值后自动禁用
     TooManyFailures,
+    /// refreshToken 已被上游撤销，需要重新登录
+    RefreshTokenRevoked,
 }
 
 // ============================================================================
@@ -424,6 +615,119 @@ pub struct ManagerSnapshot {
     pub available: usize,
 }
 
+/// 凭据健康状态
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AccountHealth {
+    /// 正常可用
+    Healthy,
+    /// 可用，但最近有 API 调用失败
+    Degraded,
+    /// 已禁用（手动或连续失败）
+    Disabled,
+    /// 在 `TokenPool` 中冷却，暂时移出轮询
+    CoolingDown,
+}
+
+/// 单个账号的非敏感摘要，不包含任何 Token 内容
+#[allow(dead_code)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountSummary {
+    /// 凭据 ID
+    pub id: u64,
+    /// 账号标签（`#<ID>`）
+    pub label: String,
+    /// 认证方式（`social` / `idc`）
+    pub auth_method: &'static str,
+    /// Token 过期时间（RFC3339）
+    pub expires_at: Option<String>,
+    /// Token 是否已过期（提前 5 分钟判断）
+    pub expired: bool,
+    /// 是否有 refreshToken
+    pub has_refresh_token: bool,
+    /// 连续失败次数
+    pub failure_count: u32,
+    /// 健康状态
+    pub health: AccountHealth,
+}
+
+/// 所有账号的非敏感摘要
+#[allow(dead_code)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenSummary {
+    /// 各账号摘要（按 ID 升序）
+    pub accounts: Vec<AccountSummary>,
+    /// 当前活跃凭据 ID
+    pub current_id: u64,
+}
+
+/// 账号标签（`#<ID>`），管理命令与刷新观察者共用
+pub(crate) fn account_label(id: u64) -> String {
+    format!("#{}", id)
+}
+
+/// 单个账号的预热结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WarmUpOutcome {
+    /// 凭据 ID
+    pub id: u64,
+    /// 刷新失败的原因，成功时为 `None`
+    pub error: Option<String>,
+    /// refreshToken 是否已被撤销（对应凭据已被禁用）
+    pub revoked: bool,
+}
+
+impl WarmUpOutcome {
+    #[allow(dead_code)]
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// 启动预热结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WarmUpReport {
+    /// 各账号结果（按 ID 升序）
+    pub accounts: Vec<WarmUpOutcome>,
+}
+
+impl WarmUpReport {
+    /// 刷新成功的账号数
+    #[allow(dead_code)]
+    pub fn succeeded(&self) -> usize {
+        self.accounts.iter().filter(|a| a.is_ok()).count()
+    }
+
+    /// 刷新失败的账号数
+    #[allow(dead_code)]
+    pub fn failed(&self) -> usize {
+        self.accounts.len() - self.succeeded()
+    }
+
+    /// 是否有部分账号失败
+    #[allow(dead_code)]
+    pub fn is_partial(&self) -> bool {
+        self.failed() > 0
+    }
+}
+
+/// 列出失败账号及原因，如 `#1: 刷新失败; #2: invalid_grant`
+impl fmt::Display for WarmUpReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failures: Vec<String> = self
+            .accounts
+            .iter()
+            .filter_map(|a| a.error.as_ref().map(|e| format!("#{}: {}", a.id, e)))
+            .collect();
+        write!(f, "{}", failures.join("; "))
+    }
+}
+
 /// 多凭据 Token 管理器
 ///
 /// 支持多个凭据的管理，实现固定优先级 + 故障转移策略
@@ -437,15 +741,40 @@ pub struct MultiTokenManager {
     current_id: Mutex<u64>,
     /// Token 刷新锁，确保同一时间只有一个刷新操作
     refresh_lock: TokioMutex<()>,
+    /// 已完成的刷新次数（用于判断等待期间是否有刷新完成）
+    refresh_seq: AtomicU64,
+    /// 最近一次刷新失败：(凭据 ID, 刷新序号, 错误信息, refreshToken 是否已被撤销)
+    last_refresh_error: Mutex<Option<(u64, u64, String, bool)>>,
     /// 凭据文件路径（用于回写）
     credentials_path: Option<PathBuf>,
     /// 是否为多凭据格式（数组格式才回写）
     is_multiple_format: bool,
+    /// 自定义刷新回调（未设置时请求上游刷新接口）
+    refresher: Option<RefreshFn>,
+    /// 凭据 ID -> Token 存储后端（刷新后回写）
+    stores: Mutex<HashMap<u64, Arc<dyn TokenStore>>>,
+    /// 过期判断使用的时钟
+    clock: Arc<dyn Clock>,
+    /// Token 刷新端点与 IdC 客户端配置
+    token_config: TokenManagerConfig,
+    /// Token 刷新事件观察者
+    refresh_observer: Option<Arc<dyn RefreshObserver>>,
 }
 
 /// 每个凭据最大 API 调用失败次数
 const MAX_FAILURES_PER_CREDENTIAL: u32 = 3;
 
+/// `ensure_fresh` 默认的提前刷新时间
+#[allow(dead_code)]
+pub const DEFAULT_REFRESH_SKEW: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Token 刷新回调
+///
+/// 接收待刷新的凭据，返回刷新后的凭据；默认使用 `refresh_token` 请求上游
+pub type RefreshFn = Arc<
+    dyn Fn(KiroCredentials) -> BoxFuture<'static, anyhow::Result<KiroCredentials>> + Send + Sync,
+>;
+
 /// API 调用上下文
 ///
 /// 绑定特定凭据的调用上下文，确保 token、credentials 和 id 的一致性
@@ -460,6 +789,16 @@ pub struct CallContext {
     pub token: String,
 }
 
+impl fmt::Debug for CallContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallContext")
+            .field("id", &self.id)
+            .field("credentials", &self.credentials)
+            .field("token", &Redacted(&self.token))
+            .finish()
+    }
+}
+
 impl MultiTokenManager {
     /// 创建多凭据 Token 管理器
     ///
@@ -526,8 +865,15 @@ impl MultiTokenManager {
             entries: Mutex::new(entries),
             current_id: Mutex::new(initial_id),
             refresh_lock: TokioMutex::new(()),
+            refresh_seq: AtomicU64::new(0),
+            last_refresh_error: Mutex::new(None),
             credentials_path,
             is_multiple_format,
+            refresher: None,
+            stores: Mutex::new(HashMap::new()),
+            clock: Arc::new(SystemClock),
+            token_config: TokenManagerConfig::default(),
+            refresh_observer: None,
         };
 
         // 如果有新分配的 ID，立即持久化到配置文件
@@ -542,12 +888,78 @@ impl MultiTokenManager {
         Ok(manager)
     }
 
+    /// 设置自定义 Token 刷新回调
+    #[allow(dead_code)]
+    pub fn with_refresher(mut self, refresher: RefreshFn) -> Self {
+        self.refresher = Some(refresher);
+        self
+    }
+
+    /// 设置 Token 刷新事件观察者
+    #[allow(dead_code)]
+    pub fn with_refresh_observer(mut self, observer: Arc<dyn RefreshObserver>) -> Self {
+        self.refresh_observer = Some(observer);
+        self
+    }
+
+    /// 设置 Token 刷新端点与 IdC 客户端信息（默认使用内置值）
+    #[allow(dead_code)]
+    pub fn with_token_config(mut self, token_config: TokenManagerConfig) -> Self {
+        self.token_config = token_config;
+        self
+    }
+
+    /// 设置过期判断使用的时钟（默认系统时钟）
+    #[allow(dead_code)]
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// 时钟给出的当前时间
+    fn now(&self) -> DateTime<Utc> {
+        self.clock.now().into()
+    }
+
+    /// 为指定凭据挂载 Token 存储后端
+    ///
+    /// 存储中已有 Token 时会覆盖内存中的 Token；之后每次刷新都会回写到该存储
+    #[allow(dead_code)]
+    pub fn attach_store(&self, id: u64, store: Box<dyn TokenStore>) -> anyhow::Result<()> {
+        let stored = store.load()?;
+        {
+            let mut entries = self.entries.lock();
+            let entry = entries
+                .iter_mut()
+                .find(|e| e.id == id)
+                .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?;
+            if let Some(stored) = stored {
+                stored.apply_to(&mut entry.credentials);
+            }
+        }
+        self.stores.lock().insert(id, Arc::from(store));
+        Ok(())
+    }
+
+    /// 将刷新后的 Token 回写到挂载的存储后端，失败只记录警告
+    ///
+    /// 写入（可能是阻塞的文件 IO）在释放 `stores` 锁之后进行
+    fn save_to_store(&self, id: u64, credentials: &KiroCredentials) {
+        let store = self.stores.lock().get(&id).cloned();
+        if let Some(store) = store
+            && let Err(e) = store.save(&StoredToken::from_credentials(credentials))
+        {
+            tracing::warn!("凭据 #{} Token 写入存储失败: {}", id, e);
+        }
+    }
+
     /// 获取配置的引用
     pub fn config(&self) -> &Config {
         &self.config
     }
 
     /// 获取当前活动凭据的克隆
+    #[allow(dead_code)]
     pub fn credentials(&self) -> KiroCredentials {
         let entries = self.entries.lock();
         let current_id = *self.current_id.lock();
@@ -558,6 +970,26 @@ impl MultiTokenManager {
             .unwrap_or_default()
     }
 
+    /// 当前凭据缓存的 Token 过期时间（不会触发刷新）
+    #[allow(dead_code)]
+    pub fn expires_at(&self) -> Option<std::time::SystemTime> {
+        let expires_at = self.credentials().expires_at?;
+        DateTime::parse_from_rfc3339(&expires_at)
+            .ok()
+            .map(std::time::SystemTime::from)
+    }
+
+    /// 当前凭据缓存的 Token 剩余有效时长（已过期时为 0，不会触发刷新）
+    #[allow(dead_code)]
+    pub fn time_to_expiry(&self) -> Option<std::time::Duration> {
+        let expires_at = self.expires_at()?;
+        Some(
+            expires_at
+                .duration_since(self.clock.now())
+                .unwrap_or_default(),
+        )
+    }
+
     /// 获取凭据总数
     pub fn total_count(&self) -> usize {
         self.entries.lock().len()
@@ -575,17 +1007,21 @@ impl MultiTokenManager {
     ///
     /// 如果 Token 过期或即将过期，会自动刷新
     /// Token 刷新失败时会尝试下一个可用凭据（不计入失败次数）
-    pub async fn acquire_context(&self) -> anyhow::Result<CallContext> {
+    pub async fn acquire_context(&self) -> Result<CallContext, KiroError> {
         let total = self.total_count();
         let mut tried_count = 0;
+        let mut last_error = None;
 
         loop {
             if tried_count >= total {
-                anyhow::bail!(
-                    "所有凭据均无法获取有效 Token（可用: {}/{}）",
-                    self.available_count(),
-                    total
-                );
+                return Err(KiroError::Auth {
+                    message: format!(
+                        "所有凭据均无法获取有效 Token（可用: {}/{}）",
+                        self.available_count(),
+                        total
+                    ),
+                    source: last_error,
+                });
             }
 
             let (id, credentials) = {
@@ -634,11 +1070,14 @@ impl MultiTokenManager {
                         *current_id = new_id;
                         (new_id, new_creds)
                     } else {
-                        // 注意：必须在 bail! 之前计算 available_count，
+                        // 注意：必须在返回错误之前计算 available_count，
                         // 因为 available_count() 会尝试获取 entries 锁，
                         // 而此时我们已经持有该锁，会导致死锁
                         let available = entries.iter().filter(|e| !e.disabled).count();
-                        anyhow::bail!("所有凭据均已禁用（{}/{}）", available, total);
+                        return Err(KiroError::auth(format!(
+                            "所有凭据均已禁用（{}/{}）",
+                            available, total
+                        )));
                     }
                 }
             };
@@ -648,17 +1087,50 @@ impl MultiTokenManager {
                 Ok(ctx) => {
                     return Ok(ctx);
                 }
+                Err(e) if e.is::<RefreshTokenRevoked>() => {
+                    // refreshToken 被撤销后刷新不可能成功，禁用该凭据
+                    self.mark_refresh_token_revoked(id);
+                    if self.available_count() == 0 {
+                        return Err(refresh_error(id, e));
+                    }
+                    tried_count += 1;
+                    last_error = Some(e);
+                }
                 Err(e) => {
                     tracing::warn!("凭据 #{} Token 刷新失败，尝试下一个凭据: {}", id, e);
 
                     // Token 刷新失败，切换到下一个优先级的凭据（不计入失败次数）
                     self.switch_to_next_by_priority();
                     tried_count += 1;
+                    last_error = Some(e);
                 }
             }
         }
     }
 
+    /// 禁用 refreshToken 已被撤销的凭据，并切换到优先级最高的可用凭据（内部方法）
+    fn mark_refresh_token_revoked(&self, id: u64) {
+        let mut entries = self.entries.lock();
+        if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
+            entry.disabled = true;
+            entry.disabled_reason = Some(DisabledReason::RefreshTokenRevoked);
+            tracing::error!(
+                "凭据 #{} 的 refreshToken 已被撤销，已禁用，需要重新登录",
+                id
+            );
+        }
+
+        let mut current_id = self.current_id.lock();
+        if *current_id == id
+            && let Some(next) = entries
+                .iter()
+                .filter(|e| !e.disabled)
+                .min_by_key(|e| e.credentials.priority)
+        {
+            *current_id = next.id;
+        }
+    }
+
     /// 切换到下一个优先级最高的可用凭据（内部方法）
     fn switch_to_next_by_priority(&self) {
         let entries = self.entries.lock();
@@ -716,14 +1188,41 @@ impl MultiTokenManager {
         &self,
         id: u64,
         credentials: &KiroCredentials,
+    ) -> anyhow::Result<CallContext> {
+        self.try_ensure_token_with(id, credentials, |c| needs_refresh_at(c, self.now()))
+            .await
+    }
+
+    /// 尝试使用指定凭据获取有效 Token，由 `needs_refresh` 判断是否需要刷新
+    ///
+    /// 单飞（single-flight）语义：并发调用只会触发一次刷新，
+    /// 等待中的调用方直接复用该次刷新的结果（包括失败）
+    async fn try_ensure_token_with(
+        &self,
+        id: u64,
+        credentials: &KiroCredentials,
+        needs_refresh: impl Fn(&KiroCredentials) -> bool,
     ) -> anyhow::Result<CallContext> {
         // 第一次检查（无锁）：快速判断是否需要刷新
-        let needs_refresh = is_token_expired(credentials) || is_token_expiring_soon(credentials);
+        let needs_refresh_now = needs_refresh(credentials);
 
-        let creds = if needs_refresh {
+        let creds = if needs_refresh_now {
             // 获取刷新锁，确保同一时间只有一个刷新操作
+            let observed_seq = self.refresh_seq.load(Ordering::SeqCst);
             let _guard = self.refresh_lock.lock().await;
 
+            // 等待期间同一凭据的刷新已失败，直接返回该错误，避免重复请求上游
+            if let Some((failed_id, failed_seq, message, revoked)) =
+                &*self.last_refresh_error.lock()
+                && *failed_id == id
+                && *failed_seq > observed_seq
+            {
+                if *revoked {
+                    return Err(RefreshTokenRevoked(message.clone()).into());
+                }
+                bail!("{}", message);
+            }
+
             // 第二次检查：获取锁后重新读取凭据，因为其他请求可能已经完成刷新
             let current_creds = {
                 let entries = self.entries.lock();
@@ -734,12 +1233,23 @@ impl MultiTokenManager {
                     .ok_or_else(|| anyhow::anyhow!("凭据 #{} 不存在", id))?
             };
 
-            if is_token_expired(&current_creds) || is_token_expiring_soon(&current_creds) {
+            if needs_refresh(&current_creds) {
                 // 确实需要刷新
-                let new_creds =
-                    refresh_token(&current_creds, &self.config, self.proxy.as_ref()).await?;
+                let result = self.refresh(id, &current_creds).await;
+                let seq = self.refresh_seq.fetch_add(1, Ordering::SeqCst) + 1;
+                let new_creds = match result {
+                    Ok(new_creds) => {
+                        *self.last_refresh_error.lock() = None;
+                        new_creds
+                    }
+                    Err(e) => {
+                        let revoked = e.is::<RefreshTokenRevoked>();
+                        *self.last_refresh_error.lock() = Some((id, seq, e.to_string(), revoked));
+                        return Err(e);
+                    }
+                };
 
-                if is_token_expired(&new_creds) {
+                if is_token_expired_at(&new_creds, self.now()) {
                     anyhow::bail!("刷新后的 Token 仍然无效或已过期");
                 }
 
@@ -755,6 +1265,7 @@ impl MultiTokenManager {
                 if let Err(e) = self.persist_credentials() {
                     tracing::warn!("Token 刷新后持久化失败（不影响本次请求）: {}", e);
                 }
+                self.save_to_store(id, &new_creds);
 
                 new_creds
             } else {
@@ -778,6 +1289,139 @@ impl MultiTokenManager {
         })
     }
 
+    /// 刷新凭据：优先使用自定义回调，否则请求上游刷新接口
+    ///
+    /// 超过 `refresh_timeout` 时放弃本次刷新，按普通刷新失败处理
+    /// 开始与结束时通知刷新观察者（如已设置）
+    async fn refresh(
+        &self,
+        id: u64,
+        credentials: &KiroCredentials,
+    ) -> anyhow::Result<KiroCredentials> {
+        let span = trace::refresh_span(credentials.id, credentials.auth_method_kind().as_str());
+        let refresh = async {
+            match &self.refresher {
+                Some(refresher) => refresher(credentials.clone()).await,
+                None => {
+                    refresh_token(
+                        credentials,
+                        &self.config,
+                        &self.token_config,
+                        self.proxy.as_ref(),
+                        self.now(),
+                    )
+                    .await
+                }
+            }
+        };
+        let account = account_label(id);
+        if let Some(observer) = &self.refresh_observer {
+            observer.on_refresh_start(&account);
+        }
+
+        let timeout = self.token_config.refresh_timeout;
+        let result = match tokio::time::timeout(timeout, refresh.instrument(span)).await {
+            Ok(result) => result,
+            Err(_) => Err(anyhow::anyhow!("Token 刷新超时（{:?}）", timeout)),
+        };
+
+        if let Some(observer) = &self.refresh_observer {
+            let outcome = match &result {
+                Ok(_) => RefreshOutcome::Success,
+                Err(e) => RefreshOutcome::Failed {
+                    reason: e.to_string(),
+                    revoked: e.is::<RefreshTokenRevoked>(),
+                },
+            };
+            observer.on_refresh_finish(&account, &outcome);
+        }
+        result
+    }
+
+    /// 确保当前凭据的访问 Token 在 `skew` 时长内不会过期
+    ///
+    /// 距离过期不足 `skew` 时主动刷新，避免请求因 Token 过期而失败；
+    /// 返回的 Token 总是可直接使用的
+    #[allow(dead_code)]
+    pub async fn ensure_fresh(&self, skew: std::time::Duration) -> Result<String, KiroError> {
+        let (id, credentials) = {
+            let entries = self.entries.lock();
+            let current_id = *self.current_id.lock();
+            entries
+                .iter()
+                .find(|e| e.id == current_id && !e.disabled)
+                .map(|e| (e.id, e.credentials.clone()))
+                .ok_or_else(|| KiroError::auth("没有可用的凭据"))?
+        };
+
+        let skew = Duration::from_std(skew).unwrap_or(Duration::MAX);
+        let ctx = self
+            .try_ensure_token_with(id, &credentials, |c| {
+                is_token_expiring_at(c, skew, self.now()).unwrap_or(true)
+            })
+            .await
+            .map_err(|e| {
+                if e.is::<RefreshTokenRevoked>() {
+                    self.mark_refresh_token_revoked(id);
+                }
+                refresh_error(id, e)
+            })?;
+        Ok(ctx.token)
+    }
+
+    /// 启动预热：依次为每个可用凭据强制刷新一次 Token
+    ///
+    /// 至少一个账号成功时返回各账号结果（`is_partial` 表示部分失败）；
+    /// 全部失败时返回携带各账号结果的 `KiroError::WarmUpFailed`，没有可用凭据时返回
+    /// `KiroError::Auth`，便于启动时快速失败。
+    /// refreshToken 已被撤销的凭据会被禁用
+    #[allow(dead_code)]
+    pub async fn warm_up(&self) -> Result<WarmUpReport, KiroError> {
+        let candidates: Vec<(u64, KiroCredentials)> = {
+            let entries = self.entries.lock();
+            let mut candidates: Vec<_> = entries
+                .iter()
+                .filter(|e| !e.disabled)
+                .map(|e| (e.id, e.credentials.clone()))
+                .collect();
+            candidates.sort_by_key(|(id, _)| *id);
+            candidates
+        };
+        if candidates.is_empty() {
+            return Err(KiroError::auth("没有可用的凭据"));
+        }
+
+        let mut accounts = Vec::with_capacity(candidates.len());
+        for (id, credentials) in candidates {
+            let outcome = match self.try_ensure_token_with(id, &credentials, |_| true).await {
+                Ok(_) => WarmUpOutcome {
+                    id,
+                    error: None,
+                    revoked: false,
+                },
+                Err(e) => {
+                    let revoked = e.is::<RefreshTokenRevoked>();
+                    if revoked {
+                        self.mark_refresh_token_revoked(id);
+                    }
+                    tracing::warn!("凭据 #{} 预热失败: {}", id, e);
+                    WarmUpOutcome {
+                        id,
+                        error: Some(e.to_string()),
+                        revoked,
+                    }
+                }
+            };
+            accounts.push(outcome);
+        }
+
+        let report = WarmUpReport { accounts };
+        if report.succeeded() == 0 {
+            return Err(KiroError::WarmUpFailed(report));
+        }
+        Ok(report)
+    }
+
     /// 将凭据列表回写到源文件
     ///
     /// 仅在以下条件满足时回写：
@@ -927,6 +1571,38 @@ impl MultiTokenManager {
         .await
     }
 
+    /// 获取所有未禁用凭据的 ID（按 ID 升序）
+    #[allow(dead_code)]
+    pub(crate) fn available_ids(&self) -> Vec<u64> {
+        let mut ids: Vec<u64> = self
+            .entries
+            .lock()
+            .iter()
+            .filter(|e| !e.disabled)
+            .map(|e| e.id)
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// 获取指定凭据的调用上下文（必要时刷新 Token）
+    ///
+    /// 与 `acquire_context` 不同，不会切换当前凭据；第二个值表示本次是否刷新了 Token
+    #[allow(dead_code)]
+    pub(crate) async fn context_for(&self, id: u64) -> anyhow::Result<(CallContext, bool)> {
+        let credentials = {
+            let entries = self.entries.lock();
+            entries
+                .iter()
+                .find(|e| e.id == id && !e.disabled)
+                .map(|e| e.credentials.clone())
+                .ok_or_else(|| anyhow::anyhow!("凭据 #{} 不存在或已禁用", id))?
+        };
+        let ctx = self.try_ensure_token(id, &credentials).await?;
+        let refreshed = credentials.access_token.as_deref() != Some(ctx.token.as_str());
+        Ok((ctx, refreshed))
+    }
+
     // ========================================================================
     // Admin API 方法
     // ========================================================================
@@ -956,6 +1632,42 @@ impl MultiTokenManager {
         }
     }
 
+    /// 获取所有账号的非敏感摘要（用于管理命令）
+    #[allow(dead_code)]
+    pub fn describe(&self) -> TokenSummary {
+        let now = self.now();
+        let entries = self.entries.lock();
+        let mut accounts: Vec<AccountSummary> = entries
+            .iter()
+            .map(|e| AccountSummary {
+                id: e.id,
+                label: account_label(e.id),
+                auth_method: e.credentials.auth_method_kind().as_str(),
+                expires_at: e.credentials.expires_at.clone(),
+                expired: is_token_expired_at(&e.credentials, now),
+                has_refresh_token: e
+                    .credentials
+                    .refresh_token
+                    .as_deref()
+                    .is_some_and(|t| !t.is_empty()),
+                failure_count: e.failure_count,
+                health: if e.disabled {
+                    AccountHealth::Disabled
+                } else if e.failure_count > 0 {
+                    AccountHealth::Degraded
+                } else {
+                    AccountHealth::Healthy
+                },
+            })
+            .collect();
+        accounts.sort_unstable_by_key(|a| a.id);
+
+        TokenSummary {
+            accounts,
+            current_id: *self.current_id.lock(),
+        }
+    }
+
     /// 设置凭据禁用状态（Admin API）
     pub fn set_disabled(&self, id: u64, disabled: bool) -> anyhow::Result<()> {
         {
@@ -1027,7 +1739,7 @@ impl MultiTokenManager {
         };
 
         // 检查是否需要刷新 token
-        let needs_refresh = is_token_expired(&credentials) || is_token_expiring_soon(&credentials);
+        let needs_refresh = needs_refresh_at(&credentials, self.now());
 
         let token = if needs_refresh {
             let _guard = self.refresh_lock.lock().await;
@@ -1040,9 +1752,8 @@ impl MultiTokenManager {
                     .ok_or_else(|| anyhow::anyhow!("凭据不存在: {}", id))?
             };
 
-            if is_token_expired(&current_creds) || is_token_expiring_soon(&current_creds) {
-                let new_creds =
-                    refresh_token(&current_creds, &self.config, self.proxy.as_ref()).await?;
+            if needs_refresh_at(&current_creds, self.now()) {
+                let new_creds = self.refresh(id, &current_creds).await?;
                 {
                     let mut entries = self.entries.lock();
                     if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
@@ -1053,6 +1764,7 @@ impl MultiTokenManager {
                 if let Err(e) = self.persist_credentials() {
                     tracing::warn!("Token 刷新后持久化失败（不影响本次请求）: {}", e);
                 }
+                self.save_to_store(id, &new_creds);
                 new_creds
                     .access_token
                     .ok_or_else(|| anyhow::anyhow!("刷新后无 access_token"))?
@@ -1096,8 +1808,14 @@ impl MultiTokenManager {
         validate_refresh_token(&new_cred)?;
 
         // 2. 尝试刷新 Token 验证凭据有效性
-        let mut validated_cred =
-            refresh_token(&new_cred, &self.config, self.proxy.as_ref()).await?;
+        let mut validated_cred = refresh_token(
+            &new_cred,
+            &self.config,
+            &self.token_config,
+            self.proxy.as_ref(),
+            self.now(),
+        )
+        .await?;
 
         // 3. 分配新 ID
         let new_id = {
@@ -1194,6 +1912,388 @@ impl MultiTokenManager {
     }
 }
 
+// ============================================================================
+// 多账号轮询池
+// ============================================================================
+
+/// 凭据池默认冷却时间
+#[allow(dead_code)]
+pub const DEFAULT_POOL_COOLDOWN: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// 凭据失败类型（用于决定冷却策略）
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolFailure {
+    /// 触发限流（429），仅临时移出轮询
+    RateLimited,
+    /// 认证失败（401/403），移出轮询并计入凭据失败次数
+    Auth,
+}
+
+/// 多账号轮询池
+///
+/// 基于 `MultiTokenManager` 的凭据，按平滑加权轮询（smooth weighted round-robin）方式分配 Token，
+/// 将请求按权重分散到多个账号以规避单账号限流（权重默认均为 1，即普通轮询）；
+/// 失败的账号会在冷却期内被移出轮询，冷却结束后自动恢复
+#[allow(dead_code)]
+pub struct TokenPool {
+    manager: Arc<MultiTokenManager>,
+    /// 冷却时长
+    cooldown: std::time::Duration,
+    /// 各账号的权重，未设置的账号为 1
+    weights: HashMap<u64, u32>,
+    /// 平滑加权轮询的当前权重：ID -> 当前值
+    current_weights: Mutex<HashMap<u64, i64>>,
+    /// 冷却中的凭据：ID -> 冷却结束时间（tokio 时钟，测试中可暂停快进）
+    cooldowns: Mutex<HashMap<u64, Instant>>,
+    /// 相邻两次获取之间的随机间隔范围，`None` 表示不启用
+    acquire_jitter: Option<(std::time::Duration, std::time::Duration)>,
+    /// 上一次获取被安排的时间
+    last_acquire: Mutex<Option<tokio::time::Instant>>,
+    /// 各账号的调用计数
+    metrics: PoolMetrics,
+}
+
+/// 单个账号的计数器
+#[derive(Default)]
+struct AccountCounters {
+    requests: AtomicU64,
+    successes: AtomicU64,
+    rate_limited: AtomicU64,
+    auth_failures: AtomicU64,
+    refreshes: AtomicU64,
+    refresh_failures: AtomicU64,
+}
+
+/// `TokenPool` 的按账号计数
+///
+/// 计数器均为原子变量；账号首次出现时才需要写锁，之后的更新与读取只持有读锁
+#[derive(Default)]
+struct PoolMetrics {
+    accounts: RwLock<HashMap<u64, Arc<AccountCounters>>>,
+}
+
+impl PoolMetrics {
+    /// 对指定账号的计数器执行更新
+    fn record(&self, id: u64, update: impl FnOnce(&AccountCounters)) {
+        if let Some(counters) = self.accounts.read().get(&id) {
+            update(counters);
+            return;
+        }
+        let counters = self.accounts.write().entry(id).or_default().clone();
+        update(&counters);
+    }
+
+    fn snapshot(&self) -> PoolMetricsSnapshot {
+        let mut accounts: Vec<AccountMetrics> = self
+            .accounts
+            .read()
+            .iter()
+            .map(|(&id, c)| AccountMetrics {
+                id,
+                requests: c.requests.load(Ordering::Relaxed),
+                successes: c.successes.load(Ordering::Relaxed),
+                rate_limited: c.rate_limited.load(Ordering::Relaxed),
+                auth_failures: c.auth_failures.load(Ordering::Relaxed),
+                refreshes: c.refreshes.load(Ordering::Relaxed),
+                refresh_failures: c.refresh_failures.load(Ordering::Relaxed),
+            })
+            .collect();
+        accounts.sort_by_key(|a| a.id);
+        PoolMetricsSnapshot { accounts }
+    }
+}
+
+/// 单个账号的累计计数
+#[allow(dead_code)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountMetrics {
+    /// 凭据 ID
+    pub id: u64,
+    /// 成功分配 Token 的次数
+    pub requests: u64,
+    /// 报告成功的次数
+    pub successes: u64,
+    /// 报告限流（429）的次数
+    pub rate_limited: u64,
+    /// 报告认证失败（401/403）的次数
+    pub auth_failures: u64,
+    /// 获取时刷新 Token 的次数
+    pub refreshes: u64,
+    /// 获取 Token 失败（含刷新失败）的次数
+    pub refresh_failures: u64,
+}
+
+/// `TokenPool::metrics` 返回的计数快照，按凭据 ID 排序
+#[allow(dead_code)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PoolMetricsSnapshot {
+    pub accounts: Vec<AccountMetrics>,
+}
+
+impl PoolMetricsSnapshot {
+    /// 指定账号的计数，该账号尚未被使用时返回 `None`
+    #[allow(dead_code)]
+    pub fn account(&self, id: u64) -> Option<&AccountMetrics> {
+        self.accounts.iter().find(|a| a.id == id)
+    }
+}
+
+/// 从 `TokenPool` 获取的 Token 句柄
+///
+/// 调用方在请求结束后通过 `report_success` / `report_failure` 反馈账号健康状态
+#[allow(dead_code)]
+pub struct TokenHandle<'a> {
+    pool: &'a TokenPool,
+    ctx: CallContext,
+}
+
+impl TokenPool {
+    /// 创建凭据池
+    #[allow(dead_code)]
+    pub fn new(manager: Arc<MultiTokenManager>) -> Self {
+        Self {
+            manager,
+            cooldown: DEFAULT_POOL_COOLDOWN,
+            weights: HashMap::new(),
+            current_weights: Mutex::new(HashMap::new()),
+            cooldowns: Mutex::new(HashMap::new()),
+            acquire_jitter: None,
+            last_acquire: Mutex::new(None),
+            metrics: PoolMetrics::default(),
+        }
+    }
+
+    /// 设置冷却时长
+    #[allow(dead_code)]
+    pub fn with_cooldown(mut self, cooldown: std::time::Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// 设置账号权重，权重越大分到的请求越多（最小为 1）
+    #[allow(dead_code)]
+    pub fn with_weight(mut self, id: u64, weight: u32) -> Self {
+        self.weights.insert(id, weight.max(1));
+        self
+    }
+
+    /// 启用获取间隔随机化：相邻两次获取至少间隔 `min..=max` 内的随机时长
+    ///
+    /// 避免轮换账号时请求以完全相同的节奏发出；默认不启用
+    #[allow(dead_code)]
+    pub fn with_acquire_jitter(
+        mut self,
+        min: std::time::Duration,
+        max: std::time::Duration,
+    ) -> Self {
+        self.acquire_jitter = Some((min, max.max(min)));
+        self
+    }
+
+    /// 关闭获取间隔随机化
+    #[allow(dead_code)]
+    pub fn without_acquire_jitter(mut self) -> Self {
+        self.acquire_jitter = None;
+        self
+    }
+
+    /// 按加权轮询顺序获取下一个可用账号的 Token
+    ///
+    /// 跳过冷却中的账号（无论权重）；Token 刷新失败的账号同样进入冷却，并改选下一个账号
+    #[allow(dead_code)]
+    pub async fn acquire(&self) -> Result<TokenHandle<'_>, KiroError> {
+        self.wait_for_slot().await;
+
+        let mut candidates = self.manager.available_ids();
+        if candidates.is_empty() {
+            return Err(KiroError::auth("没有可用的凭据"));
+        }
+
+        let mut last_error = None;
+
+        loop {
+            candidates.retain(|&id| !self.is_cooling_down(id));
+            let Some(id) = self.select_weighted(&candidates) else {
+                break;
+            };
+            candidates.retain(|&c| c != id);
+
+            match self.manager.context_for(id).await {
+                Ok((ctx, refreshed)) => {
+                    self.metrics.record(id, |c| {
+                        c.requests.fetch_add(1, Ordering::Relaxed);
+                        if refreshed {
+                            c.refreshes.fetch_add(1, Ordering::Relaxed);
+                        }
+                    });
+                    return Ok(TokenHandle { pool: self, ctx });
+                }
+                Err(e) if e.is::<RefreshTokenRevoked>() => {
+                    self.record_refresh_failure(id);
+                    self.manager.mark_refresh_token_revoked(id);
+                    last_error = Some(refresh_error(id, e));
+                }
+                Err(e) => {
+                    tracing::warn!("凭据 #{} 获取 Token 失败，进入冷却: {}", id, e);
+                    self.record_refresh_failure(id);
+                    self.start_cooldown(id);
+                    last_error = Some(KiroError::auth_from(e));
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| KiroError::auth("所有凭据均处于冷却中")))
+    }
+
+    /// 平滑加权轮询：每个候选的当前权重加上其权重，选出当前权重最大者并减去候选权重之和
+    ///
+    /// 权重相同时按候选顺序选择，相同权重的账号依次轮换
+    fn select_weighted(&self, candidates: &[u64]) -> Option<u64> {
+        let mut current = self.current_weights.lock();
+        let mut total = 0i64;
+        let mut selected: Option<(u64, i64)> = None;
+        for &id in candidates {
+            let weight = i64::from(self.weights.get(&id).copied().unwrap_or(1));
+            total += weight;
+            let value = current.entry(id).or_insert(0);
+            *value += weight;
+            if selected.is_none_or(|(_, best)| *value > best) {
+                selected = Some((id, *value));
+            }
+        }
+
+        let (id, _) = selected?;
+        *current.get_mut(&id).expect("候选账号已写入") -= total;
+        Some(id)
+    }
+
+    /// 启用随机间隔时，等待到本次获取被安排的时间
+    ///
+    /// 在锁内预留时间点，并发获取也会依次错开
+    async fn wait_for_slot(&self) {
+        let Some((min, max)) = self.acquire_jitter else {
+            return;
+        };
+
+        let slot = {
+            let mut last = self.last_acquire.lock();
+            let now = tokio::time::Instant::now();
+            let slot = match *last {
+                Some(prev) => (prev + random_duration(min, max)).max(now),
+                None => now,
+            };
+            *last = Some(slot);
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+
+    /// 判断凭据是否处于冷却中（冷却结束的条目会被清除）
+    fn is_cooling_down(&self, id: u64) -> bool {
+        let mut cooldowns = self.cooldowns.lock();
+        match cooldowns.get(&id) {
+            Some(until) if *until > Instant::now() => true,
+            Some(_) => {
+                cooldowns.remove(&id);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// 获取各账号的非敏感摘要，冷却中的可用账号标记为 `CoolingDown`
+    #[allow(dead_code)]
+    pub fn describe(&self) -> TokenSummary {
+        let mut summary = self.manager.describe();
+        for account in &mut summary.accounts {
+            if account.health != AccountHealth::Disabled && self.is_cooling_down(account.id) {
+                account.health = AccountHealth::CoolingDown;
+            }
+        }
+        summary
+    }
+
+    /// 各账号的请求、成功、限流与刷新计数
+    #[allow(dead_code)]
+    pub fn metrics(&self) -> PoolMetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// 启动预热，见 `MultiTokenManager::warm_up`
+    #[allow(dead_code)]
+    pub async fn warm_up(&self) -> Result<WarmUpReport, KiroError> {
+        self.manager.warm_up().await
+    }
+
+    fn record_refresh_failure(&self, id: u64) {
+        self.metrics.record(id, |c| {
+            c.refresh_failures.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+
+    /// 将凭据移出轮询直到冷却结束
+    fn start_cooldown(&self, id: u64) {
+        self.cooldowns
+            .lock()
+            .insert(id, Instant::now() + self.cooldown);
+    }
+}
+
+/// 生成 `[min, max]` 内的随机时长
+fn random_duration(min: std::time::Duration, max: std::time::Duration) -> std::time::Duration {
+    let min_nanos = min.as_nanos() as u64;
+    let max_nanos = max.as_nanos() as u64;
+    std::time::Duration::from_nanos(fastrand::u64(min_nanos..=max_nanos.max(min_nanos)))
+}
+
+impl TokenHandle<'_> {
+    /// 凭据 ID
+    #[allow(dead_code)]
+    pub fn id(&self) -> u64 {
+        self.ctx.id
+    }
+
+    /// 访问 Token
+    #[allow(dead_code)]
+    pub fn token(&self) -> &str {
+        &self.ctx.token
+    }
+
+    /// 调用上下文
+    #[allow(dead_code)]
+    pub fn context(&self) -> &CallContext {
+        &self.ctx
+    }
+
+    /// 报告调用成功
+    #[allow(dead_code)]
+    pub fn report_success(self) {
+        self.pool.metrics.record(self.ctx.id, |c| {
+            c.successes.fetch_add(1, Ordering::Relaxed);
+        });
+        self.pool.manager.report_success(self.ctx.id);
+    }
+
+    /// 报告调用失败，账号进入冷却
+    #[allow(dead_code)]
+    pub fn report_failure(self, failure: PoolFailure) {
+        self.pool.metrics.record(self.ctx.id, |c| {
+            let counter = match failure {
+                PoolFailure::RateLimited => &c.rate_limited,
+                PoolFailure::Auth => &c.auth_failures,
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+        if failure == PoolFailure::Auth {
+            self.pool.manager.report_failure(self.ctx.id);
+        }
+        tracing::info!("凭据 #{} 调用失败（{:?}），进入冷却", self.ctx.id, failure);
+        self.pool.start_cooldown(self.ctx.id);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1410,4 +2510,920 @@ mod tests {
         assert!(ctx.token == "t1" || ctx.token == "t2");
         assert_eq!(manager.available_count(), 2);
     }
+
+    fn counting_refresher(counter: Arc<std::sync::atomic::AtomicUsize>) -> RefreshFn {
+        Arc::new(move |mut creds: KiroCredentials| {
+            let counter = counter.clone();
+            Box::pin(async move {
+                let n = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                creds.access_token = Some(format!("refreshed-{}", n));
+                creds.expires_at = Some((Utc::now() + Duration::hours(1)).to_rfc3339());
+                Ok(creds)
+            })
+        })
+    }
+
+    #[tokio::test]
+    async fn test_ensure_fresh_refreshes_within_skew() {
+        let counter = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let cred = KiroCredentials {
+            access_token: Some("old".to_string()),
+            expires_at: Some((Utc::now() + Duration::seconds(30)).to_rfc3339()),
+            ..Default::default()
+        };
+
+        let manager = MultiTokenManager::new(Config::default(), vec![cred], None, None, false)
+            .unwrap()
+            .with_refresher(counting_refresher(counter.clone()));
+
+        let token = manager.ensure_fresh(DEFAULT_REFRESH_SKEW).await.unwrap();
+        assert_eq!(token, "refreshed-1");
+        assert_eq!(counter.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        // 刷新后的 Token 距过期还有 1 小时，不应再次刷新
+        let token = manager.ensure_fresh(DEFAULT_REFRESH_SKEW).await.unwrap();
+        assert_eq!(token, "refreshed-1");
+        assert_eq!(counter.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_ensure_fresh_skips_refresh_outside_skew() {
+        let counter = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let cred = KiroCredentials {
+            access_token: Some("still-valid".to_string()),
+            expires_at: Some((Utc::now() + Duration::minutes(5)).to_rfc3339()),
+            ..Default::default()
+        };
+
+        let manager = MultiTokenManager::new(Config::default(), vec![cred], None, None, false)
+            .unwrap()
+            .with_refresher(counting_refresher(counter.clone()));
+
+        let token = manager
+            .ensure_fresh(std::time::Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(token, "still-valid");
+        assert_eq!(counter.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+        // 增大 skew 后应触发刷新
+        let token = manager
+            .ensure_fresh(std::time::Duration::from_secs(600))
+            .await
+            .unwrap();
+        assert_eq!(token, "refreshed-1");
+    }
+
+    #[tokio::test]
+    async fn test_ensure_fresh_with_test_clock() {
+        use crate::kiro::clock::TestClock;
+
+        let clock = Arc::new(TestClock::new(
+            std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000),
+        ));
+        let start: DateTime<Utc> = clock.now().into();
+        let counter = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let cred = KiroCredentials {
+            access_token: Some("old".to_string()),
+            expires_at: Some((start + Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+
+        // 刷新后的过期时间以测试时钟为准
+        let refresher: RefreshFn = {
+            let clock = clock.clone();
+            let counter = counter.clone();
+            Arc::new(move |mut creds: KiroCredentials| {
+                let now: DateTime<Utc> = clock.now().into();
+                let n = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                Box::pin(async move {
+                    creds.access_token = Some(format!("refreshed-{}", n));
+                    creds.expires_at = Some((now + Duration::hours(1)).to_rfc3339());
+                    Ok(creds)
+                })
+            })
+        };
+        let manager = MultiTokenManager::new(Config::default(), vec![cred], None, None, false)
+            .unwrap()
+            .with_refresher(refresher)
+            .with_clock(clock.clone());
+
+        let token = manager.ensure_fresh(DEFAULT_REFRESH_SKEW).await.unwrap();
+        assert_eq!(token, "old");
+        assert_eq!(counter.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+        // 越过过期时间后应触发刷新
+        clock.advance(std::time::Duration::from_secs(2 * 3600));
+        let token = manager.ensure_fresh(DEFAULT_REFRESH_SKEW).await.unwrap();
+        assert_eq!(token, "refreshed-1");
+        assert_eq!(counter.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        let token = manager.ensure_fresh(DEFAULT_REFRESH_SKEW).await.unwrap();
+        assert_eq!(token, "refreshed-1");
+        assert_eq!(counter.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_ensure_fresh_real_refresh_uses_test_clock() {
+        use crate::kiro::clock::TestClock;
+        use crate::test_support::{MockResponse, MockServer};
+
+        let server = MockServer::start(vec![
+            MockResponse::json(200, r#"{"accessToken":"idc-access","expiresIn":3600}"#),
+            MockResponse::json(200, r#"{"accessToken":"social-access","expiresIn":3600}"#),
+        ])
+        .await;
+        // 测试时钟远在系统时间之后：按系统时间计算的过期时间在管理器看来早已过期
+        let clock = Arc::new(TestClock::new(
+            std::time::SystemTime::now() + std::time::Duration::from_secs(30 * 24 * 3600),
+        ));
+        let now: DateTime<Utc> = clock.now().into();
+
+        for (cred, expected) in [
+            (idc_credentials(), "idc-access"),
+            (
+                KiroCredentials {
+                    refresh_token: Some("s".repeat(150)),
+                    ..Default::default()
+                },
+                "social-access",
+            ),
+        ] {
+            let cred = KiroCredentials {
+                access_token: Some("old".to_string()),
+                expires_at: Some((now - Duration::hours(1)).to_rfc3339()),
+                ..cred
+            };
+            let manager = MultiTokenManager::new(Config::default(), vec![cred], None, None, false)
+                .unwrap()
+                .with_token_config(
+                    TokenManagerConfig::new().with_refresh_endpoint(server.url("/token")),
+                )
+                .with_clock(clock.clone());
+
+            let token = manager.ensure_fresh(DEFAULT_REFRESH_SKEW).await.unwrap();
+            assert_eq!(token, expected);
+            assert_eq!(
+                manager.expires_at(),
+                Some(clock.now() + std::time::Duration::from_secs(3600))
+            );
+
+            // 刷新结果在管理器时钟下仍有效，不应再次刷新
+            let token = manager.ensure_fresh(DEFAULT_REFRESH_SKEW).await.unwrap();
+            assert_eq!(token, expected);
+        }
+        assert_eq!(server.request_count(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_ensure_fresh_concurrent_refresh_runs_once() {
+        let counter = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let refresher: RefreshFn = {
+            let counter = counter.clone();
+            Arc::new(move |mut creds: KiroCredentials| {
+                let counter = counter.clone();
+                Box::pin(async move {
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                    counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    creds.access_token = Some("shared".to_string());
+                    creds.expires_at = Some((Utc::now() + Duration::hours(1)).to_rfc3339());
+                    Ok(creds)
+                })
+            })
+        };
+        let cred = KiroCredentials {
+            expires_at: Some("2020-01-01T00:00:00Z".to_string()),
+            ..Default::default()
+        };
+        let manager = Arc::new(
+            MultiTokenManager::new(Config::default(), vec![cred], None, None, false)
+                .unwrap()
+                .with_refresher(refresher),
+        );
+
+        let handles: Vec<_> = (0..16)
+            .map(|_| {
+                let manager = manager.clone();
+                tokio::spawn(async move { manager.ensure_fresh(DEFAULT_REFRESH_SKEW).await })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.await.unwrap().unwrap(), "shared");
+        }
+
+        assert_eq!(counter.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_ensure_fresh_concurrent_refresh_shares_failure() {
+        let counter = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let refresher: RefreshFn = {
+            let counter = counter.clone();
+            Arc::new(move |_creds: KiroCredentials| {
+                let counter = counter.clone();
+                Box::pin(async move {
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                    counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    anyhow::bail!("刷新失败")
+                })
+            })
+        };
+        let cred = KiroCredentials {
+            expires_at: Some("2020-01-01T00:00:00Z".to_string()),
+            ..Default::default()
+        };
+        let manager = Arc::new(
+            MultiTokenManager::new(Config::default(), vec![cred], None, None, false)
+                .unwrap()
+                .with_refresher(refresher),
+        );
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let manager = manager.clone();
+                tokio::spawn(async move { manager.ensure_fresh(DEFAULT_REFRESH_SKEW).await })
+            })
+            .collect();
+        for handle in handles {
+            let err = handle.await.unwrap().unwrap_err();
+            assert!(matches!(err, KiroError::Auth { .. }));
+            assert!(err.to_string().contains("刷新失败"));
+        }
+
+        assert_eq!(counter.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        events: Mutex<Vec<(String, Option<RefreshOutcome>)>>,
+    }
+
+    impl RefreshObserver for RecordingObserver {
+        fn on_refresh_start(&self, account: &str) {
+            self.events.lock().push((account.to_string(), None));
+        }
+
+        fn on_refresh_finish(&self, account: &str, outcome: &RefreshOutcome) {
+            self.events
+                .lock()
+                .push((account.to_string(), Some(outcome.clone())));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_observer_reports_outcomes() {
+        let counter = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        // 第一次刷新成功，第二次 refreshToken 被撤销
+        let refresher: RefreshFn = {
+            let counter = counter.clone();
+            Arc::new(move |mut creds: KiroCredentials| {
+                let n = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Box::pin(async move {
+                    if n > 0 {
+                        return Err(RefreshTokenRevoked("invalid_grant".to_string()).into());
+                    }
+                    creds.access_token = Some("secret-access-token".to_string());
+                    Ok(creds)
+                })
+            })
+        };
+        let cred = KiroCredentials {
+            id: Some(7),
+            refresh_token: Some("secret-refresh-token".to_string()),
+            ..Default::default()
+        };
+        let observer = Arc::new(RecordingObserver::default());
+        let manager =
+            MultiTokenManager::new(Config::default(), vec![cred.clone()], None, None, false)
+                .unwrap()
+                .with_refresher(refresher)
+                .with_refresh_observer(observer.clone());
+
+        assert!(manager.refresh(7, &cred).await.is_ok());
+        assert!(manager.refresh(7, &cred).await.is_err());
+
+        let events = observer.events.lock().clone();
+        // 与 describe 的账号标签一致
+        let account = manager.describe().accounts[0].label.clone();
+        assert_eq!(account, "#7");
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0], (account.clone(), None));
+        assert_eq!(events[1], (account.clone(), Some(RefreshOutcome::Success)));
+        assert_eq!(events[2], (account.clone(), None));
+        assert!(matches!(
+            &events[3],
+            (a, Some(RefreshOutcome::Failed { revoked: true, .. })) if *a == account
+        ));
+        // 回调中不携带 Token 内容
+        assert!(!format!("{:?}", events).contains("secret"));
+    }
+
+    fn pool_credentials(count: usize) -> Vec<KiroCredentials> {
+        (0..count)
+            .map(|i| KiroCredentials {
+                access_token: Some(format!("t{}", i + 1)),
+                expires_at: Some((Utc::now() + Duration::hours(1)).to_rfc3339()),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_describe_hides_token_material() {
+        let expires_at = (Utc::now() + Duration::hours(1)).to_rfc3339();
+        let credentials = vec![
+            KiroCredentials {
+                access_token: Some("secret-access-token".to_string()),
+                refresh_token: Some("secret-refresh-token".to_string()),
+                expires_at: Some(expires_at.clone()),
+                auth_method: Some("idc".to_string()),
+                client_secret: Some("secret-client".to_string()),
+                ..Default::default()
+            },
+            KiroCredentials {
+                access_token: Some("secret-access-2".to_string()),
+                ..Default::default()
+            },
+        ];
+        let manager = Arc::new(
+            MultiTokenManager::new(Config::default(), credentials, None, None, false).unwrap(),
+        );
+
+        let summary = manager.describe();
+        assert_eq!(summary.accounts.len(), 2);
+        let first = &summary.accounts[0];
+        assert_eq!(first.label, "#1");
+        assert_eq!(first.auth_method, "idc");
+        assert_eq!(first.expires_at.as_deref(), Some(expires_at.as_str()));
+        assert!(!first.expired);
+        assert!(first.has_refresh_token);
+        assert_eq!(first.health, AccountHealth::Healthy);
+        let second = &summary.accounts[1];
+        assert_eq!(second.auth_method, "social");
+        assert!(second.expired);
+        assert!(!second.has_refresh_token);
+
+        let json = serde_json::to_string(&summary).unwrap();
+        let debug = format!("{:?}", summary);
+        assert!(json.contains(&expires_at) && json.contains("\"authMethod\":\"idc\""));
+        for output in [&json, &debug] {
+            assert!(!output.contains("secret"), "{}", output);
+        }
+
+        // 凭据池叠加冷却状态
+        let pool = TokenPool::new(manager.clone());
+        let handle = pool.acquire().await.unwrap();
+        assert_eq!(handle.id(), 1);
+        handle.report_failure(PoolFailure::RateLimited);
+        let summary = pool.describe();
+        assert_eq!(summary.accounts[0].health, AccountHealth::CoolingDown);
+        assert_eq!(summary.accounts[1].health, AccountHealth::Healthy);
+    }
+
+    #[tokio::test]
+    async fn test_token_pool_round_robin() {
+        let manager = Arc::new(
+            MultiTokenManager::new(Config::default(), pool_credentials(3), None, None, false)
+                .unwrap(),
+        );
+        let pool = TokenPool::new(manager);
+
+        let mut order = Vec::new();
+        for _ in 0..6 {
+            let handle = pool.acquire().await.unwrap();
+            order.push(handle.id());
+            handle.report_success();
+        }
+        assert_eq!(order, vec![1, 2, 3, 1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_token_pool_weighted_selection() {
+        let manager = Arc::new(
+            MultiTokenManager::new(Config::default(), pool_credentials(2), None, None, false)
+                .unwrap(),
+        );
+        let pool = TokenPool::new(manager).with_weight(1, 3).with_weight(2, 1);
+
+        let mut counts = HashMap::new();
+        let mut order = Vec::new();
+        for i in 0..400 {
+            let handle = pool.acquire().await.unwrap();
+            *counts.entry(handle.id()).or_insert(0) += 1;
+            if i < 8 {
+                order.push(handle.id());
+            }
+            handle.report_success();
+        }
+        let first = counts[&1] as f64 / 400.0;
+        assert!((first - 0.75).abs() < 0.02, "{:?}", counts);
+        // 平滑加权：权重小的账号穿插在中间，而不是连续分配
+        assert_eq!(order, vec![1, 1, 2, 1, 1, 1, 2, 1]);
+
+        // 冷却中的账号无论权重都被跳过
+        pool.acquire()
+            .await
+            .unwrap()
+            .report_failure(PoolFailure::RateLimited);
+        for _ in 0..5 {
+            assert_eq!(pool.acquire().await.unwrap().id(), 2);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_token_pool_cooldown_and_reentry() {
+        let manager = Arc::new(
+            MultiTokenManager::new(Config::default(), pool_credentials(2), None, None, false)
+                .unwrap(),
+        );
+        let pool =
+            TokenPool::new(manager.clone()).with_cooldown(std::time::Duration::from_millis(50));
+
+        let handle = pool.acquire().await.unwrap();
+        assert_eq!(handle.id(), 1);
+        handle.report_failure(PoolFailure::RateLimited);
+
+        // 冷却期内只会拿到凭据 #2
+        for _ in 0..3 {
+            assert_eq!(pool.acquire().await.unwrap().id(), 2);
+        }
+        // 限流不计入凭据失败次数
+        assert_eq!(manager.available_count(), 2);
+
+        tokio::time::advance(std::time::Duration::from_millis(80)).await;
+
+        let ids: Vec<u64> = vec![
+            pool.acquire().await.unwrap().id(),
+            pool.acquire().await.unwrap().id(),
+        ];
+        assert!(ids.contains(&1));
+    }
+
+    #[tokio::test]
+    async fn test_token_pool_all_cooling_down() {
+        let manager = Arc::new(
+            MultiTokenManager::new(Config::default(), pool_credentials(1), None, None, false)
+                .unwrap(),
+        );
+        let pool = TokenPool::new(manager);
+
+        pool.acquire()
+            .await
+            .unwrap()
+            .report_failure(PoolFailure::Auth);
+        assert!(matches!(
+            pool.acquire().await.err(),
+            Some(KiroError::Auth { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_token_pool_metrics() {
+        let counter = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut credentials = pool_credentials(2);
+        credentials[1].expires_at = Some("2020-01-01T00:00:00Z".to_string());
+        let manager = Arc::new(
+            MultiTokenManager::new(Config::default(), credentials, None, None, false)
+                .unwrap()
+                .with_refresher(counting_refresher(counter)),
+        );
+        let pool = TokenPool::new(manager);
+        assert!(pool.metrics().accounts.is_empty());
+
+        // #1 成功，#2 刷新后限流，#1 认证失败（冷却中），#2 成功
+        pool.acquire().await.unwrap().report_success();
+        pool.acquire()
+            .await
+            .unwrap()
+            .report_failure(PoolFailure::RateLimited);
+        pool.acquire()
+            .await
+            .unwrap()
+            .report_failure(PoolFailure::Auth);
+        // 两个账号都在冷却
+        assert!(pool.acquire().await.is_err());
+
+        let metrics = pool.metrics();
+        assert_eq!(
+            metrics.account(1),
+            Some(&AccountMetrics {
+                id: 1,
+                requests: 2,
+                successes: 1,
+                auth_failures: 1,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            metrics.account(2),
+            Some(&AccountMetrics {
+                id: 2,
+                requests: 1,
+                rate_limited: 1,
+                refreshes: 1,
+                ..Default::default()
+            })
+        );
+        assert_eq!(metrics.accounts.len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_token_pool_acquire_jitter() {
+        let manager = Arc::new(
+            MultiTokenManager::new(Config::default(), pool_credentials(3), None, None, false)
+                .unwrap(),
+        );
+        let min = std::time::Duration::from_millis(100);
+        let max = std::time::Duration::from_millis(300);
+        let pool = TokenPool::new(manager).with_acquire_jitter(min, max);
+
+        // 首次获取不等待
+        let start = tokio::time::Instant::now();
+        pool.acquire().await.unwrap().report_success();
+        assert_eq!(start.elapsed(), std::time::Duration::ZERO);
+
+        let mut prev = tokio::time::Instant::now();
+        for _ in 0..20 {
+            pool.acquire().await.unwrap().report_success();
+            let now = tokio::time::Instant::now();
+            let gap = now - prev;
+            assert!(gap >= min && gap <= max, "间隔 {:?} 超出范围", gap);
+            prev = now;
+        }
+
+        // 关闭后不再等待
+        let pool = pool.without_acquire_jitter();
+        let start = tokio::time::Instant::now();
+        pool.acquire().await.unwrap().report_success();
+        pool.acquire().await.unwrap().report_success();
+        assert_eq!(start.elapsed(), std::time::Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_attach_store_loads_and_saves_refreshed_token() {
+        use crate::kiro::token_store::MemoryTokenStore;
+
+        let store = MemoryTokenStore::new();
+        store
+            .save(&StoredToken {
+                access_token: Some("stored".to_string()),
+                refresh_token: Some("stored-refresh".to_string()),
+                expires_at: Some("2020-01-01T00:00:00Z".to_string()),
+            })
+            .unwrap();
+
+        let counter = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![KiroCredentials::default()],
+            None,
+            None,
+            false,
+        )
+        .unwrap()
+        .with_refresher(counting_refresher(counter));
+        manager.attach_store(1, Box::new(store)).unwrap();
+        assert_eq!(
+            manager.credentials().refresh_token,
+            Some("stored-refresh".to_string())
+        );
+
+        // 存储中的 Token 已过期，触发刷新后应回写到存储
+        let token = manager.ensure_fresh(DEFAULT_REFRESH_SKEW).await.unwrap();
+        assert_eq!(token, "refreshed-1");
+
+        let stores = manager.stores.lock();
+        let saved = stores.get(&1).unwrap().load().unwrap().unwrap();
+        assert_eq!(saved.access_token, Some("refreshed-1".to_string()));
+        assert_eq!(saved.refresh_token, Some("stored-refresh".to_string()));
+    }
+
+    fn idc_credentials() -> KiroCredentials {
+        KiroCredentials {
+            refresh_token: Some("r".repeat(150)),
+            auth_method: Some("idc".to_string()),
+            client_id: Some("client-id".to_string()),
+            client_secret: Some("client-secret".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_idc_token_with_mock_server() {
+        use crate::test_support::{MockResponse, MockServer};
+
+        let server = MockServer::start(vec![MockResponse::json(
+            200,
+            r#"{"accessToken":"idc-access","refreshToken":"idc-refresh","expiresIn":3600,"tokenType":"Bearer"}"#,
+        )])
+        .await;
+
+        let creds = idc_credentials();
+        let new_creds = refresh_idc_token_at(
+            &server.url("/token"),
+            &creds,
+            &TokenManagerConfig::default(),
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(new_creds.access_token, Some("idc-access".to_string()));
+        assert_eq!(new_creds.refresh_token, Some("idc-refresh".to_string()));
+        assert!(!is_token_expired(&new_creds));
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/token");
+        let body = requests[0].body_json();
+        assert_eq!(body["grantType"], "refresh_token");
+        assert_eq!(body["clientId"], "client-id");
+        assert_eq!(body["clientSecret"], "client-secret");
+        assert_eq!(body["refreshToken"], "r".repeat(150));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_idc_token_error_status() {
+        use crate::test_support::{MockResponse, MockServer};
+
+        let server = MockServer::start(vec![MockResponse::json(
+            401,
+            r#"{"error":"invalid_grant"}"#,
+        )])
+        .await;
+
+        let err = refresh_idc_token_at(
+            &server.url("/token"),
+            &idc_credentials(),
+            &TokenManagerConfig::default(),
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("IdC 凭证已过期或无效"));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token_revoked_is_not_retried() {
+        use crate::test_support::{MockResponse, MockServer};
+
+        let server = MockServer::start(vec![MockResponse::json(
+            400,
+            r#"{"error":"invalid_grant","error_description":"Invalid refresh token provided"}"#,
+        )])
+        .await;
+        let url = server.url("/token");
+        let refresher: RefreshFn = Arc::new(move |creds: KiroCredentials| {
+            let url = url.clone();
+            Box::pin(async move {
+                refresh_idc_token_at(
+                    &url,
+                    &creds,
+                    &TokenManagerConfig::default(),
+                    None,
+                    Utc::now(),
+                )
+                .await
+            })
+        });
+        let cred = KiroCredentials {
+            expires_at: Some("2020-01-01T00:00:00Z".to_string()),
+            ..idc_credentials()
+        };
+        let manager = MultiTokenManager::new(Config::default(), vec![cred], None, None, false)
+            .unwrap()
+            .with_refresher(refresher);
+
+        let err = manager.acquire_context().await.unwrap_err();
+        assert!(
+            matches!(
+                err,
+                KiroError::RefreshTokenRevoked {
+                    credential_id: Some(_),
+                    ..
+                }
+            ),
+            "{:?}",
+            err
+        );
+        assert!(!err.is_retryable());
+        assert!(err.to_string().contains("invalid_grant"));
+
+        // 凭据已被禁用，不会再次请求刷新接口
+        assert_eq!(manager.available_count(), 0);
+        assert!(manager.acquire_context().await.is_err());
+        assert_eq!(server.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_idc_token_requires_client_id() {
+        let mut creds = idc_credentials();
+        creds.client_id = None;
+        let err = refresh_token(
+            &creds,
+            &Config::default(),
+            &TokenManagerConfig::default(),
+            None,
+            Utc::now(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("clientId"));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_uses_configured_endpoint_and_client() {
+        use crate::test_support::{MockResponse, MockServer};
+
+        let server = MockServer::start(vec![MockResponse::json(
+            200,
+            r#"{"accessToken":"idc-access","expiresIn":3600}"#,
+        )])
+        .await;
+        let cred = KiroCredentials {
+            expires_at: Some("2020-01-01T00:00:00Z".to_string()),
+            ..idc_credentials()
+        };
+        let token_config = TokenManagerConfig::new()
+            .with_refresh_endpoint(server.url("/realm/token"))
+            .with_client_id("rotated-client")
+            .with_client_secret("rotated-secret");
+        let manager = MultiTokenManager::new(Config::default(), vec![cred], None, None, false)
+            .unwrap()
+            .with_token_config(token_config);
+
+        let ctx = manager.acquire_context().await.unwrap();
+        assert_eq!(ctx.token, "idc-access");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/realm/token");
+        assert_eq!(
+            requests[0].header("host"),
+            Some(server.base_url().trim_start_matches("http://"))
+        );
+        let body = requests[0].body_json();
+        assert_eq!(body["clientId"], "rotated-client");
+        assert_eq!(body["clientSecret"], "rotated-secret");
+    }
+
+    #[tokio::test]
+    async fn test_refresh_timeout_releases_lock() {
+        use crate::test_support::{MockResponse, MockServer};
+
+        let server = MockServer::start(vec![
+            MockResponse::json(200, r#"{"accessToken":"late","expiresIn":3600}"#)
+                .with_header_delay(std::time::Duration::from_secs(10)),
+            MockResponse::json(200, r#"{"accessToken":"idc-access","expiresIn":3600}"#),
+        ])
+        .await;
+        let cred = KiroCredentials {
+            expires_at: Some("2020-01-01T00:00:00Z".to_string()),
+            ..idc_credentials()
+        };
+        let token_config = TokenManagerConfig::new()
+            .with_refresh_endpoint(server.url("/token"))
+            .with_refresh_timeout(std::time::Duration::from_secs(1));
+        let manager = MultiTokenManager::new(Config::default(), vec![cred], None, None, false)
+            .unwrap()
+            .with_token_config(token_config);
+
+        let started = std::time::Instant::now();
+        let err = manager
+            .ensure_fresh(std::time::Duration::ZERO)
+            .await
+            .unwrap_err();
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        match err {
+            KiroError::Auth { message, .. } => assert!(message.contains("超时"), "{}", message),
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        // 超时后刷新锁已释放，后续调用重新请求上游并成功
+        let token = manager
+            .ensure_fresh(std::time::Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(token, "idc-access");
+        assert_eq!(server.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_reports_each_account() {
+        use crate::test_support::{MockResponse, MockServer};
+
+        let server = MockServer::start(vec![
+            MockResponse::json(200, r#"{"accessToken":"warm","expiresIn":3600}"#),
+            MockResponse::json(400, r#"{"error":"invalid_grant"}"#),
+        ])
+        .await;
+        let token_config = TokenManagerConfig::new().with_refresh_endpoint(server.url("/token"));
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![idc_credentials(), idc_credentials()],
+            None,
+            None,
+            false,
+        )
+        .unwrap()
+        .with_token_config(token_config);
+
+        let report = manager.warm_up().await.unwrap();
+        assert!(report.is_partial());
+        assert_eq!((report.succeeded(), report.failed()), (1, 1));
+        assert!(report.accounts[0].is_ok());
+        assert!(report.accounts[1].revoked);
+        assert!(
+            report.accounts[1]
+                .error
+                .as_deref()
+                .unwrap()
+                .contains("invalid_grant")
+        );
+
+        // 被撤销的凭据已禁用，全部失败时错误中携带各账号结果
+        assert_eq!(manager.available_count(), 1);
+        let err = manager.warm_up().await.unwrap_err();
+        let KiroError::WarmUpFailed(report) = &err else {
+            panic!("expected KiroError::WarmUpFailed, got {:?}", err);
+        };
+        assert_eq!(report.failed(), 1);
+        assert_eq!(report.accounts[0].id, 1);
+        assert!(report.accounts[0].revoked);
+        assert!(err.to_string().contains("#1: "), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_social_refresh_uses_configured_endpoint() {
+        use crate::test_support::{MockResponse, MockServer};
+
+        let server = MockServer::start(vec![MockResponse::json(
+            200,
+            r#"{"accessToken":"social-access","expiresIn":3600}"#,
+        )])
+        .await;
+        let cred = KiroCredentials {
+            refresh_token: Some("s".repeat(150)),
+            expires_at: Some("2020-01-01T00:00:00Z".to_string()),
+            ..Default::default()
+        };
+        let token_config = TokenManagerConfig::new().with_refresh_endpoint(server.url("/refresh"));
+
+        let new_creds = refresh_token(&cred, &Config::default(), &token_config, None, Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(new_creds.access_token, Some("social-access".to_string()));
+        assert_eq!(server.requests()[0].path, "/refresh");
+        assert_eq!(
+            server.requests()[0].body_json()["refreshToken"],
+            "s".repeat(150)
+        );
+    }
+
+    #[test]
+    fn test_expires_at_and_time_to_expiry() {
+        let expires = Utc::now() + Duration::minutes(30);
+        let cred = KiroCredentials {
+            expires_at: Some(expires.to_rfc3339()),
+            ..Default::default()
+        };
+        let manager =
+            MultiTokenManager::new(Config::default(), vec![cred], None, None, false).unwrap();
+
+        let expires_at = manager.expires_at().unwrap();
+        assert_eq!(
+            DateTime::<Utc>::from(expires_at).timestamp(),
+            expires.timestamp()
+        );
+
+        let remaining = manager.time_to_expiry().unwrap();
+        assert!(remaining <= std::time::Duration::from_secs(30 * 60));
+        assert!(remaining > std::time::Duration::from_secs(29 * 60));
+    }
+
+    #[test]
+    fn test_time_to_expiry_expired_or_missing() {
+        let expired = KiroCredentials {
+            expires_at: Some("2020-01-01T00:00:00Z".to_string()),
+            ..Default::default()
+        };
+        let manager =
+            MultiTokenManager::new(Config::default(), vec![expired], None, None, false).unwrap();
+        assert!(manager.expires_at().is_some());
+        assert_eq!(manager.time_to_expiry(), Some(std::time::Duration::ZERO));
+
+        let manager = MultiTokenManager::new(
+            Config::default(),
+            vec![KiroCredentials::default()],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        assert!(manager.expires_at().is_none());
+        assert!(manager.time_to_expiry().is_none());
+    }
 }
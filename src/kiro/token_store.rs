@@ -0,0 +1,514 @@
+//! Token 存储后端
+//!
+//! 将 Token 的持久化抽象为 `TokenStore`，支持文件、环境变量和内存三种后端，
+//! 便于在容器中通过环境变量或 Secret 挂载提供凭据；
+//! 另可直接复用 Kiro IDE 已登录的凭据缓存（`KiroCacheTokenStore`）
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::kiro::atomic_file;
+use crate::kiro::model::credentials::{KiroCredentials, Redacted};
+
+/// 持久化的 Token 信息
+///
+/// `Debug` 输出会隐藏令牌
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredToken {
+    /// 访问令牌
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_token: Option<String>,
+
+    /// 刷新令牌
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+
+    /// 过期时间 (RFC3339 格式)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+}
+
+impl fmt::Debug for StoredToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StoredToken")
+            .field("access_token", &Redacted(&self.access_token))
+            .field("refresh_token", &Redacted(&self.refresh_token))
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+impl StoredToken {
+    /// 从凭据中提取 Token 信息
+    pub fn from_credentials(credentials: &KiroCredentials) -> Self {
+        Self {
+            access_token: credentials.access_token.clone(),
+            refresh_token: credentials.refresh_token.clone(),
+            expires_at: credentials.expires_at.clone(),
+        }
+    }
+
+    /// 将 Token 信息写入凭据（仅覆盖存在的字段）
+    #[allow(dead_code)]
+    pub fn apply_to(&self, credentials: &mut KiroCredentials) {
+        if self.access_token.is_some() {
+            credentials.access_token = self.access_token.clone();
+        }
+        if self.refresh_token.is_some() {
+            credentials.refresh_token = self.refresh_token.clone();
+        }
+        if self.expires_at.is_some() {
+            credentials.expires_at = self.expires_at.clone();
+        }
+    }
+}
+
+/// Token 存储后端
+pub trait TokenStore: Send + Sync {
+    /// 读取已保存的 Token，不存在时返回 `None`
+    fn load(&self) -> anyhow::Result<Option<StoredToken>>;
+
+    /// 保存 Token
+    fn save(&self, token: &StoredToken) -> anyhow::Result<()>;
+}
+
+/// 基于文件的 Token 存储（JSON 格式，原子写入）
+#[allow(dead_code)]
+pub struct FileTokenStore {
+    path: PathBuf,
+}
+
+impl FileTokenStore {
+    #[allow(dead_code)]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl TokenStore for FileTokenStore {
+    fn load(&self) -> anyhow::Result<Option<StoredToken>> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).with_context(|| format!("读取 Token 文件失败: {:?}", self.path));
+            }
+        };
+        if content.trim().is_empty() {
+            return Ok(None);
+        }
+        let token = serde_json::from_str(&content)
+            .with_context(|| format!("解析 Token 文件失败: {:?}", self.path))?;
+        Ok(Some(token))
+    }
+
+    fn save(&self, token: &StoredToken) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(token).context("序列化 Token 失败")?;
+        atomic_file::write(&self.path, json)
+            .with_context(|| format!("写入 Token 文件失败: {:?}", self.path))
+    }
+}
+
+/// Kiro IDE 凭据缓存文件（相对用户主目录）
+const KIRO_CACHE_PATH: [&str; 4] = [".aws", "sso", "cache", "kiro-auth-token.json"];
+
+/// Kiro IDE 凭据缓存中与 Token 相关的字段
+///
+/// 当前版本使用 camelCase；旧版本使用 snake_case，过期时间可能是 Unix 时间戳（秒或毫秒）
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KiroCacheFile {
+    #[serde(default, alias = "access_token")]
+    access_token: Option<String>,
+    #[serde(default, alias = "refresh_token")]
+    refresh_token: Option<String>,
+    #[serde(default, alias = "expires_at", alias = "expiration")]
+    expires_at: Option<Value>,
+}
+
+/// 将缓存中的过期时间统一为 RFC3339 格式
+fn normalize_expires_at(value: &Value) -> anyhow::Result<String> {
+    let time = match value {
+        Value::String(s) => DateTime::parse_from_rfc3339(s)
+            .with_context(|| format!("无法解析过期时间: {}", s))?
+            .with_timezone(&Utc),
+        Value::Number(n) => {
+            let ts = n
+                .as_i64()
+                .with_context(|| format!("无法解析过期时间: {}", n))?;
+            // 大于 1e12 视为毫秒
+            let millis = if ts > 1_000_000_000_000 {
+                ts
+            } else {
+                ts * 1000
+            };
+            DateTime::from_timestamp_millis(millis)
+                .with_context(|| format!("过期时间超出范围: {}", n))?
+        }
+        other => anyhow::bail!("无法解析过期时间: {}", other),
+    };
+    Ok(time.to_rfc3339())
+}
+
+/// 读取 Kiro IDE 凭据缓存的 Token 存储
+///
+/// 默认路径为 `~/.aws/sso/cache/kiro-auth-token.json`（Windows 下为 `%USERPROFILE%` 下的同名路径）。
+/// 文件不存在时 `load` 返回 `None`；`save` 只更新 Token 相关字段，保留 IDE 写入的其他字段，
+/// 已有文件无法解析时返回错误而不覆盖，写入通过临时文件 + rename 完成
+#[allow(dead_code)]
+pub struct KiroCacheTokenStore {
+    path: PathBuf,
+}
+
+impl KiroCacheTokenStore {
+    #[allow(dead_code)]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// 使用当前用户的默认缓存路径，无法确定用户主目录时返回 `None`
+    #[allow(dead_code)]
+    pub fn from_default_path() -> Option<Self> {
+        Self::default_path().map(Self::new)
+    }
+
+    /// 当前系统下 Kiro IDE 凭据缓存的默认路径
+    #[allow(dead_code)]
+    pub fn default_path() -> Option<PathBuf> {
+        let home = if cfg!(windows) {
+            std::env::var_os("USERPROFILE")
+        } else {
+            std::env::var_os("HOME")
+        }
+        .filter(|home| !home.is_empty())?;
+        Some(
+            KIRO_CACHE_PATH
+                .iter()
+                .fold(PathBuf::from(home), |path, part| path.join(part)),
+        )
+    }
+
+    #[allow(dead_code)]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl TokenStore for KiroCacheTokenStore {
+    fn load(&self) -> anyhow::Result<Option<StoredToken>> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("读取 Kiro IDE 凭据缓存失败: {:?}", self.path));
+            }
+        };
+        if content.trim().is_empty() {
+            return Ok(None);
+        }
+        let cache: KiroCacheFile = serde_json::from_str(&content)
+            .with_context(|| format!("解析 Kiro IDE 凭据缓存失败: {:?}", self.path))?;
+        let expires_at = cache
+            .expires_at
+            .as_ref()
+            .filter(|value| !value.is_null())
+            .map(normalize_expires_at)
+            .transpose()
+            .with_context(|| format!("解析 Kiro IDE 凭据缓存失败: {:?}", self.path))?;
+        Ok(Some(StoredToken {
+            access_token: cache.access_token,
+            refresh_token: cache.refresh_token,
+            expires_at,
+        }))
+    }
+
+    fn save(&self, token: &StoredToken) -> anyhow::Result<()> {
+        let mut cache = match fs::read_to_string(&self.path) {
+            Ok(content) if content.trim().is_empty() => serde_json::Map::new(),
+            // 无法解析时不覆盖，避免丢失 IDE 写入的 authMethod / region 等字段
+            Ok(content) => match serde_json::from_str(&content)
+                .with_context(|| format!("解析 Kiro IDE 凭据缓存失败: {:?}", self.path))?
+            {
+                Value::Object(map) => map,
+                _ => anyhow::bail!("Kiro IDE 凭据缓存不是 JSON 对象: {:?}", self.path),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => serde_json::Map::new(),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("读取 Kiro IDE 凭据缓存失败: {:?}", self.path));
+            }
+        };
+        // 统一写为当前版本的字段名
+        for legacy in ["access_token", "refresh_token", "expires_at", "expiration"] {
+            cache.remove(legacy);
+        }
+        let fields = [
+            ("accessToken", &token.access_token),
+            ("refreshToken", &token.refresh_token),
+            ("expiresAt", &token.expires_at),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                cache.insert(key.to_string(), Value::String(value.clone()));
+            }
+        }
+
+        let json = serde_json::to_string_pretty(&cache).context("序列化 Token 失败")?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("创建 Kiro IDE 凭据缓存目录失败: {:?}", parent))?;
+        }
+        atomic_file::write(&self.path, json)
+            .with_context(|| format!("写入 Kiro IDE 凭据缓存失败: {:?}", self.path))
+    }
+}
+
+/// 基于环境变量的 Token 存储
+///
+/// 从环境变量读取 JSON 格式的 Token；环境变量无法持久回写，
+/// 因此 `save` 仅在进程内生效，后续 `load` 返回最近保存的值
+#[allow(dead_code)]
+pub struct EnvTokenStore {
+    var: String,
+    saved: Mutex<Option<StoredToken>>,
+}
+
+impl EnvTokenStore {
+    #[allow(dead_code)]
+    pub fn new(var: impl Into<String>) -> Self {
+        Self {
+            var: var.into(),
+            saved: Mutex::new(None),
+        }
+    }
+}
+
+impl TokenStore for EnvTokenStore {
+    fn load(&self) -> anyhow::Result<Option<StoredToken>> {
+        self.load_from(std::env::var(&self.var).ok())
+    }
+
+    fn save(&self, token: &StoredToken) -> anyhow::Result<()> {
+        *self.saved.lock() = Some(token.clone());
+        Ok(())
+    }
+}
+
+impl EnvTokenStore {
+    /// 以 `value` 作为环境变量的值读取 Token（测试中无需修改进程环境变量）
+    fn load_from(&self, value: Option<String>) -> anyhow::Result<Option<StoredToken>> {
+        if let Some(token) = self.saved.lock().clone() {
+            return Ok(Some(token));
+        }
+        match value {
+            Some(value) if !value.trim().is_empty() => {
+                let token = serde_json::from_str(&value)
+                    .with_context(|| format!("解析环境变量 {} 失败", self.var))?;
+                Ok(Some(token))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// 内存 Token 存储（不持久化，适用于测试或临时运行）
+#[allow(dead_code)]
+#[derive(Default)]
+pub struct MemoryTokenStore {
+    token: Mutex<Option<StoredToken>>,
+}
+
+impl MemoryTokenStore {
+    #[allow(dead_code)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl TokenStore for MemoryTokenStore {
+    fn load(&self) -> anyhow::Result<Option<StoredToken>> {
+        Ok(self.token.lock().clone())
+    }
+
+    fn save(&self, token: &StoredToken) -> anyhow::Result<()> {
+        *self.token.lock() = Some(token.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_token() -> StoredToken {
+        StoredToken {
+            access_token: Some("access".to_string()),
+            refresh_token: Some("refresh".to_string()),
+            expires_at: Some("2030-01-01T00:00:00Z".to_string()),
+        }
+    }
+
+    fn assert_round_trip(store: &dyn TokenStore) {
+        let token = sample_token();
+        store.save(&token).unwrap();
+        assert_eq!(store.load().unwrap(), Some(token));
+    }
+
+    #[test]
+    fn test_file_token_store_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("kiro-rs-token-{}.json", uuid::Uuid::new_v4()));
+        let store = FileTokenStore::new(&path);
+        assert_eq!(store.load().unwrap(), None);
+
+        assert_round_trip(&store);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_env_token_store_round_trip() {
+        let var = format!("KIRO_RS_TEST_TOKEN_{}", uuid::Uuid::new_v4().simple());
+        let store = EnvTokenStore::new(&var);
+        assert_eq!(store.load().unwrap(), None);
+
+        assert_round_trip(&store);
+    }
+
+    #[test]
+    fn test_env_token_store_reads_env() {
+        let var = format!("KIRO_RS_TEST_TOKEN_{}", uuid::Uuid::new_v4().simple());
+        let json = serde_json::to_string(&sample_token()).unwrap();
+        let store = EnvTokenStore::new(&var);
+        assert_eq!(store.load_from(Some(json)).unwrap(), Some(sample_token()));
+        assert_eq!(store.load_from(Some(" ".to_string())).unwrap(), None);
+        assert!(store.load_from(Some("{".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_memory_token_store_round_trip() {
+        let store = MemoryTokenStore::new();
+        assert_eq!(store.load().unwrap(), None);
+
+        assert_round_trip(&store);
+    }
+
+    /// Kiro IDE 写入的凭据缓存样例
+    const KIRO_CACHE_FIXTURE: &str = r#"{
+        "accessToken": "aoaAAAAAcache-access",
+        "refreshToken": "aorAAAAAcache-refresh",
+        "expiresAt": "2030-01-01T00:00:00.000Z",
+        "authMethod": "social",
+        "provider": "Github",
+        "profileArn": "arn:aws:codewhisperer:us-east-1:699475941385:profile/EHGA3GRVQMUK"
+    }"#;
+
+    fn temp_cache_path() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("kiro-rs-cache-{}", uuid::Uuid::new_v4()))
+            .join("kiro-auth-token.json")
+    }
+
+    #[test]
+    fn test_kiro_cache_token_store_parses_fixture() {
+        let path = temp_cache_path();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, KIRO_CACHE_FIXTURE).unwrap();
+
+        let store = KiroCacheTokenStore::new(&path);
+        assert_eq!(
+            store.load().unwrap(),
+            Some(StoredToken {
+                access_token: Some("aoaAAAAAcache-access".to_string()),
+                refresh_token: Some("aorAAAAAcache-refresh".to_string()),
+                expires_at: Some("2030-01-01T00:00:00+00:00".to_string()),
+            })
+        );
+
+        // 保存后保留 IDE 写入的其他字段
+        store.save(&sample_token()).unwrap();
+        let saved: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["refreshToken"], "refresh");
+        assert_eq!(saved["authMethod"], "social");
+        assert_eq!(
+            store.load().unwrap().unwrap().access_token.unwrap(),
+            "access"
+        );
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_kiro_cache_token_store_legacy_schema_and_missing_file() {
+        let path = temp_cache_path();
+        let store = KiroCacheTokenStore::new(&path);
+        assert_eq!(store.load().unwrap(), None);
+
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(
+            &path,
+            r#"{"access_token":"a","refresh_token":"r","expires_at":1893456000000}"#,
+        )
+        .unwrap();
+        let token = store.load().unwrap().unwrap();
+        assert_eq!(token.refresh_token.as_deref(), Some("r"));
+        assert_eq!(
+            token.expires_at.as_deref(),
+            Some("2030-01-01T00:00:00+00:00")
+        );
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_kiro_cache_token_store_malformed_file() {
+        let path = temp_cache_path();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let store = KiroCacheTokenStore::new(&path);
+
+        fs::write(&path, "{\"accessToken\": ").unwrap();
+        let err = store.load().unwrap_err();
+        assert!(format!("{:#}", err).contains("解析 Kiro IDE 凭据缓存失败"));
+
+        fs::write(&path, r#"{"refreshToken":"r","expiresAt":"tomorrow"}"#).unwrap();
+        assert!(store.load().is_err());
+
+        // 无法解析的已有文件不会被覆盖
+        for content in ["{\"authMethod\": \"social\", ", "[1, 2]"] {
+            fs::write(&path, content).unwrap();
+            assert!(store.save(&sample_token()).is_err());
+            assert_eq!(fs::read_to_string(&path).unwrap(), content);
+        }
+        // 写入失败时不残留临时文件
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_debug_redacts_tokens() {
+        let token = StoredToken {
+            access_token: Some("aoaAT9xQzLmWvB3k".to_string()),
+            refresh_token: Some("aorRT5pJhYnGcE8u".to_string()),
+            expires_at: Some("2030-01-01T00:00:00Z".to_string()),
+        };
+
+        let output = format!("{:?}", token);
+        assert_eq!(
+            output,
+            "StoredToken { access_token: Some(\"***redacted***\"), refresh_token: Some(\"***redacted***\"), expires_at: Some(\"2030-01-01T00:00:00Z\") }"
+        );
+        assert!(!output.contains("aoaAT9") && !output.contains("aorRT5"));
+    }
+}
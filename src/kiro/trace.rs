@@ -0,0 +1,63 @@
+//! 请求生命周期的 tracing span
+//!
+//! 启用 `tracing` feature 后生成结构化 span，未启用时返回 `Span::none()`，调用方无需条件编译。
+//! span 字段只记录模型、尝试次数、状态码、字节数等元信息，绝不记录 Token
+
+use tracing::Span;
+
+/// 一次 Provider 请求（含全部重试与响应解析）
+pub(crate) fn request_span(model: &str, stream: bool) -> Span {
+    #[cfg(feature = "tracing")]
+    {
+        tracing::info_span!("kiro.request", model, stream)
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = (model, stream);
+        Span::none()
+    }
+}
+
+/// 单次发送尝试，`status` 在拿到响应头后记录
+pub(crate) fn attempt_span(attempt: usize, max_attempts: usize) -> Span {
+    #[cfg(feature = "tracing")]
+    {
+        tracing::info_span!(
+            "kiro.attempt",
+            attempt,
+            max_attempts,
+            credential_id = tracing::field::Empty,
+            status = tracing::field::Empty,
+        )
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = (attempt, max_attempts);
+        Span::none()
+    }
+}
+
+/// 响应体解析循环，`bytes_received` / `events` 随读取进度更新
+pub(crate) fn parse_span() -> Span {
+    #[cfg(feature = "tracing")]
+    {
+        tracing::info_span!("kiro.parse", bytes_received = 0u64, events = 0u64)
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        Span::none()
+    }
+}
+
+/// Token 刷新
+pub(crate) fn refresh_span(credential_id: Option<u64>, auth_method: &str) -> Span {
+    #[cfg(feature = "tracing")]
+    {
+        tracing::info_span!("kiro.token_refresh", credential_id, auth_method)
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = (credential_id, auth_method);
+        Span::none()
+    }
+}
@@ -5,6 +5,9 @@ mod common;
 mod http_client;
 mod kiro;
 mod model;
+mod openai;
+#[cfg(test)]
+mod test_support;
 pub mod token;
 
 use std::sync::Arc;
@@ -100,6 +103,27 @@ async fn main() {
         proxy: proxy_config,
     });
 
+    // 初始化图片大小限制
+    if let Some(limit) = config.max_image_bytes {
+        anthropic::image::init_max_image_bytes(limit);
+    }
+
+    // 初始化请求字段透传白名单
+    anthropic::converter::init_passthrough_fields(config.passthrough_fields.clone());
+    anthropic::converter::init_merge_consecutive_messages(config.merge_consecutive_messages);
+
+    // 初始化流式文本增量合并（任一阈值配置后启用，未配置的阈值取默认值）
+    if config.text_coalesce_bytes.is_some() || config.text_coalesce_delay_ms.is_some() {
+        let default = anthropic::stream::TextCoalescing::default();
+        anthropic::stream::init_text_coalescing(anthropic::stream::TextCoalescing {
+            max_bytes: config.text_coalesce_bytes.unwrap_or(default.max_bytes),
+            max_delay: config
+                .text_coalesce_delay_ms
+                .map(std::time::Duration::from_millis)
+                .unwrap_or(default.max_delay),
+        });
+    }
+
     // 构建 Anthropic API 路由（从第一个凭据获取 profile_arn）
     let anthropic_app = anthropic::create_router_with_provider(
         &api_key,
@@ -58,6 +58,26 @@ pub struct Config {
     /// Admin API 密钥（可选，启用 Admin API 功能）
     #[serde(default)]
     pub admin_api_key: Option<String>,
+
+    /// 单张图片的最大字节数（解码后，可选，默认 5MB）
+    #[serde(default)]
+    pub max_image_bytes: Option<usize>,
+
+    /// 原样透传到 Kiro 请求的客户端顶层字段（如 `user`），不在列表内的字段直接丢弃
+    #[serde(default)]
+    pub passthrough_fields: Vec<String>,
+
+    /// 是否将连续的同角色消息合并为一轮（默认开启）
+    #[serde(default = "default_merge_consecutive_messages")]
+    pub merge_consecutive_messages: bool,
+
+    /// 流式文本增量合并：缓冲的文本达到该字节数时输出（可选，与 `textCoalesceDelayMs` 任一设置即启用）
+    #[serde(default)]
+    pub text_coalesce_bytes: Option<usize>,
+
+    /// 流式文本增量合并：缓冲的文本最多等待的毫秒数（可选）
+    #[serde(default)]
+    pub text_coalesce_delay_ms: Option<u64>,
 }
 
 fn default_host() -> String {
@@ -89,6 +109,10 @@ fn default_count_tokens_auth_type() -> String {
     "x-api-key".to_string()
 }
 
+fn default_merge_consecutive_messages() -> bool {
+    true
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -107,6 +131,11 @@ impl Default for Config {
             proxy_username: None,
             proxy_password: None,
             admin_api_key: None,
+            max_image_bytes: None,
+            passthrough_fields: Vec::new(),
+            merge_consecutive_messages: default_merge_consecutive_messages(),
+            text_coalesce_bytes: None,
+            text_coalesce_delay_ms: None,
         }
     }
 }
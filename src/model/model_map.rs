@@ -0,0 +1,156 @@
+//! 模型名称映射
+//!
+//! 将客户端请求的模型名（如 `gpt-4o`、`claude-3-5-sonnet`）翻译为 Kiro 内部模型 ID
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// 内置的精确映射（键均为小写）
+const BUILTIN_MODELS: &[(&str, &str)] = &[
+    ("gpt-4o", "claude-sonnet-4.5"),
+    ("gpt-4o-mini", "claude-haiku-4.5"),
+    ("gpt-4.1", "claude-sonnet-4.5"),
+    ("gpt-4.1-mini", "claude-haiku-4.5"),
+    ("o1", "claude-opus-4.5"),
+    ("o3", "claude-opus-4.5"),
+];
+
+/// 内置的模型系列映射：模型名包含关键字即命中
+const BUILTIN_FAMILIES: &[(&str, &str)] = &[
+    ("sonnet", "claude-sonnet-4.5"),
+    ("opus", "claude-opus-4.5"),
+    ("haiku", "claude-haiku-4.5"),
+];
+
+/// 未知模型错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownModelError {
+    pub model: String,
+}
+
+impl fmt::Display for UnknownModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "模型不支持: {}", self.model)
+    }
+}
+
+impl std::error::Error for UnknownModelError {}
+
+/// 模型映射表
+///
+/// 解析顺序：精确匹配（不区分大小写）→ 系列关键字 → 兜底模型
+#[derive(Debug, Clone)]
+pub struct ModelMap {
+    /// 精确映射（键为小写）
+    models: HashMap<String, String>,
+    /// 系列关键字映射
+    families: Vec<(String, String)>,
+    /// 未知模型的兜底模型，为空时返回错误
+    fallback: Option<String>,
+}
+
+impl Default for ModelMap {
+    fn default() -> Self {
+        Self {
+            models: BUILTIN_MODELS
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            families: BUILTIN_FAMILIES
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            fallback: None,
+        }
+    }
+}
+
+impl ModelMap {
+    /// 在内置映射基础上追加自定义映射，同名时自定义映射优先
+    #[allow(dead_code)]
+    pub fn from_map(overrides: HashMap<String, String>) -> Self {
+        let mut map = Self::default();
+        map.models
+            .extend(overrides.into_iter().map(|(k, v)| (k.to_lowercase(), v)));
+        map
+    }
+
+    /// 设置未知模型的兜底模型
+    #[allow(dead_code)]
+    pub fn with_fallback(mut self, model: impl Into<String>) -> Self {
+        self.fallback = Some(model.into());
+        self
+    }
+
+    /// 解析请求的模型名
+    pub fn resolve_model(&self, requested: &str) -> Result<&str, UnknownModelError> {
+        let lower = requested.to_lowercase();
+
+        if let Some(model) = self.models.get(&lower) {
+            return Ok(model);
+        }
+
+        if let Some((_, model)) = self
+            .families
+            .iter()
+            .find(|(keyword, _)| lower.contains(keyword.as_str()))
+        {
+            return Ok(model);
+        }
+
+        self.fallback.as_deref().ok_or_else(|| UnknownModelError {
+            model: requested.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_known_models() {
+        let map = ModelMap::default();
+        assert_eq!(map.resolve_model("gpt-4o").unwrap(), "claude-sonnet-4.5");
+        assert_eq!(
+            map.resolve_model("GPT-4o-mini").unwrap(),
+            "claude-haiku-4.5"
+        );
+        assert_eq!(
+            map.resolve_model("claude-3-5-sonnet-20241022").unwrap(),
+            "claude-sonnet-4.5"
+        );
+        assert_eq!(
+            map.resolve_model("claude-opus-4").unwrap(),
+            "claude-opus-4.5"
+        );
+    }
+
+    #[test]
+    fn test_custom_mapping_overrides_builtin() {
+        let map = ModelMap::from_map(HashMap::from([
+            ("GPT-4o".to_string(), "claude-opus-4.5".to_string()),
+            ("my-model".to_string(), "claude-haiku-4.5".to_string()),
+        ]));
+        assert_eq!(map.resolve_model("gpt-4o").unwrap(), "claude-opus-4.5");
+        assert_eq!(map.resolve_model("my-model").unwrap(), "claude-haiku-4.5");
+    }
+
+    #[test]
+    fn test_unknown_model_with_fallback() {
+        let map = ModelMap::default().with_fallback("claude-sonnet-4.5");
+        assert_eq!(map.resolve_model("llama-3").unwrap(), "claude-sonnet-4.5");
+    }
+
+    #[test]
+    fn test_unknown_model_without_fallback() {
+        let err = ModelMap::default().resolve_model("llama-3").unwrap_err();
+        assert_eq!(
+            err,
+            UnknownModelError {
+                model: "llama-3".to_string()
+            }
+        );
+        assert_eq!(err.to_string(), "模型不支持: llama-3");
+    }
+}
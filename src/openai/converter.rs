@@ -0,0 +1,891 @@
+//! OpenAI → Kiro 协议转换器
+//!
+//! 先将 OpenAI Chat Completions 请求转换为等价的 Anthropic Messages 请求，
+//! 再复用 Anthropic 转换器生成 Kiro 请求，保证两条链路的历史配对、工具占位等行为一致
+//!
+//! 中间请求会去掉 system 消息并合并相邻同角色消息，其错误路径经 `MessageOrigins`
+//! 改写为客户端原始请求中的位置
+
+use serde_json::{Value, json};
+
+use crate::anthropic::converter::{ConversionError, convert_request};
+use crate::anthropic::types::{Message, MessagesRequest, SystemMessage, Tool};
+use crate::kiro::error::KiroError;
+use crate::kiro::model::requests::kiro::KiroRequest;
+
+use super::types::{ChatFunction, ChatFunctionCall, ChatMessage, OpenAiChatRequest, StopSequences};
+
+/// 未指定 max_tokens 时的默认值
+const DEFAULT_MAX_TOKENS: i32 = 4096;
+
+/// 将 OpenAI Chat Completions 请求转换为 Kiro 请求
+pub fn from_openai_chat(req: OpenAiChatRequest) -> Result<KiroRequest, KiroError> {
+    let (messages_request, origins) = to_messages_request(req)?;
+    let result = convert_request(&messages_request).map_err(|e| origins.rewrite(e))?;
+
+    Ok(KiroRequest {
+        conversation_state: result.conversation_state,
+        profile_arn: None,
+        inference_config: Some(result.inference_config),
+        metadata: result.metadata,
+    })
+}
+
+/// 中间请求内容块在原始 OpenAI 请求中的来源
+#[derive(Debug, Clone)]
+struct BlockOrigin {
+    /// 来源消息在原始请求中的下标
+    message: usize,
+    /// 来源位置（JSON Pointer）
+    path: String,
+    /// 中间内容块字段 → 原始字段（相对于 `path`）
+    fields: &'static [(&'static str, &'static str)],
+}
+
+/// 中间请求内容块及其来源
+type Block = (Value, BlockOrigin);
+
+// 各类内容块的字段对应关系（中间请求字段 → 原始字段）
+const IMAGE_FIELDS: &[(&str, &str)] = &[("source", "image_url")];
+const TOOL_RESULT_FIELDS: &[(&str, &str)] =
+    &[("tool_use_id", "tool_call_id"), ("content", "content")];
+const FUNCTION_RESULT_FIELDS: &[(&str, &str)] = &[("content", "content")];
+const TOOL_CALL_FIELDS: &[(&str, &str)] = &[
+    ("id", "id"),
+    ("name", "function/name"),
+    ("input", "function/arguments"),
+];
+const FUNCTION_CALL_FIELDS: &[(&str, &str)] = &[("name", "name"), ("input", "arguments")];
+
+impl BlockOrigin {
+    fn new(message: usize, path: String) -> Self {
+        Self {
+            message,
+            path,
+            fields: &[],
+        }
+    }
+
+    fn with_fields(mut self, fields: &'static [(&'static str, &'static str)]) -> Self {
+        self.fields = fields;
+        self
+    }
+}
+
+/// 中间 Anthropic 请求各内容块的来源，用于还原错误路径
+#[derive(Debug, Default)]
+pub(crate) struct MessageOrigins {
+    messages: Vec<Vec<BlockOrigin>>,
+}
+
+impl MessageOrigins {
+    /// 将指向中间请求的错误路径改写为原始请求中的位置
+    ///
+    /// `/messages/N/content/M/<字段>` 按内容块来源改写，无法对应的字段省略；
+    /// 其余路径保持不变
+    pub(crate) fn rewrite(&self, err: ConversionError) -> ConversionError {
+        let ConversionError::At { path, source } = err else {
+            return err;
+        };
+        let path = self.original_path(&path).unwrap_or(path);
+        ConversionError::At { path, source }
+    }
+
+    fn original_path(&self, path: &str) -> Option<String> {
+        let (message, rest) = split_index(path.strip_prefix("/messages/")?)?;
+        let blocks = self.messages.get(message)?;
+        let Some((block, rest)) = rest
+            .strip_prefix("/content/")
+            .and_then(split_index)
+            .and_then(|(index, rest)| Some((blocks.get(index)?, rest)))
+        else {
+            return Some(format!("/messages/{}", blocks.first()?.message));
+        };
+
+        let field = rest.strip_prefix('/').and_then(|r| r.split('/').next());
+        match field.and_then(|f| block.fields.iter().find(|(from, _)| *from == f)) {
+            Some((_, to)) => Some(format!("{}/{}", block.path, to)),
+            None => Some(block.path.clone()),
+        }
+    }
+}
+
+/// 拆分路径开头的数组下标
+fn split_index(path: &str) -> Option<(usize, &str)> {
+    let end = path.find('/').unwrap_or(path.len());
+    Some((path[..end].parse().ok()?, &path[end..]))
+}
+
+/// 将 OpenAI 请求转换为 Anthropic Messages 请求，并返回中间请求各内容块的来源
+///
+/// 同时接受旧版 `functions` / `function_call` 字段，新旧字段同时存在时以新版为准
+pub(crate) fn to_messages_request(
+    req: OpenAiChatRequest,
+) -> Result<(MessagesRequest, MessageOrigins), ConversionError> {
+    let mut system = Vec::new();
+    let mut messages: Vec<Message> = Vec::new();
+    let mut origins = MessageOrigins::default();
+    // 旧版函数调用没有 ID，按出现顺序生成，并分配给随后的 function 结果消息
+    let mut legacy_calls = 0usize;
+    let mut pending_function_call: Option<String> = None;
+
+    for (index, msg) in req.messages.into_iter().enumerate() {
+        let base = format!("/messages/{}", index);
+        let (role, blocks): (&str, Vec<Block>) = match msg.role.as_str() {
+            "system" | "developer" => {
+                let text = content_text(&msg.content);
+                if !text.is_empty() {
+                    system.push(SystemMessage {
+                        text,
+                        cache_control: None,
+                    });
+                }
+                continue;
+            }
+            "user" => ("user", user_blocks(index, &msg)),
+            "tool" => {
+                let block = tool_result_block(&msg).map_err(|e| e.at(&base))?;
+                let origin = BlockOrigin::new(index, base).with_fields(TOOL_RESULT_FIELDS);
+                ("user", vec![(block, origin)])
+            }
+            "function" => {
+                let id = pending_function_call.take().ok_or_else(|| {
+                    ConversionError::InvalidRequest(
+                        "function 消息之前没有对应的 function_call".to_string(),
+                    )
+                    .at(&base)
+                })?;
+                let block = function_result_block(&id, &msg);
+                let origin = BlockOrigin::new(index, base).with_fields(FUNCTION_RESULT_FIELDS);
+                ("user", vec![(block, origin)])
+            }
+            "assistant" => {
+                let mut blocks = assistant_blocks(index, &msg)?;
+                if let Some(call) = &msg.function_call {
+                    legacy_calls += 1;
+                    let id = format!("call_legacy_{}", legacy_calls);
+                    let path = format!("{}/function_call", base);
+                    let block = tool_use_block(&id, call)
+                        .map_err(|e| e.at(format_args!("{}/arguments", path)))?;
+                    let origin = BlockOrigin::new(index, path).with_fields(FUNCTION_CALL_FIELDS);
+                    blocks.push((block, origin));
+                    pending_function_call = Some(id);
+                }
+                ("assistant", blocks)
+            }
+            other => {
+                return Err(ConversionError::InvalidRequest(format!(
+                    "不支持的消息角色: {}",
+                    other
+                ))
+                .at(format_args!("{}/role", base)));
+            }
+        };
+
+        if blocks.is_empty() {
+            continue;
+        }
+
+        let (blocks, block_origins): (Vec<_>, Vec<_>) = blocks.into_iter().unzip();
+        // 合并相邻的同角色消息（如连续的多条 tool 结果），Kiro 要求 user/assistant 交替
+        match messages.last_mut() {
+            Some(last) if last.role == role => {
+                if let Value::Array(existing) = &mut last.content {
+                    existing.extend(blocks);
+                }
+                if let Some(last) = origins.messages.last_mut() {
+                    last.extend(block_origins);
+                }
+            }
+            _ => {
+                messages.push(Message {
+                    role: role.to_string(),
+                    content: Value::Array(blocks),
+                });
+                origins.messages.push(block_origins);
+            }
+        }
+    }
+
+    let request = MessagesRequest {
+        model: req.model,
+        max_tokens: req
+            .max_completion_tokens
+            .or(req.max_tokens)
+            .unwrap_or(DEFAULT_MAX_TOKENS),
+        messages,
+        stream: req.stream,
+        system: (!system.is_empty()).then_some(system),
+        tools: match (req.tools, req.functions) {
+            (Some(tools), _) => Some(
+                tools
+                    .iter()
+                    .map(|t| convert_function(&t.function))
+                    .collect(),
+            ),
+            (None, Some(functions)) => Some(functions.iter().map(convert_function).collect()),
+            (None, None) => None,
+        },
+        tool_choice: match (req.tool_choice, req.function_call) {
+            (Some(choice), _) => {
+                Some(convert_tool_choice(choice).map_err(|e| e.at("/tool_choice"))?)
+            }
+            (None, Some(call)) => {
+                Some(convert_function_call(call).map_err(|e| e.at("/function_call"))?)
+            }
+            (None, None) => None,
+        },
+        thinking: None,
+        temperature: req.temperature,
+        top_p: req.top_p,
+        stop_sequences: req.stop.map(StopSequences::into_vec),
+        metadata: None,
+        extra: req.extra,
+    };
+    Ok((request, origins))
+}
+
+/// 将 OpenAI 的 tool_choice 转换为 Anthropic 格式
+///
+/// - `"auto"` / `"none"` → 同名模式
+/// - `"required"` → `any`
+/// - `{"type":"function","function":{"name":...}}` → `{"type":"tool","name":...}`
+fn convert_tool_choice(choice: Value) -> Result<Value, ConversionError> {
+    match &choice {
+        Value::String(mode) => match mode.as_str() {
+            "auto" | "none" => Ok(json!({"type": mode})),
+            "required" => Ok(json!({"type": "any"})),
+            _ => Err(ConversionError::InvalidRequest(format!(
+                "无法识别的 tool_choice: {}",
+                mode
+            ))),
+        },
+        Value::Object(obj) if obj.get("type").and_then(|t| t.as_str()) == Some("function") => {
+            let name = obj
+                .get("function")
+                .and_then(|f| f.get("name"))
+                .and_then(|n| n.as_str())
+                .ok_or_else(|| {
+                    ConversionError::InvalidRequest("tool_choice 缺少 function.name".to_string())
+                })?;
+            Ok(json!({"type": "tool", "name": name}))
+        }
+        _ => Err(ConversionError::InvalidRequest(format!(
+            "无法识别的 tool_choice: {}",
+            choice
+        ))),
+    }
+}
+
+/// 将旧版 function_call 转换为 Anthropic 格式
+///
+/// - `"auto"` / `"none"` → 同名模式
+/// - `{"name":...}` → `{"type":"tool","name":...}`
+fn convert_function_call(call: Value) -> Result<Value, ConversionError> {
+    match &call {
+        Value::String(mode) if mode == "auto" || mode == "none" => Ok(json!({"type": mode})),
+        Value::Object(obj) => {
+            let name = obj.get("name").and_then(|n| n.as_str()).ok_or_else(|| {
+                ConversionError::InvalidRequest("function_call 缺少 name".to_string())
+            })?;
+            Ok(json!({"type": "tool", "name": name}))
+        }
+        _ => Err(ConversionError::InvalidRequest(format!(
+            "无法识别的 function_call: {}",
+            call
+        ))),
+    }
+}
+
+/// 提取纯文本内容（字符串或 text 片段数组）
+fn content_text(content: &Option<Value>) -> String {
+    match content {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// 转换 user 消息内容
+fn user_blocks(index: usize, msg: &ChatMessage) -> Vec<Block> {
+    let content = format!("/messages/{}/content", index);
+    match &msg.content {
+        Some(Value::String(s)) => vec![(
+            json!({"type": "text", "text": s}),
+            BlockOrigin::new(index, content),
+        )],
+        Some(Value::Array(parts)) => parts
+            .iter()
+            .enumerate()
+            .filter_map(|(part_index, part)| {
+                let origin = BlockOrigin::new(index, format!("{}/{}", content, part_index));
+                match part.get("type").and_then(|t| t.as_str()) {
+                    Some("text") => part
+                        .get("text")
+                        .and_then(|t| t.as_str())
+                        .map(|text| (json!({"type": "text", "text": text}), origin)),
+                    Some("image_url") => {
+                        image_block(part).map(|block| (block, origin.with_fields(IMAGE_FIELDS)))
+                    }
+                    _ => None,
+                }
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// 转换 image_url 片段，仅支持 `data:<media_type>;base64,<data>` 形式
+fn image_block(part: &Value) -> Option<Value> {
+    let url = part
+        .get("image_url")
+        .and_then(|u| u.get("url").or(Some(u)))
+        .and_then(|u| u.as_str())?;
+
+    let Some((media_type, data)) = url
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(";base64,"))
+    else {
+        tracing::warn!("忽略非 base64 data URL 的图片");
+        return None;
+    };
+
+    Some(json!({
+        "type": "image",
+        "source": {
+            "type": "base64",
+            "media_type": media_type,
+            "data": data
+        }
+    }))
+}
+
+/// 转换 tool 消息为 tool_result 块
+fn tool_result_block(msg: &ChatMessage) -> Result<Value, ConversionError> {
+    let tool_call_id = msg.tool_call_id.as_ref().ok_or_else(|| {
+        ConversionError::InvalidRequest("tool 消息缺少 tool_call_id".to_string())
+            .at("/tool_call_id")
+    })?;
+
+    Ok(json!({
+        "type": "tool_result",
+        "tool_use_id": tool_call_id,
+        "content": content_text(&msg.content)
+    }))
+}
+
+/// 转换旧版 function 消息为 tool_result 块
+fn function_result_block(tool_use_id: &str, msg: &ChatMessage) -> Value {
+    json!({
+        "type": "tool_result",
+        "tool_use_id": tool_use_id,
+        "content": content_text(&msg.content)
+    })
+}
+
+/// 构造 tool_use 块，参数需为合法 JSON（空字符串视为 `{}`）
+fn tool_use_block(id: &str, call: &ChatFunctionCall) -> Result<Value, ConversionError> {
+    let input: Value = if call.arguments.trim().is_empty() {
+        json!({})
+    } else {
+        serde_json::from_str(&call.arguments).map_err(|e| {
+            ConversionError::InvalidRequest(format!("工具调用参数不是合法 JSON (id={}): {}", id, e))
+        })?
+    };
+
+    Ok(json!({
+        "type": "tool_use",
+        "id": id,
+        "name": call.name,
+        "input": input
+    }))
+}
+
+/// 转换 assistant 消息内容（文本 + 工具调用）
+fn assistant_blocks(index: usize, msg: &ChatMessage) -> Result<Vec<Block>, ConversionError> {
+    let mut blocks = Vec::new();
+
+    let text = content_text(&msg.content);
+    if !text.is_empty() {
+        blocks.push((
+            json!({"type": "text", "text": text}),
+            BlockOrigin::new(index, format!("/messages/{}/content", index)),
+        ));
+    }
+
+    for (call_index, call) in msg.tool_calls.iter().flatten().enumerate() {
+        let path = format!("/messages/{}/tool_calls/{}", index, call_index);
+        let block = tool_use_block(&call.id, &call.function)
+            .map_err(|e| e.at(format_args!("{}/function/arguments", path)))?;
+        blocks.push((
+            block,
+            BlockOrigin::new(index, path).with_fields(TOOL_CALL_FIELDS),
+        ));
+    }
+
+    Ok(blocks)
+}
+
+/// 转换工具（函数）定义
+fn convert_function(function: &ChatFunction) -> Tool {
+    let input_schema = match &function.parameters {
+        Some(Value::Object(map)) => map.clone().into_iter().collect(),
+        _ => [("type".to_string(), json!("object"))]
+            .into_iter()
+            .collect(),
+    };
+
+    Tool {
+        name: function.name.clone(),
+        description: function.description.clone().unwrap_or_default(),
+        input_schema,
+        cache_control: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::model::requests::conversation::Message as KiroMessage;
+
+    fn parse(value: Value) -> OpenAiChatRequest {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_single_turn() {
+        let req = parse(json!({
+            "model": "claude-sonnet-4.5",
+            "max_tokens": 256,
+            "temperature": 0.2,
+            "messages": [
+                {"role": "system", "content": "You are terse."},
+                {"role": "user", "content": "Hello"}
+            ]
+        }));
+
+        let kiro = from_openai_chat(req).unwrap();
+        let state = &kiro.conversation_state;
+        let current = &state.current_message.user_input_message;
+
+        assert_eq!(current.content, "Hello");
+        assert_eq!(current.model_id, "claude-sonnet-4.5");
+
+        // 系统消息折叠为历史中的 user + assistant 配对
+        assert_eq!(state.history.len(), 2);
+        match &state.history[0] {
+            KiroMessage::User(user) => {
+                assert_eq!(user.user_input_message.content, "You are terse.")
+            }
+            other => panic!("expected user message, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_multi_turn_with_tools() {
+        let req = parse(json!({
+            "model": "claude-sonnet-4.5",
+            "messages": [
+                {"role": "system", "content": "Use tools when helpful."},
+                {"role": "user", "content": "Weather in Paris and Rome?"},
+                {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [
+                        {"id": "call_1", "type": "function",
+                         "function": {"name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}},
+                        {"id": "call_2", "type": "function",
+                         "function": {"name": "get_weather", "arguments": "{\"city\":\"Rome\"}"}}
+                    ]
+                },
+                {"role": "tool", "tool_call_id": "call_1", "content": "18C"},
+                {"role": "tool", "tool_call_id": "call_2", "content": "24C"}
+            ],
+            "tools": [{
+                "type": "function",
+                "function": {
+                    "name": "get_weather",
+                    "description": "Get the weather",
+                    "parameters": {
+                        "type": "object",
+                        "properties": {"city": {"type": "string"}},
+                        "required": ["city"]
+                    }
+                }
+            }]
+        }));
+
+        let kiro = from_openai_chat(req).unwrap();
+        let state = &kiro.conversation_state;
+
+        // system 配对 + user + assistant(tool_calls)
+        assert_eq!(state.history.len(), 4);
+        match &state.history[3] {
+            KiroMessage::Assistant(assistant) => {
+                let tool_uses = assistant
+                    .assistant_response_message
+                    .tool_uses
+                    .as_ref()
+                    .unwrap();
+                assert_eq!(tool_uses.len(), 2);
+                assert_eq!(tool_uses[0].tool_use_id, "call_1");
+                assert_eq!(tool_uses[1].input, json!({"city": "Rome"}));
+            }
+            other => panic!("expected assistant message, got {:?}", other),
+        }
+
+        // 连续的 tool 消息合并为当前消息的多个 tool_result
+        let context = &state
+            .current_message
+            .user_input_message
+            .user_input_message_context;
+        let results = &context.tool_results;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].tool_use_id, "call_1");
+        assert_eq!(results[1].tool_use_id, "call_2");
+
+        let tools = &context.tools;
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].tool_specification.name, "get_weather");
+        assert_eq!(
+            tools[0].tool_specification.input_schema.json["required"],
+            json!(["city"])
+        );
+    }
+
+    /// 1x1 透明 PNG
+    const TINY_PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+
+    fn image_request(url: String) -> OpenAiChatRequest {
+        parse(json!({
+            "model": "claude-sonnet-4.5",
+            "messages": [{
+                "role": "user",
+                "content": [
+                    {"type": "text", "text": "What is this?"},
+                    {"type": "image_url", "image_url": {"url": url}}
+                ]
+            }]
+        }))
+    }
+
+    #[test]
+    fn test_image_data_url() {
+        let req = image_request(format!("data:image/png;base64,{}", TINY_PNG));
+        let kiro = from_openai_chat(req).unwrap();
+        let current = &kiro.conversation_state.current_message.user_input_message;
+
+        assert_eq!(current.content, "What is this?");
+        assert_eq!(current.images.len(), 1);
+        assert_eq!(current.images[0].format, "png");
+        assert_eq!(current.images[0].source.bytes, TINY_PNG);
+    }
+
+    #[test]
+    fn test_image_unsupported_media_type() {
+        let req = image_request(format!("data:image/tiff;base64,{}", TINY_PNG));
+        assert!(matches!(
+            from_openai_chat(req),
+            Err(KiroError::Conversion(e)) if matches!(e.reason(), ConversionError::InvalidImage(_))
+        ));
+    }
+
+    #[test]
+    fn test_inference_parameters() {
+        let req = parse(json!({
+            "model": "claude-sonnet-4.5",
+            "max_completion_tokens": 512,
+            "temperature": 1.8,
+            "top_p": 0.9,
+            "stop": "END",
+            "messages": [{"role": "user", "content": "hi"}]
+        }));
+        let config = from_openai_chat(req).unwrap().inference_config.unwrap();
+        assert_eq!(config.max_tokens, Some(512));
+        // OpenAI 的 temperature 范围为 0~2，上游仅支持 0~1
+        assert_eq!(config.temperature, Some(1.0));
+        assert_eq!(config.top_p, Some(0.9));
+        assert_eq!(config.stop_sequences, vec!["END".to_string()]);
+
+        let req = parse(json!({
+            "model": "claude-sonnet-4.5",
+            "max_tokens": 0,
+            "stop": ["\n\n", "Observation:"],
+            "messages": [{"role": "user", "content": "hi"}]
+        }));
+        let config = from_openai_chat(req).unwrap().inference_config.unwrap();
+        assert_eq!(config.max_tokens, Some(1));
+        assert_eq!(config.temperature, None);
+        assert_eq!(config.stop_sequences, vec!["\n\n", "Observation:"]);
+    }
+
+    #[test]
+    fn test_invalid_role_and_arguments() {
+        let req = parse(json!({
+            "model": "claude-sonnet-4.5",
+            "messages": [{"role": "narrator", "content": "hi"}]
+        }));
+        let Err(KiroError::Conversion(err)) = from_openai_chat(req) else {
+            panic!("expected conversion error");
+        };
+        assert!(matches!(err.reason(), ConversionError::InvalidRequest(_)));
+        assert_eq!(err.path(), Some("/messages/0/role"));
+
+        let req = parse(json!({
+            "model": "claude-sonnet-4.5",
+            "messages": [
+                {"role": "user", "content": "hi"},
+                {"role": "assistant", "tool_calls": [
+                    {"id": "c", "function": {"name": "f", "arguments": "{not json"}}
+                ]},
+                {"role": "tool", "tool_call_id": "c", "content": "ok"}
+            ]
+        }));
+        let Err(KiroError::Conversion(err)) = from_openai_chat(req) else {
+            panic!("expected conversion error");
+        };
+        assert!(matches!(err.reason(), ConversionError::InvalidRequest(_)));
+        assert_eq!(
+            err.path(),
+            Some("/messages/1/tool_calls/0/function/arguments")
+        );
+    }
+
+    fn conversion_error(req: OpenAiChatRequest) -> ConversionError {
+        match from_openai_chat(req) {
+            Err(KiroError::Conversion(err)) => err,
+            other => panic!("expected conversion error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_error_paths_point_into_openai_request() {
+        // 开头的 system 消息不进入中间请求，被忽略的片段不占用内容块下标
+        let req = parse(json!({
+            "model": "claude-sonnet-4.5",
+            "messages": [
+                {"role": "system", "content": "Be brief."},
+                {"role": "user", "content": [
+                    {"type": "text", "text": "What is this?"},
+                    {"type": "input_audio", "input_audio": {}},
+                    {"type": "image_url", "image_url": {"url": format!("data:image/tiff;base64,{}", TINY_PNG)}}
+                ]}
+            ]
+        }));
+        let err = conversion_error(req);
+        assert!(matches!(err.reason(), ConversionError::InvalidImage(_)));
+        assert_eq!(err.path(), Some("/messages/1/content/2/image_url"));
+
+        // 相邻的 tool 消息被合并为一条 user 消息
+        let req = parse(json!({
+            "model": "claude-sonnet-4.5",
+            "messages": [
+                {"role": "system", "content": "Be brief."},
+                {"role": "user", "content": "Weather?"},
+                {"role": "assistant", "tool_calls": [
+                    {"id": "a", "function": {"name": "f", "arguments": "{}"}}
+                ]},
+                {"role": "tool", "tool_call_id": "a", "content": "ok"},
+                {"role": "tool", "tool_call_id": "b", "content": "ok"}
+            ]
+        }));
+        assert_eq!(
+            conversion_error(req).path(),
+            Some("/messages/4/tool_call_id")
+        );
+
+        let req = parse(json!({
+            "model": "claude-sonnet-4.5",
+            "messages": [
+                {"role": "developer", "content": "Be brief."},
+                {"role": "tool", "content": "ok"}
+            ]
+        }));
+        assert_eq!(
+            conversion_error(req).path(),
+            Some("/messages/1/tool_call_id")
+        );
+    }
+
+    fn tool_choice_request(tool_choice: Value) -> OpenAiChatRequest {
+        parse(json!({
+            "model": "claude-sonnet-4.5",
+            "tools": [{
+                "type": "function",
+                "function": {"name": "get_weather", "parameters": {"type": "object"}}
+            }],
+            "tool_choice": tool_choice,
+            "messages": [{"role": "user", "content": "Weather?"}]
+        }))
+    }
+
+    #[test]
+    fn test_tool_choice_mapping() {
+        let cases = [
+            (json!("auto"), json!({"type": "auto"})),
+            (json!("none"), json!({"type": "none"})),
+            (json!("required"), json!({"type": "any"})),
+            (
+                json!({"type": "function", "function": {"name": "get_weather"}}),
+                json!({"type": "tool", "name": "get_weather"}),
+            ),
+        ];
+        for (choice, expected) in cases {
+            let req = to_messages_request(tool_choice_request(choice)).unwrap().0;
+            assert_eq!(req.tool_choice, Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_tool_choice_end_to_end() {
+        let tools = |kiro: &KiroRequest| {
+            kiro.conversation_state
+                .current_message
+                .user_input_message
+                .user_input_message_context
+                .tools
+                .len()
+        };
+        assert_eq!(
+            tools(&from_openai_chat(tool_choice_request(json!("auto"))).unwrap()),
+            1
+        );
+        assert_eq!(
+            tools(&from_openai_chat(tool_choice_request(json!("none"))).unwrap()),
+            0
+        );
+
+        for choice in [
+            json!("required"),
+            json!({"type": "function", "function": {"name": "get_weather"}}),
+        ] {
+            let err = from_openai_chat(tool_choice_request(choice)).unwrap_err();
+            assert!(matches!(
+                err,
+                KiroError::Conversion(ConversionError::UnsupportedToolChoice(_))
+            ));
+        }
+
+        for choice in [json!("sometimes"), json!({"type": "function"})] {
+            let err = to_messages_request(tool_choice_request(choice)).unwrap_err();
+            assert!(matches!(err.reason(), ConversionError::InvalidRequest(_)));
+            assert_eq!(err.path(), Some("/tool_choice"));
+        }
+    }
+
+    #[test]
+    fn test_extra_fields_are_carried_over() {
+        let req = parse(json!({
+            "model": "claude-sonnet-4.5",
+            "messages": [{"role": "user", "content": "Hello"}],
+            "user": "user-42"
+        }));
+        let messages_request = to_messages_request(req).unwrap().0;
+        assert_eq!(messages_request.extra["user"], "user-42");
+    }
+
+    fn weather_function(name: &str) -> Value {
+        json!({
+            "name": name,
+            "description": "Get the weather",
+            "parameters": {"type": "object", "properties": {"city": {"type": "string"}}}
+        })
+    }
+
+    #[test]
+    fn test_legacy_functions_request() {
+        let req = parse(json!({
+            "model": "claude-sonnet-4.5",
+            "functions": [weather_function("get_weather")],
+            "function_call": {"name": "get_weather"},
+            "messages": [
+                {"role": "user", "content": "Weather in Paris?"},
+                {"role": "assistant", "content": null, "function_call": {
+                    "name": "get_weather", "arguments": "{\"city\":\"Paris\"}"
+                }},
+                {"role": "function", "name": "get_weather", "content": "18C"}
+            ]
+        }));
+        let messages_request = to_messages_request(req.clone()).unwrap().0;
+        let tools = messages_request.tools.as_ref().unwrap();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].name, "get_weather");
+        assert_eq!(
+            messages_request.tool_choice,
+            Some(json!({"type": "tool", "name": "get_weather"}))
+        );
+        assert_eq!(
+            messages_request.messages[1].content,
+            json!([{
+                "type": "tool_use",
+                "id": "call_legacy_1",
+                "name": "get_weather",
+                "input": {"city": "Paris"}
+            }])
+        );
+        assert_eq!(
+            messages_request.messages[2].content[0]["tool_use_id"],
+            "call_legacy_1"
+        );
+
+        let mut req = req;
+        req.function_call = Some(json!("auto"));
+        let kiro = from_openai_chat(req).unwrap();
+        let results = &kiro
+            .conversation_state
+            .current_message
+            .user_input_message
+            .user_input_message_context
+            .tool_results;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].tool_use_id, "call_legacy_1");
+
+        // function 结果之前没有 function_call
+        let req = parse(json!({
+            "model": "claude-sonnet-4.5",
+            "messages": [{"role": "function", "name": "get_weather", "content": "18C"}]
+        }));
+        let err = to_messages_request(req).unwrap_err();
+        assert!(matches!(err.reason(), ConversionError::InvalidRequest(_)));
+        assert_eq!(err.path(), Some("/messages/0"));
+    }
+
+    #[test]
+    fn test_modern_tools_request() {
+        let req = parse(json!({
+            "model": "claude-sonnet-4.5",
+            "tools": [{"type": "function", "function": weather_function("get_weather")}],
+            "tool_choice": "auto",
+            "messages": [{"role": "user", "content": "Weather?"}]
+        }));
+        let messages_request = to_messages_request(req).unwrap().0;
+        assert_eq!(messages_request.tools.unwrap()[0].name, "get_weather");
+        assert_eq!(messages_request.tool_choice, Some(json!({"type": "auto"})));
+    }
+
+    #[test]
+    fn test_modern_fields_win_over_legacy() {
+        let req = parse(json!({
+            "model": "claude-sonnet-4.5",
+            "tools": [{"type": "function", "function": weather_function("get_forecast")}],
+            "tool_choice": "none",
+            "functions": [weather_function("get_weather")],
+            "function_call": {"name": "get_weather"},
+            "messages": [{"role": "user", "content": "Weather?"}]
+        }));
+        let messages_request = to_messages_request(req).unwrap().0;
+        let names: Vec<_> = messages_request
+            .tools
+            .unwrap()
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        assert_eq!(names, vec!["get_forecast"]);
+        assert_eq!(messages_request.tool_choice, Some(json!({"type": "none"})));
+        assert!(!messages_request.extra.contains_key("functions"));
+    }
+}
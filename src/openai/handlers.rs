@@ -0,0 +1,344 @@
+//! OpenAI API Handler 函数
+
+use std::convert::Infallible;
+
+use axum::{
+    Json as JsonExtractor,
+    body::Body,
+    extract::State,
+    http::{StatusCode, header},
+    response::{IntoResponse, Json, Response},
+};
+use bytes::Bytes;
+use futures::{StreamExt, stream};
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+use crate::anthropic::AppState;
+use crate::kiro::completion::{CompletionContent, CompletionResponse};
+use crate::kiro::error::KiroError;
+use crate::kiro::finish_reason::{FinishReason, StopSequenceTracker};
+use crate::kiro::parser::stream::ParsedEvent;
+
+use super::converter::from_openai_chat;
+use super::stream::{StreamSerState, finish_openai_sse, to_openai_sse_chunk};
+use super::types::OpenAiChatRequest;
+
+/// POST /v1/chat/completions
+///
+/// 创建对话补全，`stream` 为 true 时以 `chat.completion.chunk` SSE 返回
+pub async fn post_chat_completions(
+    State(state): State<AppState>,
+    JsonExtractor(payload): JsonExtractor<OpenAiChatRequest>,
+) -> Response {
+    tracing::info!(
+        model = %payload.model,
+        stream = %payload.stream,
+        message_count = %payload.messages.len(),
+        "Received POST /v1/chat/completions request"
+    );
+    let Some(provider) = state.kiro_provider.clone() else {
+        tracing::error!("KiroProvider 未配置");
+        return error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "service_unavailable",
+            "Kiro API provider not configured",
+            None,
+        );
+    };
+
+    let model = payload.model.clone();
+    let is_stream = payload.stream;
+    let stop_sequences = payload
+        .stop
+        .clone()
+        .map(|stop| stop.into_vec())
+        .unwrap_or_default();
+
+    let mut kiro_request = match from_openai_chat(payload) {
+        Ok(req) => req,
+        Err(KiroError::Conversion(e)) => {
+            tracing::warn!("请求转换失败: {}", e);
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                "invalid_request_error",
+                &e.to_string(),
+                e.path(),
+            );
+        }
+        Err(e) => return upstream_error(e),
+    };
+    kiro_request.profile_arn = state.profile_arn.clone();
+
+    if is_stream {
+        let events = match provider.stream_completion(kiro_request).await {
+            Ok(events) => events,
+            Err(e) => return upstream_error(e),
+        };
+
+        let ser = StreamSerState::new(model).with_stop_sequences(stop_sequences);
+        let body = stream::unfold(Some((Box::pin(events), ser)), |s| async move {
+            let (mut events, mut ser) = s?;
+            let frame = match events.next().await {
+                Some(Ok(event)) => to_openai_sse_chunk(&event, &mut ser).unwrap_or_default(),
+                // 超过响应上限前已发出截断事件，按 length 正常结束
+                Some(Err(KiroError::ResponseTooLarge(_))) => {
+                    return Some((finish_openai_sse(&mut ser), None));
+                }
+                Some(Err(e)) => {
+                    tracing::error!("读取响应流失败: {}", e);
+                    let event = ParsedEvent::Error {
+                        code: "api_error".to_string(),
+                        message: e.to_string(),
+                    };
+                    let mut out = to_openai_sse_chunk(&event, &mut ser).unwrap_or_default();
+                    out.push_str(&finish_openai_sse(&mut ser));
+                    return Some((out, None));
+                }
+                None => return Some((finish_openai_sse(&mut ser), None)),
+            };
+            Some((frame, Some((events, ser))))
+        })
+        .filter(|frame| std::future::ready(!frame.is_empty()))
+        .map(|frame| Ok::<_, Infallible>(Bytes::from(frame)));
+
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "text/event-stream")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .header(header::CONNECTION, "keep-alive")
+            .body(Body::from_stream(body))
+            .unwrap()
+    } else {
+        match provider.complete(kiro_request).await {
+            Ok(response) => {
+                Json(chat_completion(&response, &model, stop_sequences)).into_response()
+            }
+            Err(e) => upstream_error(e),
+        }
+    }
+}
+
+/// 将拼装好的响应转换为 `chat.completion` 对象
+///
+/// 文本以停止序列结尾时去掉该序列；思考内容放入 `reasoning`
+fn chat_completion(
+    response: &CompletionResponse,
+    model: &str,
+    stop_sequences: Vec<String>,
+) -> Value {
+    let mut stop = StopSequenceTracker::new(stop_sequences);
+    let mut content = String::new();
+    let mut tool_calls = Vec::new();
+    for block in &response.content {
+        match block {
+            CompletionContent::Text(text) => content.push_str(&stop.push(text)),
+            CompletionContent::ToolUse(tool_use) => {
+                content.push_str(&stop.reset());
+                let arguments = tool_use
+                    .raw_input
+                    .clone()
+                    .unwrap_or_else(|| tool_use.input.to_string());
+                tool_calls.push(json!({
+                    "id": tool_use.id,
+                    "type": "function",
+                    "function": { "name": tool_use.name, "arguments": arguments }
+                }));
+            }
+            CompletionContent::Thinking { .. } => {}
+        }
+    }
+    match response.stop_reason {
+        FinishReason::Stop => content.push_str(&stop.finish().0),
+        _ => content.push_str(&stop.reset()),
+    }
+
+    // 只有工具调用时 content 为 null
+    let content = if content.is_empty() && !tool_calls.is_empty() {
+        Value::Null
+    } else {
+        json!(content)
+    };
+    let mut message = json!({ "role": "assistant", "content": content });
+    if !tool_calls.is_empty() {
+        message["tool_calls"] = json!(tool_calls);
+    }
+    let thinking = response.thinking();
+    if !thinking.is_empty() {
+        message["reasoning"] = json!(thinking);
+    }
+
+    let usage = &response.usage;
+    json!({
+        "id": format!("chatcmpl-{}", Uuid::new_v4().simple()),
+        "object": "chat.completion",
+        "created": chrono::Utc::now().timestamp(),
+        "model": model,
+        "choices": [{
+            "index": 0,
+            "message": message,
+            "finish_reason": response.stop_reason.openai()
+        }],
+        "usage": {
+            "prompt_tokens": usage.input_tokens,
+            "completion_tokens": usage.output_tokens,
+            "total_tokens": usage.input_tokens + usage.output_tokens
+        }
+    })
+}
+
+/// 上游调用失败
+fn upstream_error(e: KiroError) -> Response {
+    tracing::error!("Kiro API 调用失败: {}", e);
+    error_response(
+        StatusCode::BAD_GATEWAY,
+        "api_error",
+        &format!("上游 API 调用失败: {}", e),
+        None,
+    )
+}
+
+/// OpenAI 格式的错误响应，`param` 为出错字段的位置
+fn error_response(
+    status: StatusCode,
+    error_type: &str,
+    message: &str,
+    param: Option<&str>,
+) -> Response {
+    let body = json!({
+        "error": {
+            "message": message,
+            "type": error_type,
+            "param": param,
+            "code": null
+        }
+    });
+    (status, Json(body)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::kiro::model::credentials::KiroCredentials;
+    use crate::kiro::provider::KiroProvider;
+    use crate::kiro::token_manager::MultiTokenManager;
+    use crate::model::config::Config;
+    use crate::test_support::{MockResponse, MockServer, encode_event};
+
+    fn app_state(server: &MockServer) -> AppState {
+        let credentials = KiroCredentials {
+            access_token: Some("test_token".to_string()),
+            refresh_token: Some("a".repeat(150)),
+            expires_at: Some((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+        let tm = MultiTokenManager::new(Config::default(), vec![credentials], None, None, false)
+            .unwrap();
+        let provider =
+            KiroProvider::new(Arc::new(tm)).with_base_url(server.url("/generateAssistantResponse"));
+        AppState::new("key").with_kiro_provider(provider)
+    }
+
+    fn upstream_body() -> Vec<u8> {
+        let mut body = encode_event("assistantResponseEvent", r#"{"content":"Hello wor"}"#);
+        body.extend(encode_event(
+            "assistantResponseEvent",
+            r#"{"content":"ld END"}"#,
+        ));
+        body
+    }
+
+    fn chat_request(stream: bool) -> OpenAiChatRequest {
+        serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5-20250929",
+            "messages": [{ "role": "user", "content": "Hi" }],
+            "stop": " END",
+            "stream": stream
+        }))
+        .unwrap()
+    }
+
+    async fn body_string(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_non_stream_chat_completion() {
+        let server =
+            MockServer::start(vec![MockResponse::new(200).with_body(upstream_body())]).await;
+        let response = post_chat_completions(
+            State(app_state(&server)),
+            JsonExtractor(chat_request(false)),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body: Value = serde_json::from_str(&body_string(response).await).unwrap();
+        assert_eq!(body["object"], "chat.completion");
+        assert_eq!(body["model"], "claude-sonnet-4-5-20250929");
+        assert_eq!(body["choices"][0]["message"]["role"], "assistant");
+        assert_eq!(body["choices"][0]["message"]["content"], "Hello world");
+        assert_eq!(body["choices"][0]["finish_reason"], "stop");
+        assert_eq!(server.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stream_chat_completion() {
+        let server =
+            MockServer::start(vec![MockResponse::new(200).with_body(upstream_body())]).await;
+        let response =
+            post_chat_completions(State(app_state(&server)), JsonExtractor(chat_request(true)))
+                .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/event-stream"
+        );
+
+        let body = body_string(response).await;
+        let frames: Vec<&str> = body
+            .split("\n\n")
+            .filter(|frame| !frame.is_empty())
+            .map(|frame| frame.strip_prefix("data: ").unwrap())
+            .collect();
+        assert_eq!(frames.last(), Some(&"[DONE]"));
+
+        let chunks: Vec<Value> = frames[..frames.len() - 1]
+            .iter()
+            .map(|frame| serde_json::from_str(frame).unwrap())
+            .collect();
+        let text: String = chunks
+            .iter()
+            .filter_map(|c| c["choices"][0]["delta"]["content"].as_str())
+            .collect();
+        assert_eq!(text, "Hello world");
+        assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+        assert_eq!(
+            chunks.last().unwrap()["choices"][0]["finish_reason"],
+            "stop"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_conversion_error_reports_param() {
+        let server = MockServer::start(vec![MockResponse::new(200)]).await;
+        let request: OpenAiChatRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5-20250929",
+            "messages": [{ "role": "narrator", "content": "Hi" }]
+        }))
+        .unwrap();
+        let response =
+            post_chat_completions(State(app_state(&server)), JsonExtractor(request)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body: Value = serde_json::from_str(&body_string(response).await).unwrap();
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+        assert_eq!(body["error"]["param"], "/messages/0/role");
+        assert_eq!(server.request_count(), 0);
+    }
+}
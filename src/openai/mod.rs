@@ -0,0 +1,9 @@
+//! OpenAI API 兼容模块
+//!
+//! 将 OpenAI Chat Completions 请求转换为 Kiro 请求，并将 Kiro 事件序列化为
+//! OpenAI 流式响应，通过 `POST /v1/chat/completions` 供 OpenAI 兼容客户端直接接入
+
+pub mod converter;
+pub mod handlers;
+pub mod stream;
+pub mod types;
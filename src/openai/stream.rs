@@ -0,0 +1,481 @@
+//! OpenAI 流式响应序列化
+//!
+//! 将解析后的 Kiro 事件转换为 `chat.completion.chunk` SSE 帧
+
+use std::collections::HashMap;
+
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+use crate::kiro::finish_reason::{FinishReason, FinishTracker, StopSequenceTracker};
+use crate::kiro::parser::stream::ParsedEvent;
+
+/// 流式序列化状态
+///
+/// 同一次响应的所有 chunk 共享 `id`、`created` 与 `model`
+#[derive(Debug)]
+pub struct StreamSerState {
+    /// 补全 ID（`chatcmpl-` 前缀）
+    pub id: String,
+    /// 模型名称
+    pub model: String,
+    /// 创建时间（Unix 秒）
+    pub created: i64,
+    /// 是否已发送 role delta
+    role_sent: bool,
+    /// 工具调用 ID -> tool_calls 数组下标
+    tool_indices: HashMap<String, usize>,
+    /// 结束原因跟踪
+    finish: FinishTracker,
+    /// 停止序列跟踪，命中的停止序列不输出
+    stop: StopSequenceTracker,
+}
+
+impl StreamSerState {
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            id: format!("chatcmpl-{}", Uuid::new_v4().simple()),
+            model: model.into(),
+            created: chrono::Utc::now().timestamp(),
+            role_sent: false,
+            tool_indices: HashMap::new(),
+            finish: FinishTracker::new(),
+            stop: StopSequenceTracker::default(),
+        }
+    }
+
+    /// 设置请求中的停止序列：以停止序列结束时，输出的文本不包含该序列
+    pub fn with_stop_sequences(mut self, stop_sequences: Vec<String>) -> Self {
+        self.stop = StopSequenceTracker::new(stop_sequences);
+        self
+    }
+
+    /// 最终的结束原因
+    pub fn finish_reason(&self) -> FinishReason {
+        self.finish.reason()
+    }
+
+    /// 构造一个 chunk SSE 帧
+    fn chunk(&mut self, mut delta: Value, finish_reason: Option<&str>) -> String {
+        if !self.role_sent {
+            self.role_sent = true;
+            delta["role"] = json!("assistant");
+        }
+
+        let chunk = json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{
+                "index": 0,
+                "delta": delta,
+                "finish_reason": finish_reason
+            }]
+        });
+        sse_data(&chunk.to_string())
+    }
+
+    /// 构造文本 chunk，文本为空时不输出
+    fn text_chunk(&mut self, content: String) -> String {
+        if content.is_empty() {
+            return String::new();
+        }
+        self.chunk(json!({ "content": content }), None)
+    }
+}
+
+/// 将单个事件序列化为 SSE 帧，无需输出时返回 `None`
+pub fn to_openai_sse_chunk(event: &ParsedEvent, state: &mut StreamSerState) -> Option<String> {
+    state.finish.observe(event);
+
+    // 可能属于停止序列的文本结尾先保留，确定不是后再输出
+    if let ParsedEvent::AssistantResponse(resp) = event {
+        let content = state.stop.push(&resp.content);
+        let chunk = state.text_chunk(content);
+        return (!chunk.is_empty()).then_some(chunk);
+    }
+
+    let output = event_output(event, state)?;
+    // 其他内容之前先输出保留的文本，role delta 随之落在最先输出的 chunk 上
+    let content = state.stop.reset();
+    let mut out = state.text_chunk(content);
+    match output {
+        EventOutput::Delta(delta) => out.push_str(&state.chunk(delta, None)),
+        EventOutput::Error(error) => out.push_str(&sse_data(&error.to_string())),
+    }
+    Some(out)
+}
+
+/// 文本以外的事件的输出内容
+enum EventOutput {
+    /// chunk 的 delta
+    Delta(Value),
+    /// 独立的 error 帧
+    Error(Value),
+}
+
+/// 转换文本以外的事件，无需输出时返回 `None`
+fn event_output(event: &ParsedEvent, state: &mut StreamSerState) -> Option<EventOutput> {
+    match event {
+        // 思考内容以 `reasoning` 增量输出，不混入 `content`
+        ParsedEvent::ThinkingDelta(thinking) if !thinking.text.is_empty() => {
+            Some(EventOutput::Delta(json!({ "reasoning": thinking.text })))
+        }
+        ParsedEvent::ToolUse(tool_use) => {
+            let next_index = state.tool_indices.len();
+            let call = match state.tool_indices.get(&tool_use.tool_use_id) {
+                Some(_) if tool_use.input.is_empty() => return None,
+                Some(&index) => json!({
+                    "index": index,
+                    "function": { "arguments": tool_use.input }
+                }),
+                None => {
+                    state
+                        .tool_indices
+                        .insert(tool_use.tool_use_id.clone(), next_index);
+                    json!({
+                        "index": next_index,
+                        "id": tool_use.tool_use_id,
+                        "type": "function",
+                        "function": {
+                            "name": tool_use.name,
+                            "arguments": tool_use.input
+                        }
+                    })
+                }
+            };
+            Some(EventOutput::Delta(json!({ "tool_calls": [call] })))
+        }
+        ParsedEvent::Error { code, message } => Some(EventOutput::Error(json!({
+            "error": {
+                "message": message,
+                "type": "upstream_error",
+                "code": code
+            }
+        }))),
+        _ => None,
+    }
+}
+
+/// 生成结束帧：携带 `finish_reason` 的最后一个 chunk 与 `[DONE]` 哨兵
+///
+/// 正常结束时先输出仍保留的文本（去掉结尾命中的停止序列）。
+/// 上游返回错误时已输出 error 帧，只发送 `[DONE]`
+pub fn finish_openai_sse(state: &mut StreamSerState) -> String {
+    let reason = state.finish_reason();
+    let content = match reason {
+        FinishReason::Stop => state.stop.finish().0,
+        _ => state.stop.reset(),
+    };
+    let mut out = state.text_chunk(content);
+    if let Some(finish_reason) = reason.openai() {
+        out.push_str(&state.chunk(json!({}), Some(finish_reason)));
+    }
+    out.push_str(&sse_data("[DONE]"));
+    out
+}
+
+fn sse_data(data: &str) -> String {
+    format!("data: {}\n\n", data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::model::events::{AssistantResponseEvent, ReasoningContentEvent, ToolUseEvent};
+
+    fn text(content: &str) -> ParsedEvent {
+        let event: AssistantResponseEvent =
+            serde_json::from_value(json!({ "content": content })).unwrap();
+        ParsedEvent::AssistantResponse(event)
+    }
+
+    fn tool(input: &str, stop: bool) -> ParsedEvent {
+        ParsedEvent::ToolUse(ToolUseEvent {
+            name: "get_weather".to_string(),
+            tool_use_id: "tooluse_1".to_string(),
+            input: input.to_string(),
+            stop,
+        })
+    }
+
+    /// 解析 SSE 输出为 data 负载列表
+    fn frames(output: &str) -> Vec<String> {
+        output
+            .split("\n\n")
+            .filter(|frame| !frame.is_empty())
+            .map(|frame| frame.strip_prefix("data: ").unwrap().to_string())
+            .collect()
+    }
+
+    fn assert_chunk_schema(chunk: &Value, state: &StreamSerState) {
+        assert_eq!(chunk["id"], json!(state.id));
+        assert_eq!(chunk["object"], "chat.completion.chunk");
+        assert_eq!(chunk["model"], json!(state.model));
+        assert_eq!(chunk["created"], json!(state.created));
+        assert_eq!(chunk["choices"][0]["index"], 0);
+        assert!(chunk["choices"][0]["delta"].is_object());
+    }
+
+    #[test]
+    fn test_text_stream() {
+        let mut state = StreamSerState::new("claude-sonnet-4.5");
+        let mut output = String::new();
+        for event in [text("Hel"), text(""), text("lo")] {
+            output.extend(to_openai_sse_chunk(&event, &mut state));
+        }
+        output.push_str(&finish_openai_sse(&mut state));
+
+        let frames = frames(&output);
+        assert_eq!(frames.len(), 4);
+        assert_eq!(frames.last().unwrap(), "[DONE]");
+
+        let chunks: Vec<Value> = frames[..3]
+            .iter()
+            .map(|f| serde_json::from_str(f).unwrap())
+            .collect();
+        for chunk in &chunks {
+            assert_chunk_schema(chunk, &state);
+        }
+
+        // role 仅出现在首个 chunk
+        assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+        assert_eq!(chunks[0]["choices"][0]["delta"]["content"], "Hel");
+        assert!(chunks[1]["choices"][0]["delta"].get("role").is_none());
+        assert_eq!(chunks[1]["choices"][0]["delta"]["content"], "lo");
+
+        assert_eq!(chunks[2]["choices"][0]["delta"], json!({}));
+        assert_eq!(chunks[2]["choices"][0]["finish_reason"], "stop");
+        assert!(chunks[0]["choices"][0]["finish_reason"].is_null());
+    }
+
+    #[test]
+    fn test_thinking_stream_uses_reasoning_deltas() {
+        let thinking = |text: &str| ParsedEvent::ThinkingDelta(ReasoningContentEvent::new(text));
+        let mut state = StreamSerState::new("claude-sonnet-4.5");
+        let mut output = String::new();
+        for event in [
+            thinking("Let me "),
+            thinking("think."),
+            text("4"),
+            thinking(""),
+            thinking("Sure."),
+            text("!"),
+        ] {
+            output.extend(to_openai_sse_chunk(&event, &mut state));
+        }
+
+        let deltas: Vec<Value> = frames(&output)
+            .iter()
+            .map(|f| serde_json::from_str::<Value>(f).unwrap()["choices"][0]["delta"].clone())
+            .collect();
+        let deltas: Vec<(Option<&str>, Option<&str>)> = deltas
+            .iter()
+            .map(|d| (d["reasoning"].as_str(), d["content"].as_str()))
+            .collect();
+        assert_eq!(
+            deltas,
+            vec![
+                (Some("Let me "), None),
+                (Some("think."), None),
+                (None, Some("4")),
+                (Some("Sure."), None),
+                (None, Some("!")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tool_call_stream() {
+        let mut state = StreamSerState::new("claude-sonnet-4.5");
+        let mut output = String::new();
+        for event in [
+            text("Checking."),
+            tool("", false),
+            tool("{\"city\":", false),
+            tool("\"Paris\"}", false),
+            tool("", true),
+        ] {
+            output.extend(to_openai_sse_chunk(&event, &mut state));
+        }
+        output.push_str(&finish_openai_sse(&mut state));
+
+        let frames = frames(&output);
+        assert_eq!(frames.last().unwrap(), "[DONE]");
+        let chunks: Vec<Value> = frames[..frames.len() - 1]
+            .iter()
+            .map(|f| serde_json::from_str(f).unwrap())
+            .collect();
+
+        // 文本 + 工具开始 + 两段参数 + 结束
+        assert_eq!(chunks.len(), 5);
+        let start = &chunks[1]["choices"][0]["delta"]["tool_calls"][0];
+        assert_eq!(start["index"], 0);
+        assert_eq!(start["id"], "tooluse_1");
+        assert_eq!(start["type"], "function");
+        assert_eq!(start["function"]["name"], "get_weather");
+
+        let arguments: String = chunks[2..4]
+            .iter()
+            .map(|c| {
+                let call = &c["choices"][0]["delta"]["tool_calls"][0];
+                assert_eq!(call["index"], 0);
+                call["function"]["arguments"].as_str().unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(arguments, "{\"city\":\"Paris\"}");
+
+        assert_eq!(chunks[4]["choices"][0]["finish_reason"], "tool_calls");
+    }
+
+    #[test]
+    fn test_interleaved_tool_calls() {
+        let call = |id: &str, input: &str, stop: bool| {
+            ParsedEvent::ToolUse(ToolUseEvent {
+                name: format!("fn_{}", id),
+                tool_use_id: id.to_string(),
+                input: input.to_string(),
+                stop,
+            })
+        };
+
+        let mut state = StreamSerState::new("m");
+        let mut output = String::new();
+        for event in [
+            call("a", "{\"x\":", false),
+            call("b", "{\"y\":", false),
+            call("a", "1}", true),
+            call("b", "2}", true),
+        ] {
+            output.extend(to_openai_sse_chunk(&event, &mut state));
+        }
+        output.push_str(&finish_openai_sse(&mut state));
+
+        let frames = frames(&output);
+        let mut ids: Vec<Option<String>> = Vec::new();
+        let mut arguments: Vec<String> = Vec::new();
+        for frame in &frames[..frames.len() - 1] {
+            let chunk: Value = serde_json::from_str(frame).unwrap();
+            let Some(calls) = chunk["choices"][0]["delta"]["tool_calls"].as_array() else {
+                continue;
+            };
+            for call in calls {
+                let index = call["index"].as_u64().unwrap() as usize;
+                if index == ids.len() {
+                    ids.push(None);
+                    arguments.push(String::new());
+                }
+                if let Some(id) = call["id"].as_str() {
+                    ids[index] = Some(id.to_string());
+                }
+                arguments[index].push_str(call["function"]["arguments"].as_str().unwrap());
+            }
+        }
+
+        assert_eq!(ids, vec![Some("a".to_string()), Some("b".to_string())]);
+        assert_eq!(arguments, vec!["{\"x\":1}", "{\"y\":2}"]);
+    }
+
+    #[test]
+    fn test_stop_sequence_is_trimmed() {
+        let mut state = StreamSerState::new("m").with_stop_sequences(vec!["END".to_string()]);
+        let mut output = String::new();
+        for event in [text("The answer is 4. E"), text("N"), text("D")] {
+            output.extend(to_openai_sse_chunk(&event, &mut state));
+        }
+        output.push_str(&finish_openai_sse(&mut state));
+
+        let chunks: Vec<Value> = frames(&output)
+            .iter()
+            .filter(|f| *f != "[DONE]")
+            .map(|f| serde_json::from_str(f).unwrap())
+            .collect();
+        let content: String = chunks
+            .iter()
+            .filter_map(|c| c["choices"][0]["delta"]["content"].as_str())
+            .collect();
+        assert_eq!(content, "The answer is 4. ");
+        assert_eq!(
+            chunks.last().unwrap()["choices"][0]["finish_reason"],
+            "stop"
+        );
+    }
+
+    #[test]
+    fn test_partial_stop_sequence_is_released() {
+        let mut state = StreamSerState::new("m").with_stop_sequences(vec!["END".to_string()]);
+        let mut output = String::new();
+        for event in [text("Go to the EN"), text("Dless loop"), tool("{}", true)] {
+            output.extend(to_openai_sse_chunk(&event, &mut state));
+        }
+        output.push_str(&finish_openai_sse(&mut state));
+
+        let content: String = frames(&output)
+            .iter()
+            .filter(|f| *f != "[DONE]")
+            .map(|f| serde_json::from_str::<Value>(f).unwrap())
+            .filter_map(|c| {
+                c["choices"][0]["delta"]["content"]
+                    .as_str()
+                    .map(str::to_string)
+            })
+            .collect();
+        assert_eq!(content, "Go to the ENDless loop");
+    }
+
+    #[test]
+    fn test_held_text_carries_role_before_tool_call() {
+        let mut state = StreamSerState::new("m").with_stop_sequences(vec!["END".to_string()]);
+        // "E" 可能是停止序列的开头，先被保留，随工具调用之前输出
+        assert_eq!(to_openai_sse_chunk(&text("E"), &mut state), None);
+        let output = to_openai_sse_chunk(&tool("{}", true), &mut state).unwrap();
+
+        let chunks: Vec<Value> = frames(&output)
+            .iter()
+            .map(|f| serde_json::from_str(f).unwrap())
+            .collect();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+        assert_eq!(chunks[0]["choices"][0]["delta"]["content"], "E");
+        assert!(chunks[1]["choices"][0]["delta"].get("role").is_none());
+        assert!(chunks[1]["choices"][0]["delta"]["tool_calls"].is_array());
+    }
+
+    #[test]
+    fn test_length_finish_reason() {
+        let mut state = StreamSerState::new("m");
+        let event = ParsedEvent::Exception {
+            exception_type: "ContentLengthExceededException".to_string(),
+            message: String::new(),
+        };
+        assert!(to_openai_sse_chunk(&event, &mut state).is_none());
+
+        let output = finish_openai_sse(&mut state);
+        let chunk: Value = serde_json::from_str(&frames(&output)[0]).unwrap();
+        assert_eq!(chunk["choices"][0]["finish_reason"], "length");
+        // 没有任何内容时，结束 chunk 仍携带 role
+        assert_eq!(chunk["choices"][0]["delta"]["role"], "assistant");
+    }
+
+    #[test]
+    fn test_upstream_error_omits_finish_chunk() {
+        let mut state = StreamSerState::new("m");
+        let mut output = String::new();
+        let error = ParsedEvent::Error {
+            code: "InternalServerException".to_string(),
+            message: "boom".to_string(),
+        };
+        for event in [text("Hel"), error] {
+            output.extend(to_openai_sse_chunk(&event, &mut state));
+        }
+        assert_eq!(state.finish_reason(), FinishReason::Error);
+        output.push_str(&finish_openai_sse(&mut state));
+
+        let frames = frames(&output);
+        assert_eq!(frames.len(), 3);
+        let error: Value = serde_json::from_str(&frames[1]).unwrap();
+        assert_eq!(error["error"]["code"], "InternalServerException");
+        assert_eq!(frames[2], "[DONE]");
+    }
+}
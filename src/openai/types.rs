@@ -0,0 +1,117 @@
+//! OpenAI Chat Completions 类型定义
+
+use serde::{Deserialize, Serialize};
+
+/// Chat Completions 请求体
+#[derive(Debug, Clone, Deserialize)]
+pub struct OpenAiChatRequest {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    #[serde(default)]
+    pub max_tokens: Option<i32>,
+    /// 新版字段，与 `max_tokens` 含义相同
+    #[serde(default)]
+    pub max_completion_tokens: Option<i32>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub top_p: Option<f32>,
+    /// 停止序列，字符串或字符串数组
+    #[serde(default)]
+    pub stop: Option<StopSequences>,
+    #[serde(default)]
+    pub stream: bool,
+    #[serde(default)]
+    pub tools: Option<Vec<ChatTool>>,
+    #[serde(default)]
+    pub tool_choice: Option<serde_json::Value>,
+    /// 旧版工具定义，`tools` 存在时忽略
+    #[serde(default)]
+    pub functions: Option<Vec<ChatFunction>>,
+    /// 旧版工具选择（`"auto"` / `"none"` / `{"name": ...}`），`tool_choice` 存在时忽略
+    #[serde(default)]
+    pub function_call: Option<serde_json::Value>,
+    /// 未识别的顶层字段，原样交给 Anthropic 请求按白名单透传
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// 停止序列（`stop` 字段接受单个字符串或数组）
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum StopSequences {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl StopSequences {
+    pub fn into_vec(self) -> Vec<String> {
+        match self {
+            StopSequences::One(s) => vec![s],
+            StopSequences::Many(v) => v,
+        }
+    }
+}
+
+/// 对话消息
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChatMessage {
+    /// system / developer / user / assistant / tool / function（旧版）
+    pub role: String,
+    /// 字符串或内容片段数组，assistant 仅包含工具调用时为 null
+    #[serde(default)]
+    pub content: Option<serde_json::Value>,
+    /// assistant 消息中的工具调用
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ChatToolCall>>,
+    /// tool 消息对应的工具调用 ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    /// 旧版 assistant 消息中的函数调用（没有调用 ID）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function_call: Option<ChatFunctionCall>,
+    /// 旧版 function 消息对应的函数名
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+/// 工具调用
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChatToolCall {
+    pub id: String,
+    #[serde(rename = "type", default = "default_tool_type")]
+    pub call_type: String,
+    pub function: ChatFunctionCall,
+}
+
+/// 工具调用的函数名与参数
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChatFunctionCall {
+    pub name: String,
+    /// JSON 字符串形式的参数
+    #[serde(default)]
+    pub arguments: String,
+}
+
+/// 工具定义
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChatTool {
+    #[serde(rename = "type", default = "default_tool_type")]
+    pub tool_type: String,
+    pub function: ChatFunction,
+}
+
+/// 函数定义
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChatFunction {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// JSON Schema 形式的参数定义
+    #[serde(default)]
+    pub parameters: Option<serde_json::Value>,
+}
+
+fn default_tool_type() -> String {
+    "function".to_string()
+}
@@ -72,9 +72,10 @@ pub(crate) async fn call_stream_api() -> anyhow::Result<()> {
                 }
 
                 // 解码所有可用的帧
-                for result in decoder.decode_iter() {
-                    match result {
-                        Ok(frame) => {
+                while !decoder.is_stopped() {
+                    match decoder.decode() {
+                        Ok(None) => break,
+                        Ok(Some(frame)) => {
                             // 解析事件
                             match Event::from_frame(frame) {
                                 Ok(event) => {
@@ -0,0 +1,393 @@
+//! 测试辅助工具
+//!
+//! - 基于 TcpListener 的简易 HTTP 模拟服务器，按顺序返回预设响应并记录收到的请求
+//! - AWS Event Stream 帧编码，用于构造解析器测试数据
+//! - gzip 编码（仅使用不压缩的 stored 块），用于测试响应解压
+//! - 记录 tracing span 的 Layer（`tracing` feature）
+
+#![allow(dead_code)]
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::kiro::parser::crc::crc32;
+
+/// 编码一个 AWS Event Stream 帧（头部值均为字符串类型）
+pub fn encode_frame(headers: &[(&str, &str)], payload: &[u8]) -> Vec<u8> {
+    let mut header_bytes = Vec::new();
+    for (name, value) in headers {
+        header_bytes.push(name.len() as u8);
+        header_bytes.extend_from_slice(name.as_bytes());
+        header_bytes.push(7); // String
+        header_bytes.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        header_bytes.extend_from_slice(value.as_bytes());
+    }
+
+    let total_length = 12 + header_bytes.len() + payload.len() + 4;
+    let mut frame = Vec::with_capacity(total_length);
+    frame.extend_from_slice(&(total_length as u32).to_be_bytes());
+    frame.extend_from_slice(&(header_bytes.len() as u32).to_be_bytes());
+    let prelude_crc = crc32(&frame[..8]);
+    frame.extend_from_slice(&prelude_crc.to_be_bytes());
+    frame.extend_from_slice(&header_bytes);
+    frame.extend_from_slice(payload);
+    let message_crc = crc32(&frame);
+    frame.extend_from_slice(&message_crc.to_be_bytes());
+    frame
+}
+
+/// 编码一个事件帧（`:message-type` 为 `event`）
+pub fn encode_event(event_type: &str, payload: &str) -> Vec<u8> {
+    encode_frame(
+        &[
+            (":message-type", "event"),
+            (":event-type", event_type),
+            (":content-type", "application/json"),
+        ],
+        payload.as_bytes(),
+    )
+}
+
+/// 以 gzip 格式封装数据（deflate stored 块，不做实际压缩）
+pub fn gzip_encode(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
+
+/// 预设的 HTTP 响应
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    /// 响应体分片：(发送前等待时间, 数据)
+    chunks: Vec<(Duration, Vec<u8>)>,
+    /// 发送响应头前的等待时间
+    header_delay: Duration,
+}
+
+impl MockResponse {
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            chunks: Vec::new(),
+            header_delay: Duration::ZERO,
+        }
+    }
+
+    /// JSON 响应
+    pub fn json(status: u16, body: &str) -> Self {
+        Self::new(status)
+            .with_header("content-type", "application/json")
+            .with_body(body.as_bytes().to_vec())
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_body(self, body: Vec<u8>) -> Self {
+        self.with_chunk(Duration::ZERO, body)
+    }
+
+    /// 追加一个响应体分片，发送前等待 `delay`
+    pub fn with_chunk(mut self, delay: Duration, chunk: Vec<u8>) -> Self {
+        self.chunks.push((delay, chunk));
+        self
+    }
+
+    /// 延迟发送响应头
+    pub fn with_header_delay(mut self, delay: Duration) -> Self {
+        self.header_delay = delay;
+        self
+    }
+}
+
+/// 服务器收到的请求
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl RecordedRequest {
+    /// 获取请求头（不区分大小写）
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn body_json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).expect("请求体不是合法 JSON")
+    }
+}
+
+/// HTTP 模拟服务器
+///
+/// 按顺序返回 `responses`，用完后重复最后一个
+pub struct MockServer {
+    port: u16,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    /// 响应体发送完之前客户端就关闭连接的次数
+    disconnects: Arc<AtomicUsize>,
+    handle: tokio::task::JoinHandle<()>,
+}
+
+impl MockServer {
+    pub async fn start(responses: Vec<MockResponse>) -> Self {
+        assert!(!responses.is_empty(), "至少需要一个预设响应");
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let disconnects = Arc::new(AtomicUsize::new(0));
+        let disconnected = disconnects.clone();
+
+        let handle = tokio::spawn(async move {
+            let mut index = 0usize;
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    break;
+                };
+                let response = responses[index.min(responses.len() - 1)].clone();
+                index += 1;
+                let recorded = recorded.clone();
+                let disconnected = disconnected.clone();
+                tokio::spawn(async move {
+                    handle_connection(stream, response, recorded, disconnected).await;
+                });
+            }
+        });
+
+        Self {
+            port,
+            requests,
+            disconnects,
+            handle,
+        }
+    }
+
+    /// 服务器地址（不含路径），如 `http://127.0.0.1:12345`
+    pub fn base_url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url(), path)
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().clone()
+    }
+
+    pub fn request_count(&self) -> usize {
+        self.requests.lock().len()
+    }
+
+    /// 响应体发送完之前客户端就关闭连接的次数
+    pub fn disconnect_count(&self) -> usize {
+        self.disconnects.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    response: MockResponse,
+    recorded: Arc<Mutex<Vec<RecordedRequest>>>,
+    disconnected: Arc<AtomicUsize>,
+) {
+    let Some(request) = read_request(&mut stream).await else {
+        return;
+    };
+    recorded.lock().push(request);
+
+    tokio::time::sleep(response.header_delay).await;
+
+    let content_length: usize = response.chunks.iter().map(|(_, c)| c.len()).sum();
+    let mut head = format!("HTTP/1.1 {} Mock\r\n", response.status);
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str(&format!(
+        "content-length: {}\r\nconnection: close\r\n\r\n",
+        content_length
+    ));
+    if stream.write_all(head.as_bytes()).await.is_err() {
+        return;
+    }
+
+    for (delay, chunk) in &response.chunks {
+        // 等待期间客户端关闭连接时，read 返回 0 或出错
+        let mut probe = [0u8; 1];
+        tokio::select! {
+            _ = tokio::time::sleep(*delay) => {}
+            result = stream.read(&mut probe) => {
+                if matches!(result, Ok(0) | Err(_)) {
+                    disconnected.fetch_add(1, Ordering::SeqCst);
+                    return;
+                }
+            }
+        }
+        if stream.write_all(chunk).await.is_err() || stream.flush().await.is_err() {
+            disconnected.fetch_add(1, Ordering::SeqCst);
+            return;
+        }
+    }
+    let _ = stream.shutdown().await;
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<RecordedRequest> {
+    let mut buf = Vec::new();
+    let mut tmp = [0u8; 4096];
+
+    let header_end = loop {
+        let n = stream.read(&mut tmp).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&tmp[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect();
+
+    let content_length = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.parse::<usize>().ok())
+        .unwrap_or(0);
+
+    let mut body = buf[header_end + 4..].to_vec();
+    while body.len() < content_length {
+        let n = stream.read(&mut tmp).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&tmp[..n]);
+    }
+
+    Some(RecordedRequest {
+        method,
+        path,
+        headers,
+        body,
+    })
+}
+
+/// 记录的 tracing span
+#[cfg(feature = "tracing")]
+#[derive(Debug, Clone)]
+pub struct RecordedSpan {
+    pub name: &'static str,
+    pub fields: std::collections::HashMap<String, String>,
+}
+
+/// 记录所有 span 及其字段（含后续 `record` 的值）的 Layer
+#[cfg(feature = "tracing")]
+#[derive(Clone, Default)]
+pub struct SpanRecorder {
+    spans: Arc<Mutex<Vec<RecordedSpan>>>,
+    index: Arc<Mutex<std::collections::HashMap<tracing::span::Id, usize>>>,
+}
+
+#[cfg(feature = "tracing")]
+impl SpanRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spans(&self) -> Vec<RecordedSpan> {
+        self.spans.lock().clone()
+    }
+
+    /// 名称匹配的第一个 span
+    pub fn find(&self, name: &str) -> Option<RecordedSpan> {
+        self.spans().into_iter().find(|s| s.name == name)
+    }
+}
+
+#[cfg(feature = "tracing")]
+struct FieldVisitor<'a>(&'a mut std::collections::HashMap<String, String>);
+
+#[cfg(feature = "tracing")]
+impl tracing::field::Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for SpanRecorder {
+    fn on_new_span(
+        &self,
+        attrs: &tracing::span::Attributes<'_>,
+        id: &tracing::span::Id,
+        _ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let mut fields = std::collections::HashMap::new();
+        attrs.record(&mut FieldVisitor(&mut fields));
+        let mut spans = self.spans.lock();
+        self.index.lock().insert(id.clone(), spans.len());
+        spans.push(RecordedSpan {
+            name: attrs.metadata().name(),
+            fields,
+        });
+    }
+
+    fn on_record(
+        &self,
+        id: &tracing::span::Id,
+        values: &tracing::span::Record<'_>,
+        _ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        if let Some(&i) = self.index.lock().get(id) {
+            values.record(&mut FieldVisitor(&mut self.spans.lock()[i].fields));
+        }
+    }
+}
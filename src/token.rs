@@ -8,9 +8,10 @@
 //! - 4 个字符单位 = 1 token（四舍五入）
 
 use crate::anthropic::types::{
-    CountTokensRequest, CountTokensResponse, Message, SystemMessage, Tool,
+    CountTokensRequest, CountTokensResponse, Message, MessagesRequest, SystemMessage, Tool,
 };
 use crate::http_client::{ProxyConfig, build_client};
+use crate::kiro::model::events::Event;
 use std::sync::OnceLock;
 
 /// Count Tokens API 配置
@@ -74,16 +75,21 @@ fn is_non_western_char(c: char) -> bool {
 /// - 4 个字符单位 = 1 token（四舍五入）
 /// ```
 pub fn count_tokens(text: &str) -> u64 {
-    // println!("text: {}", text);
+    tokens_from_units(char_units(text))
+}
 
-    let char_units: f64 = text
-        .chars()
+/// 计算文本的字符单位数
+fn char_units(text: &str) -> f64 {
+    text.chars()
         .map(|c| if is_non_western_char(c) { 4.0 } else { 1.0 })
-        .sum();
+        .sum()
+}
 
+/// 将字符单位数换算为 token 数量
+fn tokens_from_units(char_units: f64) -> u64 {
     let tokens = char_units / 4.0;
 
-    let acc_token = if tokens < 100.0 {
+    (if tokens < 100.0 {
         tokens * 1.5
     } else if tokens < 200.0 {
         tokens * 1.3
@@ -93,10 +99,7 @@ pub fn count_tokens(text: &str) -> u64 {
         tokens * 1.2
     } else {
         tokens * 1.0
-    } as u64;
-
-    // println!("tokens: {}, acc_tokens: {}", tokens, acc_token);
-    acc_token
+    }) as u64
 }
 
 /// 估算请求的输入 tokens
@@ -131,7 +134,17 @@ pub(crate) fn count_all_tokens(
     }
 
     // 本地计算
-    count_all_tokens_local(system, messages, tools)
+    count_all_tokens_local(system.as_deref(), &messages, tools.as_deref())
+}
+
+/// 本地估算 Messages 请求的输入 tokens，不调用上游
+///
+/// 统计系统消息、消息文本与工具定义，估算规则与 `UsageTracker` 一致
+pub fn count_request_tokens(req: &MessagesRequest) -> CountTokensResponse {
+    let tokens = count_all_tokens_local(req.system.as_deref(), &req.messages, req.tools.as_deref());
+    CountTokensResponse {
+        input_tokens: tokens as i32,
+    }
 }
 
 /// 调用远程 count_tokens API
@@ -182,21 +195,21 @@ async fn call_remote_count_tokens(
 
 /// 本地计算请求的输入 tokens
 fn count_all_tokens_local(
-    system: Option<Vec<SystemMessage>>,
-    messages: Vec<Message>,
-    tools: Option<Vec<Tool>>,
+    system: Option<&[SystemMessage]>,
+    messages: &[Message],
+    tools: Option<&[Tool]>,
 ) -> u64 {
     let mut total = 0;
 
     // 系统消息
-    if let Some(ref system) = system {
+    if let Some(system) = system {
         for msg in system {
             total += count_tokens(&msg.text);
         }
     }
 
     // 用户消息
-    for msg in &messages {
+    for msg in messages {
         if let serde_json::Value::String(s) = &msg.content {
             total += count_tokens(s);
         } else if let serde_json::Value::Array(arr) = &msg.content {
@@ -209,7 +222,7 @@ fn count_all_tokens_local(
     }
 
     // 工具定义
-    if let Some(ref tools) = tools {
+    if let Some(tools) = tools {
         for tool in tools {
             total += count_tokens(&tool.name);
             total += count_tokens(&tool.description);
@@ -221,22 +234,226 @@ fn count_all_tokens_local(
     total.max(1)
 }
 
-/// 估算输出 tokens
-pub(crate) fn estimate_output_tokens(content: &[serde_json::Value]) -> i32 {
-    let mut total = 0;
+/// 上下文窗口大小（200k tokens），用于将 contextUsageEvent 的百分比换算为 tokens
+const CONTEXT_WINDOW_SIZE: f64 = 200_000.0;
 
-    for block in content {
-        if let Some(text) = block.get("text").and_then(|v| v.as_str()) {
-            total += count_tokens(text) as i32;
+/// token 用量
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Usage {
+    pub input_tokens: i32,
+    pub output_tokens: i32,
+    /// 是否为估算值：上游未下发完整的权威用量（metadataEvent）时为 `true`
+    pub estimated: bool,
+}
+
+/// token 用量统计
+///
+/// 输入 tokens 由序列化后的请求估算，输出 tokens 随流式增量累加；
+/// 上游下发权威用量（metadataEvent）时以其为准，其次使用 contextUsageEvent 推算的输入
+#[derive(Debug, Clone, Default)]
+pub struct UsageTracker {
+    /// 估算的输入 tokens
+    estimated_input: i32,
+    /// 已累计的输出字符单位
+    output_units: f64,
+    /// 由 contextUsageEvent 推算的输入 tokens
+    context_input: Option<i32>,
+    /// 上游下发的输入 tokens
+    authoritative_input: Option<i32>,
+    /// 上游下发的输出 tokens
+    authoritative_output: Option<i32>,
+}
+
+impl UsageTracker {
+    /// 从序列化后的请求体估算输入 tokens
+    pub fn new(request_body: &str) -> Self {
+        Self::with_estimated_input(count_tokens(request_body) as i32)
+    }
+
+    /// 使用已估算好的输入 tokens
+    pub fn with_estimated_input(input_tokens: i32) -> Self {
+        Self {
+            estimated_input: input_tokens,
+            ..Default::default()
         }
-        if block.get("type").and_then(|v| v.as_str()) == Some("tool_use") {
-            // 工具调用开销
-            if let Some(input) = block.get("input") {
-                let input_str = serde_json::to_string(input).unwrap_or_default();
-                total += count_tokens(&input_str) as i32;
+    }
+
+    /// 处理一个上游事件
+    pub fn observe(&mut self, event: &Event) {
+        match event {
+            Event::AssistantResponse(resp) => self.output_units += char_units(&resp.content),
+            Event::ThinkingDelta(thinking) => self.output_units += char_units(&thinking.text),
+            Event::ToolUse(tool_use) => self.output_units += char_units(&tool_use.input),
+            Event::ContextUsage(context_usage) => {
+                let input_tokens =
+                    (context_usage.context_usage_percentage * CONTEXT_WINDOW_SIZE / 100.0) as i32;
+                tracing::debug!(
+                    "收到 contextUsageEvent: {}%, 计算 input_tokens: {}",
+                    context_usage.context_usage_percentage,
+                    input_tokens
+                );
+                self.context_input = Some(input_tokens);
+            }
+            Event::Usage { input, output } => {
+                self.authoritative_input = input.or(self.authoritative_input);
+                self.authoritative_output = output.or(self.authoritative_output);
             }
+            _ => {}
         }
     }
 
-    total.max(1)
+    /// 当前的 token 用量
+    ///
+    /// 上游始终未下发用量帧时，以累计的增量与请求估算值作为尽力而为的结果，
+    /// 并将 `estimated` 置为 `true`
+    pub fn usage(&self) -> Usage {
+        Usage {
+            input_tokens: self
+                .authoritative_input
+                .or(self.context_input)
+                .unwrap_or(self.estimated_input),
+            output_tokens: self
+                .authoritative_output
+                .unwrap_or_else(|| (tokens_from_units(self.output_units) as i32).max(1)),
+            estimated: self.authoritative_input.is_none() || self.authoritative_output.is_none(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kiro::model::events::{AssistantResponseEvent, ContextUsageEvent, MetadataEvent};
+    use serde_json::json;
+
+    fn text(content: &str) -> Event {
+        let event: AssistantResponseEvent =
+            serde_json::from_value(json!({ "content": content })).unwrap();
+        Event::AssistantResponse(event)
+    }
+
+    fn metadata(token_usage: serde_json::Value) -> Event {
+        let event: MetadataEvent =
+            serde_json::from_value(json!({ "tokenUsage": token_usage })).unwrap();
+        let usage = event.token_usage.unwrap();
+        Event::Usage {
+            input: usage.input_tokens,
+            output: usage.output_tokens,
+        }
+    }
+
+    fn messages_request(value: serde_json::Value) -> MessagesRequest {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_count_request_tokens() {
+        let req = messages_request(json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 256,
+            "system": "You are a helpful assistant.",
+            "messages": [
+                {"role": "user", "content": "What is the capital of France?"},
+                {"role": "assistant", "content": [{"type": "text", "text": "Paris."}]},
+                {"role": "user", "content": "你好，世界"}
+            ]
+        }));
+
+        let tokens = count_request_tokens(&req).input_tokens;
+        assert!((25..=35).contains(&tokens), "tokens = {}", tokens);
+        assert_eq!(
+            serde_json::to_value(count_request_tokens(&req)).unwrap(),
+            json!({ "input_tokens": tokens })
+        );
+    }
+
+    #[test]
+    fn test_count_request_tokens_includes_tools() {
+        let mut req = messages_request(json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 256,
+            "messages": [{"role": "user", "content": "Weather in Paris?"}]
+        }));
+        let without_tools = count_request_tokens(&req).input_tokens;
+
+        req.tools = Some(vec![
+            serde_json::from_value(json!({
+                "name": "get_weather",
+                "description": "Get the current weather for a city",
+                "input_schema": {
+                    "type": "object",
+                    "properties": {"city": {"type": "string"}},
+                    "required": ["city"]
+                }
+            }))
+            .unwrap(),
+        ]);
+        let with_tools = count_request_tokens(&req).input_tokens;
+
+        assert!(
+            (25..=45).contains(&(with_tools - without_tools)),
+            "{} -> {}",
+            without_tools,
+            with_tools
+        );
+    }
+
+    #[test]
+    fn test_estimated_usage() {
+        let body = r#"{"conversationState":{"currentMessage":"Hello"}}"#;
+        let mut tracker = UsageTracker::new(body);
+        for chunk in ["Hello", ", ", "world!"] {
+            tracker.observe(&text(chunk));
+        }
+
+        let usage = tracker.usage();
+        assert_eq!(usage.input_tokens, count_tokens(body) as i32);
+        // 增量累加与一次性计算完整文本的结果一致
+        assert_eq!(usage.output_tokens, count_tokens("Hello, world!") as i32);
+        // 没有用量帧时标记为估算值
+        assert!(usage.estimated);
+    }
+
+    #[test]
+    fn test_context_usage_overrides_input_estimate() {
+        let mut tracker = UsageTracker::with_estimated_input(10);
+        tracker.observe(&Event::ContextUsage(ContextUsageEvent {
+            context_usage_percentage: 1.5,
+        }));
+        assert_eq!(tracker.usage().input_tokens, 3000);
+    }
+
+    #[test]
+    fn test_authoritative_usage_overrides_estimate() {
+        let mut tracker = UsageTracker::with_estimated_input(10);
+        tracker.observe(&text("Hello, world!"));
+        tracker.observe(&Event::ContextUsage(ContextUsageEvent {
+            context_usage_percentage: 1.5,
+        }));
+        tracker.observe(&metadata(json!({"inputTokens": 1234, "outputTokens": 56})));
+        // 权威值之后的增量不再影响输出 tokens
+        tracker.observe(&text("more"));
+
+        assert_eq!(
+            tracker.usage(),
+            Usage {
+                input_tokens: 1234,
+                output_tokens: 56,
+                estimated: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_partial_authoritative_usage() {
+        let mut tracker = UsageTracker::with_estimated_input(10);
+        tracker.observe(&text("Hello"));
+        tracker.observe(&metadata(json!({"outputTokens": 7})));
+
+        let usage = tracker.usage();
+        assert_eq!(usage.input_tokens, 10);
+        assert_eq!(usage.output_tokens, 7);
+        // 只下发了部分用量，输入仍为估算值
+        assert!(usage.estimated);
+    }
 }